@@ -9,7 +9,6 @@ use hir_def::{
     expr::{BindingAnnotation, LabelId, Pat, PatId},
     import_map,
     item_tree::ItemTreeNode,
-    lang_item::LangItemTarget,
     path::ModPath,
     per_ns::PerNs,
     resolver::{HasResolver, Resolver},
@@ -30,16 +29,15 @@ use hir_ty::{
     autoderef,
     display::{write_bounds_like_dyn_trait_with_prefix, HirDisplayError, HirFormatter},
     method_resolution,
-    traits::{FnTrait, Solution, SolutionVariables},
-    BoundVar, CallableDefId, CallableSig, Canonical, DebruijnIndex, GenericPredicate,
-    InEnvironment, Obligation, ProjectionPredicate, ProjectionTy, Scalar, Substs, TraitEnvironment,
-    Ty, TyDefId, TyVariableKind,
+    traits::{self, FnTrait, Implements},
+    CallableDefId, CallableSig, Canonical, GenericPredicate, InEnvironment, Scalar, Substs,
+    TraitEnvironment, Ty, TyDefId,
 };
 use rustc_hash::FxHashSet;
 use stdx::{format_to, impl_from};
 use syntax::{
     ast::{self, AttrsOwner, NameOwner},
-    AstNode, SmolStr,
+    AstNode,
 };
 use tt::{Ident, Leaf, Literal, TokenTree};
 
@@ -370,6 +368,14 @@ impl Module {
         for decl in self.declarations(db) {
             match decl {
                 crate::ModuleDef::Function(f) => f.diagnostics(db, sink),
+                crate::ModuleDef::Const(c) => {
+                    decl.diagnostics(db, sink);
+                    hir_ty::diagnostics::validate_body(db, c.id.into(), sink);
+                }
+                crate::ModuleDef::Static(s) => {
+                    decl.diagnostics(db, sink);
+                    hir_ty::diagnostics::validate_body(db, s.id.into(), sink);
+                }
                 crate::ModuleDef::Module(m) => {
                     // Only add diagnostics from inline modules
                     if crate_def_map[m.id.local_id].origin.is_inline() {
@@ -658,6 +664,21 @@ impl Adt {
             Adt::Enum(e) => e.name(db),
         }
     }
+
+    /// Whether this is a `#[fundamental]` type like `Box`. For the orphan
+    /// rules, `Box<Local>` is treated like `Local`.
+    pub fn is_fundamental(self, db: &dyn HirDatabase) -> bool {
+        db.attrs(AdtId::from(self).into()).by_key("fundamental").exists()
+    }
+
+    /// Checks whether `trait_` can be derived for this ADT, i.e. whether all
+    /// its field types implement it. Returns the first field type that doesn't.
+    pub fn field_not_implementing(self, db: &dyn HirDatabase, trait_: Trait) -> Option<Type> {
+        let id = AdtId::from(self);
+        let krate = id.module(db.upcast()).krate();
+        let ty = traits::all_fields_implement(db, krate, id, trait_.id)?;
+        Some(Type::new(db, krate, id, ty))
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -1348,7 +1369,7 @@ impl TypeParam {
     pub fn trait_bounds(self, db: &dyn HirDatabase) -> Vec<Trait> {
         db.generic_predicates_for_param(self.id)
             .into_iter()
-            .filter_map(|pred| match &pred.value {
+            .filter_map(|pred| match pred.value.skip_binders() {
                 hir_ty::GenericPredicate::Implemented(trait_ref) => {
                     Some(Trait::from(trait_ref.trait_))
                 }
@@ -1443,10 +1464,22 @@ impl Impl {
 
         inherent.all_impls().chain(trait_.all_impls()).map(Self::from).collect()
     }
+    /// All inherent and trait impls visible from the type's crate whose self
+    /// type could be `ty`. Blanket impls are not included.
+    pub fn all_for_type(db: &dyn HirDatabase, ty: Type) -> Vec<Impl> {
+        method_resolution::all_impls_for_ty(db, &ty.ty.value, ty.krate, false)
+            .into_iter()
+            .map(Self::from)
+            .collect()
+    }
     pub fn for_trait(db: &dyn HirDatabase, krate: Crate, trait_: Trait) -> Vec<Impl> {
         let impls = db.trait_impls_in_crate(krate.id);
         impls.for_trait(trait_.id).map(Self::from).collect()
     }
+    /// All impls of `trait_` in any crate that can see it.
+    pub fn all_for_trait(db: &dyn HirDatabase, trait_: Trait) -> Vec<Impl> {
+        db.all_impls_of_trait(trait_.id).into_iter().map(Self::from).collect()
+    }
 
     // FIXME: the return type is wrong. This should be a hir version of
     // `TraitRef` (ie, resolved `TypeRef`).
@@ -1583,14 +1616,8 @@ impl Type {
             None => return false,
         };
 
-        let canonical_ty = Canonical { value: self.ty.value.clone(), kinds: Arc::new([]) };
-        method_resolution::implements_trait(
-            &canonical_ty,
-            db,
-            self.ty.environment.clone(),
-            krate,
-            std_future_trait,
-        )
+        self.ty.value.check_impls_trait(db, krate, self.ty.environment.clone(), std_future_trait)
+            != Implements::No
     }
 
     /// Checks that particular type `ty` implements `std::ops::FnOnce`.
@@ -1605,14 +1632,7 @@ impl Type {
             None => return false,
         };
 
-        let canonical_ty = Canonical { value: self.ty.value.clone(), kinds: Arc::new([]) };
-        method_resolution::implements_trait_unique(
-            &canonical_ty,
-            db,
-            self.ty.environment.clone(),
-            krate,
-            fnonce_trait,
-        )
+        self.ty.value.impls_trait(db, krate, self.ty.environment.clone(), fnonce_trait)
     }
 
     pub fn impls_trait(&self, db: &dyn HirDatabase, trait_: Trait, args: &[Type]) -> bool {
@@ -1642,46 +1662,37 @@ impl Type {
         args: &[Type],
         alias: TypeAlias,
     ) -> Option<Type> {
-        let subst = Substs::build_for_def(db, trait_.id)
+        let substs = Substs::build_for_def(db, trait_.id)
             .push(self.ty.value.clone())
             .fill(args.iter().map(|t| t.ty.value.clone()))
             .build();
-        let predicate = ProjectionPredicate {
-            projection_ty: ProjectionTy { associated_ty: alias.id, parameters: subst },
-            ty: Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0)),
-        };
-        let goal = Canonical {
-            value: InEnvironment::new(
-                self.ty.environment.clone(),
-                Obligation::Projection(predicate),
-            ),
-            kinds: Arc::new([TyVariableKind::General]),
-        };
-
-        match db.trait_solve(self.krate, goal)? {
-            Solution::Unique(SolutionVariables(subst)) => subst.value.first().cloned(),
-            Solution::Ambig(_) => None,
+        let trait_ref = hir_ty::TraitRef { trait_: trait_.id, substs };
+        let ty = traits::normalize_assoc(
+            db,
+            self.krate,
+            self.ty.environment.clone(),
+            trait_ref,
+            alias.id,
+        );
+        if ty.is_unknown() {
+            return None;
         }
-        .map(|ty| Type {
+        Some(Type {
             krate: self.krate,
             ty: InEnvironment { value: ty, environment: Arc::clone(&self.ty.environment) },
         })
     }
 
     pub fn is_copy(&self, db: &dyn HirDatabase) -> bool {
-        let lang_item = db.lang_item(self.krate, SmolStr::new("copy"));
-        let copy_trait = match lang_item {
-            Some(LangItemTarget::TraitId(it)) => it,
-            _ => return false,
-        };
-        self.impls_trait(db, copy_trait.into(), &[])
+        self.ty.value.is_copy(db, self.krate, self.ty.environment.clone())
+    }
+
+    pub fn is_clone(&self, db: &dyn HirDatabase) -> bool {
+        self.ty.value.is_clone(db, self.krate, self.ty.environment.clone())
     }
 
     pub fn as_callable(&self, db: &dyn HirDatabase) -> Option<Callable> {
-        let def = match self.ty.value {
-            Ty::FnDef(def, _) => Some(def),
-            _ => None,
-        };
+        let def = self.ty.value.as_callable_def().map(|(def, _)| def);
 
         let sig = self.ty.value.callable_sig(db)?;
         Some(Callable { ty: self.clone(), sig, def, is_bound_method: false })
@@ -1855,6 +1866,10 @@ impl Type {
         Some(adt.into())
     }
 
+    pub fn as_builtin(&self) -> Option<BuiltinType> {
+        self.ty.value.as_builtin().map(|inner| BuiltinType { inner })
+    }
+
     pub fn as_dyn_trait(&self) -> Option<Trait> {
         self.ty.value.dyn_trait().map(Into::into)
     }
@@ -1862,7 +1877,7 @@ impl Type {
     pub fn as_impl_traits(&self, db: &dyn HirDatabase) -> Option<Vec<Trait>> {
         self.ty.value.impl_trait_bounds(db).map(|it| {
             it.into_iter()
-                .filter_map(|pred| match pred {
+                .filter_map(|pred| match pred.into_skip_binders() {
                     hir_ty::GenericPredicate::Implemented(trait_ref) => {
                         Some(Trait::from(trait_ref.trait_))
                     }
@@ -1911,11 +1926,14 @@ impl Type {
             cb: &mut impl FnMut(Type),
         ) {
             for pred in bounds {
-                match pred {
+                match pred.skip_binders() {
                     GenericPredicate::Implemented(trait_ref) => {
                         cb(type_.clone());
                         walk_substs(db, type_, &trait_ref.substs, cb);
                     }
+                    GenericPredicate::Projection(proj) => {
+                        walk_type(db, &type_.derived(proj.ty.clone()), cb);
+                    }
                     _ => (),
                 }
             }
@@ -1966,7 +1984,7 @@ impl Type {
 
 impl HirDisplay for Type {
     fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
-        self.ty.value.hir_fmt(f)
+        self.ty.environment.normalize_ty(self.ty.value.clone()).hir_fmt(f)
     }
 }
 
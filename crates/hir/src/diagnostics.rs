@@ -6,6 +6,10 @@ pub use hir_expand::diagnostics::{
     Diagnostic, DiagnosticCode, DiagnosticSink, DiagnosticSinkBuilder,
 };
 pub use hir_ty::diagnostics::{
-    IncorrectCase, MismatchedArgCount, MissingFields, MissingMatchArms, MissingOkOrSomeInTailExpr,
-    NoSuchField, RemoveThisSemicolon, ReplaceFilterMapNextWithFindMap,
+    BodyDiagnostic, GenericArgsCountMismatch, IdentityCast, IncorrectCase, IncorrectTryExpr,
+    InvalidMainReturnType, LosslessCast, MismatchedArgCount, MissingAwait, MissingFields,
+    MissingMatchArms, MissingOkOrSomeInTailExpr, MissingPatFields, NoSuchField,
+    NonConstFnInConstContext, PrivateField, RecursionLimitReached, RecursiveTypeWithoutIndirection,
+    RemoveThisSemicolon, ReplaceFilterMapNextWithFindMap, RequiresCopy, TypeMismatch,
+    UnreachableCode, UnresolvedMethodCall, UseOfMovedValue,
 };
@@ -61,7 +61,7 @@ pub use hir_expand::{
     ExpandResult, HirFileId, InFile, MacroCallId, MacroCallLoc, /* FIXME */ MacroDefId,
     MacroFile, Origin,
 };
-pub use hir_ty::display::HirDisplay;
+pub use hir_ty::display::{FnSignature, HirDisplay};
 
 // These are negative re-exports: pub using these names is forbidden, they
 // should remain private to hir internals.
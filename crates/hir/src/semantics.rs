@@ -24,8 +24,8 @@ use crate::{
     db::HirDatabase,
     semantics::source_to_def::{ChildContainer, SourceToDefCache, SourceToDefCtx},
     source_analyzer::{resolve_hir_path, SourceAnalyzer},
-    AssocItem, Callable, ConstParam, Crate, Field, Function, HirFileId, Impl, InFile, Label,
-    LifetimeParam, Local, MacroDef, Module, ModuleDef, Name, Path, ScopeDef, Trait, Type,
+    AssocItem, Callable, ConstParam, Crate, Field, FnSignature, Function, HirFileId, Impl, InFile,
+    Label, LifetimeParam, Local, MacroDef, Module, ModuleDef, Name, Path, ScopeDef, Trait, Type,
     TypeAlias, TypeParam, VariantDef,
 };
 
@@ -208,6 +208,12 @@ impl<'db, DB: HirDatabase> Semantics<'db, DB> {
         self.imp.resolve_method_call_as_callable(call)
     }
 
+    /// The signature of the called method, with the generic parameters of the
+    /// method and its impl or trait replaced by the inferred arguments.
+    pub fn resolve_method_call_signature(&self, call: &ast::MethodCallExpr) -> Option<FnSignature> {
+        self.imp.resolve_method_call_signature(call)
+    }
+
     pub fn resolve_field(&self, field: &ast::FieldExpr) -> Option<Field> {
         self.imp.resolve_field(field)
     }
@@ -479,6 +485,10 @@ impl<'db> SemanticsImpl<'db> {
         Some(res)
     }
 
+    fn resolve_method_call_signature(&self, call: &ast::MethodCallExpr) -> Option<FnSignature> {
+        self.analyze(call.syntax()).resolve_method_call_signature(self.db, call)
+    }
+
     fn resolve_field(&self, field: &ast::FieldExpr) -> Option<Field> {
         self.analyze(field.syntax()).resolve_field(self.db, field)
     }
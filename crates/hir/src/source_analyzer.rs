@@ -20,6 +20,7 @@ use hir_def::{
 use hir_expand::{hygiene::Hygiene, name::AsName, HirFileId, InFile};
 use hir_ty::{
     diagnostics::{record_literal_missing_fields, record_pattern_missing_fields},
+    display::FnSignature,
     InferenceResult, Substs,
 };
 use syntax::{
@@ -148,6 +149,22 @@ impl SourceAnalyzer {
         self.infer.as_ref()?.method_resolution(expr_id)
     }
 
+    pub(crate) fn resolve_method_call_signature(
+        &self,
+        db: &dyn HirDatabase,
+        call: &ast::MethodCallExpr,
+    ) -> Option<FnSignature> {
+        let expr_id = self.expr_id(db, &call.clone().into())?;
+        let infer = self.infer.as_ref()?;
+        let func = infer.method_resolution(expr_id)?;
+        // arguments are only recorded for generic methods
+        let substs = match infer.generic_args_for_expr(expr_id) {
+            Some(args) => args.substs.clone(),
+            None => Substs::type_params(db, func),
+        };
+        Some(FnSignature::new(db, func, &substs))
+    }
+
     pub(crate) fn resolve_field(
         &self,
         db: &dyn HirDatabase,
@@ -17,7 +17,7 @@ use syntax::{
         self, ArgListOwner, ArrayExprKind, AstChildren, LiteralKind, LoopBodyOwner, NameOwner,
         SlicePatComponents,
     },
-    AstNode, AstPtr, SyntaxNodePtr,
+    AstNode, AstPtr, SyntaxNodePtr, T,
 };
 use test_utils::mark;
 
@@ -30,7 +30,7 @@ use crate::{
     expr::{
         dummy_expr_id, ArithOp, Array, BinaryOp, BindingAnnotation, CmpOp, Expr, ExprId, Label,
         LabelId, Literal, LogicOp, MatchArm, Ordering, Pat, PatId, RecordFieldPat, RecordLitField,
-        Statement,
+        Statement, UnaryOp,
     },
     item_scope::BuiltinShadowMode,
     item_tree::{ItemTree, ItemTreeId, ItemTreeNode},
@@ -211,6 +211,9 @@ impl ExprCollector<'_> {
 
                 let condition = match e.condition() {
                     None => self.missing_expr(),
+                    Some(condition) if condition.chained().is_some() => {
+                        self.collect_let_chain(condition)
+                    }
                     Some(condition) => match condition.pat() {
                         None => self.collect_expr_opt(condition.expr()),
                         // if let -- desugar to match
@@ -282,6 +285,9 @@ impl ExprCollector<'_> {
 
                 let condition = match e.condition() {
                     None => self.missing_expr(),
+                    Some(condition) if condition.chained().is_some() => {
+                        self.collect_let_chain(condition)
+                    }
                     Some(condition) => match condition.pat() {
                         None => self.collect_expr_opt(condition.expr()),
                         // if let -- desugar to match
@@ -522,7 +528,7 @@ impl ExprCollector<'_> {
                 }
             }
 
-            ast::Expr::Literal(e) => self.alloc_expr(Expr::Literal(e.kind().into()), syntax_ptr),
+            ast::Expr::Literal(e) => self.alloc_expr(Expr::Literal(e.into()), syntax_ptr),
             ast::Expr::IndexExpr(e) => {
                 let base = self.collect_expr_opt(e.base());
                 let index = self.collect_expr_opt(e.index());
@@ -632,6 +638,26 @@ impl ExprCollector<'_> {
         Some(ItemTreeId::new(id.file_id, item_tree_id))
     }
 
+    /// Lowers a let chain like `let Some(x) = a && b && let Ok(y) = c` into
+    /// `&&`s of `Expr::Let`s and plain boolean operands.
+    fn collect_let_chain(&mut self, condition: ast::Condition) -> ExprId {
+        let expr = self.collect_expr_opt(condition.expr());
+        let lhs = if condition.let_token().is_some() {
+            let pat = self.collect_pat_opt(condition.pat());
+            self.alloc_expr_desugared(Expr::Let { pat, expr })
+        } else {
+            expr
+        };
+        match condition.chained() {
+            Some(chained) => {
+                let rhs = self.collect_let_chain(chained);
+                let op = Some(BinaryOp::LogicOp(LogicOp::And));
+                self.alloc_expr_desugared(Expr::BinaryOp { lhs, rhs, op })
+            }
+            None => lhs,
+        }
+    }
+
     fn collect_expr_opt(&mut self, expr: Option<ast::Expr>) -> ExprId {
         if let Some(expr) = expr {
             self.collect_expr(expr)
@@ -935,16 +961,10 @@ impl ExprCollector<'_> {
                     suffix: suffix.into_iter().map(|p| self.collect_pat(p)).collect(),
                 }
             }
-            ast::Pat::LiteralPat(lit) => {
-                if let Some(ast_lit) = lit.literal() {
-                    let expr = Expr::Literal(ast_lit.kind().into());
-                    let expr_ptr = AstPtr::new(&ast::Expr::Literal(ast_lit));
-                    let expr_id = self.alloc_expr(expr, expr_ptr);
-                    Pat::Lit(expr_id)
-                } else {
-                    Pat::Missing
-                }
-            }
+            ast::Pat::LiteralPat(lit) => match self.collect_literal_pat(&lit) {
+                Some(expr_id) => Pat::Lit(expr_id),
+                None => Pat::Missing,
+            },
             ast::Pat::RestPat(_) => {
                 // `RestPat` requires special handling and should not be mapped
                 // to a Pat. Here we are using `Pat::Missing` as a fallback for
@@ -966,13 +986,45 @@ impl ExprCollector<'_> {
                     Pat::Missing
                 }
             }
+            ast::Pat::RangePat(range) => {
+                // FIXME: exclusive and half-open ranges, and paths to consts as bounds
+                let is_inclusive = range
+                    .syntax()
+                    .children_with_tokens()
+                    .any(|it| it.kind() == T![..=] || it.kind() == T![...]);
+                let bound = |this: &mut Self, pat| match pat {
+                    Some(ast::Pat::LiteralPat(lit)) => this.collect_literal_pat(&lit),
+                    _ => None,
+                };
+                let start = bound(self, range.start());
+                let end = bound(self, range.end());
+                match (start, end) {
+                    (Some(start), Some(end)) if is_inclusive => Pat::Range { start, end },
+                    _ => Pat::Missing,
+                }
+            }
             // FIXME: implement
-            ast::Pat::RangePat(_) | ast::Pat::MacroPat(_) => Pat::Missing,
+            ast::Pat::MacroPat(_) => Pat::Missing,
         };
         let ptr = AstPtr::new(&pat);
         self.alloc_pat(pattern, Either::Left(ptr))
     }
 
+    /// Lowers the literal of a literal pattern, which unlike a literal
+    /// expression may be negated, like in `-1`.
+    fn collect_literal_pat(&mut self, lit: &ast::LiteralPat) -> Option<ExprId> {
+        let ast_lit = lit.literal()?;
+        let expr = Expr::Literal(ast_lit.clone().into());
+        let expr_ptr = AstPtr::new(&ast::Expr::Literal(ast_lit));
+        let expr_id = self.alloc_expr(expr, expr_ptr);
+        let is_negated = lit.syntax().first_token().map_or(false, |it| it.kind() == T![-]);
+        if is_negated {
+            Some(self.alloc_expr_desugared(Expr::UnaryOp { expr: expr_id, op: UnaryOp::Neg }))
+        } else {
+            Some(expr_id)
+        }
+    }
+
     fn collect_pat_opt(&mut self, pat: Option<ast::Pat>) -> PatId {
         if let Some(pat) = pat {
             self.collect_pat(pat)
@@ -1061,19 +1113,19 @@ impl From<ast::BinOp> for BinaryOp {
     }
 }
 
-impl From<ast::LiteralKind> for Literal {
-    fn from(ast_lit_kind: ast::LiteralKind) -> Self {
-        match ast_lit_kind {
+impl From<ast::Literal> for Literal {
+    fn from(ast_lit: ast::Literal) -> Self {
+        match ast_lit.kind() {
             LiteralKind::IntNumber(lit) => {
                 if let builtin @ Some(_) = lit.suffix().and_then(BuiltinFloat::from_suffix) {
                     return Literal::Float(Default::default(), builtin);
                 } else if let builtin @ Some(_) =
                     lit.suffix().and_then(|it| BuiltinInt::from_suffix(&it))
                 {
-                    Literal::Int(Default::default(), builtin)
+                    Literal::Int(lit.value().unwrap_or(0) as i128, builtin)
                 } else {
                     let builtin = lit.suffix().and_then(|it| BuiltinUint::from_suffix(&it));
-                    Literal::Uint(Default::default(), builtin)
+                    Literal::Uint(lit.value().unwrap_or(0), builtin)
                 }
             }
             LiteralKind::FloatNumber(lit) => {
@@ -1082,9 +1134,12 @@ impl From<ast::LiteralKind> for Literal {
             }
             LiteralKind::ByteString(_) => Literal::ByteString(Default::default()),
             LiteralKind::String(_) => Literal::String(Default::default()),
-            LiteralKind::Byte => Literal::Uint(Default::default(), Some(BuiltinUint::U8)),
+            LiteralKind::Byte => Literal::Uint(
+                ast_lit.byte_value().unwrap_or_default().into(),
+                Some(BuiltinUint::U8),
+            ),
             LiteralKind::Bool(val) => Literal::Bool(val),
-            LiteralKind::Char => Literal::Char(Default::default()),
+            LiteralKind::Char => Literal::Char(ast_lit.char_value().unwrap_or_default()),
         }
     }
 }
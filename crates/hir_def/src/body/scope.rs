@@ -8,7 +8,7 @@ use rustc_hash::FxHashMap;
 use crate::{
     body::Body,
     db::DefDatabase,
-    expr::{Expr, ExprId, Pat, PatId, Statement},
+    expr::{BinaryOp, Expr, ExprId, LogicOp, Pat, PatId, Statement},
     BlockId, DefWithBodyId,
 };
 
@@ -159,6 +159,17 @@ fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut ExprScopes, scope
             scopes.add_bindings(body, scope, *pat);
             compute_expr_scopes(*body_expr, body, scopes, scope);
         }
+        Expr::If { condition, then_branch, else_branch } => {
+            let then_scope = compute_condition_scopes(*condition, body, scopes, scope);
+            compute_expr_scopes(*then_branch, body, scopes, then_scope);
+            if let Some(else_branch) = else_branch {
+                compute_expr_scopes(*else_branch, body, scopes, scope);
+            }
+        }
+        Expr::While { condition, body: body_expr, .. } => {
+            let body_scope = compute_condition_scopes(*condition, body, scopes, scope);
+            compute_expr_scopes(*body_expr, body, scopes, body_scope);
+        }
         Expr::Lambda { args, body: body_expr, .. } => {
             let scope = scopes.new_scope(scope);
             scopes.add_params_bindings(body, scope, &args);
@@ -181,6 +192,34 @@ fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut ExprScopes, scope
     };
 }
 
+/// Computes scopes for an `if` or `while` condition, returning the scope with
+/// all bindings of its let chain, for the rest of the chain and the body.
+fn compute_condition_scopes(
+    expr: ExprId,
+    body: &Body,
+    scopes: &mut ExprScopes,
+    scope: ScopeId,
+) -> ScopeId {
+    match &body[expr] {
+        Expr::Let { pat, expr: scrutinee } => {
+            scopes.set_scope(expr, scope);
+            compute_expr_scopes(*scrutinee, body, scopes, scope);
+            let scope = scopes.new_scope(scope);
+            scopes.add_bindings(body, scope, *pat);
+            scope
+        }
+        Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::LogicOp(LogicOp::And)) } => {
+            scopes.set_scope(expr, scope);
+            let scope = compute_condition_scopes(*lhs, body, scopes, scope);
+            compute_condition_scopes(*rhs, body, scopes, scope)
+        }
+        _ => {
+            compute_expr_scopes(expr, body, scopes, scope);
+            scope
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use base_db::{fixture::WithFixture, FileId, SourceDatabase};
@@ -303,6 +342,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_let_chain() {
+        do_check(
+            r"
+            fn quux() {
+                if let Some(x) = a && let Ok(y) = b {
+                    $0
+                }
+            }",
+            &["y", "x"],
+        );
+    }
+
+    #[test]
+    fn test_let_chain_later_operand() {
+        do_check(
+            r"
+            fn quux() {
+                if let Some(x) = a && $0 {}
+            }",
+            &["x"],
+        );
+    }
+
     #[test]
     fn test_shadow_variable() {
         do_check(
@@ -27,8 +27,11 @@ pub struct FunctionData {
     pub has_self_param: bool,
     pub has_body: bool,
     pub is_unsafe: bool,
+    pub is_const: bool,
+    pub is_async: bool,
     pub is_varargs: bool,
     pub is_extern: bool,
+    pub is_intrinsic: bool,
     pub visibility: RawVisibility,
 }
 
@@ -47,11 +50,32 @@ impl FunctionData {
             has_self_param: func.has_self_param,
             has_body: func.has_body,
             is_unsafe: func.is_unsafe,
+            is_const: func.is_const,
+            is_async: func.is_async,
             is_varargs: func.is_varargs,
             is_extern: func.is_extern,
+            is_intrinsic: func.is_intrinsic,
             visibility: item_tree[func.visibility].clone(),
         })
     }
+
+    /// For an `async fn`, the return type as written, i.e. the `T` in the
+    /// `impl Future<Output = T>` that `ret_type` is desugared to. This is the
+    /// type the body evaluates to.
+    pub fn async_ret_type(&self) -> Option<&TypeRef> {
+        if !self.is_async {
+            return None;
+        }
+        let path = match &self.ret_type {
+            TypeRef::ImplTrait(bounds) => match bounds.first()? {
+                TypeBound::Path(path) => path,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let args = path.segments().last()?.args_and_bindings?;
+        args.bindings.first()?.type_ref.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -127,6 +151,13 @@ impl TraitData {
             _ => None,
         })
     }
+
+    pub fn method_by_name(&self, name: &Name) -> Option<FunctionId> {
+        self.items.iter().find_map(|(item_name, item)| match item {
+            AssocItemId::FunctionId(f) if item_name == name => Some(*f),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -135,6 +166,8 @@ pub struct ImplData {
     pub target_type: TypeRef,
     pub items: Vec<AssocItemId>,
     pub is_negative: bool,
+    /// Whether this is a `default impl` or has `default` items.
+    pub is_specializable: bool,
 }
 
 impl ImplData {
@@ -147,6 +180,7 @@ impl ImplData {
         let target_trait = impl_def.target_trait.map(|id| item_tree[id].clone());
         let target_type = item_tree[impl_def.target_type].clone();
         let is_negative = impl_def.is_negative;
+        let is_specializable = impl_def.is_specializable;
         let module_id = impl_loc.container.module(db);
         let container = AssocContainerId::ImplId(id);
         let mut expander = Expander::new(db, impl_loc.id.file_id, module_id);
@@ -162,7 +196,7 @@ impl ImplData {
         );
         let items = items.into_iter().map(|(_, item)| item).collect();
 
-        Arc::new(ImplData { target_trait, target_type, items, is_negative })
+        Arc::new(ImplData { target_trait, target_type, items, is_negative, is_specializable })
     }
 }
 
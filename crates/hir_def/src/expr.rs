@@ -42,8 +42,8 @@ pub enum Literal {
     ByteString(Vec<u8>),
     Char(char),
     Bool(bool),
-    Int(u64, Option<BuiltinInt>),
-    Uint(u64, Option<BuiltinUint>),
+    Int(i128, Option<BuiltinInt>),
+    Uint(u128, Option<BuiltinUint>),
     Float(u64, Option<BuiltinFloat>), // FIXME: f64 is not Eq
 }
 
@@ -57,6 +57,13 @@ pub enum Expr {
         then_branch: ExprId,
         else_branch: Option<ExprId>,
     },
+    /// A `let` operand of a let chain, like `let Some(x) = a` in
+    /// `if let Some(x) = a && x > 0 {}`. Plain `if let` and `while let` are
+    /// desugared to `match` instead.
+    Let {
+        pat: PatId,
+        expr: ExprId,
+    },
     Block {
         id: BlockId,
         statements: Vec<Statement>,
@@ -338,7 +345,8 @@ impl Expr {
             | Expr::Cast { expr, .. }
             | Expr::Ref { expr, .. }
             | Expr::UnaryOp { expr, .. }
-            | Expr::Box { expr } => {
+            | Expr::Box { expr }
+            | Expr::Let { expr, .. } => {
                 f(*expr);
             }
             Expr::Tuple { exprs } => {
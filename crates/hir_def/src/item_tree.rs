@@ -565,9 +565,13 @@ pub struct Function {
     pub has_self_param: bool,
     pub has_body: bool,
     pub is_unsafe: bool,
+    pub is_const: bool,
+    pub is_async: bool,
     /// Whether the function is located in an `extern` block (*not* whether it is an
     /// `extern "abi" fn`).
     pub is_extern: bool,
+    /// Whether the function is located in an `extern "rust-intrinsic"` block.
+    pub is_intrinsic: bool,
     pub params: Box<[Idx<TypeRef>]>,
     pub is_varargs: bool,
     pub ret_type: Idx<TypeRef>,
@@ -648,6 +652,9 @@ pub struct Impl {
     pub target_trait: Option<Idx<TypeRef>>,
     pub target_type: Idx<TypeRef>,
     pub is_negative: bool,
+    /// Whether this is a `default impl` or has `default` items, i.e. can be
+    /// specialized by another impl.
+    pub is_specializable: bool,
     pub items: Box<[AssocItem]>,
     pub ast_id: FileAstId<ast::Impl>,
 }
@@ -6,7 +6,7 @@ use hir_expand::{ast_id_map::AstIdMap, hygiene::Hygiene, name::known, HirFileId}
 use smallvec::SmallVec;
 use syntax::{
     ast::{self, ModuleItemOwner},
-    SyntaxNode, WalkEvent,
+    AstNode, SyntaxKind, SyntaxNode, WalkEvent,
 };
 
 use crate::{
@@ -398,7 +398,10 @@ impl Ctx {
             has_self_param,
             has_body,
             is_unsafe: func.unsafe_token().is_some(),
+            is_const: func.const_token().is_some(),
+            is_async: func.async_token().is_some(),
             is_extern: false,
+            is_intrinsic: false,
             params,
             is_varargs,
             ret_type,
@@ -514,6 +517,15 @@ impl Ctx {
         let target_trait = impl_def.trait_().map(|tr| self.lower_type_ref(&tr));
         let target_type = self.lower_type_ref(&impl_def.self_ty()?);
         let is_negative = impl_def.excl_token().is_some();
+        let is_specializable = impl_def.default_token().is_some()
+            || impl_def.assoc_item_list().into_iter().flat_map(|it| it.assoc_items()).any(|item| {
+                match item {
+                    ast::AssocItem::Const(it) => it.default_token().is_some(),
+                    ast::AssocItem::Fn(it) => it.default_token().is_some(),
+                    ast::AssocItem::TypeAlias(it) => it.default_token().is_some(),
+                    ast::AssocItem::MacroCall(_) => false,
+                }
+            });
 
         // We cannot use `assoc_items()` here as that does not include macro calls.
         let items = impl_def
@@ -529,7 +541,15 @@ impl Ctx {
             })
             .collect();
         let ast_id = self.source_ast_id_map.ast_id(impl_def);
-        let res = Impl { generic_params, target_trait, target_type, is_negative, items, ast_id };
+        let res = Impl {
+            generic_params,
+            target_trait,
+            target_type,
+            is_negative,
+            is_specializable,
+            items,
+            ast_id,
+        };
         Some(id(self.data().impls.alloc(res)))
     }
 
@@ -599,6 +619,7 @@ impl Ctx {
     }
 
     fn lower_extern_block(&mut self, block: &ast::ExternBlock) -> Vec<ModItem> {
+        let is_intrinsic_block = is_intrinsic_abi(block.abi());
         block.extern_item_list().map_or(Vec::new(), |list| {
             list.extern_items()
                 .filter_map(|item| {
@@ -608,8 +629,11 @@ impl Ctx {
                         ast::ExternItem::Fn(ast) => {
                             let func_id = self.lower_function(&ast)?;
                             let func = &mut self.data().functions[func_id.index];
-                            func.is_unsafe = is_intrinsic_fn_unsafe(&func.name);
+                            // functions from other ABIs are always unsafe to call
+                            func.is_unsafe =
+                                !is_intrinsic_block || is_intrinsic_fn_unsafe(&func.name);
                             func.is_extern = true;
+                            func.is_intrinsic = is_intrinsic_block;
                             func_id.into()
                         }
                         ast::ExternItem::Static(ast) => {
@@ -774,6 +798,16 @@ enum GenericsOwner<'a> {
     Impl,
 }
 
+/// Returns `true` if the ABI is `"rust-intrinsic"`.
+fn is_intrinsic_abi(abi: Option<ast::Abi>) -> bool {
+    abi.map_or(false, |abi| {
+        abi.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .any(|token| token.kind() == SyntaxKind::STRING && token.text() == "\"rust-intrinsic\"")
+    })
+}
+
 /// Returns `true` if the given intrinsic is unsafe to call, or false otherwise.
 fn is_intrinsic_fn_unsafe(name: &Name) -> bool {
     // Should be kept in sync with https://github.com/rust-lang/rust/blob/c6e4db620a7d2f569f11dcab627430921ea8aacf/compiler/rustc_typeck/src/check/intrinsic.rs#L68
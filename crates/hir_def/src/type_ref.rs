@@ -92,7 +92,9 @@ impl LifetimeRef {
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum TypeBound {
     Path(Path),
-    // ForLifetime(Vec<LifetimeRef>, Path), FIXME ForLifetime
+    /// A trait bound under a higher-ranked lifetime binder, like the
+    /// `for<'a> Fn(&'a str)` in `dyn for<'a> Fn(&'a str)`.
+    ForLifetime(Box<[Name]>, Path),
     Lifetime(LifetimeRef),
     Error,
 }
@@ -192,7 +194,9 @@ impl TypeRef {
                 TypeRef::ImplTrait(bounds) | TypeRef::DynTrait(bounds) => {
                     for bound in bounds {
                         match bound {
-                            TypeBound::Path(path) => go_path(path, f),
+                            TypeBound::Path(path) | TypeBound::ForLifetime(_, path) => {
+                                go_path(path, f)
+                            }
                             TypeBound::Lifetime(_) | TypeBound::Error => (),
                         }
                     }
@@ -222,7 +226,9 @@ impl TypeRef {
                         }
                         for bound in &binding.bounds {
                             match bound {
-                                TypeBound::Path(path) => go_path(path, f),
+                                TypeBound::Path(path) | TypeBound::ForLifetime(_, path) => {
+                                    go_path(path, f)
+                                }
                                 TypeBound::Lifetime(_) | TypeBound::Error => (),
                             }
                         }
@@ -259,7 +265,27 @@ impl TypeBound {
                 };
                 TypeBound::Path(path)
             }
-            ast::TypeBoundKind::ForType(_) => TypeBound::Error, // FIXME ForType
+            ast::TypeBoundKind::ForType(for_type) => {
+                let lifetimes = match for_type.generic_param_list() {
+                    Some(params) => params
+                        .lifetime_params()
+                        .map(|lifetime_param| {
+                            lifetime_param
+                                .lifetime()
+                                .map_or_else(Name::missing, |lt| Name::new_lifetime(&lt))
+                        })
+                        .collect(),
+                    None => Box::default(),
+                };
+                let path = match for_type.ty() {
+                    Some(ast::Type::PathType(path_type)) => path_type.path(),
+                    _ => None,
+                };
+                match path.and_then(|path| ctx.lower_path(path)) {
+                    Some(path) => TypeBound::ForLifetime(lifetimes, path),
+                    None => TypeBound::Error,
+                }
+            }
             ast::TypeBoundKind::Lifetime(lifetime) => {
                 TypeBound::Lifetime(LifetimeRef::new(&lifetime))
             }
@@ -268,7 +294,7 @@ impl TypeBound {
 
     pub fn as_path(&self) -> Option<&Path> {
         match self {
-            TypeBound::Path(p) => Some(p),
+            TypeBound::Path(p) | TypeBound::ForLifetime(_, p) => Some(p),
             _ => None,
         }
     }
@@ -165,12 +165,15 @@ pub mod known {
         result,
         boxed,
         option,
+        convert,
         // Components of known path (type name)
         Iterator,
         IntoIterator,
         Item,
         Try,
         Ok,
+        Error,
+        From,
         Future,
         Result,
         Option,
@@ -190,6 +193,11 @@ pub mod known {
         filter_map,
         next,
         iter_mut,
+        transmute,
+        call,
+        call_mut,
+        call_once,
+        main,
         // Builtin macros
         file,
         column,
@@ -3,7 +3,7 @@
 //! reference to a type with the field `bar`. This is an approximation of the
 //! logic in rustc (which lives in librustc_typeck/check/autoderef.rs).
 
-use std::iter::successors;
+use std::sync::Arc;
 
 use base_db::CrateId;
 use hir_def::lang_item::LangItemTarget;
@@ -12,9 +12,9 @@ use log::{info, warn};
 
 use crate::{
     db::HirDatabase,
-    traits::{InEnvironment, Solution},
+    traits::{solve_implements, Implements, InEnvironment, Solution},
     utils::generics,
-    BoundVar, Canonical, DebruijnIndex, Obligation, Substs, TraitRef, Ty,
+    BoundVar, Canonical, DebruijnIndex, Obligation, Substs, TraitEnvironment, TraitRef, Ty,
 };
 
 const AUTODEREF_RECURSION_LIMIT: usize = 10;
@@ -24,11 +24,57 @@ pub fn autoderef<'a>(
     krate: Option<CrateId>,
     ty: InEnvironment<Canonical<Ty>>,
 ) -> impl Iterator<Item = Canonical<Ty>> + 'a {
+    autoderef_iter(db, krate, ty)
+}
+
+/// Like `autoderef`, but returns the iterator type directly. Each deref step
+/// is only computed once it's requested, so callers that only look at the
+/// first few steps don't pay for solving the rest of the chain.
+pub fn autoderef_iter(
+    db: &dyn HirDatabase,
+    krate: Option<CrateId>,
+    ty: InEnvironment<Canonical<Ty>>,
+) -> Autoderef<'_> {
     let InEnvironment { value: ty, environment } = ty;
-    successors(Some(ty), move |ty| {
-        deref(db, krate?, InEnvironment { value: ty, environment: environment.clone() })
-    })
-    .take(AUTODEREF_RECURSION_LIMIT)
+    Autoderef { db, krate, environment, next: AutoderefStep::Start(ty), steps: 0 }
+}
+
+pub struct Autoderef<'a> {
+    db: &'a dyn HirDatabase,
+    krate: Option<CrateId>,
+    environment: Arc<TraitEnvironment>,
+    next: AutoderefStep,
+    steps: usize,
+}
+
+enum AutoderefStep {
+    /// The type we started with, which hasn't been returned yet.
+    Start(Canonical<Ty>),
+    /// The last returned type; the next one is found by dereferencing it.
+    Deref(Canonical<Ty>),
+    Done,
+}
+
+impl Iterator for Autoderef<'_> {
+    type Item = Canonical<Ty>;
+
+    fn next(&mut self) -> Option<Canonical<Ty>> {
+        if self.steps >= AUTODEREF_RECURSION_LIMIT {
+            return None;
+        }
+        let ty = match std::mem::replace(&mut self.next, AutoderefStep::Done) {
+            AutoderefStep::Start(ty) => ty,
+            AutoderefStep::Deref(prev) => {
+                self.db.check_canceled();
+                let environment = self.environment.clone();
+                deref(self.db, self.krate?, InEnvironment { value: &prev, environment })?
+            }
+            AutoderefStep::Done => return None,
+        };
+        self.steps += 1;
+        self.next = AutoderefStep::Deref(ty.clone());
+        Some(ty)
+    }
 }
 
 pub(crate) fn deref(
@@ -75,7 +121,7 @@ fn deref_by_trait(
             environment: ty.environment.clone(),
         },
     };
-    if db.trait_solve(krate, implements_goal).is_none() {
+    if solve_implements(db, krate, implements_goal) == Implements::No {
         return None;
     }
 
@@ -125,7 +171,7 @@ fn deref_by_trait(
                 kinds: vars.0.kinds.clone(),
             })
         }
-        Solution::Ambig(_) => {
+        Solution::Ambig(_) | Solution::Overflow => {
             info!("Ambiguous solution for derefing {:?}: {:?}", ty.value, solution);
             None
         }
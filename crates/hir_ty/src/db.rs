@@ -4,21 +4,38 @@ use std::sync::Arc;
 
 use base_db::{impl_intern_key, salsa, CrateId, Upcast};
 use hir_def::{
-    db::DefDatabase, expr::ExprId, ConstParamId, DefWithBodyId, FunctionId, GenericDefId, ImplId,
-    LocalFieldId, TypeParamId, VariantId,
+    db::DefDatabase, expr::ExprId, AdtId, ConstParamId, DefWithBodyId, FieldId, FunctionId,
+    GenericDefId, ImplId, LocalFieldId, TraitId, TypeAliasId, TypeParamId, VariantId,
 };
 use la_arena::ArenaMap;
 
 use crate::{
+    diagnostics::BodyDiagnostic,
     method_resolution::{InherentImpls, TraitImpls},
+    object_safety::ObjectSafetyViolation,
     traits::chalk,
     Binders, CallableDefId, GenericPredicate, InferenceResult, OpaqueTyId, PolyFnSig,
-    ReturnTypeImplTraits, TraitRef, Ty, TyDefId, ValueTyDefId,
+    ReturnTypeImplTraits, Substs, TraitEnvironment, TraitRef, Ty, TyDefId, ValueTyDefId,
 };
 use hir_expand::name::Name;
 
+pub use crate::{
+    method_resolution::DEFAULT_METHOD_RESOLUTION_STEP_LIMIT,
+    traits::{TraitSolverConfig, TRAIT_SOLVE_LRU_CAP},
+};
+
 #[salsa::query_group(HirDatabaseStorage)]
 pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
+    /// The maximum number of steps (autoderef steps, candidate impls and
+    /// traits) a single method lookup may take before giving up. See
+    /// `method_resolution::DEFAULT_METHOD_RESOLUTION_STEP_LIMIT`.
+    #[salsa::input]
+    fn method_resolution_step_limit(&self) -> usize;
+
+    /// Limits for the Chalk solver; see `TraitSolverConfig`.
+    #[salsa::input]
+    fn trait_solver_config(&self) -> TraitSolverConfig;
+
     #[salsa::invoke(infer_wait)]
     #[salsa::transparent]
     fn infer(&self, def: DefWithBodyId) -> Arc<InferenceResult>;
@@ -26,6 +43,11 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::invoke(crate::infer::infer_query)]
     fn infer_query(&self, def: DefWithBodyId) -> Arc<InferenceResult>;
 
+    /// All diagnostics for a body, from inference as well as the later checks.
+    /// Unlike the `InferenceResult`, this only changes when the diagnostics do.
+    #[salsa::invoke(crate::diagnostics::infer_diagnostics_query)]
+    fn infer_diagnostics(&self, def: DefWithBodyId) -> Arc<[BodyDiagnostic]>;
+
     #[salsa::invoke(crate::lower::ty_query)]
     #[salsa::cycle(crate::lower::ty_recover)]
     fn ty(&self, def: TyDefId) -> Binders<Ty>;
@@ -43,9 +65,15 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::invoke(crate::lower::impl_trait_query)]
     fn impl_trait(&self, def: ImplId) -> Option<Binders<TraitRef>>;
 
+    #[salsa::invoke(crate::lower::impl_assoc_ty_query)]
+    fn impl_assoc_ty(&self, impl_id: ImplId, assoc: TypeAliasId) -> Binders<Ty>;
+
     #[salsa::invoke(crate::lower::field_types_query)]
     fn field_types(&self, var: VariantId) -> Arc<ArenaMap<LocalFieldId, Binders<Ty>>>;
 
+    #[salsa::invoke(crate::diagnostics::recursive_field_without_indirection_query)]
+    fn recursive_field_without_indirection(&self, adt: AdtId) -> Option<FieldId>;
+
     #[salsa::invoke(crate::callable_item_sig)]
     fn callable_item_signature(&self, def: CallableDefId) -> PolyFnSig;
 
@@ -55,6 +83,13 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
         def: FunctionId,
     ) -> Option<Arc<Binders<ReturnTypeImplTraits>>>;
 
+    /// The concrete type behind the `idx`th `impl Trait` in the return type
+    /// of `def`, as determined by the values the function returns. `None` if
+    /// these disagree or the type is defined in terms of itself. Type
+    /// parameters of the function are placeholders, like in its body.
+    #[salsa::invoke(crate::infer::return_type_impl_trait_hidden_ty_query)]
+    fn return_type_impl_trait_hidden_ty(&self, def: FunctionId, idx: u16) -> Option<Ty>;
+
     #[salsa::invoke(crate::lower::generic_predicates_for_param_query)]
     #[salsa::cycle(crate::lower::generic_predicates_for_param_recover)]
     fn generic_predicates_for_param(
@@ -62,12 +97,23 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
         param_id: TypeParamId,
     ) -> Arc<[Binders<GenericPredicate>]>;
 
+    #[salsa::invoke(crate::lower::all_transitive_bounds_query)]
+    fn all_transitive_bounds(&self, param_id: TypeParamId) -> Arc<[Binders<GenericPredicate>]>;
+
     #[salsa::invoke(crate::lower::generic_predicates_query)]
     fn generic_predicates(&self, def: GenericDefId) -> Arc<[Binders<GenericPredicate>]>;
 
+    #[salsa::invoke(crate::lower::trait_environment_query)]
+    fn trait_environment(&self, def: GenericDefId) -> Arc<TraitEnvironment>;
+
     #[salsa::invoke(crate::lower::generic_defaults_query)]
     fn generic_defaults(&self, def: GenericDefId) -> Arc<[Binders<Ty>]>;
 
+    /// The defaults of `def`'s parameters applied to each other, with
+    /// `Ty::Unknown` for the parameters that don't have a default.
+    #[salsa::invoke(crate::lower::generic_defaults_substs_query)]
+    fn generic_defaults_substs(&self, def: GenericDefId) -> Substs;
+
     #[salsa::invoke(InherentImpls::inherent_impls_in_crate_query)]
     fn inherent_impls_in_crate(&self, krate: CrateId) -> Arc<InherentImpls>;
 
@@ -77,6 +123,17 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
     #[salsa::invoke(TraitImpls::trait_impls_in_deps_query)]
     fn trait_impls_in_deps(&self, krate: CrateId) -> Arc<TraitImpls>;
 
+    /// The impls of `trait_` in `krate` and its dependencies that don't
+    /// violate the orphan rules, i.e. the ones that can apply in `krate`.
+    #[salsa::invoke(TraitImpls::relevant_impls_for_trait_query)]
+    fn relevant_impls_for_trait(&self, krate: CrateId, trait_: TraitId) -> Arc<TraitImpls>;
+
+    #[salsa::invoke(crate::method_resolution::all_impls_of_trait_query)]
+    fn all_impls_of_trait(&self, trait_: TraitId) -> Vec<ImplId>;
+
+    #[salsa::invoke(crate::object_safety::object_safety_violations_query)]
+    fn object_safety_violations(&self, trait_: TraitId) -> Arc<[ObjectSafetyViolation]>;
+
     // Interned IDs for Chalk integration
     #[salsa::interned]
     fn intern_callable_def(&self, callable_def: CallableDefId) -> InternedCallableDefId;
@@ -115,13 +172,21 @@ pub trait HirDatabase: DefDatabase + Upcast<dyn DefDatabase> {
         id: chalk::AssociatedTyValueId,
     ) -> Arc<chalk::AssociatedTyValue>;
 
-    #[salsa::invoke(crate::traits::trait_solve_query)]
+    #[salsa::invoke(crate::traits::trait_solve)]
+    #[salsa::transparent]
     fn trait_solve(
         &self,
         krate: CrateId,
         goal: crate::Canonical<crate::InEnvironment<crate::Obligation>>,
     ) -> Option<crate::traits::Solution>;
 
+    #[salsa::invoke(crate::traits::trait_solve_query)]
+    fn trait_solve_query(
+        &self,
+        krate: CrateId,
+        goal: crate::Canonical<crate::InEnvironment<crate::Obligation>>,
+    ) -> Option<crate::traits::Solution>;
+
     #[salsa::invoke(crate::traits::chalk::program_clauses_for_chalk_env_query)]
     fn program_clauses_for_chalk_env(
         &self,
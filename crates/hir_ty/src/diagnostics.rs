@@ -3,19 +3,22 @@ mod expr;
 mod match_check;
 mod unsafe_check;
 mod decl_check;
+mod recursive_type;
+mod const_check;
 
-use std::{any::Any, fmt};
+use std::{any::Any, fmt, sync::Arc};
 
 use base_db::CrateId;
 use hir_def::{DefWithBodyId, ModuleDefId};
-use hir_expand::diagnostics::{Diagnostic, DiagnosticCode, DiagnosticSink};
+use hir_expand::diagnostics::{Diagnostic, DiagnosticCode, DiagnosticSink, DiagnosticSinkBuilder};
 use hir_expand::{name::Name, HirFileId, InFile};
-use stdx::format_to;
+use stdx::{format_to, never};
 use syntax::{ast, AstPtr, SyntaxNodePtr};
 
-use crate::db::HirDatabase;
+use crate::{db::HirDatabase, CallableDefId, TryFailureReason, Ty};
 
 pub use crate::diagnostics::expr::{record_literal_missing_fields, record_pattern_missing_fields};
+pub(crate) use crate::diagnostics::recursive_type::recursive_field_without_indirection_query;
 
 pub fn validate_module_item(
     db: &dyn HirDatabase,
@@ -30,21 +33,127 @@ pub fn validate_module_item(
 
 pub fn validate_body(db: &dyn HirDatabase, owner: DefWithBodyId, sink: &mut DiagnosticSink<'_>) {
     let _p = profile::span("validate_body");
+    for diagnostic in db.infer_diagnostics(owner).iter() {
+        sink.push(diagnostic.clone());
+    }
+}
+
+pub(crate) fn infer_diagnostics_query(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+) -> Arc<[BodyDiagnostic]> {
+    let _p = profile::span("infer_diagnostics_query");
+    let mut diagnostics = Vec::new();
+    let mut sink = DiagnosticSinkBuilder::new().build(|d| {
+        diagnostics.extend(BodyDiagnostic::from_diagnostic(d));
+    });
+
     let infer = db.infer(owner);
-    infer.add_diagnostics(db, owner, sink);
-    let mut validator = expr::ExprValidator::new(owner, infer.clone(), sink);
+    infer.add_diagnostics(db, owner, &mut sink);
+    let mut validator = expr::ExprValidator::new(owner, infer.clone(), &mut sink);
+    validator.validate_body(db);
+    let mut validator = unsafe_check::UnsafeValidator::new(owner, infer.clone(), &mut sink);
     validator.validate_body(db);
-    let mut validator = unsafe_check::UnsafeValidator::new(owner, infer, sink);
+    let mut validator = const_check::ConstValidator::new(owner, infer, &mut sink);
     validator.validate_body(db);
+
+    drop(sink);
+    diagnostics.into()
+}
+
+macro_rules! body_diagnostics {
+    ($($diagnostic:ident),* $(,)?) => {
+        /// A diagnostic found by checking a function, const or static body.
+        ///
+        /// This is the closed set of diagnostics that `infer_diagnostics` stores
+        /// in salsa. It implements `Diagnostic` by delegating to the wrapped
+        /// diagnostic, so sinks can still react to the concrete types.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum BodyDiagnostic {
+            $($diagnostic($diagnostic),)*
+        }
+
+        impl BodyDiagnostic {
+            fn from_diagnostic(d: &dyn Diagnostic) -> Option<BodyDiagnostic> {
+                $(
+                    if let Some(it) = d.as_any().downcast_ref::<$diagnostic>() {
+                        return Some(BodyDiagnostic::$diagnostic(it.clone()));
+                    }
+                )*
+                never!(true, "diagnostic `{}` is missing from `body_diagnostics!`", d.code().0);
+                None
+            }
+
+            pub fn as_diagnostic(&self) -> &dyn Diagnostic {
+                match self {
+                    $(BodyDiagnostic::$diagnostic(it) => it,)*
+                }
+            }
+        }
+    };
+}
+
+body_diagnostics![
+    NoSuchField,
+    PrivateField,
+    MissingFields,
+    MissingPatFields,
+    MissingMatchArms,
+    MissingOkOrSomeInTailExpr,
+    RemoveThisSemicolon,
+    BreakOutsideOfLoop,
+    RequiresCopy,
+    RecursionLimitReached,
+    MissingUnsafe,
+    UnusedUnsafe,
+    MismatchedArgCount,
+    GenericArgsCountMismatch,
+    UnresolvedMethodCall,
+    ReplaceFilterMapNextWithFindMap,
+    MissingAwait,
+    IdentityCast,
+    LosslessCast,
+    InvalidCast,
+    NonConstFnInConstContext,
+    IncorrectTryExpr,
+    UseOfMovedValue,
+    InvalidMainReturnType,
+    UnreachableCode,
+    TypeMismatch,
+];
+
+impl Diagnostic for BodyDiagnostic {
+    fn code(&self) -> DiagnosticCode {
+        self.as_diagnostic().code()
+    }
+    fn message(&self) -> String {
+        self.as_diagnostic().message()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        self.as_diagnostic().display_source()
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self.as_diagnostic().as_any()
+    }
+    fn is_experimental(&self) -> bool {
+        self.as_diagnostic().is_experimental()
+    }
 }
 
 // Diagnostic: no-such-field
 //
-// This diagnostic is triggered if created structure does not have field provided in record.
-#[derive(Debug)]
+// This diagnostic is triggered if created structure does not have field provided in record,
+// or if a field access names a field the receiver doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NoSuchField {
     pub file: HirFileId,
-    pub field: AstPtr<ast::RecordExprField>,
+    /// Either an `ast::RecordExprField` or an `ast::FieldExpr`.
+    pub field: SyntaxNodePtr,
+    pub name: Name,
+    /// The existing field whose name is closest to `name`, if any is close.
+    pub similar_field: Option<Name>,
+    /// Whether the receiver of a field access has a method called `name`.
+    pub method_exists: bool,
 }
 
 impl Diagnostic for NoSuchField {
@@ -53,11 +162,52 @@ impl Diagnostic for NoSuchField {
     }
 
     fn message(&self) -> String {
-        "no such field".to_string()
+        let mut message = "no such field".to_string();
+        if self.method_exists {
+            format_to!(message, ", `{}` is a method", self.name);
+        } else if let Some(similar_field) = &self.similar_field {
+            format_to!(message, ", did you mean `{}`?", similar_field);
+        }
+        message
     }
 
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
-        InFile::new(self.file, self.field.clone().into())
+        InFile::new(self.file, self.field.clone())
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: private-field
+//
+// This diagnostic is triggered if a field access names a field that isn't visible from where it's
+// accessed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateField {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub name: Name,
+    /// Whether the receiver also has a method called `name`.
+    pub method_exists: bool,
+}
+
+impl Diagnostic for PrivateField {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("private-field")
+    }
+
+    fn message(&self) -> String {
+        let mut message = format!("field `{}` is private", self.name);
+        if self.method_exists {
+            format_to!(message, ", `{}` is a method", self.name);
+        }
+        message
+    }
+
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile::new(self.file, self.expr.clone().into())
     }
 
     fn as_any(&self) -> &(dyn Any + Send + 'static) {
@@ -76,7 +226,7 @@ impl Diagnostic for NoSuchField {
 //
 // let a = A { a: 10 };
 // ```
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MissingFields {
     pub file: HirFileId,
     pub field_list_parent: AstPtr<ast::RecordExpr>,
@@ -127,7 +277,7 @@ impl Diagnostic for MissingFields {
 //     // ...
 // }
 // ```
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MissingPatFields {
     pub file: HirFileId,
     pub field_list_parent: AstPtr<ast::RecordPat>,
@@ -164,11 +314,13 @@ impl Diagnostic for MissingPatFields {
 // Diagnostic: missing-match-arm
 //
 // This diagnostic is triggered if `match` block is missing one or more match arms.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MissingMatchArms {
     pub file: HirFileId,
     pub match_expr: AstPtr<ast::Expr>,
     pub arms: AstPtr<ast::MatchArmList>,
+    /// Values which aren't matched by any arm, if we know them.
+    pub witnesses: Vec<String>,
 }
 
 impl Diagnostic for MissingMatchArms {
@@ -176,7 +328,23 @@ impl Diagnostic for MissingMatchArms {
         DiagnosticCode("missing-match-arm")
     }
     fn message(&self) -> String {
-        String::from("Missing match arm")
+        const LIMIT: usize = 3;
+        let quoted = |witness: &String| format!("`{}`", witness);
+        let witnesses = match self.witnesses.as_slice() {
+            [] => return String::from("Missing match arm"),
+            [witness] => quoted(witness),
+            [init @ .., last] if self.witnesses.len() <= LIMIT => format!(
+                "{} and {}",
+                init.iter().map(quoted).collect::<Vec<_>>().join(", "),
+                quoted(last)
+            ),
+            witnesses => format!(
+                "{} and {} more",
+                witnesses[..LIMIT].iter().map(quoted).collect::<Vec<_>>().join(", "),
+                witnesses.len() - LIMIT
+            ),
+        };
+        format!("Missing match arm: {} not covered", witnesses)
     }
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
         InFile { file_id: self.file, value: self.match_expr.clone().into() }
@@ -198,7 +366,7 @@ impl Diagnostic for MissingMatchArms {
 //     10
 // }
 // ```
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MissingOkOrSomeInTailExpr {
     pub file: HirFileId,
     pub expr: AstPtr<ast::Expr>,
@@ -221,45 +389,684 @@ impl Diagnostic for MissingOkOrSomeInTailExpr {
     }
 }
 
-#[derive(Debug)]
-pub struct RemoveThisSemicolon {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoveThisSemicolon {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for RemoveThisSemicolon {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("remove-this-semicolon")
+    }
+
+    fn message(&self) -> String {
+        "Remove this semicolon".to_string()
+    }
+
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: break-outside-of-loop
+//
+// This diagnostic is triggered if the `break` keyword is used outside of a loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakOutsideOfLoop {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for BreakOutsideOfLoop {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("break-outside-of-loop")
+    }
+    fn message(&self) -> String {
+        "break outside of loop".to_string()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: requires-copy
+//
+// This diagnostic is triggered if the element of an array repeat expression like `[x; N]` is not `Copy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiresCopy {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for RequiresCopy {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("requires-copy")
+    }
+    fn message(&self) -> String {
+        "the element of an array repeat expression must be `Copy`".to_string()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: recursion-limit-reached
+//
+// This diagnostic is triggered if the trait solver gives up on a requirement of a body, e.g.
+// because of impls whose where clauses recurse without end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecursionLimitReached {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub requirement: String,
+}
+
+impl Diagnostic for RecursionLimitReached {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("recursion-limit-reached")
+    }
+    fn message(&self) -> String {
+        format!("reached the recursion limit while checking `{}`", self.requirement)
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: missing-unsafe
+//
+// This diagnostic is triggered if an operation marked as `unsafe` is used outside of an `unsafe` function or block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingUnsafe {
+    pub file: HirFileId,
+    /// The unsafe expression, or the pattern destructuring a union.
+    pub node: SyntaxNodePtr,
+}
+
+impl Diagnostic for MissingUnsafe {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("missing-unsafe")
+    }
+    fn message(&self) -> String {
+        format!("This operation is unsafe and requires an unsafe function or block")
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.node.clone() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: unused-unsafe
+//
+// This diagnostic is triggered if an `unsafe` block doesn't contain any unsafe operations of its
+// own. Operations are attributed to the innermost `unsafe` block around them.
+//
+// Example:
+//
+// ```rust
+// fn foo() { unsafe { 92 }; }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedUnsafe {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for UnusedUnsafe {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("unused-unsafe")
+    }
+    fn message(&self) -> String {
+        "unnecessary `unsafe` block".to_string()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: mismatched-arg-count
+//
+// This diagnostic is triggered if a function, closure or tuple struct constructor is invoked with
+// an incorrect amount of arguments. For method calls, the receiver isn't counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MismatchedArgCount {
+    pub file: HirFileId,
+    pub call_expr: AstPtr<ast::Expr>,
+    pub expected: usize,
+    pub found: usize,
+    /// Whether the callee is C-variadic, so `expected` is only the minimum.
+    pub is_varargs: bool,
+    /// The called function or constructor, if the callee is one (and not e.g.
+    /// a closure or function pointer).
+    pub callee: Option<CallableDefId>,
+}
+
+impl Diagnostic for MismatchedArgCount {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("mismatched-arg-count")
+    }
+    fn message(&self) -> String {
+        let s = if self.expected == 1 { "" } else { "s" };
+        let at_least = if self.is_varargs { "at least " } else { "" };
+        format!("Expected {}{} argument{}, found {}", at_least, self.expected, s, self.found)
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.call_expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+    fn is_experimental(&self) -> bool {
+        true
+    }
+}
+
+// Diagnostic: generic-args-count-mismatch
+//
+// This diagnostic is triggered if a type or value path is given more generic arguments than the
+// item has type parameters, or fewer than it has type parameters without defaults.
+//
+// Example:
+//
+// ```rust
+// struct S<T>(T);
+// fn foo(s: S<u32, u32>) {}
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericArgsCountMismatch {
+    pub file: HirFileId,
+    pub node: SyntaxNodePtr,
+    pub expected: usize,
+    pub provided: usize,
+    /// How many of the expected arguments could be left out because their
+    /// parameters have defaults.
+    pub defaults: usize,
+}
+
+impl Diagnostic for GenericArgsCountMismatch {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("generic-args-count-mismatch")
+    }
+    fn message(&self) -> String {
+        let (qualifier, count) = if self.defaults == 0 {
+            ("", self.expected)
+        } else if self.provided > self.expected {
+            ("at most ", self.expected)
+        } else {
+            ("at least ", self.expected - self.defaults)
+        };
+        let s = if count == 1 { "" } else { "s" };
+        format!("Expected {}{} generic argument{}, found {}", qualifier, count, s, self.provided)
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.node.clone() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: unresolved-method-call
+//
+// This diagnostic is triggered if a method call can't be resolved. If a trait in scope has a
+// method with that name, but the receiver doesn't implement the trait, the unsatisfied bound is
+// mentioned.
+//
+// Example:
+//
+// ```rust
+// fn foo<T>(t: T) { t.clone(); }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedMethodCall {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub receiver: String,
+    pub method_name: Name,
+    pub unsatisfied_bound: Option<String>,
+}
+
+impl Diagnostic for UnresolvedMethodCall {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("unresolved-method-call")
+    }
+    fn message(&self) -> String {
+        match &self.unsatisfied_bound {
+            Some(bound) => format!(
+                "the method `{}` exists for `{}`, but `{}` is not satisfied",
+                self.method_name, self.receiver, bound
+            ),
+            None => format!("no method named `{}` found for `{}`", self.method_name, self.receiver),
+        }
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+    fn is_experimental(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub enum CaseType {
+    // `some_var`
+    LowerSnakeCase,
+    // `SOME_CONST`
+    UpperSnakeCase,
+    // `SomeStruct`
+    UpperCamelCase,
+}
+
+impl fmt::Display for CaseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            CaseType::LowerSnakeCase => "snake_case",
+            CaseType::UpperSnakeCase => "UPPER_SNAKE_CASE",
+            CaseType::UpperCamelCase => "CamelCase",
+        };
+
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Debug)]
+pub enum IdentType {
+    Argument,
+    Constant,
+    Enum,
+    Field,
+    Function,
+    StaticVariable,
+    Structure,
+    Variable,
+    Variant,
+}
+
+impl fmt::Display for IdentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            IdentType::Argument => "Argument",
+            IdentType::Constant => "Constant",
+            IdentType::Enum => "Enum",
+            IdentType::Field => "Field",
+            IdentType::Function => "Function",
+            IdentType::StaticVariable => "Static variable",
+            IdentType::Structure => "Structure",
+            IdentType::Variable => "Variable",
+            IdentType::Variant => "Variant",
+        };
+
+        write!(f, "{}", repr)
+    }
+}
+
+// Diagnostic: incorrect-ident-case
+//
+// This diagnostic is triggered if an item name doesn't follow https://doc.rust-lang.org/1.0.0/style/style/naming/README.html[Rust naming convention].
+#[derive(Debug)]
+pub struct IncorrectCase {
+    pub file: HirFileId,
+    pub ident: AstPtr<ast::Name>,
+    pub expected_case: CaseType,
+    pub ident_type: IdentType,
+    pub ident_text: String,
+    pub suggested_text: String,
+}
+
+impl Diagnostic for IncorrectCase {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("incorrect-ident-case")
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{} `{}` should have {} name, e.g. `{}`",
+            self.ident_type,
+            self.ident_text,
+            self.expected_case.to_string(),
+            self.suggested_text
+        )
+    }
+
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile::new(self.file, self.ident.clone().into())
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+
+    fn is_experimental(&self) -> bool {
+        true
+    }
+}
+
+// Diagnostic: replace-filter-map-next-with-find-map
+//
+// This diagnostic is triggered when `.filter_map(..).next()` is used, rather than the more concise `.find_map(..)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceFilterMapNextWithFindMap {
+    pub file: HirFileId,
+    /// This expression is the whole method chain up to and including `.filter_map(..).next()`.
+    pub next_expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for ReplaceFilterMapNextWithFindMap {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("replace-filter-map-next-with-find-map")
+    }
+    fn message(&self) -> String {
+        "replace filter_map(..).next() with find_map(..)".to_string()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.next_expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: recursive-type-without-indirection
+//
+// This diagnostic is triggered if a type contains itself without any indirection, which would
+// make it infinitely sized.
+//
+// Example:
+//
+// ```rust
+// struct S { s: S }
+// ```
+#[derive(Debug)]
+pub struct RecursiveTypeWithoutIndirection {
+    pub file: HirFileId,
+    pub field_ty: AstPtr<ast::Type>,
+    pub adt_name: Name,
+}
+
+impl Diagnostic for RecursiveTypeWithoutIndirection {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("recursive-type-without-indirection")
+    }
+    fn message(&self) -> String {
+        format!(
+            "recursive type `{}` has infinite size; insert some indirection (e.g. a `Box`) to make it representable",
+            self.adt_name
+        )
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.field_ty.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: missing-await
+//
+// This diagnostic is triggered if a future is created inside an `async` function or block, but
+// dropped without being `.await`ed.
+//
+// Example:
+//
+// ```rust
+// async fn foo() {}
+// async fn bar() { foo(); }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingAwait {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub future_ty: Ty,
+}
+
+impl Diagnostic for MissingAwait {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("missing-await")
+    }
+    fn message(&self) -> String {
+        "futures do nothing unless you `.await` them".to_string()
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: identity-cast
+//
+// This diagnostic is triggered if a numeric value is cast to the type it already has.
+//
+// Example:
+//
+// ```rust
+// fn foo(x: u32) -> u32 { x as u32 }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityCast {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub ty: Ty,
+}
+
+impl Diagnostic for IdentityCast {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("identity-cast")
+    }
+    fn message(&self) -> String {
+        match self.ty.as_builtin() {
+            Some(ty) => format!("casting to the same type `{}` is unnecessary", ty),
+            None => "casting to the same type is unnecessary".to_string(),
+        }
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: lossless-cast
+//
+// This diagnostic is triggered if a numeric value is cast with `as` to a type that can represent
+// every value of the source type, so that `From` could be used instead.
+//
+// Example:
+//
+// ```rust
+// fn foo(x: u8) -> u32 { x as u32 }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LosslessCast {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub from: Ty,
+    pub to: Ty,
+}
+
+impl Diagnostic for LosslessCast {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("lossless-cast")
+    }
+    fn message(&self) -> String {
+        match (self.from.as_builtin(), self.to.as_builtin()) {
+            (Some(from), Some(to)) => {
+                format!("casting `{}` to `{}` is lossless, consider using `{}::from`", from, to, to)
+            }
+            _ => "this cast is lossless, consider using `From`".to_string(),
+        }
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: invalid-cast
+//
+// This diagnostic is triggered if an `as` cast is one that the compiler rejects, like casting a
+// struct to an integer, an integer to `bool`, or a reference to a slice straight to a thin raw
+// pointer.
+//
+// Example:
+//
+// ```rust
+// fn foo(x: u32) -> bool { x as bool }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCast {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub kind: InvalidCastKind,
+    pub from: String,
+    pub to: String,
+}
+
+/// Why an `as` cast is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidCastKind {
+    /// A struct, union, tuple or array cast to a primitive type.
+    NonPrimitive,
+    /// A cast whose target is a struct, enum or union.
+    ToAdt,
+    /// A number or `char` cast to `bool`.
+    ToBool,
+    /// Anything but `u8` cast to `char`.
+    ToChar,
+    /// A reference to a slice, `str` or trait object cast to a raw pointer to a sized type.
+    FatToThinPtr,
+}
+
+impl Diagnostic for InvalidCast {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode("invalid-cast")
+    }
+    fn message(&self) -> String {
+        match self.kind {
+            InvalidCastKind::NonPrimitive => {
+                format!("non-primitive cast: `{}` as `{}`", self.from, self.to)
+            }
+            InvalidCastKind::ToAdt => format!(
+                "non-primitive cast: `{}` as `{}`, `as` can't construct a struct, enum or union",
+                self.from, self.to
+            ),
+            InvalidCastKind::ToBool => {
+                format!("cannot cast `{}` as `bool`, compare with zero instead", self.from)
+            }
+            InvalidCastKind::ToChar => {
+                format!("only `u8` can be cast as `char`, not `{}`", self.from)
+            }
+            InvalidCastKind::FatToThinPtr => format!(
+                "casting `{}` as `{}` is invalid, cast through a raw pointer first",
+                self.from, self.to
+            ),
+        }
+    }
+    fn display_source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.clone().into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+// Diagnostic: non-const-fn-in-const-context
+//
+// This diagnostic is triggered if a function that isn't `const` is called in a const context,
+// like the initializer of a `const` or `static`, the body of a `const fn` or an array length.
+//
+// Example:
+//
+// ```rust
+// fn one() -> u32 { 1 }
+// const ONE: u32 = one();
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonConstFnInConstContext {
     pub file: HirFileId,
     pub expr: AstPtr<ast::Expr>,
+    pub func: Name,
 }
 
-impl Diagnostic for RemoveThisSemicolon {
+impl Diagnostic for NonConstFnInConstContext {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("remove-this-semicolon")
+        DiagnosticCode("non-const-fn-in-const-context")
     }
-
     fn message(&self) -> String {
-        "Remove this semicolon".to_string()
+        format!("cannot call non-const fn `{}` in a const context", self.func)
     }
-
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
         InFile { file_id: self.file, value: self.expr.clone().into() }
     }
-
     fn as_any(&self) -> &(dyn Any + Send + 'static) {
         self
     }
 }
 
-// Diagnostic: break-outside-of-loop
+// Diagnostic: incorrect-try-expr
 //
-// This diagnostic is triggered if the `break` keyword is used outside of a loop.
-#[derive(Debug)]
-pub struct BreakOutsideOfLoop {
+// This diagnostic is triggered if the `?` operator is applied to a value that doesn't implement
+// `Try`, or used in a function whose return type doesn't implement `Try` or can't take the error
+// of the operand.
+//
+// Example:
+//
+// ```rust
+// fn foo(x: Option<u32>) -> Result<u32, ()> { Ok(x?) }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncorrectTryExpr {
     pub file: HirFileId,
     pub expr: AstPtr<ast::Expr>,
+    pub reason: TryFailureReason,
+    pub operand_ty: String,
+    pub return_ty: String,
 }
 
-impl Diagnostic for BreakOutsideOfLoop {
+impl Diagnostic for IncorrectTryExpr {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("break-outside-of-loop")
+        DiagnosticCode("incorrect-try-expr")
     }
     fn message(&self) -> String {
-        "break outside of loop".to_string()
+        match self.reason {
+            TryFailureReason::OperandNotTry => {
+                format!("the `?` operator can't be applied to type `{}`", self.operand_ty)
+            }
+            TryFailureReason::ErrorNotConvertible => format!(
+                "the `?` operator on `{}` can't return early with `{}`",
+                self.operand_ty, self.return_ty
+            ),
+            TryFailureReason::ReturnTypeNotTry => format!(
+                "the `?` operator can only return early with `Result` or `Option`, not `{}`",
+                self.return_ty
+            ),
+        }
     }
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
         InFile { file_id: self.file, value: self.expr.clone().into() }
@@ -269,21 +1076,28 @@ impl Diagnostic for BreakOutsideOfLoop {
     }
 }
 
-// Diagnostic: missing-unsafe
+// Diagnostic: unreachable-code
 //
-// This diagnostic is triggered if an operation marked as `unsafe` is used outside of an `unsafe` function or block.
-#[derive(Debug)]
-pub struct MissingUnsafe {
+// This diagnostic is triggered if a statement or the tail expression of a block comes after
+// something that always diverges, like a `return` or a call of a function returning `!`.
+//
+// Example:
+//
+// ```rust
+// fn foo() { return; bar(); }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableCode {
     pub file: HirFileId,
     pub expr: AstPtr<ast::Expr>,
 }
 
-impl Diagnostic for MissingUnsafe {
+impl Diagnostic for UnreachableCode {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("missing-unsafe")
+        DiagnosticCode("unreachable-code")
     }
     fn message(&self) -> String {
-        format!("This operation is unsafe and requires an unsafe function or block")
+        "unreachable code".to_string()
     }
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
         InFile { file_id: self.file, value: self.expr.clone().into() }
@@ -293,159 +1107,116 @@ impl Diagnostic for MissingUnsafe {
     }
 }
 
-// Diagnostic: mismatched-arg-count
+// Diagnostic: invalid-main-return-type
 //
-// This diagnostic is triggered if a function is invoked with an incorrect amount of arguments.
-#[derive(Debug)]
-pub struct MismatchedArgCount {
+// This diagnostic is triggered if the `main` function of a crate returns a type that doesn't
+// implement `Termination`. Besides `()` and `!`, that's mostly `Result<(), E>` where `E: Debug`.
+//
+// Example:
+//
+// ```rust
+// fn main() -> u32 { 0 }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMainReturnType {
     pub file: HirFileId,
-    pub call_expr: AstPtr<ast::Expr>,
-    pub expected: usize,
-    pub found: usize,
+    pub ret_type: AstPtr<ast::RetType>,
+    pub ty: String,
 }
 
-impl Diagnostic for MismatchedArgCount {
+impl Diagnostic for InvalidMainReturnType {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("mismatched-arg-count")
+        DiagnosticCode("invalid-main-return-type")
     }
     fn message(&self) -> String {
-        let s = if self.expected == 1 { "" } else { "s" };
-        format!("Expected {} argument{}, found {}", self.expected, s, self.found)
+        format!("`main` has invalid return type `{}`", self.ty)
     }
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
-        InFile { file_id: self.file, value: self.call_expr.clone().into() }
+        InFile { file_id: self.file, value: self.ret_type.clone().into() }
     }
     fn as_any(&self) -> &(dyn Any + Send + 'static) {
         self
     }
-    fn is_experimental(&self) -> bool {
-        true
-    }
-}
-
-#[derive(Debug)]
-pub enum CaseType {
-    // `some_var`
-    LowerSnakeCase,
-    // `SOME_CONST`
-    UpperSnakeCase,
-    // `SomeStruct`
-    UpperCamelCase,
-}
-
-impl fmt::Display for CaseType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let repr = match self {
-            CaseType::LowerSnakeCase => "snake_case",
-            CaseType::UpperSnakeCase => "UPPER_SNAKE_CASE",
-            CaseType::UpperCamelCase => "CamelCase",
-        };
-
-        write!(f, "{}", repr)
-    }
-}
-
-#[derive(Debug)]
-pub enum IdentType {
-    Argument,
-    Constant,
-    Enum,
-    Field,
-    Function,
-    StaticVariable,
-    Structure,
-    Variable,
-    Variant,
-}
-
-impl fmt::Display for IdentType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let repr = match self {
-            IdentType::Argument => "Argument",
-            IdentType::Constant => "Constant",
-            IdentType::Enum => "Enum",
-            IdentType::Field => "Field",
-            IdentType::Function => "Function",
-            IdentType::StaticVariable => "Static variable",
-            IdentType::Structure => "Structure",
-            IdentType::Variable => "Variable",
-            IdentType::Variant => "Variant",
-        };
-
-        write!(f, "{}", repr)
-    }
 }
 
-// Diagnostic: incorrect-ident-case
+// Diagnostic: use-of-moved-value
 //
-// This diagnostic is triggered if an item name doesn't follow https://doc.rust-lang.org/1.0.0/style/style/naming/README.html[Rust naming convention].
-#[derive(Debug)]
-pub struct IncorrectCase {
+// This diagnostic is triggered if a binding whose type isn't `Copy` is used after its value was
+// moved away by passing it to a function or method by value.
+//
+// This diagnostic is experimental, since only the obvious cases in straight-line code are
+// checked.
+//
+// Example:
+//
+// ```rust
+// fn foo(s: String) { drop(s); drop(s); }
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UseOfMovedValue {
     pub file: HirFileId,
-    pub ident: AstPtr<ast::Name>,
-    pub expected_case: CaseType,
-    pub ident_type: IdentType,
-    pub ident_text: String,
-    pub suggested_text: String,
+    pub expr: AstPtr<ast::Expr>,
+    /// The expression that moved the value.
+    pub moved_at: InFile<AstPtr<ast::Expr>>,
+    pub name: Name,
 }
 
-impl Diagnostic for IncorrectCase {
+impl Diagnostic for UseOfMovedValue {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("incorrect-ident-case")
+        DiagnosticCode("use-of-moved-value")
     }
-
     fn message(&self) -> String {
-        format!(
-            "{} `{}` should have {} name, e.g. `{}`",
-            self.ident_type,
-            self.ident_text,
-            self.expected_case.to_string(),
-            self.suggested_text
-        )
+        format!("use of moved value `{}`", self.name)
     }
-
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
-        InFile::new(self.file, self.ident.clone().into())
+        InFile { file_id: self.file, value: self.expr.clone().into() }
     }
-
     fn as_any(&self) -> &(dyn Any + Send + 'static) {
         self
     }
-
     fn is_experimental(&self) -> bool {
         true
     }
 }
 
-// Diagnostic: replace-filter-map-next-with-find-map
+// Diagnostic: type-mismatch
 //
-// This diagnostic is triggered when `.filter_map(..).next()` is used, rather than the more concise `.find_map(..)`.
-#[derive(Debug)]
-pub struct ReplaceFilterMapNextWithFindMap {
+// This diagnostic is triggered if the type of an expression doesn't match the type its context
+// expects, like a function argument or the return value.
+//
+// This diagnostic is experimental, since type inference doesn't know about all coercions yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
     pub file: HirFileId,
-    /// This expression is the whole method chain up to and including `.filter_map(..).next()`.
-    pub next_expr: AstPtr<ast::Expr>,
+    pub expr: AstPtr<ast::Expr>,
+    pub expected: String,
+    pub actual: String,
 }
 
-impl Diagnostic for ReplaceFilterMapNextWithFindMap {
+impl Diagnostic for TypeMismatch {
     fn code(&self) -> DiagnosticCode {
-        DiagnosticCode("replace-filter-map-next-with-find-map")
+        DiagnosticCode("type-mismatch")
     }
     fn message(&self) -> String {
-        "replace filter_map(..).next() with find_map(..)".to_string()
+        format!("expected {}, found {}", self.expected, self.actual)
     }
     fn display_source(&self) -> InFile<SyntaxNodePtr> {
-        InFile { file_id: self.file, value: self.next_expr.clone().into() }
+        InFile { file_id: self.file, value: self.expr.clone().into() }
     }
     fn as_any(&self) -> &(dyn Any + Send + 'static) {
         self
     }
+    fn is_experimental(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use base_db::{fixture::WithFixture, FileId, SourceDatabase, SourceDatabaseExt};
-    use hir_def::{db::DefDatabase, AssocItemId, ModuleDefId};
+    use hir_def::{db::DefDatabase, AssocItemId, DefWithBodyId, ModuleDefId};
     use hir_expand::{
         db::AstDatabase,
         diagnostics::{Diagnostic, DiagnosticSinkBuilder},
@@ -454,8 +1225,10 @@ mod tests {
     use syntax::{TextRange, TextSize};
 
     use crate::{
+        db::HirDatabase,
         diagnostics::{validate_body, validate_module_item},
         test_db::TestDB,
+        traits::TraitSolverConfig,
     };
 
     impl TestDB {
@@ -464,14 +1237,17 @@ mod tests {
             for krate in crate_graph.iter() {
                 let crate_def_map = self.crate_def_map(krate);
 
-                let mut fns = Vec::new();
+                let mut bodies: Vec<DefWithBodyId> = Vec::new();
                 for (module_id, _) in crate_def_map.modules() {
                     for decl in crate_def_map[module_id].scope.declarations() {
                         let mut sink = DiagnosticSinkBuilder::new().build(&mut cb);
                         validate_module_item(self, krate, decl, &mut sink);
 
-                        if let ModuleDefId::FunctionId(f) = decl {
-                            fns.push(f)
+                        match decl {
+                            ModuleDefId::FunctionId(f) => bodies.push(f.into()),
+                            ModuleDefId::ConstId(c) => bodies.push(c.into()),
+                            ModuleDefId::StaticId(s) => bodies.push(s.into()),
+                            _ => {}
                         }
                     }
 
@@ -486,22 +1262,25 @@ mod tests {
                                     ModuleDefId::FunctionId(*f),
                                     &mut sink,
                                 );
-                                fns.push(*f)
+                                bodies.push((*f).into())
                             }
                         }
                     }
                 }
 
-                for f in fns {
+                for body in bodies {
                     let mut sink = DiagnosticSinkBuilder::new().build(&mut cb);
-                    validate_body(self, f.into(), &mut sink);
+                    validate_body(self, body, &mut sink);
                 }
             }
         }
     }
 
     pub(crate) fn check_diagnostics(ra_fixture: &str) {
-        let db = TestDB::with_files(ra_fixture);
+        check_diagnostics_in(TestDB::with_files(ra_fixture));
+    }
+
+    fn check_diagnostics_in(db: TestDB) {
         let annotations = db.extract_annotations();
 
         let mut actual: FxHashMap<FileId, Vec<(TextRange, String)>> = FxHashMap::default();
@@ -530,6 +1309,38 @@ mod tests {
         assert_eq!(annotations, actual);
     }
 
+    #[test]
+    fn infer_diagnostics_collects_all_body_diagnostics() {
+        let (db, file_id) = TestDB::with_single_file(
+            r#"
+struct S { foo: i32 }
+
+fn test(p: *const u8) {
+    S { foo: 0, bar: 1 };
+    *p;
+    break;
+}
+"#,
+        );
+        let module = db.module_for_file(file_id);
+        let def_map = module.def_map(&db);
+        let func = def_map[module.local_id]
+            .scope
+            .declarations()
+            .find_map(|def| match def {
+                ModuleDefId::FunctionId(it) => Some(it),
+                _ => None,
+            })
+            .unwrap();
+
+        let diagnostics = db.infer_diagnostics(func.into());
+        let mut codes = diagnostics.iter().map(|d| d.code().0).collect::<Vec<_>>();
+        codes.sort();
+        assert_eq!(codes, vec!["break-outside-of-loop", "missing-unsafe", "no-such-field"]);
+        // The result is cached, so asking again doesn't recompute it.
+        assert!(Arc::ptr_eq(&diagnostics, &db.infer_diagnostics(func.into())));
+    }
+
     #[test]
     fn no_such_field_diagnostics() {
         check_diagnostics(
@@ -542,13 +1353,92 @@ impl S {
       //|    - bar
             foo: 92,
             baz: 62,
-          //^^^^^^^ no such field
+          //^^^^^^^ no such field, did you mean `bar`?
         }
     }
 }
 "#,
         );
     }
+    #[test]
+    fn no_such_field_access_diagnostics() {
+        check_diagnostics(
+            r#"
+struct S { foo: i32, bar: (u8, u8) }
+fn test(s: S) {
+    s.fo;
+  //^^^^ no such field, did you mean `foo`?
+    s.baz;
+  //^^^^^ no such field, did you mean `bar`?
+    s.quux;
+  //^^^^^^ no such field
+    s.bar.1;
+    s.bar.2;
+  //^^^^^^^ no such field
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn no_such_field_access_with_method_of_same_name() {
+        check_diagnostics(
+            r#"
+mod vec {
+    pub struct Vec<T> { buf: *mut T, length: usize }
+    impl<T> Vec<T> {
+        pub fn len(&self) -> usize { self.length }
+    }
+}
+fn test(v: vec::Vec<u8>) {
+    v.len;
+  //^^^^^ no such field, `len` is a method
+    v.len();
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn private_field_access() {
+        check_diagnostics(
+            r#"
+mod vec {
+    pub struct Vec<T> { buf: *mut T, len: usize }
+    impl<T> Vec<T> {
+        pub fn len(&self) -> usize { self.len }
+    }
+    pub struct Wrapper { pub buf: u8, inner: Inner }
+    pub struct Inner { pub inner: u8 }
+}
+fn test(v: vec::Vec<u8>, w: vec::Wrapper) {
+    v.buf;
+  //^^^^^ field `buf` is private
+    v.len;
+  //^^^^^ field `len` is private, `len` is a method
+    w.buf;
+    w.inner;
+  //^^^^^^^ field `inner` is private
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn no_such_field_access_through_derefs() {
+        check_diagnostics(
+            r#"
+struct S { field: i32 }
+fn test(s: &&S, t: T) {
+    s.field;
+    s.fild;
+  //^^^^^^ no such field, did you mean `field`?
+    t.field;
+}
+"#,
+        );
+    }
+
     #[test]
     fn no_such_field_with_feature_flag_diagnostics() {
         check_diagnostics(
@@ -667,6 +1557,37 @@ fn baz(s: S) {
         );
     }
 
+    #[test]
+    fn missing_record_pat_fields_diagnostic() {
+        check_diagnostics(
+            r#"
+enum E { V { foo: i32, bar: (), baz: u8 } }
+fn f(e: E) {
+    match e {
+        E::V { bar, baz } => {}
+      //^^^^ Missing structure fields:
+      //   | - foo
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn no_missing_fields_with_struct_update() {
+        check_diagnostics(
+            r#"
+struct S { foo: i32, bar: () }
+struct T { foo: i32, bar: () }
+fn f(s: S, t: T) {
+    let _ = S { foo: 0, ..s };
+    let _ = S { ..t };
+                //^ expected S, found T
+}
+"#,
+        );
+    }
+
     #[test]
     fn missing_record_pat_field_no_diagnostic_if_not_exhaustive() {
         check_diagnostics(
@@ -715,6 +1636,87 @@ fn foo() { break; }
         );
     }
 
+    #[test]
+    fn requires_copy() {
+        check_diagnostics(
+            r#"
+#[lang = "copy"]
+trait Copy {}
+impl Copy for u8 {}
+struct S;
+const C: S = S;
+
+fn foo() {
+    [0u8; 4];
+    [S; 4];
+   //^ the element of an array repeat expression must be `Copy`
+    [C; 4];
+    let x = S;
+    [x; 4];
+   //^ the element of an array repeat expression must be `Copy`
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn invalid_main_return_type() {
+        check_diagnostics(
+            r#"
+#[lang = "termination"]
+trait Termination {}
+trait Debug {}
+enum Result<T, E> { Ok(T), Err(E) }
+struct Error;
+impl Debug for Error {}
+impl Termination for () {}
+impl<E: Debug> Termination for Result<(), E> {}
+
+mod foo {
+    fn main() -> u32 { 0 }
+}
+
+fn main() -> Result<(), Error> { Result::Ok(()) }
+"#,
+        );
+        check_diagnostics(
+            r#"
+#[lang = "termination"]
+trait Termination {}
+trait Debug {}
+enum Result<T, E> { Ok(T), Err(E) }
+struct Error;
+impl Termination for () {}
+impl<E: Debug> Termination for Result<(), E> {}
+
+fn main() -> Result<(), Error> { Result::Ok(()) }
+        //^^^^^^^^^^^^^^^^^^^^ `main` has invalid return type `Result<(), Error>`
+"#,
+        );
+    }
+
+    #[test]
+    fn recursion_limit_reached() {
+        let mut db = TestDB::with_files(
+            r#"
+trait Trait {}
+struct S<T>(T);
+impl<T> Trait for S<T> where S<S<T>>: Trait {}
+fn requires_trait<T: Trait>(_: T) {}
+fn test() {
+        //^ ... reached the recursion limit while checking `S<u32>: Trait`
+    requires_trait(S(0u32));
+}
+"#,
+        );
+        db.set_trait_solver_config(TraitSolverConfig {
+            fuel: 10,
+            max_size: 1000,
+            ..TraitSolverConfig::default()
+        });
+        check_diagnostics_in(db);
+    }
+
     #[test]
     fn missing_semicolon() {
         check_diagnostics(
@@ -742,8 +1744,12 @@ fn foo() { break; }
                 type Item;
                 fn filter_map<B, F>(self, f: F) -> FilterMap where F: FnMut(Self::Item) -> Option<B> { FilterMap }
                 fn next(&mut self) -> Option<Self::Item>;
+                fn map<B, F>(self, f: F) -> FilterMap where F: FnMut(Self::Item) -> B { FilterMap }
             }
             pub struct FilterMap {}
+            impl FilterMap {
+                pub fn len(&self) -> usize { 0 }
+            }
             impl Iterator for FilterMap {
                 type Item = i32;
                 fn next(&mut self) -> i32 { 7 }
@@ -0,0 +1,175 @@
+//! Provides validations for const contexts. Currently checks that only `const`
+//! functions are called in them. Intrinsics are exempt, since whether they can
+//! be called in const contexts is decided by attributes we don't look at.
+
+use std::sync::Arc;
+
+use hir_def::{
+    body::Body,
+    expr::{Array, Expr, ExprId},
+    DefWithBodyId, FunctionId,
+};
+use hir_expand::diagnostics::DiagnosticSink;
+
+use crate::{db::HirDatabase, diagnostics::NonConstFnInConstContext, InferenceResult};
+
+pub(super) struct ConstValidator<'a, 'b: 'a> {
+    owner: DefWithBodyId,
+    infer: Arc<InferenceResult>,
+    sink: &'a mut DiagnosticSink<'b>,
+}
+
+impl<'a, 'b> ConstValidator<'a, 'b> {
+    pub(super) fn new(
+        owner: DefWithBodyId,
+        infer: Arc<InferenceResult>,
+        sink: &'a mut DiagnosticSink<'b>,
+    ) -> ConstValidator<'a, 'b> {
+        ConstValidator { owner, infer, sink }
+    }
+
+    pub(super) fn validate_body(&mut self, db: &dyn HirDatabase) {
+        let in_const_context = match self.owner {
+            DefWithBodyId::FunctionId(it) => db.function_data(it).is_const,
+            DefWithBodyId::StaticId(_) | DefWithBodyId::ConstId(_) => true,
+        };
+        let body = db.body(self.owner);
+        let mut calls = Vec::new();
+        walk_const(&mut calls, db, &self.infer, &body, body.body_expr, in_const_context);
+        if calls.is_empty() {
+            return;
+        }
+
+        let (_, body_source) = db.body_with_source_map(self.owner);
+        for (expr, func) in calls {
+            if let Ok(in_file) = body_source.as_ref().expr_syntax(expr) {
+                self.sink.push(NonConstFnInConstContext {
+                    file: in_file.file_id,
+                    expr: in_file.value,
+                    func: db.function_data(func).name.clone(),
+                })
+            }
+        }
+    }
+}
+
+/// Collects the calls of non-`const` functions in const contexts.
+fn walk_const(
+    calls: &mut Vec<(ExprId, FunctionId)>,
+    db: &dyn HirDatabase,
+    infer: &InferenceResult,
+    body: &Body,
+    current: ExprId,
+    in_const_context: bool,
+) {
+    let expr = &body.exprs[current];
+    let callee = match expr {
+        &Expr::Call { callee, .. } => infer[callee].as_fn_def(),
+        Expr::MethodCall { .. } => infer.method_resolution(current),
+        _ => None,
+    };
+    if let Some(func) = callee {
+        let data = db.function_data(func);
+        if in_const_context && !data.is_const && !data.is_intrinsic {
+            calls.push((current, func));
+        }
+    }
+
+    match expr {
+        // Closure bodies aren't evaluated at compile time
+        Expr::Lambda { body: child, .. } => {
+            walk_const(calls, db, infer, body, *child, false);
+        }
+        // The length of an array is always a const context
+        &Expr::Array(Array::Repeat { initializer, repeat }) => {
+            walk_const(calls, db, infer, body, initializer, in_const_context);
+            walk_const(calls, db, infer, body, repeat, true);
+        }
+        _ => expr.walk_child_exprs(|child| {
+            walk_const(calls, db, infer, body, child, in_const_context);
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::tests::check_diagnostics;
+
+    #[test]
+    fn const_fn_in_const_initializer() {
+        check_diagnostics(
+            r#"
+const fn one() -> u32 { 1 }
+struct S;
+impl S { const fn two(&self) -> u32 { 2 } }
+
+const A: u32 = one();
+static B: u32 = S.two();
+const fn three() -> u32 { one() + S.two() }
+"#,
+        );
+    }
+
+    #[test]
+    fn non_const_fn_in_const_initializer() {
+        check_diagnostics(
+            r#"
+fn one() -> u32 { 1 }
+struct S;
+impl S { fn two(&self) -> u32 { 2 } }
+
+const A: u32 = one();
+             //^^^^^ cannot call non-const fn `one` in a const context
+static B: u32 = S.two();
+              //^^^^^^^ cannot call non-const fn `two` in a const context
+const fn three() -> u32 { one() }
+                        //^^^^^ cannot call non-const fn `one` in a const context
+"#,
+        );
+    }
+
+    #[test]
+    fn array_length_is_const_context() {
+        check_diagnostics(
+            r#"
+fn len() -> usize { 1 }
+fn value() -> u8 { 0 }
+struct T(u8);
+
+fn main() {
+    let a = [value(); len()];
+                    //^^^^^ cannot call non-const fn `len` in a const context
+    let t = T(value());
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn closure_in_const_context_is_not_checked() {
+        check_diagnostics(
+            r#"
+fn one() -> u32 { 1 }
+const F: fn() -> u32 = || one();
+"#,
+        );
+    }
+
+    #[test]
+    fn intrinsics_are_not_checked() {
+        check_diagnostics(
+            r#"
+extern "rust-intrinsic" {
+    fn transmute<T, U>(e: T) -> U;
+}
+extern "C" {
+    fn ext() -> u32;
+}
+
+const A: u32 = unsafe { transmute(1i32) };
+const B: u32 = unsafe { ext() };
+                      //^^^^^ cannot call non-const fn `ext` in a const context
+"#,
+        );
+    }
+}
@@ -16,23 +16,32 @@ use base_db::CrateId;
 use hir_def::{
     adt::VariantData,
     expr::{Pat, PatId},
-    src::HasSource,
-    AdtId, AttrDefId, ConstId, EnumId, FunctionId, Lookup, ModuleDefId, StaticId, StructId,
+    resolver::{HasResolver, Resolver},
+    src::{HasChildSource, HasSource},
+    type_ref::TypeRef,
+    AdtId, AttrDefId, ConstId, EnumId, EnumVariantId, FunctionId, Lookup, ModuleDefId, StaticId,
+    StructId, VariantId,
 };
 use hir_expand::{
     diagnostics::DiagnosticSink,
     name::{AsName, Name},
+    HirFileId,
 };
 use stdx::{always, never};
 use syntax::{
     ast::{self, NameOwner},
-    AstNode, AstPtr,
+    AstNode, AstPtr, SyntaxNodePtr,
 };
 use test_utils::mark;
 
 use crate::{
     db::HirDatabase,
-    diagnostics::{decl_check::case_conv::*, CaseType, IdentType, IncorrectCase},
+    diagnostics::{
+        decl_check::case_conv::*, CaseType, GenericArgsCountMismatch, IdentType, IncorrectCase,
+        RecursiveTypeWithoutIndirection,
+    },
+    lower::generic_args_mismatches,
+    utils::variant_data,
 };
 
 mod allow {
@@ -64,6 +73,7 @@ impl<'a, 'b> DeclValidator<'a, 'b> {
     }
 
     pub(super) fn validate_item(&mut self, item: ModuleDefId) {
+        self.validate_generic_args_counts(item);
         match item {
             ModuleDefId::FunctionId(func) => self.validate_func(func),
             ModuleDefId::AdtId(adt) => self.validate_adt(adt),
@@ -74,6 +84,8 @@ impl<'a, 'b> DeclValidator<'a, 'b> {
     }
 
     fn validate_adt(&mut self, adt: AdtId) {
+        self.validate_adt_indirection(adt);
+
         match adt {
             AdtId::StructId(struct_id) => self.validate_struct(struct_id),
             AdtId::EnumId(enum_id) => self.validate_enum(enum_id),
@@ -83,6 +95,143 @@ impl<'a, 'b> DeclValidator<'a, 'b> {
         }
     }
 
+    /// Checks that the type doesn't contain itself without any indirection.
+    fn validate_adt_indirection(&mut self, adt: AdtId) {
+        let field = match self.db.recursive_field_without_indirection(adt) {
+            Some(field) => field,
+            None => return,
+        };
+        let adt_name = match adt {
+            AdtId::StructId(it) => self.db.struct_data(it).name.clone(),
+            AdtId::UnionId(it) => self.db.union_data(it).name.clone(),
+            AdtId::EnumId(it) => self.db.enum_data(it).name.clone(),
+        };
+
+        let fields_src = field.parent.child_source(self.db.upcast());
+        let field_ty =
+            match fields_src.value[field.local_id].as_ref().either(|it| it.ty(), |it| it.ty()) {
+                Some(ty) => ty,
+                None => return,
+            };
+
+        self.sink.push(RecursiveTypeWithoutIndirection {
+            file: fields_src.file_id,
+            field_ty: AstPtr::new(&field_ty),
+            adt_name,
+        });
+    }
+
+    /// Checks the types in the item's signature, fields or definition for paths
+    /// with the wrong number of generic arguments.
+    fn validate_generic_args_counts(&mut self, item: ModuleDefId) {
+        let db = self.db.upcast();
+        match item {
+            ModuleDefId::FunctionId(func) => {
+                let data = self.db.function_data(func);
+                let src = func.lookup(db).source(db);
+                let param_list = src.value.param_list();
+                let self_ty = param_list.as_ref().and_then(|it| it.self_param()).map(|it| it.ty());
+                let param_tys = param_list.iter().flat_map(|it| it.params()).map(|it| it.ty());
+                let resolver = func.resolver(db);
+                for (type_ref, ty) in data.params.iter().zip(self_ty.into_iter().chain(param_tys)) {
+                    self.validate_generic_args_in_type(&resolver, src.file_id, type_ref, ty);
+                }
+                let ret_ty = src.value.ret_type().and_then(|it| it.ty());
+                self.validate_generic_args_in_type(&resolver, src.file_id, &data.ret_type, ret_ty);
+            }
+            ModuleDefId::AdtId(AdtId::StructId(it)) => {
+                self.validate_generic_args_in_fields(it.into())
+            }
+            ModuleDefId::AdtId(AdtId::UnionId(it)) => {
+                self.validate_generic_args_in_fields(it.into())
+            }
+            ModuleDefId::AdtId(AdtId::EnumId(it)) => {
+                for (local_id, _) in self.db.enum_data(it).variants.iter() {
+                    let variant = EnumVariantId { parent: it, local_id };
+                    self.validate_generic_args_in_fields(variant.into());
+                }
+            }
+            ModuleDefId::ConstId(it) => {
+                let src = it.lookup(db).source(db);
+                let type_ref = &self.db.const_data(it).type_ref;
+                self.validate_generic_args_in_type(
+                    &it.resolver(db),
+                    src.file_id,
+                    type_ref,
+                    src.value.ty(),
+                );
+            }
+            ModuleDefId::StaticId(it) => {
+                let src = it.lookup(db).source(db);
+                let type_ref = &self.db.static_data(it).type_ref;
+                self.validate_generic_args_in_type(
+                    &it.resolver(db),
+                    src.file_id,
+                    type_ref,
+                    src.value.ty(),
+                );
+            }
+            ModuleDefId::TypeAliasId(it) => {
+                let src = it.lookup(db).source(db);
+                if let Some(type_ref) = &self.db.type_alias_data(it).type_ref {
+                    self.validate_generic_args_in_type(
+                        &it.resolver(db),
+                        src.file_id,
+                        type_ref,
+                        src.value.ty(),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn validate_generic_args_in_fields(&mut self, variant: VariantId) {
+        let resolver = variant.resolver(self.db.upcast());
+        let fields_src = variant.child_source(self.db.upcast());
+        for (local_id, field) in variant_data(self.db.upcast(), variant).fields().iter() {
+            let ty = fields_src.value[local_id].as_ref().either(|it| it.ty(), |it| it.ty());
+            self.validate_generic_args_in_type(&resolver, fields_src.file_id, &field.type_ref, ty);
+        }
+    }
+
+    fn validate_generic_args_in_type(
+        &mut self,
+        resolver: &Resolver,
+        file: HirFileId,
+        type_ref: &TypeRef,
+        ty: Option<ast::Type>,
+    ) {
+        let ty = match ty {
+            Some(it) => it,
+            None => return,
+        };
+        for mismatch in generic_args_mismatches(self.db, resolver, type_ref) {
+            // Point at the offending segment if we can find it, e.g. the inner
+            // `Vec<u8, u8>` in `Option<Vec<u8, u8>>`.
+            let segment = ty.syntax().descendants().filter_map(ast::PathSegment::cast).find(|it| {
+                let type_args = it.generic_arg_list().map_or(0, |args| {
+                    args.generic_args()
+                        .filter(|arg| matches!(arg, ast::GenericArg::TypeArg(_)))
+                        .count()
+                });
+                it.name_ref().map_or(false, |name| name.as_name() == mismatch.segment)
+                    && type_args == mismatch.provided
+            });
+            let node = match segment {
+                Some(segment) => SyntaxNodePtr::new(segment.syntax()),
+                None => SyntaxNodePtr::new(ty.syntax()),
+            };
+            self.sink.push(GenericArgsCountMismatch {
+                file,
+                node,
+                expected: mismatch.expected,
+                provided: mismatch.provided,
+                defaults: mismatch.defaults,
+            });
+        }
+    }
+
     /// Checks whether not following the convention is allowed for this item.
     ///
     /// Currently this method doesn't check parent attributes.
@@ -887,6 +1036,37 @@ fn main() {
         );
     }
 
+    #[test]
+    fn generic_args_count_in_items() {
+        check_diagnostics(
+            r#"
+struct S<T>(T);
+enum Option<T> { Some(T), None }
+
+struct W {
+    s: S<u32, u32, u32>,
+     //^^^^^^^^^^^^^^^^ Expected 1 generic argument, found 3
+}
+
+fn f(s: Option<S<u8, u8>>) -> S<u8> { loop {} }
+             //^^^^^^^^^ Expected 1 generic argument, found 2
+"#,
+        );
+    }
+
+    #[test]
+    fn generic_args_count_with_defaults() {
+        check_diagnostics(
+            r#"
+struct D<T, U = u32>(T, U);
+
+fn f(d: D<u8>, e: D<u8, u8>) {}
+fn g(d: D<u8, u8, u8>) {}
+      //^^^^^^^^^^^^^ Expected at most 2 generic arguments, found 3
+"#,
+        );
+    }
+
     #[test]
     fn ignores_extern_items() {
         mark::check!(extern_func_incorrect_case_ignored);
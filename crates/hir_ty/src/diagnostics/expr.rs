@@ -1,23 +1,35 @@
 //! FIXME: write short doc here
 
-use std::sync::Arc;
+use std::{iter, sync::Arc};
 
+use base_db::CrateId;
 use hir_def::{
-    expr::Statement, path::path, resolver::HasResolver, AdtId, AssocItemId, DefWithBodyId,
+    expr::{BinaryOp, Statement},
+    path::path,
+    resolver::{resolver_for_expr, HasResolver, ValueNs},
+    AdtId, AssocItemId, DefWithBodyId, HasModule,
 };
-use hir_expand::{diagnostics::DiagnosticSink, name};
-use rustc_hash::FxHashSet;
+use hir_expand::{
+    diagnostics::DiagnosticSink,
+    name::{name, Name},
+};
+use rustc_hash::{FxHashMap, FxHashSet};
 use syntax::{ast, AstPtr};
 
 use crate::{
     db::HirDatabase,
     diagnostics::{
-        match_check::{is_useful, MatchCheckCtx, Matrix, PatStack, Usefulness},
-        MismatchedArgCount, MissingFields, MissingMatchArms, MissingOkOrSomeInTailExpr,
-        MissingPatFields, RemoveThisSemicolon,
+        match_check::{is_useful, missing_int_ranges, MatchCheckCtx, Matrix, PatStack, Usefulness},
+        IdentityCast, IncorrectTryExpr, InvalidCast, InvalidCastKind, LosslessCast,
+        MismatchedArgCount, MissingAwait, MissingFields, MissingMatchArms,
+        MissingOkOrSomeInTailExpr, MissingPatFields, RemoveThisSemicolon, TypeMismatch,
+        UnreachableCode, UnresolvedMethodCall, UseOfMovedValue,
     },
+    display::HirDisplay,
+    method_resolution::{self, ReceiverAdjustments},
+    primitive::{FloatTy, IntTy, UintTy},
     utils::variant_data,
-    InferenceResult, Ty,
+    Canonical, InferenceResult, Scalar, TraitEnvironment, Ty,
 };
 
 pub(crate) use hir_def::{
@@ -64,8 +76,21 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
                 Expr::Match { expr, arms } => {
                     self.validate_match(id, *expr, arms, db, self.infer.clone());
                 }
-                Expr::Call { .. } | Expr::MethodCall { .. } => {
+                Expr::Call { .. } => {
+                    self.validate_call(db, id, expr);
+                }
+                Expr::MethodCall { receiver, method_name, .. } => {
                     self.validate_call(db, id, expr);
+                    self.validate_method_resolution(db, id, *receiver, method_name);
+                }
+                Expr::Cast { expr, .. } => {
+                    self.validate_cast(db, id, *expr);
+                }
+                Expr::Try { .. } => {
+                    self.validate_try(db, id);
+                }
+                Expr::Block { statements, tail, .. } => {
+                    self.validate_unreachable_code(db, statements, *tail);
                 }
                 _ => {}
             }
@@ -82,14 +107,81 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
                 );
             }
         }
+        self.validate_missing_await(db, &body);
+        self.validate_moves(db, &body);
+
+        // the mismatch of the body with the return type is only reported by
+        // these, if they apply, not as a type mismatch of the body or its tail
+        let mut return_exprs = vec![body.body_expr];
         let body_expr = &body[body.body_expr];
         if let Expr::Block { statements, tail, .. } = body_expr {
             if let Some(t) = tail {
                 self.validate_results_in_tail_expr(body.body_expr, *t, db);
+                return_exprs.push(*t);
             } else if let Some(Statement::Expr(id)) = statements.last() {
                 self.validate_missing_tail_expr(body.body_expr, *id, db);
             }
         }
+        self.validate_type_mismatches(db, &body, &return_exprs);
+    }
+
+    fn validate_type_mismatches(
+        &mut self,
+        db: &dyn HirDatabase,
+        body: &Body,
+        return_exprs: &[ExprId],
+    ) {
+        let module = self.owner.module(db.upcast());
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        // literals in patterns are expressions as well, but a pattern that
+        // doesn't fit its scrutinee isn't a type mismatch we report
+        let pat_exprs: FxHashSet<ExprId> = body
+            .pats
+            .iter()
+            .flat_map(|(_, pat)| match pat {
+                Pat::Lit(expr) => vec![*expr],
+                Pat::Range { start, end } => vec![*start, *end],
+                _ => Vec::new(),
+            })
+            .collect();
+        for (id, expr) in body.exprs.iter() {
+            let mismatch = match self.infer.type_mismatch_for_expr(id) {
+                Some(it) => it,
+                None => continue,
+            };
+            if return_exprs.contains(&id) || pat_exprs.contains(&id) {
+                continue;
+            }
+            // a block only mismatches because its tail does, which is reported already
+            if let Expr::Block { tail: Some(tail), .. } = expr {
+                if self.infer.type_mismatch_for_expr(*tail).is_some() {
+                    continue;
+                }
+            }
+            // these are most likely caused by things we don't understand yet,
+            // rather than actual errors
+            if mismatch.expected.contains_unknown() || mismatch.actual.contains_unknown() {
+                continue;
+            }
+            let source_ptr = match source_map.expr_syntax(id) {
+                Ok(it) => it,
+                Err(_) => continue,
+            };
+            // without expansion info, a mismatch inside a macro expansion
+            // can't be mapped back to the code it came from
+            let file_id = source_ptr.file_id;
+            if file_id.call_node(db.upcast()).is_some()
+                && file_id.expansion_info(db.upcast()).is_none()
+            {
+                continue;
+            }
+            self.sink.push(TypeMismatch {
+                file: source_ptr.file_id,
+                expr: source_ptr.value,
+                expected: mismatch.expected.display_shortened(db, module).to_string(),
+                actual: mismatch.actual.display_shortened(db, module).to_string(),
+            });
+        }
     }
 
     fn create_record_literal_missing_fields_diagnostic(
@@ -219,14 +311,15 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         }
 
         let is_method_call = matches!(expr, Expr::MethodCall { .. });
-        let (sig, args) = match expr {
+        let (sig, callee, args) = match expr {
             Expr::Call { callee, args } => {
-                let callee = &self.infer.type_of_expr[*callee];
+                // calls autoderef the callee, e.g. `(&f)()`
+                let callee = self.infer.type_of_expr[*callee].strip_references();
                 let sig = match callee.callable_sig(db) {
                     Some(sig) => sig,
                     None => return,
                 };
-                (sig, args.clone())
+                (sig, callee.as_callable_def().map(|(def, _)| def), args.clone())
             }
             Expr::MethodCall { receiver, args, .. } => {
                 let mut args = args.clone();
@@ -249,23 +342,26 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
                 };
                 let sig = db.callable_item_signature(callee.into()).value;
 
-                (sig, args)
+                (sig, Some(callee.into()), args)
             }
             _ => return,
         };
 
+        let mut param_count = sig.params().len();
+        let mut arg_count = args.len();
+        // the variadic part of a C-variadic function's signature is lowered as
+        // a parameter, but doesn't take an argument of its own
         if sig.is_varargs {
-            return;
+            param_count -= 1;
         }
 
-        let params = sig.params();
-
-        let mut param_count = params.len();
-        let mut arg_count = args.len();
-
-        if arg_count != param_count {
+        // C-variadic functions take any number of arguments after the fixed ones
+        let mismatch =
+            if sig.is_varargs { arg_count < param_count } else { arg_count != param_count };
+        if mismatch {
             let (_, source_map) = db.body_with_source_map(self.owner.into());
             if let Ok(source_ptr) = source_map.expr_syntax(call_id) {
+                // the receiver of a method call isn't counted as an argument
                 if is_method_call {
                     param_count -= 1;
                     arg_count -= 1;
@@ -275,11 +371,67 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
                     call_expr: source_ptr.value,
                     expected: param_count,
                     found: arg_count,
+                    is_varargs: sig.is_varargs,
+                    callee,
                 });
             }
         }
     }
 
+    fn validate_method_resolution(
+        &mut self,
+        db: &dyn HirDatabase,
+        call_id: ExprId,
+        receiver: ExprId,
+        method_name: &Name,
+    ) {
+        if self.infer.method_resolution(call_id).is_some() {
+            return;
+        }
+        let receiver_ty = &self.infer[receiver];
+        // an unknown receiver is most likely caused by something we don't
+        // understand yet, so we can't tell whether the method exists
+        if receiver_ty.contains_unknown() || matches!(receiver_ty, Ty::Never) {
+            return;
+        }
+        let module = self.owner.module(db.upcast());
+        // the methods of builtin types are defined in lang item impls, so if
+        // those aren't available (e.g. there's no `core`), we can't tell either
+        let builtin_ty = match receiver_ty.strip_references() {
+            Ty::Array(elem) => Ty::Slice(elem.clone()),
+            ty => ty.clone(),
+        };
+        if builtin_ty.def_crates(db, module.krate()).map_or(false, |crates| crates.is_empty()) {
+            return;
+        }
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        let source_ptr = match source_map.expr_syntax(call_id) {
+            Ok(it) => it,
+            Err(_) => return,
+        };
+
+        let resolver = resolver_for_expr(db.upcast(), self.owner, call_id);
+        let traits_in_scope = resolver.traits_in_scope(db.upcast());
+        let env = TraitEnvironment::lower(db, &resolver);
+        let unsatisfied_bound = method_resolution::unsatisfied_method_bound(
+            &Canonical::new(receiver_ty.clone(), iter::empty()),
+            db,
+            env,
+            module.krate(),
+            &traits_in_scope,
+            method_name,
+        )
+        .map(|trait_ref| trait_ref.display(db).to_string());
+
+        self.sink.push(UnresolvedMethodCall {
+            file: source_ptr.file_id,
+            expr: source_ptr.value,
+            receiver: receiver_ty.display_shortened(db, module).to_string(),
+            method_name: method_name.clone(),
+            unsatisfied_bound,
+        });
+    }
+
     fn validate_match(
         &mut self,
         id: ExprId,
@@ -298,7 +450,8 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
             Some(ty) => ty,
         };
 
-        let cx = MatchCheckCtx { match_expr, body, infer: infer.clone(), db };
+        let krate = self.owner.module(db.upcast()).krate();
+        let cx = MatchCheckCtx { match_expr, body, infer: infer.clone(), db, krate };
         let pats = arms.iter().map(|arm| arm.pat);
 
         let mut seen = Matrix::empty();
@@ -345,6 +498,12 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
             _ => return,
         }
 
+        let witnesses = missing_int_ranges(&cx, &seen)
+            .unwrap_or_default()
+            .iter()
+            .map(|range| range.to_string())
+            .collect();
+
         if let Ok(source_ptr) = source_map.expr_syntax(id) {
             let root = source_ptr.file_syntax(db.upcast());
             if let ast::Expr::MatchExpr(match_expr) = &source_ptr.value.to_node(&root) {
@@ -355,12 +514,198 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
                         file: source_ptr.file_id,
                         match_expr: AstPtr::new(&match_expr),
                         arms: AstPtr::new(&arms),
+                        witnesses,
                     })
                 }
             }
         }
     }
 
+    fn validate_cast(&mut self, db: &dyn HirDatabase, cast_id: ExprId, inner: ExprId) {
+        let (from, to) = (self.infer[inner].clone(), self.infer[cast_id].clone());
+        if from.contains_unknown() || to.contains_unknown() {
+            return;
+        }
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        let source_ptr = match source_map.expr_syntax(cast_id) {
+            Ok(it) => it,
+            Err(_) => return,
+        };
+        if let Some(kind) = invalid_cast_kind(&from, &to) {
+            let module = self.owner.module(db.upcast());
+            self.sink.push(InvalidCast {
+                file: source_ptr.file_id,
+                expr: source_ptr.value,
+                kind,
+                from: from.display_shortened(db, module).to_string(),
+                to: to.display_shortened(db, module).to_string(),
+            });
+            return;
+        }
+        let (from, to) = match (from, to) {
+            (Ty::Scalar(from), Ty::Scalar(to)) => (from, to),
+            _ => return,
+        };
+        if from == to {
+            self.sink.push(IdentityCast {
+                file: source_ptr.file_id,
+                expr: source_ptr.value,
+                ty: Ty::Scalar(to),
+            });
+        } else if is_lossless_cast(from, to) {
+            self.sink.push(LosslessCast {
+                file: source_ptr.file_id,
+                expr: source_ptr.value,
+                from: Ty::Scalar(from),
+                to: Ty::Scalar(to),
+            });
+        }
+    }
+
+    fn validate_try(&mut self, db: &dyn HirDatabase, try_id: ExprId) {
+        let failure = match self.infer.try_failure(try_id) {
+            Some(it) => it,
+            None => return,
+        };
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        let source_ptr = match source_map.expr_syntax(try_id) {
+            Ok(it) => it,
+            Err(_) => return,
+        };
+        let module = self.owner.module(db.upcast());
+        self.sink.push(IncorrectTryExpr {
+            file: source_ptr.file_id,
+            expr: source_ptr.value,
+            reason: failure.reason,
+            operand_ty: failure.operand_ty.display_shortened(db, module).to_string(),
+            return_ty: failure.return_ty.display_shortened(db, module).to_string(),
+        });
+    }
+
+    /// Reports the first statement of a block that comes after one that
+    /// diverges. Statements without an expression, i.e. `let`s without an
+    /// initializer, are skipped.
+    fn validate_unreachable_code(
+        &mut self,
+        db: &dyn HirDatabase,
+        statements: &[Statement],
+        tail: Option<ExprId>,
+    ) {
+        let exprs = statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Let { initializer, .. } => *initializer,
+                Statement::Expr(expr) => Some(*expr),
+            })
+            .chain(tail);
+        let mut diverged = false;
+        for expr in exprs {
+            if diverged {
+                let (_, source_map) = db.body_with_source_map(self.owner);
+                if let Ok(source_ptr) = source_map.expr_syntax(expr) {
+                    self.sink
+                        .push(UnreachableCode { file: source_ptr.file_id, expr: source_ptr.value });
+                }
+                return;
+            }
+            diverged = self.statement_diverges(db, expr);
+        }
+    }
+
+    /// Only statements that produce a value of type `!` themselves count, not
+    /// ones that merely contain a diverging subexpression like a `match` on
+    /// `loop {}`. A `match` without arms on a scrutinee that doesn't diverge
+    /// is already reported as missing match arms, so it doesn't count either.
+    fn statement_diverges(&self, db: &dyn HirDatabase, expr: ExprId) -> bool {
+        if !self.infer.expr_diverges(expr) || !self.infer[expr].is_never() {
+            return false;
+        }
+        let body = db.body(self.owner);
+        match &body[expr] {
+            Expr::Match { expr: scrutinee, arms } if arms.is_empty() => {
+                self.infer.expr_diverges(*scrutinee)
+            }
+            _ => true,
+        }
+    }
+
+    fn validate_missing_await(&mut self, db: &dyn HirDatabase, body: &Body) {
+        let krate = self.owner.module(db.upcast()).krate();
+        let future_trait =
+            match db.lang_item(krate, "future_trait".into()).and_then(|it| it.as_trait()) {
+                Some(it) => it,
+                None => return,
+            };
+        let in_async_fn = match self.owner {
+            DefWithBodyId::FunctionId(it) => db.function_data(it).is_async,
+            _ => false,
+        };
+
+        let mut unused_exprs = Vec::new();
+        collect_unused_exprs_in_async(body, body.body_expr, in_async_fn, &mut unused_exprs);
+        if unused_exprs.is_empty() {
+            return;
+        }
+
+        let env = TraitEnvironment::lower(db, &self.owner.resolver(db.upcast()));
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        for id in unused_exprs {
+            let ty = &self.infer[id];
+            if matches!(ty, Ty::Unknown | Ty::Never) {
+                continue;
+            }
+            if !ty.impls_trait(db, krate, env.clone(), future_trait) {
+                continue;
+            }
+            if let Ok(source_ptr) = source_map.expr_syntax(id) {
+                self.sink.push(MissingAwait {
+                    file: source_ptr.file_id,
+                    expr: source_ptr.value,
+                    future_ty: ty.clone(),
+                });
+            }
+        }
+    }
+
+    fn validate_moves(&mut self, db: &dyn HirDatabase, body: &Arc<Body>) {
+        let krate = self.owner.module(db.upcast()).krate();
+        // without it, nothing would count as `Copy`
+        if db.lang_item(krate, "copy".into()).is_none() {
+            return;
+        }
+        let mut checker = MoveChecker {
+            db,
+            owner: self.owner,
+            body: body.clone(),
+            infer: &self.infer,
+            krate,
+            env: TraitEnvironment::lower(db, &self.owner.resolver(db.upcast())),
+            moved: FxHashMap::default(),
+            diverged: false,
+            uses_after_move: Vec::new(),
+        };
+        checker.walk(body.body_expr, false);
+        let uses_after_move = checker.uses_after_move;
+
+        let (_, source_map) = db.body_with_source_map(self.owner);
+        for (use_expr, move_expr, binding) in uses_after_move {
+            let name = match &body[binding] {
+                Pat::Bind { name, .. } => name.clone(),
+                _ => continue,
+            };
+            if let (Ok(use_ptr), Ok(move_ptr)) =
+                (source_map.expr_syntax(use_expr), source_map.expr_syntax(move_expr))
+            {
+                self.sink.push(UseOfMovedValue {
+                    file: use_ptr.file_id,
+                    expr: use_ptr.value,
+                    moved_at: move_ptr,
+                    name,
+                });
+            }
+        }
+    }
+
     fn validate_results_in_tail_expr(&mut self, body_id: ExprId, id: ExprId, db: &dyn HirDatabase) {
         // the mismatch will be on the whole block currently
         let mismatch = match self.infer.type_mismatch_for_expr(body_id) {
@@ -433,6 +778,251 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
     }
 }
 
+/// Collects the expression statements inside `async` blocks and functions,
+/// i.e. the expressions whose value is dropped right away.
+fn collect_unused_exprs_in_async(
+    body: &Body,
+    current: ExprId,
+    in_async: bool,
+    acc: &mut Vec<ExprId>,
+) {
+    let expr = &body.exprs[current];
+    match expr {
+        Expr::Async { body: inner } => {
+            collect_unused_exprs_in_async(body, *inner, true, acc);
+            return;
+        }
+        Expr::Lambda { body: inner, .. } => {
+            collect_unused_exprs_in_async(body, *inner, false, acc);
+            return;
+        }
+        Expr::Block { statements, .. } if in_async => {
+            acc.extend(statements.iter().filter_map(|stmt| match stmt {
+                Statement::Expr(id) => Some(*id),
+                Statement::Let { .. } => None,
+            }));
+        }
+        _ => {}
+    }
+    expr.walk_child_exprs(|child| collect_unused_exprs_in_async(body, child, in_async, acc));
+}
+
+/// Finds the uses of local bindings after their value was moved away, for the
+/// obvious cases only: moves into arguments and by-value receivers of calls
+/// in straight-line code. Moves inside branches, loops and closures aren't
+/// tracked, and neither are bindings of references, which get reborrowed
+/// rather than moved. Code after an expression that diverges isn't checked.
+struct MoveChecker<'a> {
+    db: &'a dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: Arc<Body>,
+    infer: &'a InferenceResult,
+    krate: CrateId,
+    env: Arc<TraitEnvironment>,
+    /// The bindings whose value is moved away, with the expression moving it.
+    moved: FxHashMap<PatId, ExprId>,
+    diverged: bool,
+    /// The uses, the moves before them and the bindings they are of.
+    uses_after_move: Vec<(ExprId, ExprId, PatId)>,
+}
+
+impl MoveChecker<'_> {
+    /// Walks `expr` in evaluation order. Moves are only recorded if `expr` is
+    /// evaluated exactly once whenever the code before it is, i.e. it isn't
+    /// `conditional`.
+    fn walk(&mut self, expr: ExprId, conditional: bool) {
+        if self.diverged {
+            return;
+        }
+        let body = self.body.clone();
+        match &body[expr] {
+            Expr::Path(_) => {
+                if let Some(binding) = self.local_binding(expr) {
+                    // report each move only once
+                    if let Some(move_expr) = self.moved.remove(&binding) {
+                        self.uses_after_move.push((expr, move_expr, binding));
+                    }
+                }
+            }
+            Expr::Call { callee, args } => {
+                self.walk(*callee, conditional);
+                for &arg in args {
+                    self.walk(arg, conditional);
+                    self.record_move(arg, conditional);
+                }
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                self.walk(*receiver, conditional);
+                let by_value = matches!(
+                    self.infer.receiver_adjustments(expr),
+                    Some(ReceiverAdjustments { autoderefs: 0, autoref: None })
+                );
+                if by_value {
+                    self.record_move(*receiver, conditional);
+                }
+                for &arg in args {
+                    self.walk(arg, conditional);
+                    self.record_move(arg, conditional);
+                }
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { op: None }) } => {
+                self.walk(*rhs, conditional);
+                match self.local_binding(*lhs) {
+                    // assigning a new value makes the binding usable again
+                    Some(binding) if !conditional => {
+                        self.moved.remove(&binding);
+                    }
+                    Some(_) => {}
+                    None => self.walk(*lhs, conditional),
+                }
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::LogicOp(_)) } => {
+                self.walk(*lhs, conditional);
+                self.walk(*rhs, true);
+            }
+            Expr::If { condition, then_branch, else_branch } => {
+                self.walk(*condition, conditional);
+                self.walk(*then_branch, true);
+                if let Some(else_branch) = else_branch {
+                    self.walk(*else_branch, true);
+                }
+            }
+            Expr::Match { expr, arms } => {
+                self.walk(*expr, conditional);
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        self.walk(guard, true);
+                    }
+                    self.walk(arm.expr, true);
+                }
+            }
+            Expr::For { iterable, body, .. } => {
+                self.walk(*iterable, conditional);
+                self.walk(*body, true);
+            }
+            Expr::Loop { .. } | Expr::While { .. } | Expr::Lambda { .. } | Expr::Async { .. } => {
+                body[expr].walk_child_exprs(|child| self.walk(child, true));
+            }
+            _ => body[expr].walk_child_exprs(|child| self.walk(child, conditional)),
+        }
+        if !conditional && self.infer[expr].is_never() {
+            self.diverged = true;
+        }
+    }
+
+    fn record_move(&mut self, expr: ExprId, conditional: bool) {
+        if conditional || self.diverged {
+            return;
+        }
+        let binding = match self.local_binding(expr) {
+            Some(binding) => binding,
+            None => return,
+        };
+        let ty = &self.infer[expr];
+        let may_be_copy = ty.contains_unknown()
+            || matches!(
+                ty,
+                Ty::Scalar(_)
+                    | Ty::RawPtr(..)
+                    | Ty::Ref(..)
+                    | Ty::Closure(..)
+                    | Ty::FnDef(..)
+                    | Ty::Function(_)
+                    | Ty::Never
+            )
+            || ty.is_copy(self.db, self.krate, self.env.clone());
+        if !may_be_copy {
+            self.moved.insert(binding, expr);
+        }
+    }
+
+    /// The local binding a path expression refers to, if it does.
+    fn local_binding(&self, expr: ExprId) -> Option<PatId> {
+        let path = match &self.body[expr] {
+            Expr::Path(path) => path,
+            _ => return None,
+        };
+        let resolver = resolver_for_expr(self.db.upcast(), self.owner, expr);
+        match resolver.resolve_path_in_value_ns_fully(self.db.upcast(), path.mod_path())? {
+            ValueNs::LocalBinding(binding) => Some(binding),
+            _ => None,
+        }
+    }
+}
+
+/// Whether every value of `from` is represented exactly in `to`. Pointer-sized integers are never
+/// considered, since whether the cast loses information depends on the target.
+fn is_lossless_cast(from: Scalar, to: Scalar) -> bool {
+    // (signed, bits)
+    fn int_bits(scalar: Scalar) -> Option<(bool, u32)> {
+        let bits = match scalar {
+            Scalar::Int(IntTy::I8) | Scalar::Uint(UintTy::U8) => 8,
+            Scalar::Int(IntTy::I16) | Scalar::Uint(UintTy::U16) => 16,
+            Scalar::Int(IntTy::I32) | Scalar::Uint(UintTy::U32) => 32,
+            Scalar::Int(IntTy::I64) | Scalar::Uint(UintTy::U64) => 64,
+            Scalar::Int(IntTy::I128) | Scalar::Uint(UintTy::U128) => 128,
+            _ => return None,
+        };
+        Some((matches!(scalar, Scalar::Int(_)), bits))
+    }
+    fn mantissa_bits(ty: FloatTy) -> u32 {
+        match ty {
+            FloatTy::F32 => 24,
+            FloatTy::F64 => 53,
+        }
+    }
+
+    match (int_bits(from), int_bits(to), from, to) {
+        (Some((from_signed, from_bits)), Some((to_signed, to_bits)), ..) => {
+            // a signed value can be negative, which no unsigned type represents
+            from_bits < to_bits && (to_signed || !from_signed)
+        }
+        (Some((signed, bits)), None, _, Scalar::Float(to)) => {
+            let value_bits = if signed { bits - 1 } else { bits };
+            value_bits <= mantissa_bits(to)
+        }
+        (None, None, Scalar::Float(FloatTy::F32), Scalar::Float(FloatTy::F64)) => true,
+        _ => false,
+    }
+}
+
+/// Classifies the casts from `from` to `to` that the compiler rejects. Casts
+/// involving generic parameters, projections and the like are never reported,
+/// since we don't know what they stand for.
+fn invalid_cast_kind(from: &Ty, to: &Ty) -> Option<InvalidCastKind> {
+    let is_unsized = |ty: &Ty| matches!(ty, Ty::Slice(_) | Ty::Str | Ty::Dyn(_));
+    let is_sized = |ty: &Ty| {
+        matches!(
+            ty,
+            Ty::Scalar(_)
+                | Ty::Adt(..)
+                | Ty::Tuple(..)
+                | Ty::Array(_)
+                | Ty::RawPtr(..)
+                | Ty::Ref(..)
+        )
+    };
+    let kind = match (from, to) {
+        (_, _) if from == to => return None,
+        (Ty::Scalar(_), Ty::Scalar(Scalar::Bool)) => InvalidCastKind::ToBool,
+        (Ty::Scalar(from), Ty::Scalar(Scalar::Char)) if *from != Scalar::Uint(UintTy::U8) => {
+            InvalidCastKind::ToChar
+        }
+        (Ty::Adt(AdtId::StructId(_), _), Ty::Scalar(_))
+        | (Ty::Adt(AdtId::UnionId(_), _), Ty::Scalar(_))
+        | (Ty::Tuple(..), Ty::Scalar(_))
+        | (Ty::Array(_), Ty::Scalar(_)) => InvalidCastKind::NonPrimitive,
+        (Ty::Ref(_, inner), Ty::RawPtr(_, pointee))
+            if is_unsized(&inner[0]) && is_sized(&pointee[0]) =>
+        {
+            InvalidCastKind::FatToThinPtr
+        }
+        (from, Ty::Adt(..)) if is_sized(from) || is_unsized(from) => InvalidCastKind::ToAdt,
+        _ => return None,
+    };
+    Some(kind)
+}
+
 pub fn record_literal_missing_fields(
     db: &dyn HirDatabase,
     infer: &InferenceResult,
@@ -689,4 +1279,602 @@ fn main() {
 "#,
         )
     }
+
+    #[test]
+    fn arg_count_fn_pointer_and_closure() {
+        check_diagnostics(
+            r#"
+fn two(a: u8, b: u8) {}
+struct Tup(u8);
+
+fn main(ptr: fn(u8) -> u8) {
+    ptr(1, 2);
+  //^^^^^^^^^ Expected 1 argument, found 2
+    (&two)(1);
+  //^^^^^^^^^ Expected 2 arguments, found 1
+    let f = |a: u8, b: u8| a;
+    f(1);
+  //^^^^ Expected 2 arguments, found 1
+    let ctor = Tup;
+    ctor();
+  //^^^^^^ Expected 1 argument, found 0
+    ptr(1);
+    (&two)(1, 2);
+    f(1, 2);
+    ctor(1);
+}
+"#,
+        )
+    }
+
+    #[test]
+    fn generic_args_count_in_call() {
+        check_diagnostics(
+            r#"
+fn two<T, U>(t: T, u: U) {}
+fn none() {}
+fn main() {
+    two::<u8>(0, 0);
+  //^^^^^^^^^ Expected 2 generic arguments, found 1
+    none::<u8>();
+  //^^^^^^^^^^ Expected 0 generic arguments, found 1
+    two::<u8, u8>(0, 0);
+    two::<'static>(0, 0);
+    two(0, 0);
+}
+"#,
+        )
+    }
+
+    #[test]
+    fn type_mismatch_in_return_value() {
+        check_diagnostics(
+            r#"
+struct S;
+fn test() -> u32 {
+    if true {
+        return S;
+             //^ expected u32, found S
+    }
+    0
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn try_converts_error_via_from() {
+        check_diagnostics(
+            r#"
+//- /main.rs crate:main deps:core
+use core::{boxed::Box, error::Error, result::Result};
+
+struct IoError;
+impl Error for IoError {}
+struct ParseError;
+
+fn read() -> Result<u32, IoError> { loop {} }
+fn parse() -> Result<u32, ParseError> { loop {} }
+
+fn boxed() -> Result<u32, Box<dyn Error>> {
+    let n = read()?;
+    Result::Ok(n)
+}
+
+fn same() -> Result<u32, IoError> {
+    let n = read()?;
+    Result::Ok(n)
+}
+
+fn unconvertible() -> Result<u32, IoError> {
+    let n = parse()?;
+          //^^^^^^^^ the `?` operator on `Result<u32, ParseError>` can't return early with `Result<u32, IoError>`
+    Result::Ok(n)
+}
+
+//- /core.rs crate:core
+pub mod ops {
+    pub trait Try {
+        type Ok;
+        type Error;
+    }
+}
+pub mod convert {
+    pub trait From<T> {}
+}
+pub mod error {
+    pub trait Error {}
+}
+pub mod result {
+    pub enum Result<T, E> {
+        Ok(T),
+        Err(E),
+    }
+    impl<T, E> crate::ops::Try for Result<T, E> {
+        type Ok = T;
+        type Error = E;
+    }
+}
+pub mod boxed {
+    use crate::{convert::From, error::Error};
+    pub struct Box<T: ?Sized>(T);
+    impl<'a, E: Error + 'a> From<E> for Box<dyn Error + 'a> {}
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn try_in_try_block_converts_to_block_error() {
+        check_diagnostics(
+            r#"
+//- /main.rs crate:main deps:core
+use core::result::Result;
+
+struct IoError;
+struct ParseError;
+
+fn parse() -> Result<u32, ParseError> { loop {} }
+
+fn test() -> Result<u32, ParseError> {
+    let r: Result<u32, IoError> = try {
+        parse()?
+      //^^^^^^^^ the `?` operator on `Result<u32, ParseError>` can't return early with `Result<u32, IoError>`
+    };
+    parse()?;
+    Result::Ok(1)
+}
+
+//- /core.rs crate:core
+pub mod ops {
+    pub trait Try {
+        type Ok;
+        type Error;
+    }
+}
+pub mod convert {
+    pub trait From<T> {}
+}
+pub mod result {
+    pub enum Result<T, E> {
+        Ok(T),
+        Err(E),
+    }
+    impl<T, E> crate::ops::Try for Result<T, E> {
+        type Ok = T;
+        type Error = E;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn incorrect_try_expr() {
+        check_diagnostics(
+            r#"
+//- /main.rs crate:main deps:core
+use core::{option::Option, result::Result};
+
+struct E;
+
+fn opt() -> Option<u32> { loop {} }
+fn res() -> Result<u32, E> { loop {} }
+
+fn option_in_result() -> Result<u32, E> {
+    let x = opt()?;
+          //^^^^^^ the `?` operator on `Option<u32>` can't return early with `Result<u32, E>`
+    Result::Ok(x)
+}
+
+fn result_in_option() -> Option<u32> {
+    let x = res()?;
+          //^^^^^^ the `?` operator on `Result<u32, E>` can't return early with `Option<u32>`
+    Option::Some(x)
+}
+
+fn in_unit_fn() {
+    res()?;
+  //^^^^^^ the `?` operator can only return early with `Result` or `Option`, not `()`
+}
+
+fn not_try() -> Result<u32, E> {
+    let x = 1u32?;
+          //^^^^^ the `?` operator can't be applied to type `u32`
+    Result::Ok(x)
+}
+
+fn in_closure() {
+    let f = || -> Result<u32, E> {
+        let x = res()?;
+        Result::Ok(x)
+    };
+    let g = || -> Result<u32, E> {
+        let x = opt()?;
+              //^^^^^^ the `?` operator on `Option<u32>` can't return early with `Result<u32, E>`
+        Result::Ok(x)
+    };
+}
+
+//- /core.rs crate:core
+pub mod ops {
+    pub trait Try {
+        type Ok;
+        type Error;
+    }
+}
+pub mod convert {
+    pub trait From<T> {}
+}
+pub mod option {
+    pub enum Option<T> {
+        None,
+        Some(T),
+    }
+    pub struct NoneError;
+    impl<T> crate::ops::Try for Option<T> {
+        type Ok = T;
+        type Error = NoneError;
+    }
+}
+pub mod result {
+    pub enum Result<T, E> {
+        Ok(T),
+        Err(E),
+    }
+    impl<T, E> crate::ops::Try for Result<T, E> {
+        type Ok = T;
+        type Error = E;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn use_of_moved_value() {
+        check_diagnostics(
+            r#"
+#[lang = "copy"]
+trait Copy {}
+
+struct S;
+impl S {
+    fn consume(self) {}
+    fn borrow(&self) {}
+}
+fn drop<T>(_x: T) {}
+
+fn drop_twice(s: S) {
+    drop(s);
+    drop(s);
+       //^ use of moved value `s`
+}
+
+fn use_after_by_value_method(s: S) {
+    s.consume();
+    s.borrow();
+  //^ use of moved value `s`
+}
+
+fn same_call(s: S) {
+    let f = |_a: S, _b: S| {};
+    f(s, s);
+       //^ use of moved value `s`
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn no_use_of_moved_value() {
+        check_diagnostics(
+            r#"
+#[lang = "copy"]
+trait Copy {}
+
+struct S;
+impl S {
+    fn borrow(&self) {}
+}
+struct P;
+impl Copy for P {}
+fn drop<T>(_x: T) {}
+fn by_ref(_s: &S) {}
+
+fn copy(p: P, n: u32) {
+    drop(p);
+    drop(p);
+    drop(n);
+    drop(n);
+}
+
+fn behind_ref(s: S, r: &S) {
+    by_ref(&s);
+    s.borrow();
+    by_ref(&s);
+    drop(r);
+    drop(r);
+    drop(s);
+}
+
+fn reassigned(mut s: S) {
+    drop(s);
+    s = S;
+    drop(s);
+}
+
+fn branches(s: S, c: bool) {
+    if c {
+        drop(s);
+    } else {
+        drop(s);
+    }
+}
+
+fn in_loop(c: bool) {
+    while c {
+        let s = S;
+        drop(s);
+    }
+}
+
+fn diverges(s: S) {
+    drop(s);
+    return;
+    drop(s);
+  //^^^^^^^ unreachable code
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn unreachable_code() {
+        check_diagnostics(
+            r#"
+fn never() -> ! { loop {} }
+
+fn after_return() -> u32 {
+    return 0;
+    1
+  //^ unreachable code
+}
+
+fn after_call() {
+    never();
+    let x = 1;
+          //^ unreachable code
+}
+
+fn after_branches(c: bool) {
+    if c { return; } else { never(); }
+    after_call();
+  //^^^^^^^^^^^^ unreachable code
+}
+
+fn in_loop() {
+    loop {
+        continue;
+        after_call();
+      //^^^^^^^^^^^^ unreachable code
+    }
+}
+
+fn reachable(c: bool) {
+    if c { return; }
+    after_call();
+    loop { break; }
+    after_call();
+    let f = || { return; };
+    f();
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn type_mismatch_in_argument() {
+        check_diagnostics(
+            r#"
+mod m { pub struct S; }
+fn f(x: u32, s: m::S) {}
+fn test() {
+    f(m::S, m::S);
+    //^^^^ expected u32, found m::S
+    f(1, 1u32);
+       //^^^^ expected m::S, found u32
+    f(1, m::S);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn type_mismatch_in_closure() {
+        check_diagnostics(
+            r#"
+fn test() {
+    let f = |x: u32| -> bool { x };
+                             //^ expected bool, found u32
+    let g = |x: u32| x;
+    let _: bool = g(1);
+                //^^^^ expected bool, found u32
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn no_type_mismatch_with_unknown_types() {
+        check_diagnostics(
+            r#"
+fn test() -> u32 {
+    let x: Unresolved = 0;
+    x
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn missing_await() {
+        check_diagnostics(
+            r#"
+//- /main.rs crate:main deps:core
+async fn foo() -> u32 { 0 }
+
+async fn bar() {
+    foo();
+  //^^^^^ futures do nothing unless you `.await` them
+    foo().await;
+    let _f = foo();
+}
+
+fn baz() {
+    foo();
+    async {
+        foo();
+      //^^^^^ futures do nothing unless you `.await` them
+    };
+}
+
+//- /core.rs crate:core
+#[prelude_import] use future::*;
+mod future {
+    #[lang = "future_trait"]
+    pub trait Future {
+        type Output;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn identity_and_lossless_casts() {
+        check_diagnostics(
+            r#"
+fn f(a: u32, b: u8, c: i8, d: i64, e: usize, g: f32, h: u16) {
+    a as u32;
+  //^^^^^^^^ casting to the same type `u32` is unnecessary
+    b as u32;
+  //^^^^^^^^ casting `u8` to `u32` is lossless, consider using `u32::from`
+    b as i16;
+  //^^^^^^^^ casting `u8` to `i16` is lossless, consider using `i16::from`
+    c as u16;
+    d as i32;
+    e as u64;
+    b as usize;
+    a as f32;
+    h as f32;
+  //^^^^^^^^ casting `u16` to `f32` is lossless, consider using `f32::from`
+    g as f64;
+  //^^^^^^^^ casting `f32` to `f64` is lossless, consider using `f64::from`
+    1 as u32;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn invalid_casts() {
+        check_diagnostics(
+            r#"
+struct String;
+struct S(u32);
+enum E { A, B }
+trait Tr {}
+
+fn f(s: String, a: u32, g: f32, sl: &[u8], st: &str, d: &dyn Tr) {
+    s as u32;
+  //^^^^^^^^ non-primitive cast: `String` as `u32`
+    (a, a) as u64;
+  //^^^^^^^^^^^^^ non-primitive cast: `(u32, u32)` as `u64`
+    a as S;
+  //^^^^^^ non-primitive cast: `u32` as `S`, `as` can't construct a struct, enum or union
+    a as E;
+  //^^^^^^ non-primitive cast: `u32` as `E`, `as` can't construct a struct, enum or union
+    a as bool;
+  //^^^^^^^^^ cannot cast `u32` as `bool`, compare with zero instead
+    g as char;
+  //^^^^^^^^^ only `u8` can be cast as `char`, not `f32`
+    a as char;
+  //^^^^^^^^^ only `u8` can be cast as `char`, not `u32`
+    sl as *const u8;
+  //^^^^^^^^^^^^^^^ casting `&[u8]` as `*const u8` is invalid, cast through a raw pointer first
+    st as *const u8;
+  //^^^^^^^^^^^^^^^ casting `&str` as `*const u8` is invalid, cast through a raw pointer first
+    d as *const u8;
+  //^^^^^^^^^^^^^^ casting `&dyn Tr` as `*const u8` is invalid, cast through a raw pointer first
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn valid_casts_are_not_invalid() {
+        check_diagnostics(
+            r#"
+enum E { A, B }
+
+fn f(a: i32, b: bool, c: char, sl: &[u8], arr: &[u8; 4], p: *const [u8], x: Unresolved) {
+    a as u8;
+    -1i32 as u32;
+    b as u8;
+    c as u32;
+    7u8 as char;
+    E::A as i32;
+    arr as *const u8;
+    p as *const u8;
+    sl as *const [u8];
+    let _ = a as _;
+    let _ = x as u32;
+    let _ = a as Unresolved;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn unresolved_method_call() {
+        check_diagnostics(
+            r#"
+struct S;
+impl S { fn method(&self) {} }
+
+fn f() {
+    S.method();
+    S.methd();
+  //^^^^^^^^^ no method named `methd` found for `S`
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn unresolved_method_call_unsatisfied_bound() {
+        check_diagnostics(
+            r#"
+trait Clone { fn clone(&self) -> Self; }
+
+fn f<T, U: Clone>(t: T, u: U) {
+    t.clone();
+  //^^^^^^^^^ the method `clone` exists for `T`, but `T: Clone` is not satisfied
+    u.clone();
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn unresolved_method_call_on_unknown() {
+        check_diagnostics(
+            r#"
+fn f() {
+    Unresolved::new().foo();
+}
+"#,
+        );
+    }
 }
@@ -216,18 +216,26 @@
 //!   U(P, p) := U(P, (r_1, p_2, .., p_n))
 //!            || U(P, (r_2, p_2, .., p_n))
 //!   ```
-use std::{iter, sync::Arc};
+use std::{convert::TryFrom, fmt, iter, sync::Arc};
 
+use base_db::CrateId;
 use hir_def::{
     adt::VariantData,
     body::Body,
-    expr::{Expr, Literal, Pat, PatId},
-    AdtId, EnumVariantId, StructId, VariantId,
+    expr::{Expr, ExprId, Literal, Pat, PatId, UnaryOp},
+    resolver::HasResolver,
+    visibility::Visibility,
+    AdtId, AttrDefId, EnumVariantId, StructId, VariantId,
 };
 use la_arena::Idx;
 use smallvec::{smallvec, SmallVec};
 
-use crate::{db::HirDatabase, InferenceResult, Ty};
+use crate::{
+    db::HirDatabase,
+    primitive::{IntTy, UintTy},
+    utils::variant_data,
+    InferenceResult, Scalar, Ty,
+};
 
 #[derive(Debug, Clone, Copy)]
 /// Either a pattern from the source code being analyzed, represented as
@@ -390,6 +398,20 @@ impl PatStack {
                     _ => return Err(MatchCheckErr::NotImplemented),
                 }
             }
+            (Pat::Lit(_), &Constructor::IntRange(range))
+            | (Pat::Range { .. }, &Constructor::IntRange(range)) => {
+                let pat_id = head.as_id().expect("we know this isn't a wild");
+                let pat_range = IntRange::from_pat(cx, pat_id)?;
+                if range.is_subrange(&pat_range) {
+                    Some(self.to_tail())
+                } else if range.intersects(&pat_range) {
+                    // `is_useful` splits ranges before specializing, so this
+                    // shouldn't happen
+                    return Err(MatchCheckErr::NotImplemented);
+                } else {
+                    None
+                }
+            }
             (Pat::Wild, constructor) => Some(self.expand_wildcard(cx, constructor)?),
             (Pat::Path(_), constructor) => {
                 // unit enum variants become `Pat::Path`
@@ -414,6 +436,8 @@ impl PatStack {
                 };
                 if Some(variant_id) != cx.infer.variant_resolution_for_pat(pat_id) {
                     None
+                } else if ellipsis.is_none() && cx.requires_rest_pattern(variant_id) {
+                    return Err(MatchCheckErr::MalformedMatchArm);
                 } else {
                     let constructor_arity = constructor.arity(cx)?;
                     if let Some(ellipsis_position) = ellipsis {
@@ -450,7 +474,7 @@ impl PatStack {
                     }
                 }
             }
-            (Pat::Record { args: ref arg_patterns, .. }, constructor) => {
+            (Pat::Record { args: ref arg_patterns, ellipsis, .. }, constructor) => {
                 let pat_id = head.as_id().expect("we know this isn't a wild");
                 let (variant_id, variant_data) = match constructor {
                     &Constructor::Enum(e) => (
@@ -464,6 +488,8 @@ impl PatStack {
                 };
                 if Some(variant_id) != cx.infer.variant_resolution_for_pat(pat_id) {
                     None
+                } else if !ellipsis && cx.requires_rest_pattern(variant_id) {
+                    return Err(MatchCheckErr::MalformedMatchArm);
                 } else {
                     match variant_data.as_ref() {
                         VariantData::Record(struct_field_arena) => {
@@ -607,6 +633,39 @@ pub(super) struct MatchCheckCtx<'a> {
     pub(super) body: Arc<Body>,
     pub(super) infer: Arc<InferenceResult>,
     pub(super) db: &'a dyn HirDatabase,
+    /// The crate containing the match expression.
+    pub(super) krate: CrateId,
+}
+
+impl MatchCheckCtx<'_> {
+    /// Whether `def` is marked `#[non_exhaustive]` and defined in another
+    /// crate. Such enums may get new variants, so they can only be matched
+    /// exhaustively with a wildcard.
+    fn is_foreign_non_exhaustive(&self, def: AttrDefId) -> bool {
+        def.krate(self.db.upcast()) != self.krate
+            && self.db.attrs(def).by_key("non_exhaustive").exists()
+    }
+
+    /// Whether patterns of `variant` need a `..`, because it's a
+    /// non-exhaustive variant or struct from another crate, or because some
+    /// of its fields aren't visible outside of the crate defining it.
+    fn requires_rest_pattern(&self, variant: VariantId) -> bool {
+        let def: AttrDefId = match variant {
+            VariantId::EnumVariantId(it) => it.into(),
+            VariantId::StructId(it) => it.into(),
+            VariantId::UnionId(it) => it.into(),
+        };
+        if def.krate(self.db.upcast()) == self.krate {
+            return false;
+        }
+        if self.db.attrs(def).by_key("non_exhaustive").exists() {
+            return true;
+        }
+        let resolver = variant.resolver(self.db.upcast());
+        variant_data(self.db.upcast(), variant).fields().iter().any(|(_, field)| {
+            field.visibility.resolve(self.db.upcast(), &resolver) != Visibility::Public
+        })
+    }
 }
 
 /// Given a set of patterns `matrix`, and pattern to consider `v`, determines
@@ -628,7 +687,9 @@ pub(super) fn is_useful(
     // In those cases, no match arm is useful.
     match cx.infer[cx.match_expr].strip_references() {
         Ty::Adt(AdtId::EnumId(enum_id), ..) => {
-            if cx.db.enum_data(*enum_id).variants.is_empty() {
+            if cx.db.enum_data(*enum_id).variants.is_empty()
+                && !cx.is_foreign_non_exhaustive((*enum_id).into())
+            {
                 return Ok(Usefulness::NotUseful);
             }
         }
@@ -670,6 +731,39 @@ pub(super) fn is_useful(
     }
 
     if let Some(constructor) = pat_constructor(cx, head)? {
+        if let Constructor::IntRange(range) = constructor {
+            // A range pattern may overlap the ranges in the matrix only partially,
+            // so we split it into subranges which are either fully covered by or
+            // disjoint from each of them, and check each subrange separately.
+            let mut used_ranges = Vec::new();
+            for pat in matrix.heads() {
+                if let Some(Constructor::IntRange(used_range)) = pat_constructor(cx, pat)? {
+                    used_ranges.push(used_range);
+                }
+            }
+
+            let mut found_unimplemented = false;
+            for subrange in range.split(&used_ranges) {
+                let constructor = Constructor::IntRange(subrange);
+                let matrix = matrix.specialize_constructor(&cx, &constructor)?;
+                let v = v
+                    .specialize_constructor(&cx, &constructor)?
+                    .expect("we know this can't fail because `range` covers `subrange`");
+
+                match is_useful(&cx, &matrix, &v) {
+                    Ok(Usefulness::Useful) => return Ok(Usefulness::Useful),
+                    Ok(Usefulness::NotUseful) => continue,
+                    _ => found_unimplemented = true,
+                };
+            }
+
+            return if found_unimplemented {
+                Err(MatchCheckErr::NotImplemented)
+            } else {
+                Ok(Usefulness::NotUseful)
+            };
+        }
+
         let matrix = matrix.specialize_constructor(&cx, &constructor)?;
         let v = v
             .specialize_constructor(&cx, &constructor)?
@@ -704,7 +798,7 @@ pub(super) fn is_useful(
                 // Here we create a constructor for each variant and then check
                 // usefulness after specializing for that constructor.
                 let mut found_unimplemented = false;
-                for constructor in constructor.all_constructors(cx) {
+                for constructor in constructor.all_constructors(cx, &used_constructors) {
                     let matrix = matrix.specialize_constructor(&cx, &constructor)?;
                     let v = v.expand_wildcard(&cx, &constructor)?;
 
@@ -743,12 +837,13 @@ enum Constructor {
     Tuple { arity: usize },
     Enum(EnumVariantId),
     Struct(StructId),
+    IntRange(IntRange),
 }
 
 impl Constructor {
     fn arity(&self, cx: &MatchCheckCtx) -> MatchCheckResult<usize> {
         let arity = match self {
-            Constructor::Bool(_) => 0,
+            Constructor::Bool(_) | Constructor::IntRange(_) => 0,
             Constructor::Tuple { arity } => *arity,
             Constructor::Enum(e) => {
                 match cx.db.enum_data(e.parent).variants[e.local_id].variant_data.as_ref() {
@@ -767,7 +862,14 @@ impl Constructor {
         Ok(arity)
     }
 
-    fn all_constructors(&self, cx: &MatchCheckCtx) -> Vec<Constructor> {
+    /// Returns all the constructors of the type of `self`. Integer ranges are
+    /// split so that each of them is either fully covered by or disjoint from
+    /// each of the `used_constructors`.
+    fn all_constructors(
+        &self,
+        cx: &MatchCheckCtx,
+        used_constructors: &[Constructor],
+    ) -> Vec<Constructor> {
         match self {
             Constructor::Bool(_) => vec![Constructor::Bool(true), Constructor::Bool(false)],
             Constructor::Tuple { .. } | Constructor::Struct(_) => vec![*self],
@@ -780,6 +882,14 @@ impl Constructor {
                     Constructor::Enum(EnumVariantId { parent: e.parent, local_id })
                 })
                 .collect(),
+            Constructor::IntRange(range) => {
+                let used_ranges = IntRange::from_constructors(used_constructors);
+                IntRange::full(range.ty)
+                    .into_iter()
+                    .flat_map(|full_range| full_range.split(&used_ranges))
+                    .map(Constructor::IntRange)
+                    .collect()
+            }
         }
     }
 }
@@ -797,8 +907,15 @@ fn pat_constructor(cx: &MatchCheckCtx, pat: PatIdOrWild) -> MatchCheckResult<Opt
         }
         Pat::Lit(lit_expr) => match cx.body.exprs[lit_expr] {
             Expr::Literal(Literal::Bool(val)) => Some(Constructor::Bool(val)),
-            _ => return Err(MatchCheckErr::NotImplemented),
+            _ => {
+                let pat_id = pat.as_id().expect("we already know this pattern is not a wild");
+                Some(Constructor::IntRange(IntRange::from_pat(cx, pat_id)?))
+            }
         },
+        Pat::Range { .. } => {
+            let pat_id = pat.as_id().expect("we already know this pattern is not a wild");
+            Some(Constructor::IntRange(IntRange::from_pat(cx, pat_id)?))
+        }
         Pat::TupleStruct { .. } | Pat::Path(_) | Pat::Record { .. } => {
             let pat_id = pat.as_id().expect("we already know this pattern is not a wild");
             let variant_id =
@@ -841,6 +958,9 @@ fn all_constructors_covered(
 
             covers_true && covers_false
         }
+        // a non-exhaustive enum from another crate has an extra hidden variant,
+        // which only a wildcard covers
+        Constructor::Enum(e) if cx.is_foreign_non_exhaustive(e.parent.into()) => false,
         Constructor::Enum(e) => cx.db.enum_data(e.parent).variants.iter().all(|(id, _)| {
             for constructor in used_constructors {
                 if let Constructor::Enum(e) = constructor {
@@ -856,6 +976,222 @@ fn all_constructors_covered(
             &Constructor::Struct(sid) => sid == s,
             _ => false,
         }),
+        Constructor::IntRange(range) => {
+            IntRange::missing(range.ty, &IntRange::from_constructors(used_constructors)).is_empty()
+        }
+    }
+}
+
+/// Returns the values of an integer or `char` match expression which are not
+/// covered by any of the rows of `matrix`, to be reported as witnesses.
+///
+/// Returns nothing if the rows aren't all integer or `char` patterns.
+pub(super) fn missing_int_ranges(
+    cx: &MatchCheckCtx,
+    matrix: &Matrix,
+) -> MatchCheckResult<Vec<IntRange>> {
+    let mut used_ranges = Vec::new();
+    for pat in matrix.heads() {
+        match pat_constructor(cx, pat)? {
+            Some(Constructor::IntRange(range)) => used_ranges.push(range),
+            _ => return Ok(Vec::new()),
+        }
+    }
+    let missing = match used_ranges.first() {
+        Some(range) => IntRange::missing(range.ty, &used_ranges),
+        None => Vec::new(),
+    };
+    Ok(missing)
+}
+
+/// The sign bit of an `i128`. Signed values are stored with this bit flipped,
+/// which maps them onto `u128` while preserving their order.
+const SIGN_BIT: u128 = 1 << 127;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An inclusive range of values of an integer type or `char`, which is the
+/// constructor of literal and range patterns of these types.
+///
+/// The bounds are stored as `u128`, with signed values biased by `SIGN_BIT`
+/// so that ranges of all types can be compared directly.
+pub(super) struct IntRange {
+    lo: u128,
+    hi: u128,
+    ty: Scalar,
+}
+
+impl IntRange {
+    fn from_pat(cx: &MatchCheckCtx, pat_id: PatId) -> MatchCheckResult<IntRange> {
+        let ty = match cx.infer.type_of_pat[pat_id] {
+            Ty::Scalar(ty @ Scalar::Int(_))
+            | Ty::Scalar(ty @ Scalar::Uint(_))
+            | Ty::Scalar(ty @ Scalar::Char) => ty,
+            Ty::Unknown => return Err(MatchCheckErr::Unknown),
+            _ => return Err(MatchCheckErr::NotImplemented),
+        };
+        let (lo, hi) = match cx.body.pats[pat_id] {
+            Pat::Lit(expr) => {
+                let value = Self::eval(cx, ty, expr)?;
+                (value, value)
+            }
+            Pat::Range { start, end } => (Self::eval(cx, ty, start)?, Self::eval(cx, ty, end)?),
+            _ => return Err(MatchCheckErr::NotImplemented),
+        };
+        if lo > hi {
+            return Err(MatchCheckErr::MalformedMatchArm);
+        }
+        Ok(IntRange { lo, hi, ty })
+    }
+
+    /// Evaluates the (possibly negated) literal `expr` to a value of type `ty`.
+    fn eval(cx: &MatchCheckCtx, ty: Scalar, expr: ExprId) -> MatchCheckResult<u128> {
+        fn eval_literal(cx: &MatchCheckCtx, expr: ExprId) -> Option<i128> {
+            match cx.body.exprs[expr] {
+                Expr::Literal(Literal::Int(value, _)) => Some(value),
+                Expr::Literal(Literal::Uint(value, _)) => i128::try_from(value).ok(),
+                Expr::Literal(Literal::Char(value)) => Some(value as i128),
+                Expr::UnaryOp { expr, op: UnaryOp::Neg } => eval_literal(cx, expr)?.checked_neg(),
+                _ => None,
+            }
+        }
+
+        let value = eval_literal(cx, expr).ok_or(MatchCheckErr::NotImplemented)?;
+        let (min, max) = Self::bounds(ty);
+        let value = match ty {
+            Scalar::Int(_) => value as u128 ^ SIGN_BIT,
+            _ if value < 0 => return Err(MatchCheckErr::MalformedMatchArm),
+            _ => value as u128,
+        };
+        let is_valid_char = || std::char::from_u32(value as u32).is_some();
+        if value < min || value > max || (ty == Scalar::Char && !is_valid_char()) {
+            return Err(MatchCheckErr::MalformedMatchArm);
+        }
+        Ok(value)
+    }
+
+    /// The smallest and the largest value of `ty`.
+    fn bounds(ty: Scalar) -> (u128, u128) {
+        // FIXME: this assumes a 64-bit target
+        let bits = match ty {
+            Scalar::Int(IntTy::I8) | Scalar::Uint(UintTy::U8) => 8,
+            Scalar::Int(IntTy::I16) | Scalar::Uint(UintTy::U16) => 16,
+            Scalar::Int(IntTy::I32) | Scalar::Uint(UintTy::U32) => 32,
+            Scalar::Int(IntTy::I64)
+            | Scalar::Int(IntTy::Isize)
+            | Scalar::Uint(UintTy::U64)
+            | Scalar::Uint(UintTy::Usize) => 64,
+            Scalar::Int(IntTy::I128) | Scalar::Uint(UintTy::U128) => 128,
+            Scalar::Char => return (0, std::char::MAX as u128),
+            Scalar::Bool | Scalar::Float(_) => unreachable!("not an integer type: {:?}", ty),
+        };
+        match ty {
+            Scalar::Int(_) => {
+                let max = i128::MAX >> (128 - bits);
+                ((-max - 1) as u128 ^ SIGN_BIT, max as u128 ^ SIGN_BIT)
+            }
+            _ => (0, u128::MAX >> (128 - bits)),
+        }
+    }
+
+    /// All the values of `ty`. For `char`, these are two ranges around the
+    /// surrogate code points, which aren't valid `char`s.
+    fn full(ty: Scalar) -> Vec<IntRange> {
+        match ty {
+            Scalar::Char => vec![
+                IntRange { lo: 0, hi: 0xD7FF, ty },
+                IntRange { lo: 0xE000, hi: std::char::MAX as u128, ty },
+            ],
+            _ => {
+                let (lo, hi) = Self::bounds(ty);
+                vec![IntRange { lo, hi, ty }]
+            }
+        }
+    }
+
+    /// The values of `ty` which aren't covered by any of `used_ranges`.
+    fn missing(ty: Scalar, used_ranges: &[IntRange]) -> Vec<IntRange> {
+        let mut missing = Self::full(ty);
+        for used_range in used_ranges {
+            missing = missing.into_iter().flat_map(|range| range.subtract(used_range)).collect();
+        }
+        missing
+    }
+
+    fn from_constructors(constructors: &[Constructor]) -> Vec<IntRange> {
+        constructors
+            .iter()
+            .filter_map(|constructor| match constructor {
+                Constructor::IntRange(range) => Some(*range),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_subrange(&self, other: &IntRange) -> bool {
+        other.lo <= self.lo && self.hi <= other.hi
+    }
+
+    fn intersects(&self, other: &IntRange) -> bool {
+        self.lo <= other.hi && other.lo <= self.hi
+    }
+
+    fn subtract(self, other: &IntRange) -> SmallVec<[IntRange; 2]> {
+        if !self.intersects(other) {
+            return smallvec![self];
+        }
+        let mut res = SmallVec::new();
+        if self.lo < other.lo {
+            res.push(IntRange { hi: other.lo - 1, ..self });
+        }
+        if other.hi < self.hi {
+            res.push(IntRange { lo: other.hi + 1, ..self });
+        }
+        res
+    }
+
+    /// Splits `self` into subranges, each of which is either a subrange of or
+    /// disjoint from each of `ranges`.
+    fn split(self, ranges: &[IntRange]) -> Vec<IntRange> {
+        let mut borders = vec![self.lo];
+        for range in ranges.iter().filter(|range| range.intersects(&self)) {
+            if self.lo < range.lo {
+                borders.push(range.lo);
+            }
+            if range.hi < self.hi {
+                borders.push(range.hi + 1);
+            }
+        }
+        borders.sort_unstable();
+        borders.dedup();
+
+        let mut subranges = Vec::with_capacity(borders.len());
+        for (i, &lo) in borders.iter().enumerate() {
+            let hi = borders.get(i + 1).map_or(self.hi, |next| next - 1);
+            subranges.push(IntRange { lo, hi, ..self });
+        }
+        subranges
+    }
+
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>, value: u128) -> fmt::Result {
+        match self.ty {
+            Scalar::Char => {
+                let value = std::char::from_u32(value as u32).unwrap_or_default();
+                write!(f, "{:?}", value)
+            }
+            Scalar::Int(_) => write!(f, "{}", (value ^ SIGN_BIT) as i128),
+            _ => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for IntRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_value(f, self.lo)?;
+        if self.lo != self.hi {
+            f.write_str("..=")?;
+            self.fmt_value(f, self.hi)?;
+        }
+        Ok(())
     }
 }
 
@@ -1507,6 +1843,229 @@ fn main() {
 "#,
         );
     }
+    #[test]
+    fn unsigned_integers() {
+        check_diagnostics(
+            r#"
+fn main() {
+    match 5u8 {
+        //^^^ Missing match arm: `255` not covered
+        0..=254 => (),
+    }
+    match 5u8 {
+        //^^^ Missing match arm: `0`, `10..=20` and `100..=255` not covered
+        1..=9 => (),
+        21..=99 => (),
+    }
+    match 5u8 {
+        //^^^ Missing match arm: `1`, `3` and `5` not covered
+        0 | 2 | 4 | 6..=255 => (),
+    }
+    match 5u8 {
+        //^^^ Missing match arm: `1`, `3`, `5` and 1 more not covered
+        0 | 2 | 4 | 6 | 8..=255 => (),
+    }
+    match 5u8 {
+        0..=127 => (),
+        128..=255 => (),
+    }
+    match 5u8 {
+        0 => (),
+        1..=254 => (),
+        255 => (),
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn signed_integers() {
+        check_diagnostics(
+            r#"
+fn main() {
+    match 5i32 {
+        //^^^^ Missing match arm: `-2147483648..=-1` and `11..=2147483647` not covered
+        0..=10 => (),
+    }
+    match 5i8 {
+        //^^^ Missing match arm: `-128` not covered
+        -127..=-1 => (),
+        0 | 1..=127 => (),
+    }
+    match 5i8 {
+        -128..=0 => (),
+        0..=127 => (),
+    }
+    match 5i32 {
+        -5..=5 => (),
+        _ => (),
+    }
+    match (5i32, true) {
+        //^^^^^^^^^^^^ Missing match arm
+        (0..=10, _) => (),
+        (_, true) => (),
+    }
+    match (5i32, true) {
+        (0..=10, _) => (),
+        (_, true) => (),
+        (_, false) => (),
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn chars() {
+        check_diagnostics(
+            r#"
+fn main() {
+    match 'a' {
+        //^^^ Missing match arm: `'\u{e000}'..='\u{10ffff}'` not covered
+        '\0'..='\u{D7FF}' => (),
+    }
+    match 'a' {
+        //^^^ Missing match arm: `'\0'..='@'`, `'['..='\u{d7ff}'` and `'\u{e000}'..='\u{10ffff}'` not covered
+        'A'..='Z' => (),
+    }
+    match 'a' {
+        '\0'..='\u{D7FF}' => (),
+        '\u{E000}'..='\u{10FFFF}' => (),
+    }
+    match 'a' {
+        'a' => (),
+        _ => (),
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        check_diagnostics(
+            r#"
+fn main() {
+    match 5u8 {
+        0..=100 => (),
+        50..=200 => (),
+        150..=255 => (),
+    }
+    match 5u8 {
+        //^^^ Missing match arm: `101..=149` not covered
+        0..=100 => (),
+        50..=80 => (),
+        150..=255 => (),
+    }
+    match (5u8, true) {
+        (0..=100, true) => (),
+        (50..=255, _) => (),
+        (0..=49, false) => (),
+    }
+    match (5u8, true) {
+        //^^^^^^^^^^^ Missing match arm
+        (0..=100, true) => (),
+        (50..=255, _) => (),
+        (0..=48, false) => (),
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn non_exhaustive_enum() {
+        check_diagnostics(
+            r#"
+//- /lib.rs crate:lib
+#[non_exhaustive]
+pub enum E { A, B }
+#[non_exhaustive]
+pub enum Empty {}
+
+//- /main.rs crate:main deps:lib
+#[non_exhaustive]
+enum Local { A, B }
+
+fn main(e: lib::E, empty: lib::Empty) {
+    match e {
+        //^ Missing match arm
+        lib::E::A => (),
+        lib::E::B => (),
+    }
+    match e {
+        lib::E::A => (),
+        lib::E::B => (),
+        _ => (),
+    }
+    match empty { }
+        //^^^^^ Missing match arm
+    match Local::A {
+        Local::A => (),
+        Local::B => (),
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn non_exhaustive_and_private_fields_require_rest_pattern() {
+        check_diagnostics(
+            r#"
+//- /lib.rs crate:lib
+#[non_exhaustive]
+pub struct NonExhaustive { pub a: bool }
+pub struct Private { pub a: bool, b: bool }
+pub enum E {
+    #[non_exhaustive]
+    V { a: bool },
+}
+
+//- /main.rs crate:main deps:lib
+use lib::{NonExhaustive, Private, E};
+
+#[non_exhaustive]
+struct Local { a: bool }
+
+fn main(n: NonExhaustive, p: Private, e: E, l: Local) {
+    match n {
+        //^ Missing match arm
+        NonExhaustive { a: true, .. } => (),
+    }
+    match n {
+        NonExhaustive { a: true, .. } => (),
+        NonExhaustive { a: false, .. } => (),
+    }
+    // without `..` these patterns are errors, so we don't check the match
+    match n {
+        NonExhaustive { a: true } => (),
+    }
+    match e {
+        E::V { a: true } => (),
+    }
+    match e {
+        //^ Missing match arm
+        E::V { a: true, .. } => (),
+    }
+    match p {
+        //^ Missing match arm
+        Private { a: true, .. } => (),
+    }
+    match p {
+        Private { a: true, .. } => (),
+        Private { a: false, .. } => (),
+    }
+    match l {
+        //^ Missing match arm
+        Local { a: true } => (),
+    }
+}
+"#,
+        );
+    }
+
     mod false_negatives {
         //! The implementation of match checking here is a work in progress. As we roll this out, we
         //! prefer false negatives to false positives (ideally there would be no false positives). This
@@ -1521,7 +2080,7 @@ fn main() {
 
         #[test]
         fn integers() {
-            // We don't currently check integer exhaustiveness.
+            // We don't currently support exclusive range patterns.
             check_diagnostics(
                 r#"
 fn main() {
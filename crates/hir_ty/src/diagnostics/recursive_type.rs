@@ -0,0 +1,132 @@
+//! Detects types that contain themselves without any indirection, like
+//! `struct S { s: S }`. Such types would have infinite size.
+
+use hir_def::{AdtId, FieldId, HasModule};
+use rustc_hash::FxHashSet;
+
+use crate::{db::HirDatabase, utils::adt_variants, Substs, Ty};
+
+/// The maximum number of distinct types we're willing to look into before
+/// giving up. This makes sure we terminate on polymorphic recursion like
+/// `struct W<T>(Option<W<(T,)>>)`.
+const RECURSION_LIMIT: usize = 64;
+
+/// Returns the first field of `adt` through which `adt` contains itself
+/// without going through a pointer (`Box`, references or raw pointers).
+pub(crate) fn recursive_field_without_indirection_query(
+    db: &dyn HirDatabase,
+    adt: AdtId,
+) -> Option<FieldId> {
+    let krate = adt.module(db.upcast()).krate();
+    let owned_box =
+        db.lang_item(krate, "owned_box".into()).and_then(|it| it.as_struct()).map(AdtId::from);
+    let mut walker = RecursionWalker { db, target: adt, owned_box, visited: FxHashSet::default() };
+
+    let substs = Substs::type_params(db, adt);
+    adt_variants(db.upcast(), adt).into_iter().find_map(|variant| {
+        let field_types = db.field_types(variant);
+        let field = field_types.iter().find_map(|(local_id, ty)| {
+            let ty = ty.clone().subst(&substs);
+            if walker.reaches_target(&ty) {
+                Some(FieldId { parent: variant, local_id })
+            } else {
+                None
+            }
+        });
+        field
+    })
+}
+
+struct RecursionWalker<'a> {
+    db: &'a dyn HirDatabase,
+    target: AdtId,
+    owned_box: Option<AdtId>,
+    visited: FxHashSet<Ty>,
+}
+
+impl RecursionWalker<'_> {
+    /// Checks whether a value of type `ty` stores a value of the target type
+    /// inline.
+    fn reaches_target(&mut self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Adt(adt, _) if *adt == self.target => true,
+            Ty::Adt(adt, _) if Some(*adt) == self.owned_box => false,
+            Ty::Adt(adt, substs) => {
+                if self.visited.len() >= RECURSION_LIMIT || !self.visited.insert(ty.clone()) {
+                    return false;
+                }
+                let db = self.db;
+                adt_variants(db.upcast(), *adt).into_iter().any(|variant| {
+                    let field_types = db.field_types(variant);
+                    let result = field_types
+                        .values()
+                        .any(|field_ty| self.reaches_target(&field_ty.clone().subst(substs)));
+                    result
+                })
+            }
+            Ty::Tuple(_, substs) => substs.iter().any(|ty| self.reaches_target(ty)),
+            Ty::Array(substs) => self.reaches_target(substs.as_single()),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::tests::check_diagnostics;
+
+    #[test]
+    fn direct_recursion() {
+        check_diagnostics(
+            r#"
+struct S(S);
+       //^ recursive type `S` has infinite size; insert some indirection (e.g. a `Box`) to make it representable
+"#,
+        );
+    }
+
+    #[test]
+    fn recursion_through_other_types() {
+        check_diagnostics(
+            r#"
+enum Option<T> { Some(T), None }
+struct S { a: u32, b: (u8, Option<S>) }
+                    //^^^^^^^^^^^^^^^ recursive type `S` has infinite size; insert some indirection (e.g. a `Box`) to make it representable
+"#,
+        );
+    }
+
+    #[test]
+    fn recursion_through_box() {
+        check_diagnostics(
+            r#"
+#[lang = "owned_box"]
+struct Box<T>(T);
+
+struct S(Box<S>);
+enum List { Cons(u32, Box<List>), Nil }
+"#,
+        );
+    }
+
+    #[test]
+    fn recursion_through_pointers() {
+        check_diagnostics(
+            r#"
+struct S<'a> { r: &'a S<'a>, p: *const S<'a> }
+"#,
+        );
+    }
+
+    #[test]
+    fn polymorphic_recursion_terminates() {
+        check_diagnostics(
+            r#"
+enum Option<T> { Some(T), None }
+struct W<T>(T, Option<W<(T,)>>);
+             //^^^^^^^^^^^^^^^ recursive type `W` has infinite size; insert some indirection (e.g. a `Box`) to make it representable
+struct S(W<u32>);
+"#,
+        );
+    }
+}
@@ -1,17 +1,24 @@
-//! Provides validations for unsafe code. Currently checks if unsafe functions are missing
-//! unsafe blocks.
+//! Provides validations for unsafe code. Currently checks if unsafe operations are missing
+//! unsafe blocks, and if unsafe blocks don't contain any unsafe operations.
 
 use std::sync::Arc;
 
 use hir_def::{
     body::Body,
-    expr::{Expr, ExprId, UnaryOp},
+    expr::{BinaryOp, Expr, ExprId, Pat, PatId, Statement, UnaryOp},
     resolver::{resolver_for_expr, ResolveValueResult, ValueNs},
-    DefWithBodyId,
+    DefWithBodyId, VariantId,
 };
 use hir_expand::diagnostics::DiagnosticSink;
+use rustc_hash::FxHashSet;
+use syntax::SyntaxNodePtr;
 
-use crate::{db::HirDatabase, diagnostics::MissingUnsafe, InferenceResult, Ty};
+use crate::{
+    db::HirDatabase,
+    diagnostics::{MissingUnsafe, UnusedUnsafe},
+    infer::ExprOrPatId,
+    InferenceResult, Ty,
+};
 
 pub(super) struct UnsafeValidator<'a, 'b: 'a> {
     owner: DefWithBodyId,
@@ -29,100 +36,221 @@ impl<'a, 'b> UnsafeValidator<'a, 'b> {
     }
 
     pub(super) fn validate_body(&mut self, db: &dyn HirDatabase) {
-        let def = self.owner.into();
-        let unsafe_expressions = unsafe_expressions(db, self.infer.as_ref(), def);
+        let def = self.owner;
+        let unsafe_ops = unsafe_operations(db, self.infer.as_ref(), def);
+        // the whole body of an `unsafe fn` is an unsafe context
         let is_unsafe = match self.owner {
             DefWithBodyId::FunctionId(it) => db.function_data(it).is_unsafe,
             DefWithBodyId::StaticId(_) | DefWithBodyId::ConstId(_) => false,
         };
-        if is_unsafe
-            || unsafe_expressions
-                .iter()
-                .filter(|unsafe_expr| !unsafe_expr.inside_unsafe_block)
-                .count()
-                == 0
-        {
-            return;
-        }
 
-        let (_, body_source) = db.body_with_source_map(def);
-        for unsafe_expr in unsafe_expressions {
-            if !unsafe_expr.inside_unsafe_block {
-                if let Ok(in_file) = body_source.as_ref().expr_syntax(unsafe_expr.expr) {
-                    self.sink.push(MissingUnsafe { file: in_file.file_id, expr: in_file.value })
+        let (body, body_source) = db.body_with_source_map(def);
+        if !is_unsafe {
+            for unsafe_expr in unsafe_ops.exprs.iter().filter(|it| it.unsafe_block.is_none()) {
+                let node = match unsafe_expr.expr {
+                    ExprOrPatId::ExprId(expr) => {
+                        body_source.expr_syntax(expr).ok().map(|it| it.map(SyntaxNodePtr::from))
+                    }
+                    ExprOrPatId::PatId(pat) => body_source.pat_syntax(pat).ok().map(|it| {
+                        it.map(|ptr| ptr.either(SyntaxNodePtr::from, SyntaxNodePtr::from))
+                    }),
+                };
+                if let Some(node) = node {
+                    self.sink.push(MissingUnsafe { file: node.file_id, node: node.value })
                 }
             }
         }
+
+        let used_blocks: FxHashSet<ExprId> =
+            unsafe_ops.exprs.iter().filter_map(|it| it.unsafe_block).collect();
+        for (id, expr) in body.exprs.iter() {
+            if !matches!(expr, Expr::Unsafe { .. })
+                || used_blocks.contains(&id)
+                || unsafe_ops.uncertain_blocks.contains(&id)
+            {
+                continue;
+            }
+            let source_ptr = match body_source.expr_syntax(id) {
+                Ok(it) => it,
+                Err(_) => continue,
+            };
+            // a macro may need the block for other inputs
+            if source_ptr.file_id.call_node(db.upcast()).is_some() {
+                continue;
+            }
+            self.sink.push(UnusedUnsafe { file: source_ptr.file_id, expr: source_ptr.value });
+        }
     }
 }
 
 pub(crate) struct UnsafeExpr {
-    pub(crate) expr: ExprId,
-    pub(crate) inside_unsafe_block: bool,
+    pub(crate) expr: ExprOrPatId,
+    /// The innermost `unsafe` block the operation is in, if any.
+    pub(crate) unsafe_block: Option<ExprId>,
 }
 
-pub(crate) fn unsafe_expressions(
+#[derive(Default)]
+pub(crate) struct UnsafeOperations {
+    pub(crate) exprs: Vec<UnsafeExpr>,
+    /// The `unsafe` blocks directly containing expressions whose type is
+    /// unknown. These might be unsafe operations we don't understand.
+    pub(crate) uncertain_blocks: FxHashSet<ExprId>,
+}
+
+pub(crate) fn unsafe_operations(
     db: &dyn HirDatabase,
     infer: &InferenceResult,
     def: DefWithBodyId,
-) -> Vec<UnsafeExpr> {
-    let mut unsafe_exprs = vec![];
+) -> UnsafeOperations {
     let body = db.body(def);
-    walk_unsafe(&mut unsafe_exprs, db, infer, def, &body, body.body_expr, false);
-
-    unsafe_exprs
+    let mut walker = UnsafeWalker { db, infer, def, body: &body, ops: UnsafeOperations::default() };
+    for &param in body.params.iter() {
+        walker.walk_pat(param, None);
+    }
+    walker.walk_expr(body.body_expr, None);
+    walker.ops
 }
 
-fn walk_unsafe(
-    unsafe_exprs: &mut Vec<UnsafeExpr>,
-    db: &dyn HirDatabase,
-    infer: &InferenceResult,
+struct UnsafeWalker<'a> {
+    db: &'a dyn HirDatabase,
+    infer: &'a InferenceResult,
     def: DefWithBodyId,
-    body: &Body,
-    current: ExprId,
-    inside_unsafe_block: bool,
-) {
-    let expr = &body.exprs[current];
-    match expr {
-        &Expr::Call { callee, .. } => {
-            if let Some(func) = infer[callee].as_fn_def() {
-                if db.function_data(func).is_unsafe {
-                    unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
+    body: &'a Body,
+    ops: UnsafeOperations,
+}
+
+impl UnsafeWalker<'_> {
+    fn push(&mut self, expr: impl Into<ExprOrPatId>, unsafe_block: Option<ExprId>) {
+        self.ops.exprs.push(UnsafeExpr { expr: expr.into(), unsafe_block });
+    }
+
+    fn walk_expr(&mut self, current: ExprId, unsafe_block: Option<ExprId>) {
+        let (db, infer, body) = (self.db, self.infer, self.body);
+        let expr = &body.exprs[current];
+        if infer[current].is_unknown() {
+            self.ops.uncertain_blocks.extend(unsafe_block);
+        }
+        match expr {
+            &Expr::Call { callee, .. } => {
+                if let Some(func) = infer[callee].as_fn_def() {
+                    // this includes functions in `extern` blocks
+                    if db.function_data(func).is_unsafe {
+                        self.push(current, unsafe_block);
+                    }
                 }
             }
-        }
-        Expr::Path(path) => {
-            let resolver = resolver_for_expr(db.upcast(), def, current);
-            let value_or_partial = resolver.resolve_path_in_value_ns(db.upcast(), path.mod_path());
-            if let Some(ResolveValueResult::ValueNs(ValueNs::StaticId(id))) = value_or_partial {
-                if db.static_data(id).mutable {
-                    unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
+            Expr::Path(path) => {
+                let resolver = resolver_for_expr(db.upcast(), self.def, current);
+                let value_or_partial =
+                    resolver.resolve_path_in_value_ns(db.upcast(), path.mod_path());
+                if let Some(ResolveValueResult::ValueNs(ValueNs::StaticId(id))) = value_or_partial {
+                    if db.static_data(id).mutable {
+                        self.push(current, unsafe_block);
+                    }
                 }
             }
-        }
-        Expr::MethodCall { .. } => {
-            if infer
-                .method_resolution(current)
-                .map(|func| db.function_data(func).is_unsafe)
-                .unwrap_or(false)
-            {
-                unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
+            Expr::MethodCall { receiver, .. } => {
+                let calls_unsafe_fn = infer
+                    .method_resolution(current)
+                    .map_or(false, |func| db.function_data(func).is_unsafe);
+                let derefs_raw_ptr = infer
+                    .receiver_adjustments(current)
+                    .map_or(false, |it| autoderefs_raw_ptr(&infer[*receiver], it.autoderefs));
+                if calls_unsafe_fn || derefs_raw_ptr {
+                    self.push(current, unsafe_block);
+                }
+            }
+            Expr::Field { expr: base, .. } => {
+                if self.is_union_field(current) || autoderefs_raw_ptr(&infer[*base], usize::MAX) {
+                    self.push(current, unsafe_block);
+                }
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { op: None }) } => {
+                // writing to a union field is safe, only reading from it isn't
+                if let Expr::Field { expr: base, .. } = &body.exprs[*lhs] {
+                    if self.is_union_field(*lhs) {
+                        if autoderefs_raw_ptr(&infer[*base], usize::MAX) {
+                            self.push(*lhs, unsafe_block);
+                        }
+                        self.walk_expr(*base, unsafe_block);
+                        self.walk_expr(*rhs, unsafe_block);
+                        return;
+                    }
+                }
+            }
+            Expr::UnaryOp { expr, op: UnaryOp::Deref } => {
+                if let Ty::RawPtr(..) = &infer[*expr] {
+                    self.push(current, unsafe_block);
+                }
+            }
+            Expr::Unsafe { body: child } => {
+                return self.walk_expr(*child, Some(current));
             }
+            _ => {}
         }
-        Expr::UnaryOp { expr, op: UnaryOp::Deref } => {
-            if let Ty::RawPtr(..) = &infer[*expr] {
-                unsafe_exprs.push(UnsafeExpr { expr: current, inside_unsafe_block });
+
+        match expr {
+            Expr::Let { pat, .. } | Expr::For { pat, .. } => self.walk_pat(*pat, unsafe_block),
+            Expr::Match { arms, .. } => {
+                for arm in arms {
+                    self.walk_pat(arm.pat, unsafe_block);
+                }
             }
+            Expr::Lambda { args, .. } => {
+                for &arg in args {
+                    self.walk_pat(arg, unsafe_block);
+                }
+            }
+            Expr::Block { statements, .. } => {
+                for statement in statements {
+                    if let Statement::Let { pat, .. } = statement {
+                        self.walk_pat(*pat, unsafe_block);
+                    }
+                }
+            }
+            _ => {}
         }
-        Expr::Unsafe { body: child } => {
-            return walk_unsafe(unsafe_exprs, db, infer, def, body, *child, true);
+
+        expr.walk_child_exprs(|child| self.walk_expr(child, unsafe_block));
+    }
+
+    fn walk_pat(&mut self, current: PatId, unsafe_block: Option<ExprId>) {
+        let body = self.body;
+        let pat = &body.pats[current];
+        if let Pat::Record { args, .. } = pat {
+            // destructuring a union reads the fields that aren't ignored
+            let is_union = matches!(
+                self.infer.variant_resolution_for_pat(current),
+                Some(VariantId::UnionId(_))
+            );
+            if is_union && args.iter().any(|field| !matches!(body.pats[field.pat], Pat::Wild)) {
+                self.push(current, unsafe_block);
+            }
         }
-        _ => {}
+        pat.walk_child_pats(|child| self.walk_pat(child, unsafe_block));
+    }
+
+    fn is_union_field(&self, field_expr: ExprId) -> bool {
+        matches!(
+            self.infer.field_resolution(field_expr),
+            Some(field) if matches!(field.parent, VariantId::UnionId(_))
+        )
     }
+}
 
-    expr.walk_child_exprs(|child| {
-        walk_unsafe(unsafe_exprs, db, infer, def, body, child, inside_unsafe_block);
-    });
+/// Whether autoderefing `ty` at most `steps` times goes through a raw pointer.
+fn autoderefs_raw_ptr(ty: &Ty, steps: usize) -> bool {
+    let mut ty = ty.clone();
+    for _ in 0..steps {
+        if let Ty::RawPtr(..) = ty {
+            return true;
+        }
+        ty = match ty.builtin_deref() {
+            Some(it) => it,
+            None => return false,
+        };
+    }
+    false
 }
 
 #[cfg(test)]
@@ -209,6 +337,106 @@ fn main() {
     let _ = floorf32(12.0);
           //^^^^^^^^^^^^^^ This operation is unsafe and requires an unsafe function or block
 }
+"#,
+        );
+    }
+
+    #[test]
+    fn missing_unsafe_diagnostic_with_extern_fn() {
+        check_diagnostics(
+            r#"
+extern "C" {
+    fn abort();
+}
+
+fn main() {
+    abort();
+  //^^^^^^^ This operation is unsafe and requires an unsafe function or block
+    unsafe { abort() };
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn missing_unsafe_diagnostic_with_union_field() {
+        check_diagnostics(
+            r#"
+union U { a: u32, b: f32 }
+
+fn main() {
+    let mut u = U { a: 0 };
+    let x = u.a;
+          //^^^ This operation is unsafe and requires an unsafe function or block
+    u.a = 1;
+    u.a += 1;
+  //^^^ This operation is unsafe and requires an unsafe function or block
+    let U { b } = u;
+      //^^^^^^^ This operation is unsafe and requires an unsafe function or block
+    let U { b: _ } = u;
+    unsafe {
+        let y = u.b;
+        let U { a } = u;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn missing_unsafe_diagnostic_with_implicit_raw_ptr_deref() {
+        check_diagnostics(
+            r#"
+struct S { f: u32 }
+impl S { fn method(&self) {} }
+
+fn main() {
+    let s = S { f: 0 };
+    let p = &s as *const S;
+    p.method();
+  //^^^^^^^^^^ This operation is unsafe and requires an unsafe function or block
+    let f = p.f;
+          //^^^ This operation is unsafe and requires an unsafe function or block
+    let r = &s;
+    r.method();
+    let g = r.f;
+    unsafe {
+        p.method();
+        let h = p.f;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn unused_unsafe_blocks() {
+        check_diagnostics(
+            r#"
+unsafe fn unsafe_fn() {}
+
+fn main() {
+    let x = &5 as *const usize;
+    unsafe { 92 };
+  //^^^^^^^^^^^^^ unnecessary `unsafe` block
+    unsafe {
+  //^ ... unnecessary `unsafe` block
+        unsafe { *x };
+    }
+    unsafe {
+        unsafe_fn();
+        unsafe { 92 };
+      //^^^^^^^^^^^^^ unnecessary `unsafe` block
+    }
+    unsafe { unresolved() };
+}
+
+unsafe fn in_unsafe_fn(x: *const usize) {
+    let y = *x;
+    unsafe { *x };
+    unsafe { 92 };
+  //^^^^^^^^^^^^^ unnecessary `unsafe` block
+}
 "#,
         );
     }
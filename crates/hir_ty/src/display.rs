@@ -3,13 +3,14 @@
 use std::{borrow::Cow, fmt};
 
 use crate::{
-    db::HirDatabase, primitive, utils::generics, CallableDefId, CallableSig, GenericPredicate,
-    Lifetime, Obligation, OpaqueTy, OpaqueTyId, ProjectionTy, Scalar, Substs, TraitRef, Ty,
+    db::HirDatabase, primitive, utils::generics, Binders, BoundVar, CallableDefId, CallableSig,
+    Canonical, GenericPredicate, Lifetime, Obligation, OpaqueTy, OpaqueTyId, ProjectionTy, Scalar,
+    Substs, TraitRef, Ty, TyVariableKind,
 };
 use arrayvec::ArrayVec;
 use hir_def::{
-    db::DefDatabase, find_path, generics::TypeParamProvenance, item_scope::ItemInNs, AdtId,
-    AssocContainerId, HasModule, Lookup, ModuleId, TraitId,
+    db::DefDatabase, expr::Pat, find_path, generics::TypeParamProvenance, item_scope::ItemInNs,
+    path::PathKind, AdtId, AssocContainerId, FunctionId, HasModule, Lookup, ModuleId, TraitId,
 };
 use hir_expand::name::Name;
 
@@ -19,8 +20,17 @@ pub struct HirFormatter<'a> {
     buf: String,
     curr_size: usize,
     pub(crate) max_size: Option<usize>,
+    /// The number of tuple fields and function parameters to show before
+    /// eliding the rest with `…`.
+    max_list_len: Option<usize>,
     omit_verbose_types: bool,
     display_target: DisplayTarget,
+    /// The number of variables bound by each `Binders` we're currently
+    /// displaying (innermost last). Bound variables are named `T0`, `T1`, ...
+    /// by their ordinal across all these binders. The binder for the self type
+    /// of `dyn` and `impl Trait` bounds counts as binding none, since its
+    /// variable isn't named.
+    binders: Vec<usize>,
 }
 
 pub trait HirDisplay {
@@ -37,7 +47,14 @@ pub trait HirDisplay {
     where
         Self: Sized,
     {
-        HirDisplayWrapper { db, t: self, max_size, omit_verbose_types, display_target }
+        HirDisplayWrapper {
+            db,
+            t: self,
+            max_size,
+            max_list_len: None,
+            omit_verbose_types,
+            display_target,
+        }
     }
 
     /// Returns a `Display`able type that is human-readable.
@@ -50,6 +67,7 @@ pub trait HirDisplay {
             db,
             t: self,
             max_size: None,
+            max_list_len: None,
             omit_verbose_types: false,
             display_target: DisplayTarget::Diagnostics,
         }
@@ -69,6 +87,7 @@ pub trait HirDisplay {
             db,
             t: self,
             max_size,
+            max_list_len: None,
             omit_verbose_types: true,
             display_target: DisplayTarget::Diagnostics,
         }
@@ -88,8 +107,10 @@ pub trait HirDisplay {
             buf: String::with_capacity(20),
             curr_size: 0,
             max_size: None,
+            max_list_len: None,
             omit_verbose_types: false,
             display_target: DisplayTarget::SourceCode { module_id },
+            binders: Vec::new(),
         }) {
             Ok(()) => {}
             Err(HirDisplayError::FmtError) => panic!("Writing to String can't fail!"),
@@ -98,6 +119,37 @@ pub trait HirDisplay {
         Ok(result)
     }
 
+    /// Returns a `Display`able type that renders paths relative to `module_id`,
+    /// omitting the leading segments they share with it.
+    /// Use this for showing types in the context of some code (e.g. hovers)
+    fn display_shortened<'a>(
+        &'a self,
+        db: &'a dyn HirDatabase,
+        module_id: ModuleId,
+    ) -> ShortenedPathFormatter<'a, Self>
+    where
+        Self: Sized,
+    {
+        ShortenedPathFormatter { db, t: self, module_id }
+    }
+
+    /// Returns a `Display`able type that shows the internal structure of
+    /// `self`, i.e. bound variable indices and inference variable numbers.
+    /// Use this for debugging (e.g. `eprintln!("{}", ty.dbg_display(db))`)
+    fn dbg_display<'a>(&'a self, db: &'a dyn HirDatabase) -> HirDisplayWrapper<'a, Self>
+    where
+        Self: Sized,
+    {
+        HirDisplayWrapper {
+            db,
+            t: self,
+            max_size: None,
+            max_list_len: None,
+            omit_verbose_types: false,
+            display_target: DisplayTarget::Debug,
+        }
+    }
+
     /// Returns a String representation of `self` for test purposes
     fn display_test<'a>(&'a self, db: &'a dyn HirDatabase) -> HirDisplayWrapper<'a, Self>
     where
@@ -107,6 +159,7 @@ pub trait HirDisplay {
             db,
             t: self,
             max_size: None,
+            max_list_len: None,
             omit_verbose_types: false,
             display_target: DisplayTarget::Test,
         }
@@ -130,6 +183,19 @@ impl<'a> HirFormatter<'a> {
         Ok(())
     }
 
+    /// Writes the fields of a tuple or the parameters of a function, eliding
+    /// those after the first `max_list_len` ones.
+    fn write_list(&mut self, items: &[Ty]) -> Result<(), HirDisplayError> {
+        match self.max_list_len {
+            Some(0) if !items.is_empty() => write!(self, "{}", TYPE_HINT_TRUNCATION),
+            Some(max) if items.len() > max => {
+                self.write_joined(items[..max].iter(), ", ")?;
+                write!(self, ", {}", TYPE_HINT_TRUNCATION)
+            }
+            _ => self.write_joined(items.iter(), ", "),
+        }
+    }
+
     /// This allows using the `write!` macro directly with a `HirFormatter`.
     pub fn write_fmt(&mut self, args: fmt::Arguments) -> Result<(), HirDisplayError> {
         // We write to a buffer first to track output size
@@ -152,6 +218,31 @@ impl<'a> HirFormatter<'a> {
     pub fn omit_verbose_types(&self) -> bool {
         self.omit_verbose_types
     }
+
+    /// The name of the bound variable `idx`, if it's bound by a `Binders`
+    /// we're displaying.
+    fn bound_var_name(&self, idx: BoundVar) -> Option<String> {
+        let depth = idx.debruijn.depth() as usize;
+        let level = self.binders.len().checked_sub(depth + 1)?;
+        if idx.index >= self.binders[level] {
+            return None;
+        }
+        let offset: usize = self.binders[..level].iter().sum();
+        Some(format!("T{}", offset + idx.index))
+    }
+
+    /// Writes the bounds of a `dyn` or `impl Trait` type, which are under a
+    /// binder for its self type.
+    fn write_self_binder_bounds(
+        &mut self,
+        prefix: &str,
+        predicates: &[GenericPredicate],
+    ) -> Result<(), HirDisplayError> {
+        self.binders.push(0);
+        let result = write_bounds_like_dyn_trait_with_prefix(prefix, predicates, self);
+        self.binders.pop();
+        result
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -163,8 +254,14 @@ pub enum DisplayTarget {
     /// Display types for inserting them in source files.
     /// The generated code should compile, so paths need to be qualified.
     SourceCode { module_id: ModuleId },
+    /// Display types relative to a module, e.g. for hovers.
+    /// Paths are shortened, but may not resolve from that module.
+    ShortenedPaths { module_id: ModuleId },
     /// Only for test purpose to keep real types
     Test,
+    /// Like `Test`, but also shows the raw indices of bound and inference
+    /// variables.
+    Debug,
 }
 
 impl DisplayTarget {
@@ -172,7 +269,10 @@ impl DisplayTarget {
         matches!(self, Self::SourceCode { .. })
     }
     fn is_test(&self) -> bool {
-        matches!(self, Self::Test)
+        matches!(self, Self::Test | Self::Debug)
+    }
+    fn is_debug(&self) -> bool {
+        matches!(self, Self::Debug)
     }
 }
 
@@ -198,10 +298,20 @@ pub struct HirDisplayWrapper<'a, T> {
     db: &'a dyn HirDatabase,
     t: &'a T,
     max_size: Option<usize>,
+    max_list_len: Option<usize>,
     omit_verbose_types: bool,
     display_target: DisplayTarget,
 }
 
+impl<'a, T> HirDisplayWrapper<'a, T> {
+    /// Shows at most `max_list_len` fields of tuples and parameters of
+    /// functions, followed by `…` if there are more. By default, all of them
+    /// are shown.
+    pub fn with_max_list_len(self, max_list_len: Option<usize>) -> Self {
+        HirDisplayWrapper { max_list_len, ..self }
+    }
+}
+
 impl<'a, T> fmt::Display for HirDisplayWrapper<'a, T>
 where
     T: HirDisplay,
@@ -213,8 +323,10 @@ where
             buf: String::with_capacity(20),
             curr_size: 0,
             max_size: self.max_size,
+            max_list_len: self.max_list_len,
             omit_verbose_types: self.omit_verbose_types,
             display_target: self.display_target,
+            binders: Vec::new(),
         }) {
             Ok(()) => Ok(()),
             Err(HirDisplayError::FmtError) => Err(fmt::Error),
@@ -226,6 +338,22 @@ where
     }
 }
 
+pub struct ShortenedPathFormatter<'a, T> {
+    db: &'a dyn HirDatabase,
+    t: &'a T,
+    module_id: ModuleId,
+}
+
+impl<'a, T> fmt::Display for ShortenedPathFormatter<'a, T>
+where
+    T: HirDisplay,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let display_target = DisplayTarget::ShortenedPaths { module_id: self.module_id };
+        fmt::Display::fmt(&self.t.into_displayable(self.db, None, false, display_target), f)
+    }
+}
+
 const TYPE_HINT_TRUNCATION: &str = "…";
 
 impl HirDisplay for &Ty {
@@ -241,12 +369,9 @@ impl HirDisplay for ProjectionTy {
         }
 
         let trait_ = f.db.trait_data(self.trait_(f.db));
-        let first_parameter = self.parameters[0].into_displayable(
-            f.db,
-            f.max_size,
-            f.omit_verbose_types,
-            f.display_target,
-        );
+        let first_parameter = self.parameters[0]
+            .into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target)
+            .with_max_list_len(f.max_list_len);
         write!(f, "<{} as {}", first_parameter, trait_.name)?;
         if self.parameters.len() > 1 {
             write!(f, "<")?;
@@ -258,6 +383,38 @@ impl HirDisplay for ProjectionTy {
     }
 }
 
+/// Returns the names of the modules leading from the crate root to `module`.
+fn module_path(db: &dyn DefDatabase, module: ModuleId) -> Vec<Name> {
+    let mut segments = Vec::new();
+    let mut def_map = module.def_map(db);
+    let mut local_id = module.local_id;
+    loop {
+        match def_map[local_id].parent {
+            Some(parent) => {
+                let name = def_map[parent].children.iter().find_map(|(name, &id)| {
+                    if id == local_id {
+                        Some(name.clone())
+                    } else {
+                        None
+                    }
+                });
+                segments.extend(name);
+                local_id = parent;
+            }
+            // Block scopes don't have names, continue with the containing module.
+            None => match def_map.parent() {
+                Some(parent) => {
+                    local_id = parent.local_id;
+                    def_map = parent.def_map(db);
+                }
+                None => break,
+            },
+        }
+    }
+    segments.reverse();
+    segments
+}
+
 impl HirDisplay for Ty {
     fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
         if f.should_truncate() {
@@ -286,8 +443,9 @@ impl HirDisplay for Ty {
             }
             Ty::RawPtr(m, parameters) | Ty::Ref(m, parameters) => {
                 let t = parameters.as_single();
-                let ty_display =
-                    t.into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target);
+                let ty_display = t
+                    .into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target)
+                    .with_max_list_len(f.max_list_len);
 
                 if matches!(self, Ty::RawPtr(..)) {
                     write!(f, "*{}", m.as_keyword_for_ptr())?;
@@ -295,7 +453,6 @@ impl HirDisplay for Ty {
                     write!(f, "&{}", m.as_keyword_for_ref())?;
                 }
 
-                let datas;
                 let predicates = match t {
                     Ty::Dyn(predicates) if predicates.len() > 1 => {
                         Cow::Borrowed(predicates.as_ref())
@@ -304,18 +461,17 @@ impl HirDisplay for Ty {
                         opaque_ty_id: OpaqueTyId::ReturnTypeImplTrait(func, idx),
                         ref parameters,
                     }) => {
-                        datas =
-                            f.db.return_type_impl_traits(func).expect("impl trait id without data");
-                        let data = (*datas)
-                            .as_ref()
-                            .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                        let bounds = data.subst(parameters);
-                        Cow::Owned(bounds.value)
+                        let bounds = return_type_impl_trait_bounds(f.db, func, idx, parameters);
+                        Cow::Owned(bounds.unwrap_or_default())
                     }
                     _ => Cow::Borrowed(&[][..]),
                 };
 
-                if let [GenericPredicate::Implemented(trait_ref), _] = predicates.as_ref() {
+                if let [pred, _] = predicates.as_ref() {
+                    let trait_ref = match pred.skip_binders() {
+                        GenericPredicate::Implemented(trait_ref) => trait_ref,
+                        _ => return write!(f, "{}", ty_display),
+                    };
                     let trait_ = trait_ref.trait_;
                     if fn_traits(f.db.upcast(), trait_).any(|it| it == trait_) {
                         return write!(f, "{}", ty_display);
@@ -337,7 +493,7 @@ impl HirDisplay for Ty {
                     write!(f, ",)")?;
                 } else {
                     write!(f, "(")?;
-                    f.write_joined(&*substs.0, ", ")?;
+                    f.write_list(&*substs.0)?;
                     write!(f, ")")?;
                 }
             }
@@ -370,30 +526,66 @@ impl HirDisplay for Ty {
                     }
                 }
                 write!(f, "(")?;
-                f.write_joined(sig.params(), ", ")?;
+                f.write_list(sig.params())?;
                 write!(f, ")")?;
                 let ret = sig.ret();
                 if *ret != Ty::unit() {
-                    let ret_display = ret.into_displayable(
-                        f.db,
-                        f.max_size,
-                        f.omit_verbose_types,
-                        f.display_target,
-                    );
+                    let ret_display = ret
+                        .into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target)
+                        .with_max_list_len(f.max_list_len);
 
                     write!(f, " -> {}", ret_display)?;
                 }
             }
             Ty::Adt(def_id, parameters) => {
+                let name = match *def_id {
+                    AdtId::StructId(it) => f.db.struct_data(it).name.clone(),
+                    AdtId::UnionId(it) => f.db.union_data(it).name.clone(),
+                    AdtId::EnumId(it) => f.db.enum_data(it).name.clone(),
+                };
                 match f.display_target {
-                    DisplayTarget::Diagnostics | DisplayTarget::Test => {
-                        let name = match *def_id {
-                            AdtId::StructId(it) => f.db.struct_data(it).name.clone(),
-                            AdtId::UnionId(it) => f.db.union_data(it).name.clone(),
-                            AdtId::EnumId(it) => f.db.enum_data(it).name.clone(),
-                        };
+                    DisplayTarget::Diagnostics | DisplayTarget::Test | DisplayTarget::Debug => {
                         write!(f, "{}", name)?;
                     }
+                    DisplayTarget::ShortenedPaths { module_id } => {
+                        let item_module = def_id.module(f.db.upcast());
+                        let mut segments = Vec::new();
+                        if item_module.krate() != module_id.krate() {
+                            let crate_graph = f.db.crate_graph();
+                            if let Some(crate_name) = &crate_graph[item_module.krate()].display_name
+                            {
+                                segments.push(crate_name.to_string());
+                            }
+                            segments.extend(
+                                module_path(f.db.upcast(), item_module).iter().map(Name::to_string),
+                            );
+                        } else {
+                            let item_path = module_path(f.db.upcast(), item_module);
+                            let current_path = module_path(f.db.upcast(), module_id);
+                            let common = item_path
+                                .iter()
+                                .zip(&current_path)
+                                .take_while(|(a, b)| a == b)
+                                .count();
+                            segments.extend(item_path[common..].iter().map(Name::to_string));
+                        }
+                        segments.push(name.to_string());
+
+                        // If the type is in scope (e.g. imported), the path it's
+                        // reachable by is usually shorter still.
+                        match find_path::find_path(
+                            f.db.upcast(),
+                            ItemInNs::Types((*def_id).into()),
+                            module_id,
+                        ) {
+                            Some(path)
+                                if path.kind == PathKind::Plain && path.len() < segments.len() =>
+                            {
+                                write!(f, "{}", path)?
+                            }
+                            _ => write!(f, "{}", segments.join("::"))?,
+                        }
+                    }
                     DisplayTarget::SourceCode { module_id } => {
                         if let Some(path) = find_path::find_path(
                             f.db.upcast(),
@@ -478,13 +670,7 @@ impl HirDisplay for Ty {
             Ty::OpaqueType(opaque_ty_id, parameters) => {
                 match opaque_ty_id {
                     &OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        let datas =
-                            f.db.return_type_impl_traits(func).expect("impl trait id without data");
-                        let data = (*datas)
-                            .as_ref()
-                            .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                        let bounds = data.subst(&parameters);
-                        write_bounds_like_dyn_trait_with_prefix("impl", &bounds.value, f)?;
+                        write_return_type_impl_trait(func, idx, parameters, f)?;
                         // FIXME: it would maybe be good to distinguish this from the alias type (when debug printing), and to show the substitution
                     }
                     OpaqueTyId::AsyncBlockTypeImplTrait(..) => {
@@ -503,16 +689,14 @@ impl HirDisplay for Ty {
                         write!(f, "|{}|", TYPE_HINT_TRUNCATION)?;
                     } else {
                         write!(f, "|")?;
-                        f.write_joined(sig.params(), ", ")?;
+                        f.write_list(sig.params())?;
                         write!(f, "|")?;
                     };
 
-                    let ret_display = sig.ret().into_displayable(
-                        f.db,
-                        f.max_size,
-                        f.omit_verbose_types,
-                        f.display_target,
-                    );
+                    let ret_display = sig
+                        .ret()
+                        .into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target)
+                        .with_max_list_len(f.max_list_len);
                     write!(f, " -> {}", ret_display)?;
                 } else {
                     write!(f, "{{closure}}")?;
@@ -537,20 +721,20 @@ impl HirDisplay for Ty {
                     }
                 }
             }
-            Ty::Bound(idx) => write!(f, "?{}.{}", idx.debruijn.depth(), idx.index)?,
+            Ty::Bound(idx) if f.display_target.is_debug() => {
+                write!(f, "^{}.{}", idx.debruijn.depth(), idx.index)?
+            }
+            Ty::Bound(idx) => match f.bound_var_name(*idx) {
+                Some(name) => write!(f, "{}", name)?,
+                None => write!(f, "?{}.{}", idx.debruijn.depth(), idx.index)?,
+            },
             Ty::Dyn(predicates) => {
-                write_bounds_like_dyn_trait_with_prefix("dyn", predicates, f)?;
+                f.write_self_binder_bounds("dyn", predicates)?;
             }
             Ty::Opaque(opaque_ty) => {
                 match opaque_ty.opaque_ty_id {
                     OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        let datas =
-                            f.db.return_type_impl_traits(func).expect("impl trait id without data");
-                        let data = (*datas)
-                            .as_ref()
-                            .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                        let bounds = data.subst(&opaque_ty.parameters);
-                        write_bounds_like_dyn_trait_with_prefix("impl", &bounds.value, f)?;
+                        write_return_type_impl_trait(func, idx, &opaque_ty.parameters, f)?;
                     }
                     OpaqueTyId::AsyncBlockTypeImplTrait(..) => {
                         write!(f, "{{async block}}")?;
@@ -565,6 +749,9 @@ impl HirDisplay for Ty {
                 }
                 write!(f, "{{unknown}}")?;
             }
+            Ty::InferenceVar(var, kind) if f.display_target.is_debug() => {
+                write!(f, "?{}{}", var.index(), ty_variable_kind_suffix(*kind))?
+            }
             Ty::InferenceVar(..) => write!(f, "_")?,
         }
         Ok(())
@@ -574,7 +761,7 @@ impl HirDisplay for Ty {
 impl HirDisplay for CallableSig {
     fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
         write!(f, "fn(")?;
-        f.write_joined(self.params(), ", ")?;
+        f.write_list(self.params())?;
         if self.is_varargs {
             if self.params().is_empty() {
                 write!(f, "...")?;
@@ -585,14 +772,60 @@ impl HirDisplay for CallableSig {
         write!(f, ")")?;
         let ret = self.ret();
         if *ret != Ty::unit() {
-            let ret_display =
-                ret.into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target);
+            let ret_display = ret
+                .into_displayable(f.db, f.max_size, f.omit_verbose_types, f.display_target)
+                .with_max_list_len(f.max_list_len);
             write!(f, " -> {}", ret_display)?;
         }
         Ok(())
     }
 }
 
+/// The signature of a function with its generic parameters substituted, for
+/// showing e.g. a trait method called on a concrete receiver with `Self`
+/// replaced by the receiver type: `fn len(self: &Vec<u8>) -> usize`.
+#[derive(Debug)]
+pub struct FnSignature {
+    func: FunctionId,
+    sig: CallableSig,
+}
+
+impl FnSignature {
+    /// `substs` are for all generic parameters of `func`, including the ones
+    /// of its impl or trait; for trait methods, the first one is `Self`.
+    pub fn new(db: &dyn HirDatabase, func: FunctionId, substs: &Substs) -> FnSignature {
+        let sig = db.callable_item_signature(func.into()).subst(substs);
+        FnSignature { func, sig }
+    }
+}
+
+impl HirDisplay for FnSignature {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        write!(f, "fn {}(", f.db.function_data(self.func).name)?;
+        let body = f.db.body(self.func.into());
+        for (idx, ty) in self.sig.params().iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            match body.params.get(idx).map(|&pat| &body[pat]) {
+                Some(Pat::Bind { name, .. }) => write!(f, "{}: ", name)?,
+                _ => write!(f, "_: ")?,
+            }
+            ty.hir_fmt(f)?;
+        }
+        if self.sig.is_varargs {
+            write!(f, ", ...")?;
+        }
+        write!(f, ")")?;
+        let ret = self.sig.ret();
+        if *ret != Ty::unit() {
+            write!(f, " -> ")?;
+            ret.hir_fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
 fn fn_traits(db: &dyn DefDatabase, trait_: TraitId) -> impl Iterator<Item = TraitId> {
     let krate = trait_.lookup(db).container.module(db).krate();
     let fn_traits = [
@@ -604,6 +837,34 @@ fn fn_traits(db: &dyn DefDatabase, trait_: TraitId) -> impl Iterator<Item = Trai
     ArrayVec::from(fn_traits).into_iter().flatten().flat_map(|it| it.as_trait())
 }
 
+/// The bounds of the `idx`th `impl Trait` in the return type of `func`, or
+/// `None` if the function's signature doesn't have that many (anymore).
+fn return_type_impl_trait_bounds(
+    db: &dyn HirDatabase,
+    func: FunctionId,
+    idx: u16,
+    parameters: &Substs,
+) -> Option<Vec<GenericPredicate>> {
+    let datas = db.return_type_impl_traits(func)?;
+    if datas.value.impl_traits.len() <= idx as usize {
+        return None;
+    }
+    let data = (*datas).as_ref().map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
+    Some(data.subst(parameters).value)
+}
+
+fn write_return_type_impl_trait(
+    func: FunctionId,
+    idx: u16,
+    parameters: &Substs,
+    f: &mut HirFormatter,
+) -> Result<(), HirDisplayError> {
+    match return_type_impl_trait_bounds(f.db, func, idx, parameters) {
+        Some(bounds) => f.write_self_binder_bounds("impl", &bounds),
+        None => write!(f, "impl Trait"),
+    }
+}
+
 pub fn write_bounds_like_dyn_trait_with_prefix(
     prefix: &str,
     predicates: &[GenericPredicate],
@@ -627,11 +888,43 @@ fn write_bounds_like_dyn_trait(
     // actual Rust. It will have weird results if the predicates
     // aren't as expected (i.e. self types = $0, projection
     // predicates for a certain trait come after the Implemented
-    // predicate for that trait).
+    // predicate for that trait). For `dyn` types, this is the order
+    // `sort_dyn_predicates` establishes, so they're printed with the
+    // principal trait first and the auto traits sorted by name.
     let mut first = true;
     let mut angle_open = false;
     let mut is_fn_trait = false;
+    let mut last_assoc_type_bound = None;
     for p in predicates.iter() {
+        // lifetimes are erased, so there's nothing to show for a `for<'a>`
+        let p = p.skip_binders();
+        if let GenericPredicate::Implemented(trait_ref) = p {
+            if let Ty::Projection(proj) = trait_ref.self_ty() {
+                // an associated type bound like the `Item: Display` in
+                // `impl Iterator<Item: Display>`, which comes after the
+                // Implemented predicate for the trait of `Item`
+                let associated_ty = proj.associated_ty;
+                if last_assoc_type_bound == Some(associated_ty) {
+                    write!(f, " + ")?;
+                } else {
+                    write!(f, "{}", if angle_open { ", " } else { "<" })?;
+                    angle_open = true;
+                    write!(f, "{}: ", f.db.type_alias_data(associated_ty).name)?;
+                }
+                write!(f, "{}", f.db.trait_data(trait_ref.trait_).name)?;
+                if let [_, params @ ..] = &*trait_ref.substs.0 {
+                    if !params.is_empty() {
+                        write!(f, "<")?;
+                        f.write_joined(params, ", ")?;
+                        write!(f, ">")?;
+                    }
+                }
+                last_assoc_type_bound = Some(associated_ty);
+                first = false;
+                continue;
+            }
+        }
+        last_assoc_type_bound = None;
         match p {
             GenericPredicate::Implemented(trait_ref) => {
                 let trait_ = trait_ref.trait_;
@@ -682,7 +975,7 @@ fn write_bounds_like_dyn_trait(
                 write!(f, "{} = ", type_alias.name)?;
                 projection_pred.ty.hir_fmt(f)?;
             }
-            GenericPredicate::Error => {
+            GenericPredicate::ForLifetimes(..) | GenericPredicate::Error => {
                 if angle_open {
                     // impl Trait<X, {error}>
                     write!(f, ", ")?;
@@ -723,6 +1016,61 @@ impl TraitRef {
     }
 }
 
+impl<T: HirDisplay> HirDisplay for Binders<T> {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        if self.num_binders == 0 {
+            return self.value.hir_fmt(f);
+        }
+        write_binders_prefix(f, (0..self.num_binders).map(|_| TyVariableKind::General))?;
+        f.binders.push(self.num_binders);
+        let result = self.value.hir_fmt(f);
+        f.binders.pop();
+        result
+    }
+}
+
+impl<T: HirDisplay> HirDisplay for Canonical<T> {
+    fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
+        if self.kinds.is_empty() {
+            return self.value.hir_fmt(f);
+        }
+        write_binders_prefix(f, self.kinds.iter().copied())?;
+        f.binders.push(self.kinds.len());
+        let result = self.value.hir_fmt(f);
+        f.binders.pop();
+        result
+    }
+}
+
+/// Writes the `for<..> ` prefix for a binder with variables of the given
+/// kinds, which are about to be pushed onto `f.binders`.
+fn write_binders_prefix(
+    f: &mut HirFormatter,
+    kinds: impl Iterator<Item = TyVariableKind>,
+) -> Result<(), HirDisplayError> {
+    let offset: usize = f.binders.iter().sum();
+    write!(f, "for<")?;
+    for (i, kind) in kinds.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        if f.display_target.is_debug() {
+            write!(f, "^0.{}{}", i, ty_variable_kind_suffix(kind))?;
+        } else {
+            write!(f, "T{}", offset + i)?;
+        }
+    }
+    write!(f, "> ")
+}
+
+fn ty_variable_kind_suffix(kind: TyVariableKind) -> &'static str {
+    match kind {
+        TyVariableKind::General => "",
+        TyVariableKind::Integer => "i",
+        TyVariableKind::Float => "f",
+    }
+}
+
 impl HirDisplay for TraitRef {
     fn hir_fmt(&self, f: &mut HirFormatter) -> Result<(), HirDisplayError> {
         self.hir_fmt_ext(f, false)
@@ -753,6 +1101,7 @@ impl HirDisplay for GenericPredicate {
                 )?;
                 projection_pred.ty.hir_fmt(f)?;
             }
+            GenericPredicate::ForLifetimes(_, pred) => pred.hir_fmt(f)?,
             GenericPredicate::Error => write!(f, "{{error}}")?,
         }
         Ok(())
@@ -787,6 +1136,16 @@ impl HirDisplay for Obligation {
                 proj.ty.hir_fmt(f)?;
                 write!(f, ")")
             }
+            Obligation::WellFormed(ty) => {
+                write!(f, "WellFormed(")?;
+                ty.hir_fmt(f)?;
+                write!(f, ")")
+            }
+            Obligation::ForLifetimes(_, obligation) => {
+                write!(f, "ForAll(")?;
+                obligation.hir_fmt(f)?;
+                write!(f, ")")
+            }
         }
     }
 }
@@ -21,35 +21,39 @@ use std::sync::Arc;
 use hir_def::{
     body::Body,
     data::{ConstData, FunctionData, StaticData},
-    expr::{ArithOp, BinaryOp, BindingAnnotation, ExprId, PatId},
+    expr::{ArithOp, BinaryOp, BindingAnnotation, Expr, ExprId, PatId},
     lang_item::LangItemTarget,
     path::{path, Path},
-    resolver::{HasResolver, Resolver, TypeNs},
+    resolver::{resolver_for_expr, HasResolver, Resolver, TypeNs, ValueNs},
     type_ref::{Mutability, TypeRef},
-    AdtId, AssocItemId, DefWithBodyId, EnumVariantId, FieldId, FunctionId, Lookup, TraitId,
-    TypeAliasId, VariantId,
+    AdtId, AssocContainerId, AssocItemId, ContainerId, DefWithBodyId, EnumVariantId, FieldId,
+    FunctionId, Lookup, TraitId, TypeAliasId, VariantId,
 };
 use hir_expand::{diagnostics::DiagnosticSink, name::name};
 use la_arena::ArenaMap;
-use rustc_hash::FxHashMap;
-use stdx::impl_from;
+use rustc_hash::{FxHashMap, FxHashSet};
+use stdx::{impl_from, never};
 use syntax::SmolStr;
 
 use super::{
-    traits::{Guidance, Obligation, ProjectionPredicate, Solution},
-    InEnvironment, ProjectionTy, Substs, TraitEnvironment, TraitRef, Ty, TypeWalk,
+    traits::{check_implements, Guidance, Implements, Obligation, ProjectionPredicate, Solution},
+    Canonical, InEnvironment, OpaqueTy, OpaqueTyId, ProjectionTy, Substs, TraitEnvironment,
+    TraitRef, Ty, TyVariableKind, TypeWalk,
 };
 use crate::{
-    db::HirDatabase, infer::diagnostics::InferenceDiagnostic, lower::ImplTraitLoweringMode,
+    db::HirDatabase, display::HirDisplay, infer::diagnostics::InferenceDiagnostic,
+    lower::ImplTraitLoweringMode, method_resolution::ReceiverAdjustments,
+    utils::closest_generic_def,
 };
 
-pub(crate) use unify::unify;
+pub(crate) use unify::{unify, unify_substs};
 
 mod unify;
 mod path;
 mod expr;
 mod pat;
 mod coerce;
+mod closure;
 
 /// The entry point of type inference.
 pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<InferenceResult> {
@@ -59,7 +63,7 @@ pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<Infer
 
     match def {
         DefWithBodyId::ConstId(c) => ctx.collect_const(&db.const_data(c)),
-        DefWithBodyId::FunctionId(f) => ctx.collect_fn(&db.function_data(f)),
+        DefWithBodyId::FunctionId(f) => ctx.collect_fn(f, &db.function_data(f)),
         DefWithBodyId::StaticId(s) => ctx.collect_static(&db.static_data(s)),
     }
 
@@ -68,8 +72,16 @@ pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<Infer
     Arc::new(ctx.resolve_all())
 }
 
+pub(crate) fn return_type_impl_trait_hidden_ty_query(
+    db: &dyn HirDatabase,
+    def: FunctionId,
+    idx: u16,
+) -> Option<Ty> {
+    db.infer(def.into()).return_type_impl_trait_hidden_ty(idx).cloned()
+}
+
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
-enum ExprOrPatId {
+pub(crate) enum ExprOrPatId {
     ExprId(ExprId),
     PatId(PatId),
 }
@@ -78,7 +90,7 @@ impl_from!(ExprId, PatId for ExprOrPatId);
 /// Binding modes inferred for patterns.
 /// https://doc.rust-lang.org/reference/patterns.html#binding-modes
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum BindingMode {
+pub enum BindingMode {
     Move,
     Ref(Mutability),
 }
@@ -106,16 +118,73 @@ pub struct TypeMismatch {
     pub actual: Ty,
 }
 
+/// A `?` expression that definitely can't return early the way it has to.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct TryFailure {
+    pub reason: TryFailureReason,
+    /// The type of the operand of `?`.
+    pub operand_ty: Ty,
+    /// The type `?` returns early with, i.e. that of the enclosing function,
+    /// closure or `try` block.
+    pub return_ty: Ty,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum TryFailureReason {
+    /// The operand doesn't implement `Try`.
+    OperandNotTry,
+    /// The `Try::Error` of the operand can't be converted into the one of the
+    /// return type, e.g. for an `Option` in a function returning `Result`.
+    ErrorNotConvertible,
+    /// The return type doesn't implement `Try`, e.g. it's `()`.
+    ReturnTypeNotTry,
+}
+
+/// An implicit conversion applied to the value of an expression.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Adjustment {
+    pub kind: Adjust,
+    /// The type of the expression after the adjustment.
+    pub target: Ty,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Adjust {
+    /// An unsizing coercion, e.g. `&[T; N]` to `&[T]` or `Box<S>` to
+    /// `Box<dyn Trait>`.
+    Unsize,
+}
+
+/// The generic arguments of a path or method call expression (e.g. the `u32`
+/// in `foo::<u32>()`), including the ones that were left out and inferred.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct InferredGenericArgs {
+    /// All generic arguments of the referenced item, including the parent's
+    /// (e.g. the impl's).
+    pub substs: Substs,
+    /// For each argument in `substs`, whether it was written out explicitly.
+    /// `_` doesn't count as explicit.
+    pub explicit: Vec<bool>,
+}
+
 /// The result of type inference: A mapping from expressions and patterns to types.
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
 pub struct InferenceResult {
-    /// For each method call expr, records the function it resolves to.
+    /// For each method call expr, records the function it resolves to. Calls
+    /// of closures and other `Fn*` trait implementors record the trait method.
     method_resolutions: FxHashMap<ExprId, FunctionId>,
+    /// For each resolved method call expr, records how its receiver is
+    /// autoderefed and autorefed.
+    receiver_adjustments: FxHashMap<ExprId, ReceiverAdjustments>,
     /// For each field access expr, records the field it resolves to.
     field_resolutions: FxHashMap<ExprId, FieldId>,
     /// For each field in record literal, records the field it resolves to.
     record_field_resolutions: FxHashMap<ExprId, FieldId>,
     record_pat_field_resolutions: FxHashMap<PatId, FieldId>,
+    /// For each binding pattern, records whether it binds by value or by
+    /// reference, taking the default binding mode of match ergonomics into
+    /// account.
+    binding_modes: FxHashMap<PatId, BindingMode>,
     /// For each struct literal, records the variant it resolves to.
     variant_resolutions: FxHashMap<ExprOrPatId, VariantId>,
     /// For each associated item record what it resolves to
@@ -124,12 +193,30 @@ pub struct InferenceResult {
     pub type_of_expr: ArenaMap<ExprId, Ty>,
     pub type_of_pat: ArenaMap<PatId, Ty>,
     pub(super) type_mismatches: ArenaMap<ExprId, TypeMismatch>,
+    /// For each expression whose value gets implicitly converted, records the
+    /// conversion.
+    adjustments: FxHashMap<ExprId, Adjustment>,
+    /// For each path or method call expression referring to a generic item,
+    /// records its generic arguments.
+    generic_args: FxHashMap<ExprId, InferredGenericArgs>,
+    /// For each `impl Trait` in the return type of the function, the concrete
+    /// type its defining uses agree on, if they do.
+    return_type_impl_trait_hidden_tys: FxHashMap<u16, Ty>,
+    /// For each `?` expression that can't work, records why.
+    try_failures: FxHashMap<ExprId, TryFailure>,
+    /// The expressions whose evaluation never finishes, like `return`s, calls
+    /// of functions returning `!` and blocks containing one of them. In code
+    /// that is already unreachable, only expressions of type `!` are recorded.
+    diverging_exprs: FxHashSet<ExprId>,
 }
 
 impl InferenceResult {
     pub fn method_resolution(&self, expr: ExprId) -> Option<FunctionId> {
         self.method_resolutions.get(&expr).copied()
     }
+    pub fn receiver_adjustments(&self, expr: ExprId) -> Option<ReceiverAdjustments> {
+        self.receiver_adjustments.get(&expr).copied()
+    }
     pub fn field_resolution(&self, expr: ExprId) -> Option<FieldId> {
         self.field_resolutions.get(&expr).copied()
     }
@@ -139,6 +226,9 @@ impl InferenceResult {
     pub fn record_pat_field_resolution(&self, pat: PatId) -> Option<FieldId> {
         self.record_pat_field_resolutions.get(&pat).copied()
     }
+    pub fn binding_mode(&self, pat: PatId) -> Option<BindingMode> {
+        self.binding_modes.get(&pat).copied()
+    }
     pub fn variant_resolution_for_expr(&self, id: ExprId) -> Option<VariantId> {
         self.variant_resolutions.get(&id.into()).copied()
     }
@@ -154,6 +244,21 @@ impl InferenceResult {
     pub fn type_mismatch_for_expr(&self, expr: ExprId) -> Option<&TypeMismatch> {
         self.type_mismatches.get(expr)
     }
+    pub fn expr_adjustment(&self, expr: ExprId) -> Option<&Adjustment> {
+        self.adjustments.get(&expr)
+    }
+    pub fn generic_args_for_expr(&self, expr: ExprId) -> Option<&InferredGenericArgs> {
+        self.generic_args.get(&expr)
+    }
+    pub fn return_type_impl_trait_hidden_ty(&self, idx: u16) -> Option<&Ty> {
+        self.return_type_impl_trait_hidden_tys.get(&idx)
+    }
+    pub fn expr_diverges(&self, expr: ExprId) -> bool {
+        self.diverging_exprs.contains(&expr)
+    }
+    pub fn try_failure(&self, expr: ExprId) -> Option<&TryFailure> {
+        self.try_failures.get(&expr)
+    }
     pub fn add_diagnostics(
         &self,
         db: &dyn HirDatabase,
@@ -180,6 +285,16 @@ impl Index<PatId> for InferenceResult {
     }
 }
 
+/// The maximum number of passes over the pending obligations in
+/// `resolve_obligations_as_possible`. A pass only retries obligations whose
+/// variables got resolved by the previous one, so this is only reached for
+/// pathological code.
+const OBLIGATION_PASS_LIMIT: usize = 16;
+
+/// A canonicalized obligation, together with the inference variables its
+/// bound variables stand for.
+type ObligationKey = (Canonical<InEnvironment<Obligation>>, Vec<(InferenceVar, TyVariableKind)>);
+
 /// The inference context contains all information needed during type inference.
 #[derive(Clone, Debug)]
 struct InferenceContext<'a> {
@@ -189,7 +304,11 @@ struct InferenceContext<'a> {
     resolver: Resolver,
     table: unify::InferenceTable,
     trait_env: Arc<TraitEnvironment>,
+    /// Obligations that haven't been tried yet.
     obligations: Vec<Obligation>,
+    /// Obligations that couldn't be solved yet, with the canonicalized form
+    /// they had when we last tried.
+    stalled_obligations: Vec<(Obligation, ObligationKey)>,
     result: InferenceResult,
     /// The return type of the function being inferred, or the closure if we're
     /// currently within one.
@@ -198,8 +317,33 @@ struct InferenceContext<'a> {
     /// closures, but currently this is the only field that will change there,
     /// so it doesn't make sense.
     return_ty: Ty,
+    /// The type of the innermost `try` block we're in, if any. `?` returns
+    /// from it rather than from the function.
+    try_block_ty: Option<Ty>,
     diverges: Diverges,
     breakables: Vec<BreakableContext>,
+    /// The initializers of array repeat expressions like `[x; N]`, which have
+    /// to be `Copy`. We check that once all types are known.
+    repeat_initializers: Vec<ExprId>,
+    /// The `?` expressions, whose operand and return type (or `try` block
+    /// type) have to implement `Try`, with the error of the former converting
+    /// into the error of the latter via `From`. We check that once all types
+    /// are known.
+    try_exprs: Vec<TryExpr>,
+    /// The type variables standing in for the hidden types of the `impl
+    /// Trait`s in the return type, indexed like their opaque types.
+    return_type_impl_trait_vars: Vec<Ty>,
+}
+
+/// A `?` expression, whose operand's error of type `from` gets converted into
+/// the error of type `to` it returns with.
+#[derive(Clone, Debug)]
+struct TryExpr {
+    expr: ExprId,
+    operand_ty: Ty,
+    return_ty: Ty,
+    from: Ty,
+    to: Ty,
 }
 
 #[derive(Clone, Debug)]
@@ -225,19 +369,30 @@ impl<'a> InferenceContext<'a> {
             result: InferenceResult::default(),
             table: unify::InferenceTable::new(),
             obligations: Vec::default(),
+            stalled_obligations: Vec::default(),
             return_ty: Ty::Unknown, // set in collect_fn_signature
-            trait_env: TraitEnvironment::lower(db, &resolver),
+            try_block_ty: None,
+            trait_env: match closest_generic_def(db, owner) {
+                Some(def) => db.trait_environment(def),
+                None => Arc::new(TraitEnvironment { predicates: Vec::new() }),
+            },
             db,
             owner,
             body: db.body(owner),
             resolver,
             diverges: Diverges::Maybe,
             breakables: Vec::new(),
+            repeat_initializers: Vec::new(),
+            try_exprs: Vec::new(),
+            return_type_impl_trait_vars: Vec::new(),
         }
     }
 
     fn resolve_all(mut self) -> InferenceResult {
-        // FIXME resolve obligations as well (use Guidance if necessary)
+        // Variables might have been resolved after the obligations mentioning
+        // them were last tried
+        self.resolve_obligations_as_possible();
+        self.resolve_obligations_by_guidance();
         let mut result = std::mem::take(&mut self.result);
         for ty in result.type_of_expr.values_mut() {
             let resolved = self.table.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
@@ -247,15 +402,181 @@ impl<'a> InferenceContext<'a> {
             let resolved = self.table.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
             *ty = resolved;
         }
+        // Variables that couldn't be resolved have fallen back to their
+        // defaults (`i32`, `f64` or `{unknown}`) by now
+        never!(
+            result
+                .type_of_expr
+                .values()
+                .chain(result.type_of_pat.values())
+                .any(Ty::contains_inference_var),
+            "inference variables left in the types of {:?}",
+            self.owner
+        );
+        for args in result.generic_args.values_mut() {
+            let substs = mem::replace(&mut args.substs, Substs::empty());
+            args.substs = Substs(
+                substs.iter().map(|ty| self.table.resolve_ty_completely(ty.clone())).collect(),
+            );
+        }
+        for adjustment in result.adjustments.values_mut() {
+            let target = mem::replace(&mut adjustment.target, Ty::Unknown);
+            adjustment.target = self.table.resolve_ty_completely(target);
+        }
+        let defining_uses_disagree =
+            self.defining_uses().iter().any(|expr| result.type_mismatches.get(*expr).is_some());
+        for (idx, var) in mem::take(&mut self.return_type_impl_trait_vars).into_iter().enumerate() {
+            let hidden_ty = self.table.resolve_ty_completely(var);
+            if defining_uses_disagree || hidden_ty == Ty::Unknown || self.is_recursive(&hidden_ty) {
+                continue;
+            }
+            result.return_type_impl_trait_hidden_tys.insert(idx as u16, hidden_ty);
+        }
+        for try_expr in mem::take(&mut self.try_exprs) {
+            let operand_ty = self.table.resolve_ty_completely(try_expr.operand_ty);
+            let return_ty = self.table.resolve_ty_completely(try_expr.return_ty);
+            let from = self.table.resolve_ty_completely(try_expr.from);
+            let to = self.table.resolve_ty_completely(try_expr.to);
+            let reason = if self.is_definitely_not_try(&operand_ty) {
+                TryFailureReason::OperandNotTry
+            } else if self.is_definitely_not_try(&return_ty) {
+                TryFailureReason::ReturnTypeNotTry
+            } else if self.is_definitely_not_convertible(&from, &to) {
+                TryFailureReason::ErrorNotConvertible
+            } else {
+                continue;
+            };
+            result.try_failures.insert(try_expr.expr, TryFailure { reason, operand_ty, return_ty });
+        }
+        for initializer in mem::take(&mut self.repeat_initializers) {
+            if self.is_definitely_not_copy(initializer, &result[initializer]) {
+                result.diagnostics.push(InferenceDiagnostic::RequiresCopy { expr: initializer });
+            }
+        }
         result
     }
 
+    /// The expressions whose values get returned from the function, i.e. the
+    /// operands of `return` and the tail positions of the body. These define
+    /// the hidden types of `impl Trait`s in the return type.
+    // FIXME: `break` with a value out of a tail position loop
+    fn defining_uses(&self) -> Vec<ExprId> {
+        let mut returned = vec![self.body.body_expr];
+        let mut todo = vec![self.body.body_expr];
+        while let Some(expr) = todo.pop() {
+            match &self.body[expr] {
+                // Their `return`s don't return from the function.
+                Expr::Lambda { .. } | Expr::Async { .. } => continue,
+                Expr::Return { expr: Some(expr) } => returned.push(*expr),
+                _ => {}
+            }
+            self.body[expr].walk_child_exprs(|child| todo.push(child));
+        }
+        let mut uses = Vec::new();
+        while let Some(expr) = returned.pop() {
+            uses.push(expr);
+            match &self.body[expr] {
+                Expr::Block { tail: Some(tail), .. } => returned.push(*tail),
+                Expr::If { then_branch, else_branch, .. } => {
+                    returned.push(*then_branch);
+                    returned.extend(*else_branch);
+                }
+                Expr::Match { arms, .. } => returned.extend(arms.iter().map(|arm| arm.expr)),
+                _ => {}
+            }
+        }
+        uses
+    }
+
+    /// Whether the hidden type of an `impl Trait` in the return type refers to
+    /// the opaque types of the function itself, e.g. through a recursive call.
+    fn is_recursive(&self, hidden_ty: &Ty) -> bool {
+        let func = match self.owner {
+            DefWithBodyId::FunctionId(func) => func,
+            _ => return false,
+        };
+        let mut found = false;
+        hidden_ty.walk(&mut |ty| match ty {
+            Ty::OpaqueType(OpaqueTyId::ReturnTypeImplTrait(f, _), _)
+            | Ty::Opaque(OpaqueTy {
+                opaque_ty_id: OpaqueTyId::ReturnTypeImplTrait(f, _), ..
+            }) if *f == func => found = true,
+            _ => {}
+        });
+        found
+    }
+
+    /// Whether `ty`, the type of the initializer of an array repeat
+    /// expression, is definitely not `Copy` although it has to be. Like rustc,
+    /// we allow constants, which get copied as a whole.
+    // FIXME: rustc also allows anything for a length of 0 or 1
+    fn is_definitely_not_copy(&self, initializer: ExprId, ty: &Ty) -> bool {
+        if let Expr::Path(path) = &self.body[initializer] {
+            let resolver = resolver_for_expr(self.db.upcast(), self.owner, initializer);
+            let value = resolver.resolve_path_in_value_ns_fully(self.db.upcast(), path.mod_path());
+            if let Some(ValueNs::ConstId(_)) = value {
+                return false;
+            }
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return false,
+        };
+        let copy_trait = match self.resolve_lang_item("copy").and_then(|it| it.as_trait()) {
+            Some(trait_) => trait_,
+            None => return false,
+        };
+        ty.check_impls_trait(self.db, krate, self.trait_env.clone(), copy_trait) == Implements::No
+    }
+
+    /// Whether `ty` is definitely not `Try`, so that `?` can't be applied to it
+    /// or return early with it.
+    fn is_definitely_not_try(&self, ty: &Ty) -> bool {
+        if ty.contains_unknown() {
+            return false;
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return false,
+        };
+        let try_trait = match self.resolve_ops_try() {
+            Some(trait_) => trait_,
+            None => return false,
+        };
+        ty.check_impls_trait(self.db, krate, self.trait_env.clone(), try_trait) == Implements::No
+    }
+
+    /// Whether `?` definitely can't convert an error of type `from` into one of
+    /// type `to`, i.e. the types differ and there is no `From` impl for it.
+    fn is_definitely_not_convertible(&self, from: &Ty, to: &Ty) -> bool {
+        if from == to || from.contains_unknown() || to.contains_unknown() {
+            return false;
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return false,
+        };
+        let from_trait = match self.resolve_convert_from() {
+            Some(trait_) => trait_,
+            None => return false,
+        };
+        let args = [from.clone()];
+        check_implements(self.db, krate, self.trait_env.clone(), from_trait, to.clone(), &args)
+            == Implements::No
+    }
+
     fn write_expr_ty(&mut self, expr: ExprId, ty: Ty) {
         self.result.type_of_expr.insert(expr, ty);
     }
 
-    fn write_method_resolution(&mut self, expr: ExprId, func: FunctionId) {
+    fn write_method_resolution(
+        &mut self,
+        expr: ExprId,
+        func: FunctionId,
+        adjustments: ReceiverAdjustments,
+    ) {
         self.result.method_resolutions.insert(expr, func);
+        self.result.receiver_adjustments.insert(expr, adjustments);
     }
 
     fn write_field_resolution(&mut self, expr: ExprId, field: FieldId) {
@@ -270,6 +591,18 @@ impl<'a> InferenceContext<'a> {
         self.result.assoc_resolutions.insert(id, item);
     }
 
+    /// Records the generic arguments of `expr`, which has type `ty` (a
+    /// `FnDef` or ADT type) after inference variables have been inserted.
+    fn write_generic_args(&mut self, expr: ExprId, ty: &Ty, explicit: Vec<bool>) {
+        match ty.substs() {
+            Some(substs) if !substs.is_empty() && substs.len() == explicit.len() => {
+                let args = InferredGenericArgs { substs: substs.clone(), explicit };
+                self.result.generic_args.insert(expr, args);
+            }
+            _ => {}
+        }
+    }
+
     fn write_pat_ty(&mut self, pat: PatId, ty: Ty) {
         self.result.type_of_pat.insert(pat, ty);
     }
@@ -308,30 +641,127 @@ impl<'a> InferenceContext<'a> {
     }
 
     fn resolve_obligations_as_possible(&mut self) {
-        let obligations = mem::replace(&mut self.obligations, Vec::new());
-        for obligation in obligations {
+        for _ in 0..OBLIGATION_PASS_LIMIT {
+            if !self.resolve_obligations_once() {
+                break;
+            }
+        }
+    }
+
+    /// Tries to solve the pending obligations, skipping the ones that haven't
+    /// changed since the last try and dropping duplicates. Returns whether
+    /// any solution was applied, i.e. whether another pass could make more
+    /// progress.
+    fn resolve_obligations_once(&mut self) -> bool {
+        let new_obligations = mem::take(&mut self.obligations);
+        let stalled_obligations = mem::take(&mut self.stalled_obligations);
+        let krate = self.resolver.krate().unwrap();
+        let mut seen = FxHashSet::default();
+        let mut progress = false;
+        for (obligation, last_key) in stalled_obligations
+            .into_iter()
+            .map(|(obligation, key)| (obligation, Some(key)))
+            .chain(new_obligations.into_iter().map(|obligation| (obligation, None)))
+        {
             let in_env = InEnvironment::new(self.trait_env.clone(), obligation.clone());
             let canonicalized = self.canonicalizer().canonicalize_obligation(in_env);
-            let solution =
-                self.db.trait_solve(self.resolver.krate().unwrap(), canonicalized.value.clone());
+            let key = (canonicalized.value.clone(), canonicalized.free_vars.clone());
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            if last_key.as_ref() == Some(&key) {
+                // None of the variables of the obligation got resolved since we
+                // last tried, so the result would be the same
+                self.stalled_obligations.push((obligation, key));
+                continue;
+            }
+
+            let solution = self.db.trait_solve(krate, canonicalized.value.clone());
 
             match solution {
                 Some(Solution::Unique(substs)) => {
                     canonicalized.apply_solution(self, substs.0);
+                    progress = true;
                 }
                 Some(Solution::Ambig(Guidance::Definite(substs))) => {
                     canonicalized.apply_solution(self, substs.0);
-                    self.obligations.push(obligation);
+                    progress = true;
+                    self.stalled_obligations.push((obligation, key));
+                }
+                Some(Solution::Overflow) => {
+                    self.report_overflow(&obligation);
+                    self.stalled_obligations.push((obligation, key));
                 }
                 Some(_) => {
-                    // FIXME use this when trying to resolve everything at the end
-                    self.obligations.push(obligation);
+                    // suggestions are only applied as a fallback, see
+                    // `resolve_obligations_by_guidance`
+                    self.stalled_obligations.push((obligation, key));
                 }
                 None => {
                     // FIXME obligation cannot be fulfilled => diagnostic
                 }
             };
         }
+        progress
+    }
+
+    /// Reports that the trait solver gave up on `obligation`. We don't know
+    /// which expression an obligation comes from, so this is only reported
+    /// once per body.
+    fn report_overflow(&mut self, obligation: &Obligation) {
+        let obligation = obligation.skip_binders();
+        let already_reported = self
+            .result
+            .diagnostics
+            .iter()
+            .any(|it| matches!(it, InferenceDiagnostic::RecursionLimitReached { .. }));
+        if already_reported {
+            return;
+        }
+        let db = self.db;
+        let table = &mut self.table;
+        let mut resolve = |ty| table.resolve_ty_completely(ty);
+        let requirement = match obligation {
+            Obligation::Trait(trait_ref) => {
+                trait_ref.clone().fold(&mut resolve).display(db).to_string()
+            }
+            Obligation::Projection(pred) => {
+                let pred = pred.clone().fold(&mut resolve);
+                format!("{} == {}", pred.projection_ty.display(db), pred.ty.display(db))
+            }
+            Obligation::WellFormed(ty) => {
+                format!("{} well-formed", resolve(ty.clone()).display(db))
+            }
+            Obligation::ForLifetimes(..) => unreachable!(),
+        };
+        let expr = self.body.body_expr;
+        self.push_diagnostic(InferenceDiagnostic::RecursionLimitReached { expr, requirement });
+    }
+
+    /// As a fallback once everything else is inferred, applies the
+    /// substitutions the solver suggests for obligations that are still
+    /// ambiguous. Unlike definite guidance, these aren't known to be the only
+    /// way the obligation could hold, so they're not applied any earlier.
+    fn resolve_obligations_by_guidance(&mut self) {
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return,
+        };
+        let mut progress = false;
+        for (obligation, key) in mem::take(&mut self.stalled_obligations) {
+            let in_env = InEnvironment::new(self.trait_env.clone(), obligation.clone());
+            let canonicalized = self.canonicalizer().canonicalize_obligation(in_env);
+            if let Some(Solution::Ambig(Guidance::Suggested(substs))) =
+                self.db.trait_solve(krate, canonicalized.value.clone())
+            {
+                canonicalized.apply_solution(self, substs.0);
+                progress = true;
+            }
+            self.stalled_obligations.push((obligation, key));
+        }
+        if progress {
+            self.resolve_obligations_as_possible();
+        }
     }
 
     fn unify(&mut self, ty1: &Ty, ty2: &Ty) -> bool {
@@ -401,6 +831,13 @@ impl<'a> InferenceContext<'a> {
     }
 
     fn normalize_projection_ty(&mut self, proj_ty: ProjectionTy) -> Ty {
+        // Equalities from where clauses don't need to go through the solver
+        if let Some(ty) = self.trait_env.normalize_projection_ty(&proj_ty) {
+            return ty;
+        }
+        if let Some(ty) = crate::lower::normalize_by_assoc_type_bounds(self.db, &proj_ty) {
+            return ty;
+        }
         let var = self.table.new_type_var();
         let predicate = ProjectionPredicate { projection_ty: proj_ty, ty: var.clone() };
         let obligation = Obligation::Projection(predicate);
@@ -523,7 +960,7 @@ impl<'a> InferenceContext<'a> {
         self.return_ty = self.make_ty(&data.type_ref);
     }
 
-    fn collect_fn(&mut self, data: &FunctionData) {
+    fn collect_fn(&mut self, func: FunctionId, data: &FunctionData) {
         let body = Arc::clone(&self.body); // avoid borrow checker problem
         let ctx = crate::lower::TyLoweringContext::new(self.db, &self.resolver)
             .with_impl_trait_mode(ImplTraitLoweringMode::Param);
@@ -535,8 +972,71 @@ impl<'a> InferenceContext<'a> {
 
             self.infer_pat(*pat, &ty, BindingMode::default());
         }
-        let return_ty = self.make_ty_with_mode(&data.ret_type, ImplTraitLoweringMode::Disallowed); // FIXME implement RPIT
-        self.return_ty = return_ty;
+        // The `impl Trait`s in the return type get replaced by type variables,
+        // which the returned values then determine. The body of an `async fn`
+        // doesn't return the future though, but its output; the future's
+        // hidden type isn't something we can write down.
+        let ctx = crate::lower::TyLoweringContext::new(self.db, &self.resolver)
+            .with_opaque_type_root(&data.ret_type);
+        let return_ty = Ty::from_hir(&ctx, data.async_ret_type().unwrap_or(&data.ret_type));
+        let mut vars = Vec::new();
+        let return_ty = return_ty.fold(&mut |ty| match ty {
+            Ty::Opaque(OpaqueTy {
+                opaque_ty_id: OpaqueTyId::ReturnTypeImplTrait(f, idx), ..
+            }) if f == func => {
+                let var = self.table.new_type_var();
+                if vars.len() <= idx as usize {
+                    vars.resize(idx as usize + 1, Ty::Unknown);
+                }
+                vars[idx as usize] = var.clone();
+                var
+            }
+            ty => ty,
+        });
+        self.return_type_impl_trait_vars = vars;
+        let return_ty = self.insert_type_vars(return_ty);
+        self.return_ty = self.normalize_associated_types_in(return_ty);
+        if self.is_crate_main(func, data) && self.is_definitely_not_termination(&self.return_ty) {
+            self.push_diagnostic(InferenceDiagnostic::InvalidMainReturnType {
+                ty: self.return_ty.clone(),
+            });
+        }
+    }
+
+    /// Whether `func` is the `main` function at the root of its crate, whose
+    /// return type is checked against `Termination` instead of being taken as
+    /// is.
+    // FIXME: we don't know whether the crate is a binary, so library crates
+    // with a `main` at their root get checked as well.
+    fn is_crate_main(&self, func: FunctionId, data: &FunctionData) -> bool {
+        if data.name != name![main] || data.is_async {
+            return false;
+        }
+        let module = match func.lookup(self.db.upcast()).container {
+            AssocContainerId::ContainerId(ContainerId::ModuleId(module)) => module,
+            _ => return false,
+        };
+        let def_map = module.def_map(self.db.upcast());
+        def_map.parent().is_none() && module.local_id == def_map.root()
+    }
+
+    /// Whether `ty` definitely doesn't implement `Termination`. Like `()`,
+    /// `Result<(), E>` does if `E: Debug`, so the `Err` variant of the return
+    /// type of `main` doesn't need to be anything more specific.
+    fn is_definitely_not_termination(&self, ty: &Ty) -> bool {
+        if ty.contains_unknown() || ty.contains_inference_var() {
+            return false;
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return false,
+        };
+        let termination_trait = match self.resolve_lang_item("termination") {
+            Some(LangItemTarget::TraitId(trait_)) => trait_,
+            _ => return false,
+        };
+        ty.check_impls_trait(self.db, krate, self.trait_env.clone(), termination_trait)
+            == Implements::No
     }
 
     fn infer_body(&mut self) {
@@ -555,12 +1055,32 @@ impl<'a> InferenceContext<'a> {
         self.db.trait_data(trait_).associated_type_by_name(&name![Item])
     }
 
-    fn resolve_ops_try_ok(&self) -> Option<TypeAliasId> {
+    fn resolve_ops_try(&self) -> Option<TraitId> {
         let path = path![core::ops::Try];
-        let trait_ = self.resolver.resolve_known_trait(self.db.upcast(), &path)?;
+        self.resolver.resolve_known_trait(self.db.upcast(), &path)
+    }
+
+    fn resolve_ops_try_ok(&self) -> Option<TypeAliasId> {
+        let trait_ = self.resolve_ops_try()?;
         self.db.trait_data(trait_).associated_type_by_name(&name![Ok])
     }
 
+    fn resolve_ops_try_error(&self) -> Option<TypeAliasId> {
+        let trait_ = self.resolve_ops_try()?;
+        self.db.trait_data(trait_).associated_type_by_name(&name![Error])
+    }
+
+    fn resolve_convert_from(&self) -> Option<TraitId> {
+        let path = path![core::convert::From];
+        self.resolver.resolve_known_trait(self.db.upcast(), &path)
+    }
+
+    fn resolve_result_enum(&self) -> Option<AdtId> {
+        let path = path![core::result::Result];
+        let enum_ = self.resolver.resolve_known_enum(self.db.upcast(), &path)?;
+        Some(enum_.into())
+    }
+
     fn resolve_ops_neg_output(&self) -> Option<TypeAliasId> {
         let trait_ = self.resolve_lang_item("neg")?.as_trait()?;
         self.db.trait_data(trait_).associated_type_by_name(&name![Output])
@@ -666,6 +1186,15 @@ impl InferenceVar {
     fn from_inner(unify::TypeVarId(index): unify::TypeVarId) -> Self {
         InferenceVar { index }
     }
+
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_for_test(index: u32) -> Self {
+        InferenceVar { index }
+    }
 }
 
 /// When inferring an expression, we propagate downward whatever type hint we
@@ -761,18 +1290,36 @@ impl std::ops::BitOrAssign for Diverges {
 }
 
 mod diagnostics {
-    use hir_def::{expr::ExprId, DefWithBodyId};
-    use hir_expand::diagnostics::DiagnosticSink;
+    use hir_def::{
+        expr::{Expr, ExprId},
+        src::HasSource,
+        DefWithBodyId, FieldId, Lookup,
+    };
+    use hir_expand::{diagnostics::DiagnosticSink, name::Name};
+    use syntax::AstPtr;
 
     use crate::{
         db::HirDatabase,
-        diagnostics::{BreakOutsideOfLoop, NoSuchField},
+        diagnostics::{
+            BreakOutsideOfLoop, GenericArgsCountMismatch, InvalidMainReturnType, NoSuchField,
+            PrivateField, RecursionLimitReached, RequiresCopy,
+        },
+        display::HirDisplay,
+        lower::GenericArgsMismatch,
+        utils::variant_data,
+        Ty,
     };
 
     #[derive(Debug, PartialEq, Eq, Clone)]
     pub(super) enum InferenceDiagnostic {
-        NoSuchField { expr: ExprId, field: usize },
+        NoSuchField { expr: ExprId, field: usize, similar: Option<Name> },
+        NoSuchFieldAccess { expr: ExprId, similar: Option<Name>, is_method: bool },
+        PrivateField { expr: ExprId, field: FieldId, is_method: bool },
         BreakOutsideOfLoop { expr: ExprId },
+        RequiresCopy { expr: ExprId },
+        RecursionLimitReached { expr: ExprId, requirement: String },
+        GenericArgsCountMismatch { expr: ExprId, mismatch: GenericArgsMismatch },
+        InvalidMainReturnType { ty: Ty },
     }
 
     impl InferenceDiagnostic {
@@ -783,10 +1330,48 @@ mod diagnostics {
             sink: &mut DiagnosticSink,
         ) {
             match self {
-                InferenceDiagnostic::NoSuchField { expr, field } => {
-                    let (_, source_map) = db.body_with_source_map(owner);
+                InferenceDiagnostic::NoSuchField { expr, field, similar } => {
+                    let (body, source_map) = db.body_with_source_map(owner);
+                    let name = match &body[*expr] {
+                        Expr::RecordLit { fields, .. } => fields[*field].name.clone(),
+                        _ => return,
+                    };
                     let field = source_map.field_syntax(*expr, *field);
-                    sink.push(NoSuchField { file: field.file_id, field: field.value })
+                    sink.push(NoSuchField {
+                        file: field.file_id,
+                        field: field.value.into(),
+                        name,
+                        similar_field: similar.clone(),
+                        method_exists: false,
+                    })
+                }
+                InferenceDiagnostic::NoSuchFieldAccess { expr, similar, is_method } => {
+                    let (body, source_map) = db.body_with_source_map(owner);
+                    let name = match &body[*expr] {
+                        Expr::Field { name, .. } => name.clone(),
+                        _ => return,
+                    };
+                    if let Ok(ptr) = source_map.expr_syntax(*expr) {
+                        sink.push(NoSuchField {
+                            file: ptr.file_id,
+                            field: ptr.value.into(),
+                            name,
+                            similar_field: similar.clone(),
+                            method_exists: *is_method,
+                        })
+                    }
+                }
+                InferenceDiagnostic::PrivateField { expr, field, is_method } => {
+                    let (_, source_map) = db.body_with_source_map(owner);
+                    let variant_data = variant_data(db.upcast(), field.parent);
+                    if let Ok(ptr) = source_map.expr_syntax(*expr) {
+                        sink.push(PrivateField {
+                            file: ptr.file_id,
+                            expr: ptr.value,
+                            name: variant_data.fields()[field.local_id].name.clone(),
+                            method_exists: *is_method,
+                        })
+                    }
                 }
                 InferenceDiagnostic::BreakOutsideOfLoop { expr } => {
                     let (_, source_map) = db.body_with_source_map(owner);
@@ -795,6 +1380,48 @@ mod diagnostics {
                         .expect("break outside of loop in synthetic syntax");
                     sink.push(BreakOutsideOfLoop { file: ptr.file_id, expr: ptr.value })
                 }
+                InferenceDiagnostic::RequiresCopy { expr } => {
+                    let (_, source_map) = db.body_with_source_map(owner);
+                    if let Ok(ptr) = source_map.expr_syntax(*expr) {
+                        sink.push(RequiresCopy { file: ptr.file_id, expr: ptr.value })
+                    }
+                }
+                InferenceDiagnostic::RecursionLimitReached { expr, requirement } => {
+                    let (_, source_map) = db.body_with_source_map(owner);
+                    if let Ok(ptr) = source_map.expr_syntax(*expr) {
+                        sink.push(RecursionLimitReached {
+                            file: ptr.file_id,
+                            expr: ptr.value,
+                            requirement: requirement.clone(),
+                        })
+                    }
+                }
+                InferenceDiagnostic::GenericArgsCountMismatch { expr, mismatch } => {
+                    let (_, source_map) = db.body_with_source_map(owner);
+                    if let Ok(ptr) = source_map.expr_syntax(*expr) {
+                        sink.push(GenericArgsCountMismatch {
+                            file: ptr.file_id,
+                            node: ptr.value.into(),
+                            expected: mismatch.expected,
+                            provided: mismatch.provided,
+                            defaults: mismatch.defaults,
+                        })
+                    }
+                }
+                InferenceDiagnostic::InvalidMainReturnType { ty } => {
+                    let func = match owner {
+                        DefWithBodyId::FunctionId(func) => func,
+                        _ => return,
+                    };
+                    let src = func.lookup(db.upcast()).source(db.upcast());
+                    if let Some(ret_type) = src.value.ret_type() {
+                        sink.push(InvalidMainReturnType {
+                            file: src.file_id,
+                            ret_type: AstPtr::new(&ret_type),
+                            ty: ty.display(db).to_string(),
+                        })
+                    }
+                }
             }
         }
     }
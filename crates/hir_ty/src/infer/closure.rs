@@ -0,0 +1,259 @@
+//! Inference of closure kinds, i.e. which of the `Fn` traits a closure
+//! implements, from how its body uses the variables it captures.
+
+use hir_def::{
+    expr::{BinaryOp, Expr, ExprId, PatId, Statement, UnaryOp},
+    resolver::{resolver_for_expr, ValueNs},
+    type_ref::Mutability,
+};
+use rustc_hash::FxHashSet;
+
+use super::{BindingMode, InferenceContext};
+use crate::{
+    traits::{FnTrait, Implements},
+    Ty,
+};
+
+/// How a closure uses a captured variable, from the least to the most
+/// demanding use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CaptureUsage {
+    Ref,
+    MutRef,
+    Move,
+}
+
+impl<'a> InferenceContext<'a> {
+    /// Determines the kind of the closure `closure`, whose body has to be
+    /// inferred already: a closure moving out of a variable it captures only
+    /// implements `FnOnce`, one mutating a captured variable `FnMut`, and all
+    /// others `Fn`. Values whose type isn't known yet are assumed to be `Copy`.
+    pub(super) fn infer_closure_kind(&mut self, closure: ExprId) -> FnTrait {
+        let body = match &self.body[closure] {
+            Expr::Lambda { body, .. } => *body,
+            _ => return FnTrait::Fn,
+        };
+        let mut locals = FxHashSet::default();
+        self.collect_locals(closure, &mut locals);
+        let mut usage = None;
+        self.walk_captures(body, CaptureUsage::Move, &locals, &mut usage);
+        match usage {
+            Some(CaptureUsage::Move) => FnTrait::FnOnce,
+            Some(CaptureUsage::MutRef) => FnTrait::FnMut,
+            Some(CaptureUsage::Ref) | None => FnTrait::Fn,
+        }
+    }
+
+    /// Collects the bindings declared within `expr`, which aren't captures.
+    fn collect_locals(&self, expr: ExprId, acc: &mut FxHashSet<PatId>) {
+        match &self.body[expr] {
+            Expr::Lambda { args, .. } => args.iter().for_each(|&pat| self.collect_pat(pat, acc)),
+            Expr::Block { statements, .. } => {
+                for stmt in statements {
+                    if let Statement::Let { pat, .. } = stmt {
+                        self.collect_pat(*pat, acc);
+                    }
+                }
+            }
+            Expr::Match { arms, .. } => arms.iter().for_each(|arm| self.collect_pat(arm.pat, acc)),
+            Expr::For { pat, .. } | Expr::Let { pat, .. } => self.collect_pat(*pat, acc),
+            _ => {}
+        }
+        self.body[expr].walk_child_exprs(|child| self.collect_locals(child, acc));
+    }
+
+    fn collect_pat(&self, pat: PatId, acc: &mut FxHashSet<PatId>) {
+        acc.insert(pat);
+        self.body[pat].walk_child_pats(|child| self.collect_pat(child, acc));
+    }
+
+    /// Walks `expr`, which is used in the way described by `usage`, and
+    /// records the most demanding use of a captured variable in `acc`.
+    fn walk_captures(
+        &mut self,
+        expr: ExprId,
+        usage: CaptureUsage,
+        locals: &FxHashSet<PatId>,
+        acc: &mut Option<CaptureUsage>,
+    ) {
+        let body = self.body.clone();
+        match &body[expr] {
+            Expr::Path(path) => {
+                let resolver = resolver_for_expr(self.db.upcast(), self.owner, expr);
+                let value =
+                    resolver.resolve_path_in_value_ns_fully(self.db.upcast(), path.mod_path());
+                if let Some(ValueNs::LocalBinding(binding)) = value {
+                    if !locals.contains(&binding) {
+                        let usage = self.place_usage(expr, usage);
+                        *acc = std::cmp::max(*acc, Some(usage));
+                    }
+                }
+            }
+            // moving out of a field or a `Box` moves (part of) the base
+            Expr::Field { expr: base, .. } | Expr::UnaryOp { expr: base, op: UnaryOp::Deref } => {
+                let usage = self.place_usage(expr, usage);
+                self.walk_captures(*base, usage, locals, acc);
+            }
+            Expr::Index { base, index } => {
+                // indexing goes through `Index::index` or `IndexMut::index_mut`
+                let usage = std::cmp::min(usage, CaptureUsage::MutRef);
+                self.walk_captures(*base, usage, locals, acc);
+                self.walk_captures(*index, CaptureUsage::Move, locals, acc);
+            }
+            Expr::Ref { expr: inner, mutability, .. } => {
+                let usage = match mutability {
+                    Mutability::Shared => CaptureUsage::Ref,
+                    Mutability::Mut => CaptureUsage::MutRef,
+                };
+                self.walk_captures(*inner, usage, locals, acc);
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { .. }) } => {
+                self.walk_captures(*lhs, CaptureUsage::MutRef, locals, acc);
+                self.walk_captures(*rhs, CaptureUsage::Move, locals, acc);
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::CmpOp(_)) } => {
+                // comparisons take their operands by reference
+                self.walk_captures(*lhs, CaptureUsage::Ref, locals, acc);
+                self.walk_captures(*rhs, CaptureUsage::Ref, locals, acc);
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                let usage = match self.result.receiver_adjustments(expr) {
+                    Some(adjustments) => match adjustments.autoref {
+                        Some(Mutability::Shared) => CaptureUsage::Ref,
+                        Some(Mutability::Mut) => CaptureUsage::MutRef,
+                        None if adjustments.autoderefs == 0 => CaptureUsage::Move,
+                        None => CaptureUsage::Ref,
+                    },
+                    None => CaptureUsage::Ref,
+                };
+                self.walk_captures(*receiver, usage, locals, acc);
+                for &arg in args {
+                    self.walk_captures(arg, CaptureUsage::Move, locals, acc);
+                }
+            }
+            Expr::Call { callee, args } => {
+                // calling a closure uses it like its kind says
+                let callee_ty = self.resolved_expr_ty(*callee);
+                let usage = match &callee_ty {
+                    Ty::Closure(.., substs) => {
+                        match substs.get(1).and_then(FnTrait::from_closure_kind_ty) {
+                            Some(FnTrait::FnOnce) => CaptureUsage::Move,
+                            Some(FnTrait::FnMut) => CaptureUsage::MutRef,
+                            _ => CaptureUsage::Ref,
+                        }
+                    }
+                    _ => CaptureUsage::Ref,
+                };
+                self.walk_captures(*callee, usage, locals, acc);
+                for &arg in args {
+                    self.walk_captures(arg, CaptureUsage::Move, locals, acc);
+                }
+            }
+            Expr::Match { expr: scrutinee, arms } => {
+                let usage = arms
+                    .iter()
+                    .map(|arm| self.pat_usage(arm.pat))
+                    .max()
+                    .unwrap_or(CaptureUsage::Ref);
+                self.walk_captures(*scrutinee, usage, locals, acc);
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        self.walk_captures(guard, CaptureUsage::Move, locals, acc);
+                    }
+                    self.walk_captures(arm.expr, CaptureUsage::Move, locals, acc);
+                }
+            }
+            Expr::Let { pat, expr: scrutinee } => {
+                let usage = self.pat_usage(*pat);
+                self.walk_captures(*scrutinee, usage, locals, acc);
+            }
+            Expr::Block { statements, tail, .. } => {
+                for stmt in statements {
+                    match stmt {
+                        Statement::Let { pat, initializer: Some(initializer), .. } => {
+                            let usage = self.pat_usage(*pat);
+                            self.walk_captures(*initializer, usage, locals, acc);
+                        }
+                        Statement::Let { initializer: None, .. } => {}
+                        Statement::Expr(expr) => {
+                            self.walk_captures(*expr, CaptureUsage::Move, locals, acc)
+                        }
+                    }
+                }
+                if let Some(tail) = tail {
+                    self.walk_captures(*tail, CaptureUsage::Move, locals, acc);
+                }
+            }
+            _ => body[expr].walk_child_exprs(|child| {
+                self.walk_captures(child, CaptureUsage::Move, locals, acc)
+            }),
+        }
+    }
+
+    /// Moving a value of a `Copy` type only needs a shared reference to it.
+    fn place_usage(&mut self, place: ExprId, usage: CaptureUsage) -> CaptureUsage {
+        let ty = self.resolved_expr_ty(place);
+        if usage == CaptureUsage::Move && self.may_be_copy(&ty) {
+            CaptureUsage::Ref
+        } else {
+            usage
+        }
+    }
+
+    /// How matching `pat` against a place uses that place, depending on how
+    /// the bindings in it bind.
+    fn pat_usage(&mut self, pat: PatId) -> CaptureUsage {
+        let mut usage = CaptureUsage::Ref;
+        let mut pats = vec![pat];
+        while let Some(pat) = pats.pop() {
+            self.body[pat].walk_child_pats(|child| pats.push(child));
+            let binding_usage = match self.result.binding_mode(pat) {
+                Some(BindingMode::Move) => {
+                    let ty = self.table.resolve_ty_completely(self.result[pat].clone());
+                    if self.may_be_copy(&ty) {
+                        CaptureUsage::Ref
+                    } else {
+                        CaptureUsage::Move
+                    }
+                }
+                Some(BindingMode::Ref(Mutability::Mut)) => CaptureUsage::MutRef,
+                Some(BindingMode::Ref(Mutability::Shared)) | None => CaptureUsage::Ref,
+            };
+            usage = std::cmp::max(usage, binding_usage);
+        }
+        usage
+    }
+
+    fn resolved_expr_ty(&mut self, expr: ExprId) -> Ty {
+        self.table.resolve_ty_completely(self.result[expr].clone())
+    }
+
+    fn may_be_copy(&self, ty: &Ty) -> bool {
+        // the impls of `Copy` for primitive types come from `core`, so
+        // don't rely on them; references count as well, since `&mut`
+        // references are reborrowed rather than moved
+        if ty.contains_unknown()
+            || matches!(
+                ty,
+                Ty::Scalar(_)
+                    | Ty::RawPtr(..)
+                    | Ty::Ref(..)
+                    | Ty::Closure(..)
+                    | Ty::FnDef(..)
+                    | Ty::Function(_)
+                    | Ty::Never
+            )
+        {
+            return true;
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return true,
+        };
+        let copy_trait = match self.resolve_lang_item("copy").and_then(|it| it.as_trait()) {
+            Some(trait_) => trait_,
+            None => return true,
+        };
+        ty.check_impls_trait(self.db, krate, self.trait_env.clone(), copy_trait) != Implements::No
+    }
+}
@@ -5,12 +5,15 @@
 //! See: https://doc.rust-lang.org/nomicon/coercions.html
 
 use chalk_ir::TyVariableKind;
-use hir_def::{lang_item::LangItemTarget, type_ref::Mutability};
+use hir_def::{expr::ExprId, lang_item::LangItemTarget, type_ref::Mutability};
 use test_utils::mark;
 
-use crate::{autoderef, traits::Solution, Obligation, Substs, TraitRef, Ty};
+use crate::{
+    autoderef, traits::Solution, utils::all_super_trait_refs, GenericPredicate, Obligation, Substs,
+    TraitRef, Ty,
+};
 
-use super::{InEnvironment, InferenceContext};
+use super::{Adjust, Adjustment, InEnvironment, InferenceContext};
 
 impl<'a> InferenceContext<'a> {
     /// Unify two types, but may coerce the first one to the second one
@@ -18,7 +21,22 @@ impl<'a> InferenceContext<'a> {
     pub(super) fn coerce(&mut self, from_ty: &Ty, to_ty: &Ty) -> bool {
         let from_ty = self.resolve_ty_shallow(from_ty).into_owned();
         let to_ty = self.resolve_ty_shallow(to_ty);
-        self.coerce_inner(from_ty, &to_ty)
+        self.coerce_inner(from_ty, &to_ty, &mut None)
+    }
+
+    /// Like `coerce`, but also records the adjustment needed for the coercion
+    /// of the value of `expr`, if any.
+    pub(super) fn coerce_expr(&mut self, expr: ExprId, from_ty: &Ty, to_ty: &Ty) -> bool {
+        let from_ty = self.resolve_ty_shallow(from_ty).into_owned();
+        let to_ty = self.resolve_ty_shallow(to_ty).into_owned();
+        let mut adjust = None;
+        if !self.coerce_inner(from_ty, &to_ty, &mut adjust) {
+            return false;
+        }
+        if let Some(kind) = adjust {
+            self.result.adjustments.insert(expr, Adjustment { kind, target: to_ty });
+        }
+        true
     }
 
     /// Merge two types from different branches, with possible coercion.
@@ -51,7 +69,7 @@ impl<'a> InferenceContext<'a> {
         }
     }
 
-    fn coerce_inner(&mut self, mut from_ty: Ty, to_ty: &Ty) -> bool {
+    fn coerce_inner(&mut self, mut from_ty: Ty, to_ty: &Ty, adjust: &mut Option<Adjust>) -> bool {
         match (&from_ty, to_ty) {
             // Never type will make type variable to fallback to Never Type instead of Unknown.
             (Ty::Never, Ty::InferenceVar(tv, TyVariableKind::General)) => {
@@ -103,7 +121,13 @@ impl<'a> InferenceContext<'a> {
             _ => {}
         }
 
-        if let Some(ret) = self.try_coerce_unsized(&from_ty, &to_ty) {
+        let unsized_ = self
+            .try_upcast_trait_object(&from_ty, &to_ty)
+            .or_else(|| self.try_coerce_unsized(&from_ty, &to_ty));
+        if let Some(ret) = unsized_ {
+            if ret {
+                *adjust = Some(Adjust::Unsize);
+            }
             return ret;
         }
 
@@ -155,6 +179,72 @@ impl<'a> InferenceContext<'a> {
         Some(true)
     }
 
+    /// Coerce a pointer to a trait object to a pointer to a trait object of one
+    /// of its supertraits, like `&dyn Sub` to `&dyn Super`. Chalk's `Unsize`
+    /// only allows dropping auto traits, so we handle this ourselves.
+    ///
+    /// The auto traits of the target need to be present on the source, and
+    /// its associated type bindings need to be carried over from the source.
+    fn try_upcast_trait_object(&mut self, from_ty: &Ty, to_ty: &Ty) -> Option<bool> {
+        let (from_pointee, to_pointee) = match (from_ty, to_ty) {
+            (Ty::Ref(m1, st1), Ty::Ref(m2, st2)) | (Ty::RawPtr(m1, st1), Ty::RawPtr(m2, st2))
+                if m1 == m2 =>
+            {
+                (&st1[0], &st2[0])
+            }
+            (Ty::Adt(adt1, st1), Ty::Adt(adt2, st2))
+                if adt1 == adt2 && Some(*adt1) == self.resolve_boxed_box() =>
+            {
+                (&st1[0], &st2[0])
+            }
+            _ => return None,
+        };
+        let from_pointee = self.resolve_ty_shallow(from_pointee).into_owned();
+        let to_pointee = self.resolve_ty_shallow(to_pointee).into_owned();
+        let to_preds = match &to_pointee {
+            Ty::Dyn(preds) => preds.clone(),
+            _ => return None,
+        };
+        let from_principal = from_pointee.principal_trait_ref(self.db)?.clone();
+        let to_principal = to_pointee.principal_trait_ref(self.db)?;
+        if from_principal.trait_ == to_principal.trait_ {
+            // Not an upcast; dropping auto traits is up to `CoerceUnsized`.
+            return None;
+        }
+        let super_trait_ref = all_super_trait_refs(self.db, from_principal)
+            .into_iter()
+            .find(|tr| tr.trait_ == to_principal.trait_)?;
+
+        // Build the type we'd get by upcasting, with the bounds in the order
+        // of the target so that they can be unified pairwise.
+        let from_auto_traits = from_pointee.auto_traits(self.db).collect::<Vec<_>>();
+        let mut upcast_preds = Vec::with_capacity(to_preds.len());
+        for pred in to_preds.iter() {
+            let upcast_pred = match pred {
+                GenericPredicate::Implemented(tr) if tr.trait_ == super_trait_ref.trait_ => {
+                    GenericPredicate::Implemented(super_trait_ref.clone())
+                }
+                GenericPredicate::Implemented(tr) if from_auto_traits.contains(&tr.trait_) => {
+                    pred.clone()
+                }
+                GenericPredicate::Projection(proj) => {
+                    let associated_ty = proj.projection_ty.associated_ty;
+                    match from_pointee
+                        .projection_bounds()
+                        .find(|it| it.projection_ty.associated_ty == associated_ty)
+                    {
+                        Some(it) => GenericPredicate::Projection(it.clone()),
+                        None => return Some(false),
+                    }
+                }
+                _ => return Some(false),
+            };
+            upcast_preds.push(upcast_pred);
+        }
+        let upcast_ty = Ty::Dyn(upcast_preds.into());
+        Some(self.unify(&upcast_ty, &to_pointee))
+    }
+
     /// Unify `from_ty` to `to_ty` with optional auto Deref
     ///
     /// Note that the parameters are already stripped the outer reference.
@@ -1,22 +1,25 @@
 //! Type inference for expressions.
 
 use std::iter::{repeat, repeat_with};
-use std::{mem, sync::Arc};
+use std::{cmp, mem, sync::Arc};
 
 use chalk_ir::TyVariableKind;
 use hir_def::{
-    expr::{Array, BinaryOp, Expr, ExprId, Literal, Statement, UnaryOp},
+    expr::{Array, BinaryOp, Expr, ExprId, Literal, Pat, PatId, Statement, UnaryOp},
     path::{GenericArg, GenericArgs},
     resolver::resolver_for_expr,
-    AdtId, AssocContainerId, FieldId, Lookup,
+    type_ref::TypeRef,
+    AdtId, AssocContainerId, FieldId, FunctionId, Lookup, VariantId,
 };
 use hir_expand::name::{name, Name};
 use syntax::ast::RangeOp;
 use test_utils::mark;
 
 use crate::{
-    autoderef, method_resolution, op,
-    primitive::{self, UintTy},
+    autoderef,
+    method_resolution::{self, ReceiverAdjustments},
+    op,
+    primitive::{self, FloatTy, IntTy, UintTy},
     traits::{FnTrait, InEnvironment},
     utils::{generics, variant_data, Generics},
     Binders, CallableDefId, FnPointer, FnSig, Mutability, Obligation, OpaqueTyId, Rawness, Scalar,
@@ -25,16 +28,12 @@ use crate::{
 
 use super::{
     find_breakable, BindingMode, BreakableContext, Diverges, Expectation, InferenceContext,
-    InferenceDiagnostic, TypeMismatch,
+    InferenceDiagnostic, TryExpr, TypeMismatch,
 };
 
 impl<'a> InferenceContext<'a> {
     pub(super) fn infer_expr(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
         let ty = self.infer_expr_inner(tgt_expr, expected);
-        if ty.is_never() {
-            // Any expression that produces a value of type `!` must have diverged
-            self.diverges = Diverges::Always;
-        }
         let could_unify = self.unify(&ty, &expected.ty);
         if !could_unify {
             self.result.type_mismatches.insert(
@@ -49,7 +48,7 @@ impl<'a> InferenceContext<'a> {
     /// Return the type after possible coercion.
     pub(super) fn infer_expr_coerce(&mut self, expr: ExprId, expected: &Expectation) -> Ty {
         let ty = self.infer_expr_inner(expr, &expected);
-        let ty = if !self.coerce(&ty, &expected.coercion_target()) {
+        let ty = if !self.coerce_expr(expr, &ty, &expected.coercion_target()) {
             self.result
                 .type_mismatches
                 .insert(expr, TypeMismatch { expected: expected.ty.clone(), actual: ty.clone() });
@@ -105,6 +104,56 @@ impl<'a> InferenceContext<'a> {
         }
     }
 
+    /// Records the method of the `Fn*` trait a call of `callee_ty` goes
+    /// through, unless it's a function item or pointer: `Fn::call` if the
+    /// callee implements `Fn`, else `FnMut::call_mut` or `FnOnce::call_once`.
+    /// The callee is the receiver, borrowed as the method's `self` requires.
+    fn resolve_call_operator(
+        &mut self,
+        call: ExprId,
+        callee_ty: &Ty,
+        param_tys: &[Ty],
+        autoderefs: usize,
+    ) {
+        if matches!(callee_ty, Ty::FnDef(..) | Ty::Function(_)) {
+            return;
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return,
+        };
+        let args_ty = Ty::Tuple(param_tys.len(), Substs(param_tys.into()));
+        for &fn_trait in &[FnTrait::Fn, FnTrait::FnMut, FnTrait::FnOnce] {
+            let trait_ = match fn_trait.get_id(self.db, krate) {
+                Some(trait_) => trait_,
+                None => continue,
+            };
+            let method = match self.db.trait_data(trait_).method_by_name(&fn_trait.method_name()) {
+                Some(method) => method,
+                None => continue,
+            };
+            if generics(self.db.upcast(), trait_.into()).len() != 2 {
+                continue;
+            }
+            let substs = Substs(vec![callee_ty.clone(), args_ty.clone()].into());
+            let trait_env = self.trait_env.clone();
+            let goal = self.canonicalizer().canonicalize_obligation(InEnvironment {
+                value: Obligation::Trait(TraitRef { trait_, substs }),
+                environment: trait_env,
+            });
+            if self.db.trait_solve(krate, goal.value).is_some() {
+                let autoref = match fn_trait {
+                    FnTrait::Fn => Some(Mutability::Shared),
+                    FnTrait::FnMut => Some(Mutability::Mut),
+                    FnTrait::FnOnce => None,
+                };
+                let adjustments = ReceiverAdjustments { autoderefs, autoref };
+                self.write_method_resolution(call, method, adjustments);
+                return;
+            }
+        }
+    }
+
     pub(crate) fn callable_sig(&mut self, ty: &Ty, num_args: usize) -> Option<(Vec<Ty>, Ty)> {
         match ty.callable_sig(self.db) {
             Some(sig) => Some((sig.params().to_vec(), sig.ret().clone())),
@@ -114,10 +163,12 @@ impl<'a> InferenceContext<'a> {
 
     fn infer_expr_inner(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
         let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let diverged_before = self.diverges;
         let ty = match &body[tgt_expr] {
             Expr::Missing => Ty::Unknown,
             Expr::If { condition, then_branch, else_branch } => {
-                // if let is desugared to match, so this is always simple if
+                // plain if let is desugared to match, so this is either a boolean
+                // or a let chain, whose `Expr::Let`s are booleans as well
                 self.infer_expr(*condition, &Expectation::has_type(Ty::Scalar(Scalar::Bool)));
 
                 let condition_diverges = mem::replace(&mut self.diverges, Diverges::Maybe);
@@ -164,14 +215,38 @@ impl<'a> InferenceContext<'a> {
             }
             Expr::Unsafe { body } | Expr::Const { body } => self.infer_expr(*body, expected),
             Expr::TryBlock { body } => {
-                let _inner = self.infer_expr(*body, expected);
-                // FIXME should be std::result::Result<{inner}, _>
-                Ty::Unknown
+                // `try { expr }` is `Ok(expr)`, with the error type coming
+                // from the context or the `?`s inside
+                let result_ty = match self.resolve_result_enum() {
+                    Some(result) => {
+                        let substs = Substs::build_for_def(self.db, result)
+                            .fill(repeat_with(|| self.table.new_type_var()))
+                            .build();
+                        Ty::Adt(result, substs)
+                    }
+                    None => Ty::Unknown,
+                };
+                // A mismatch with the expected type gets recorded by the caller
+                self.unify(&result_ty, &expected.ty);
+                let ok_ty = match &result_ty {
+                    Ty::Adt(_, substs) => substs[0].clone(),
+                    _ => Ty::Unknown,
+                };
+                let prev_try_block_ty = mem::replace(&mut self.try_block_ty, Some(result_ty));
+                self.infer_expr_coerce(*body, &Expectation::has_type(ok_ty));
+                let result_ty = mem::replace(&mut self.try_block_ty, prev_try_block_ty);
+                result_ty.unwrap_or(Ty::Unknown)
             }
             Expr::Async { body } => {
                 // Use the first type parameter as the output type of future.
                 // existenail type AsyncBlockImplTrait<InnerType>: Future<Output = InnerType>
+                // FIXME: `return` and `?` return from the block, whose output
+                // type we don't track separately yet
+                let prev_ret_ty = mem::replace(&mut self.return_ty, Ty::Unknown);
+                let prev_try_block_ty = self.try_block_ty.take();
                 let inner_ty = self.infer_expr(*body, &Expectation::none());
+                self.return_ty = prev_ret_ty;
+                self.try_block_ty = prev_try_block_ty;
                 let opaque_ty_id = OpaqueTyId::AsyncBlockTypeImplTrait(self.owner, *body);
                 Ty::OpaqueType(opaque_ty_id, Substs::single(inner_ty))
             }
@@ -200,7 +275,8 @@ impl<'a> InferenceContext<'a> {
                     break_ty: Ty::Unknown,
                     label: label.map(|label| self.body[label].name.clone()),
                 });
-                // while let is desugared to a match loop, so this is always simple while
+                // plain while let is desugared to a match loop, so this is either a
+                // boolean or a let chain
                 self.infer_expr(*condition, &Expectation::has_type(Ty::Scalar(Scalar::Bool)));
                 self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
                 let _ctxt = self.breakables.pop().expect("breakable stack broken");
@@ -253,7 +329,10 @@ impl<'a> InferenceContext<'a> {
                     sig: FnSig { variadic: false },
                     substs: Substs(sig_tys.clone().into()),
                 });
-                let closure_ty = Ty::Closure(self.owner, tgt_expr, Substs::single(sig_ty));
+                // the closure kind is only known once we've seen the body
+                let kind_ty = self.table.new_type_var();
+                let closure_ty =
+                    Ty::Closure(self.owner, tgt_expr, Substs(vec![sig_ty, kind_ty.clone()].into()));
 
                 // Eagerly try to relate the closure type with the expected
                 // type, otherwise we often won't have enough information to
@@ -268,11 +347,16 @@ impl<'a> InferenceContext<'a> {
 
                 let prev_diverges = mem::replace(&mut self.diverges, Diverges::Maybe);
                 let prev_ret_ty = mem::replace(&mut self.return_ty, ret_ty.clone());
+                let prev_try_block_ty = self.try_block_ty.take();
 
                 self.infer_expr_coerce(*body, &Expectation::has_type(ret_ty));
 
                 self.diverges = prev_diverges;
                 self.return_ty = prev_ret_ty;
+                self.try_block_ty = prev_try_block_ty;
+
+                let kind = self.infer_closure_kind(tgt_expr);
+                self.unify(&kind_ty, &kind.to_closure_kind_ty());
 
                 closure_ty
             }
@@ -287,14 +371,28 @@ impl<'a> InferenceContext<'a> {
                         environment: self.trait_env.clone(),
                     },
                 );
-                let (param_tys, ret_ty): (Vec<Ty>, Ty) = derefs
-                    .find_map(|callee_deref_ty| {
-                        self.callable_sig(
-                            &canonicalized.decanonicalize_ty(callee_deref_ty.value),
-                            args.len(),
-                        )
-                    })
-                    .unwrap_or((Vec::new(), Ty::Unknown));
+                let callable = derefs.enumerate().find_map(|(autoderefs, callee_deref_ty)| {
+                    let callee_deref_ty = canonicalized.decanonicalize_ty(callee_deref_ty.value);
+                    let (param_tys, ret_ty) = self.callable_sig(&callee_deref_ty, args.len())?;
+                    Some((param_tys, ret_ty, callee_deref_ty, autoderefs))
+                });
+                let (param_tys, ret_ty) = match callable {
+                    Some((param_tys, ret_ty, callee_deref_ty, autoderefs)) => {
+                        self.resolve_call_operator(
+                            tgt_expr,
+                            &callee_deref_ty,
+                            &param_tys,
+                            autoderefs,
+                        );
+                        (param_tys, ret_ty)
+                    }
+                    None => (Vec::new(), Ty::Unknown),
+                };
+                if self.is_transmute(&callee_ty) && !expected.ty.is_unknown() {
+                    // the target type of `transmute` can't be inferred from
+                    // the argument, so take it from the context right away
+                    self.unify(&ret_ty, &expected.ty);
+                }
                 self.register_obligations_for_call(&callee_ty);
                 self.check_call_arguments(args, &param_tys);
                 self.normalize_associated_types_in(ret_ty)
@@ -303,6 +401,16 @@ impl<'a> InferenceContext<'a> {
                 .infer_method_call(tgt_expr, *receiver, &args, &method_name, generic_args.as_ref()),
             Expr::Match { expr, arms } => {
                 let input_ty = self.infer_expr(*expr, &Expectation::none());
+                if let Ty::InferenceVar(_, TyVariableKind::General) =
+                    &*self.resolve_ty_shallow(&input_ty)
+                {
+                    // Make the type known before inferring the arms in order, so
+                    // that e.g. bindings in arms before a `0u32 => ...` arm
+                    // already have the right type.
+                    if let Some(ty) = arms.iter().find_map(|arm| self.pat_literal_ty(arm.pat)) {
+                        self.unify(&input_ty, &ty);
+                    }
+                }
 
                 let mut result_ty =
                     if arms.is_empty() { Ty::Never } else { self.table.new_type_var() };
@@ -393,9 +501,14 @@ impl<'a> InferenceContext<'a> {
                         variant_data.as_ref().and_then(|it| match it.field(&field.name) {
                             Some(local_id) => Some(FieldId { parent: def_id.unwrap(), local_id }),
                             None => {
+                                let similar = similar_name(
+                                    &field.name,
+                                    it.fields().iter().map(|(_, data)| &data.name),
+                                );
                                 self.push_diagnostic(InferenceDiagnostic::NoSuchField {
                                     expr: tgt_expr,
                                     field: field_idx,
+                                    similar,
                                 });
                                 None
                             }
@@ -414,7 +527,11 @@ impl<'a> InferenceContext<'a> {
             }
             Expr::Field { expr, name } => {
                 let receiver_ty = self.infer_expr_inner(*expr, &Expectation::none());
-                let canonicalized = self.canonicalizer().canonicalize_ty(receiver_ty);
+                let canonicalized = self.canonicalizer().canonicalize_ty(receiver_ty.clone());
+                let mut derefed_tys = Vec::new();
+                // like rustc, we keep looking for a visible field further down
+                // the autoderef chain, but fall back to the first one we found
+                let mut private_field = None;
                 let ty = autoderef::autoderef(
                     self.db,
                     self.resolver.krate(),
@@ -423,27 +540,62 @@ impl<'a> InferenceContext<'a> {
                         environment: self.trait_env.clone(),
                     },
                 )
-                .find_map(|derefed_ty| match canonicalized.decanonicalize_ty(derefed_ty.value) {
-                    Ty::Tuple(_, substs) => {
-                        name.as_tuple_index().and_then(|idx| substs.0.get(idx).cloned())
-                    }
-                    Ty::Adt(AdtId::StructId(s), parameters) => {
-                        self.db.struct_data(s).variant_data.field(name).map(|local_id| {
-                            let field = FieldId { parent: s.into(), local_id };
+                .find_map(|derefed_ty| {
+                    let derefed_ty = canonicalized.decanonicalize_ty(derefed_ty.value);
+                    derefed_tys.push(derefed_ty.clone());
+                    match &derefed_ty {
+                        Ty::Tuple(_, substs) => {
+                            name.as_tuple_index().and_then(|idx| substs.0.get(idx).cloned())
+                        }
+                        Ty::Adt(_, parameters) => {
+                            let module = self.resolver.module()?;
+                            let field = method_resolution::resolve_field(
+                                self.db,
+                                &derefed_ty,
+                                name,
+                                module,
+                            );
+                            let field = match field {
+                                Some(field) => field,
+                                None => {
+                                    if private_field.is_none() {
+                                        private_field = method_resolution::lookup_field(
+                                            self.db,
+                                            &derefed_ty,
+                                            name,
+                                        )
+                                        .map(|field| (field, parameters.clone()));
+                                    }
+                                    return None;
+                                }
+                            };
                             self.write_field_resolution(tgt_expr, field);
-                            self.db.field_types(s.into())[field.local_id].clone().subst(&parameters)
-                        })
+                            Some(
+                                self.db.field_types(field.parent)[field.local_id]
+                                    .clone()
+                                    .subst(parameters),
+                            )
+                        }
+                        _ => None,
                     }
-                    Ty::Adt(AdtId::UnionId(u), parameters) => {
-                        self.db.union_data(u).variant_data.field(name).map(|local_id| {
-                            let field = FieldId { parent: u.into(), local_id };
-                            self.write_field_resolution(tgt_expr, field);
-                            self.db.field_types(u.into())[field.local_id].clone().subst(&parameters)
-                        })
+                });
+                let ty = match (ty, private_field) {
+                    (Some(ty), _) => ty,
+                    (None, Some((field, parameters))) => {
+                        self.write_field_resolution(tgt_expr, field);
+                        let is_method = self.lookup_method(&receiver_ty, name).is_some();
+                        self.push_diagnostic(InferenceDiagnostic::PrivateField {
+                            expr: tgt_expr,
+                            field,
+                            is_method,
+                        });
+                        self.db.field_types(field.parent)[field.local_id].clone().subst(&parameters)
+                    }
+                    (None, None) => {
+                        self.report_no_such_field(tgt_expr, &receiver_ty, name, &derefed_tys);
+                        Ty::Unknown
                     }
-                    _ => None,
-                })
-                .unwrap_or(Ty::Unknown);
+                };
                 let ty = self.insert_type_vars(ty);
                 self.normalize_associated_types_in(ty)
             }
@@ -453,7 +605,29 @@ impl<'a> InferenceContext<'a> {
             }
             Expr::Try { expr } => {
                 let inner_ty = self.infer_expr_inner(*expr, &Expectation::none());
-                self.resolve_associated_type(inner_ty, self.resolve_ops_try_ok())
+                let try_error = self.resolve_ops_try_error();
+                if try_error.is_some() {
+                    let from = self.resolve_associated_type(inner_ty.clone(), try_error);
+                    let return_ty =
+                        self.try_block_ty.clone().unwrap_or_else(|| self.return_ty.clone());
+                    let to = self.resolve_associated_type(return_ty.clone(), try_error);
+                    self.try_exprs.push(TryExpr {
+                        expr: tgt_expr,
+                        operand_ty: inner_ty.clone(),
+                        return_ty,
+                        from,
+                        to,
+                    });
+                }
+                // The `?` on an operand that isn't `Try` gets reported on its
+                // own, so don't leave an unnormalizable `Try::Ok` around that
+                // would show up as a type mismatch
+                let inner_ty = self.resolve_ty_as_possible(inner_ty);
+                if !inner_ty.contains_inference_var() && self.is_definitely_not_try(&inner_ty) {
+                    Ty::Unknown
+                } else {
+                    self.resolve_associated_type(inner_ty, self.resolve_ops_try_ok())
+                }
             }
             Expr::Cast { expr, type_ref } => {
                 let _inner_ty = self.infer_expr_inner(*expr, &Expectation::none());
@@ -483,6 +657,11 @@ impl<'a> InferenceContext<'a> {
                     Rawness::Ref => Ty::Ref(*mutability, Substs::single(inner_ty)),
                 }
             }
+            Expr::Let { pat, expr } => {
+                let scrutinee_ty = self.infer_expr(*expr, &Expectation::none());
+                self.infer_pat(*pat, &scrutinee_ty, BindingMode::default());
+                Ty::Scalar(Scalar::Bool)
+            }
             Expr::Box { expr } => {
                 let inner_ty = self.infer_expr_inner(*expr, &Expectation::none());
                 if let Some(box_) = self.resolve_boxed_box() {
@@ -673,6 +852,7 @@ impl<'a> InferenceContext<'a> {
                             *initializer,
                             &Expectation::has_type(elem_ty.clone()),
                         );
+                        self.repeat_initializers.push(*initializer);
                         self.infer_expr(
                             *repeat,
                             &Expectation::has_type(Ty::Scalar(Scalar::Uint(UintTy::Usize))),
@@ -682,42 +862,62 @@ impl<'a> InferenceContext<'a> {
 
                 Ty::Array(Substs::single(elem_ty))
             }
-            Expr::Literal(lit) => match lit {
-                Literal::Bool(..) => Ty::Scalar(Scalar::Bool),
-                Literal::String(..) => Ty::Ref(Mutability::Shared, Substs::single(Ty::Str)),
-                Literal::ByteString(..) => {
-                    let byte_type = Ty::Scalar(Scalar::Uint(UintTy::U8));
-                    let array_type = Ty::Array(Substs::single(byte_type));
-                    Ty::Ref(Mutability::Shared, Substs::single(array_type))
-                }
-                Literal::Char(..) => Ty::Scalar(Scalar::Char),
-                Literal::Int(_v, ty) => match ty {
-                    Some(int_ty) => {
-                        Ty::Scalar(Scalar::Int(primitive::int_ty_from_builtin(*int_ty)))
-                    }
-                    None => self.table.new_integer_var(),
-                },
-                Literal::Uint(_v, ty) => match ty {
-                    Some(int_ty) => {
-                        Ty::Scalar(Scalar::Uint(primitive::uint_ty_from_builtin(*int_ty)))
-                    }
-                    None => self.table.new_integer_var(),
-                },
-                Literal::Float(_v, ty) => match ty {
-                    Some(float_ty) => {
-                        Ty::Scalar(Scalar::Float(primitive::float_ty_from_builtin(*float_ty)))
-                    }
-                    None => self.table.new_float_var(),
-                },
-            },
+            Expr::Literal(lit) => self.literal_ty(lit),
         };
         // use a new type variable if we got Ty::Unknown here
         let ty = self.insert_type_vars_shallow(ty);
         let ty = self.resolve_ty_as_possible(ty);
+        if ty.is_never() {
+            // Any expression that produces a value of type `!` must have diverged
+            self.diverges = Diverges::Always;
+            self.result.diverging_exprs.insert(tgt_expr);
+        } else if !diverged_before.is_always() && self.diverges.is_always() {
+            self.result.diverging_exprs.insert(tgt_expr);
+        }
         self.write_expr_ty(tgt_expr, ty.clone());
         ty
     }
 
+    fn literal_ty(&mut self, lit: &Literal) -> Ty {
+        match lit {
+            Literal::Bool(..) => Ty::Scalar(Scalar::Bool),
+            Literal::String(..) => Ty::Ref(Mutability::Shared, Substs::single(Ty::Str)),
+            Literal::ByteString(..) => {
+                let byte_type = Ty::Scalar(Scalar::Uint(UintTy::U8));
+                let array_type = Ty::Array(Substs::single(byte_type));
+                Ty::Ref(Mutability::Shared, Substs::single(array_type))
+            }
+            Literal::Char(..) => Ty::Scalar(Scalar::Char),
+            Literal::Int(_v, ty) => match ty {
+                Some(int_ty) => Ty::Scalar(Scalar::Int(primitive::int_ty_from_builtin(*int_ty))),
+                None => self.table.new_integer_var(),
+            },
+            Literal::Uint(_v, ty) => match ty {
+                Some(int_ty) => Ty::Scalar(Scalar::Uint(primitive::uint_ty_from_builtin(*int_ty))),
+                None => self.table.new_integer_var(),
+            },
+            Literal::Float(_v, ty) => match ty {
+                Some(float_ty) => {
+                    Ty::Scalar(Scalar::Float(primitive::float_ty_from_builtin(*float_ty)))
+                }
+                None => self.table.new_float_var(),
+            },
+        }
+    }
+
+    /// Returns the type of the literal(s) in a literal or range pattern.
+    fn pat_literal_ty(&mut self, pat: PatId) -> Option<Ty> {
+        let body = Arc::clone(&self.body);
+        match &body[pat] {
+            Pat::Lit(expr) | Pat::Range { start: expr, .. } => match &body[*expr] {
+                Expr::Literal(lit) => Some(self.literal_ty(lit)),
+                _ => None,
+            },
+            Pat::Or(pats) => pats.iter().find_map(|&pat| self.pat_literal_ty(pat)),
+            _ => None,
+        }
+    }
+
     fn infer_block(
         &mut self,
         statements: &[Statement],
@@ -733,6 +933,9 @@ impl<'a> InferenceContext<'a> {
                     // Always use the declared type when specified
                     let mut ty = decl_ty.clone();
 
+                    // The initializer needs to be inferred even if the pattern
+                    // doesn't bind anything (`let _ = ...`), for the types of
+                    // its subexpressions and any mismatches in there
                     if let Some(expr) = initializer {
                         let actual_ty =
                             self.infer_expr_coerce(*expr, &Expectation::has_type(decl_ty.clone()));
@@ -771,6 +974,73 @@ impl<'a> InferenceContext<'a> {
         ty
     }
 
+    /// Reports a field access for which no autoderef step of the receiver has
+    /// a field `name` visible from here. Receivers whose type isn't fully
+    /// known might still have the field, so they are left alone, as are ones
+    /// that have no fields at all.
+    fn report_no_such_field(
+        &mut self,
+        expr: ExprId,
+        receiver_ty: &Ty,
+        name: &Name,
+        derefed_tys: &[Ty],
+    ) {
+        let module = match self.resolver.module() {
+            Some(module) => module,
+            None => return,
+        };
+        if derefed_tys.iter().any(|ty| matches!(ty, Ty::Unknown | Ty::InferenceVar(..))) {
+            return;
+        }
+        let mut visible_fields = Vec::new();
+        let mut has_fields = false;
+        for ty in derefed_tys {
+            let variant: VariantId = match ty {
+                Ty::Adt(AdtId::StructId(it), _) => (*it).into(),
+                Ty::Adt(AdtId::UnionId(it), _) => (*it).into(),
+                Ty::Tuple(..) => {
+                    has_fields = true;
+                    continue;
+                }
+                _ => continue,
+            };
+            has_fields = true;
+            let variant_data = variant_data(self.db.upcast(), variant);
+            visible_fields.extend(
+                variant_data.fields().iter().map(|(_, field)| field.name.clone()).filter(|it| {
+                    method_resolution::resolve_field(self.db, ty, it, module).is_some()
+                }),
+            );
+        }
+        if !has_fields {
+            return;
+        }
+        let similar = similar_name(name, visible_fields.iter());
+        let is_method = self.lookup_method(receiver_ty, name).is_some();
+        self.push_diagnostic(InferenceDiagnostic::NoSuchFieldAccess { expr, similar, is_method });
+    }
+
+    /// Looks up a method on `receiver_ty`, returning the autoderefed receiver
+    /// type along with the method and the receiver's adjustments.
+    fn lookup_method(
+        &mut self,
+        receiver_ty: &Ty,
+        method_name: &Name,
+    ) -> Option<(Ty, FunctionId, ReceiverAdjustments)> {
+        let canonicalized_receiver = self.canonicalizer().canonicalize_ty(receiver_ty.clone());
+        let traits_in_scope = self.resolver.traits_in_scope(self.db.upcast());
+        let krate = self.resolver.krate()?;
+        let (ty, func, adjustments) = method_resolution::lookup_method(
+            &canonicalized_receiver.value,
+            self.db,
+            self.trait_env.clone(),
+            krate,
+            &traits_in_scope,
+            method_name,
+        )?;
+        Some((canonicalized_receiver.decanonicalize_ty(ty), func, adjustments))
+    }
+
     fn infer_method_call(
         &mut self,
         tgt_expr: ExprId,
@@ -780,31 +1050,58 @@ impl<'a> InferenceContext<'a> {
         generic_args: Option<&GenericArgs>,
     ) -> Ty {
         let receiver_ty = self.infer_expr(receiver, &Expectation::none());
-        let canonicalized_receiver = self.canonicalizer().canonicalize_ty(receiver_ty.clone());
-
-        let traits_in_scope = self.resolver.traits_in_scope(self.db.upcast());
-
-        let resolved = self.resolver.krate().and_then(|krate| {
-            method_resolution::lookup_method(
-                &canonicalized_receiver.value,
-                self.db,
-                self.trait_env.clone(),
-                krate,
-                &traits_in_scope,
-                method_name,
-            )
-        });
-        let (derefed_receiver_ty, method_ty, def_generics) = match resolved {
-            Some((ty, func)) => {
-                let ty = canonicalized_receiver.decanonicalize_ty(ty);
-                self.write_method_resolution(tgt_expr, func);
-                (ty, self.db.value_ty(func.into()), Some(generics(self.db.upcast(), func.into())))
+        let mut resolved = self.lookup_method(&receiver_ty, method_name);
+        if resolved.is_none() {
+            // rustc refuses to call methods on `{integer}` or `{float}`, but
+            // it's more useful to resolve `1.pow(2)` as if the literal had
+            // already fallen back to `i32` (or `f64`)
+            let fallback = match &*self.resolve_ty_shallow(&receiver_ty) {
+                Ty::InferenceVar(_, TyVariableKind::Integer) => {
+                    Some(Ty::Scalar(Scalar::Int(IntTy::I32)))
+                }
+                Ty::InferenceVar(_, TyVariableKind::Float) => {
+                    Some(Ty::Scalar(Scalar::Float(FloatTy::F64)))
+                }
+                _ => None,
+            };
+            if let Some(fallback) = fallback {
+                // only commit to the fallback if it actually has the method
+                resolved = self.lookup_method(&fallback, method_name);
+                if resolved.is_some() {
+                    mark::hit!(method_call_on_literal_falls_back);
+                    self.unify(&receiver_ty, &fallback);
+                }
+            }
+        }
+        let (derefed_receiver_ty, method_ty, def_generics, adjustments) = match resolved {
+            Some((ty, func, adjustments)) => {
+                self.write_method_resolution(tgt_expr, func, adjustments);
+                let def_generics = Some(generics(self.db.upcast(), func.into()));
+                (ty, self.db.value_ty(func.into()), def_generics, adjustments)
             }
-            None => (receiver_ty, Binders::new(0, Ty::Unknown), None),
+            None => (
+                receiver_ty.clone(),
+                Binders::new(0, Ty::Unknown),
+                None,
+                ReceiverAdjustments::default(),
+            ),
         };
+        let parent_params = def_generics.as_ref().map_or(0, |g| g.iter_parent().count());
         let substs = self.substs_for_method_call(def_generics, generic_args, &derefed_receiver_ty);
+        let explicit_args = repeat(false)
+            .take(parent_params)
+            .chain(generic_args.into_iter().flat_map(|args| args.args.iter()).filter_map(|arg| {
+                match arg {
+                    GenericArg::Type(type_ref) => Some(*type_ref != TypeRef::Placeholder),
+                    GenericArg::Lifetime(_) => None,
+                }
+            }))
+            .chain(repeat(false))
+            .take(substs.len())
+            .collect();
         let method_ty = method_ty.subst(&substs);
         let method_ty = self.insert_type_vars(method_ty);
+        self.write_generic_args(tgt_expr, &method_ty, explicit_args);
         self.register_obligations_for_call(&method_ty);
         let (expected_receiver_ty, param_tys, ret_ty) = match method_ty.callable_sig(self.db) {
             Some(sig) => {
@@ -816,11 +1113,17 @@ impl<'a> InferenceContext<'a> {
             }
             None => (Ty::Unknown, Vec::new(), Ty::Unknown),
         };
-        // Apply autoref so the below unification works correctly
-        // FIXME: return correct autorefs from lookup_method
-        let actual_receiver_ty = match expected_receiver_ty.as_reference() {
-            Some((_, mutability)) => Ty::Ref(mutability, Substs::single(derefed_receiver_ty)),
-            _ => derefed_receiver_ty,
+        // Method resolution only picks a method whose declared `self` type
+        // (like `&self` or `self: Rc<Self>`) matches the receiver after the
+        // adjustments, so apply them to get the type to unify it with.
+        // The only step past the autoderef chain is the unsizing of an array
+        // to a slice, which is the self type then.
+        let adjusted_receiver_ty = self
+            .receiver_deref_step(receiver_ty, adjustments.autoderefs)
+            .unwrap_or(derefed_receiver_ty);
+        let actual_receiver_ty = match adjustments.autoref {
+            Some(mutability) => Ty::Ref(mutability, Substs::single(adjusted_receiver_ty)),
+            None => adjusted_receiver_ty,
         };
         self.unify(&expected_receiver_ty, &actual_receiver_ty);
 
@@ -828,6 +1131,35 @@ impl<'a> InferenceContext<'a> {
         self.normalize_associated_types_in(ret_ty)
     }
 
+    /// The type `receiver_ty` has after `autoderefs` steps of autoderef.
+    fn receiver_deref_step(&mut self, receiver_ty: Ty, autoderefs: usize) -> Option<Ty> {
+        if autoderefs == 0 {
+            return Some(receiver_ty);
+        }
+        let canonicalized = self.canonicalizer().canonicalize_ty(receiver_ty);
+        let step = autoderef::autoderef(
+            self.db,
+            self.resolver.krate(),
+            InEnvironment {
+                value: canonicalized.value.clone(),
+                environment: self.trait_env.clone(),
+            },
+        )
+        .nth(autoderefs)?;
+        Some(canonicalized.decanonicalize_ty(step.value))
+    }
+
+    /// Whether `callee_ty` is the `transmute` intrinsic.
+    fn is_transmute(&self, callee_ty: &Ty) -> bool {
+        match callee_ty.as_fn_def() {
+            Some(func) => {
+                let data = self.db.function_data(func);
+                data.is_extern && data.name == name![transmute]
+            }
+            None => false,
+        }
+    }
+
     fn check_call_arguments(&mut self, args: &[ExprId], param_tys: &[Ty]) {
         // Quoting https://github.com/rust-lang/rust/blob/6ef275e6c3cb1384ec78128eceeb4963ff788dca/src/librustc_typeck/check/mod.rs#L3325 --
         // We do this in a pretty awful way: first we type-check any arguments
@@ -920,3 +1252,15 @@ impl<'a> InferenceContext<'a> {
         }
     }
 }
+
+/// Picks the candidate closest to `name` by edit distance, as long as it's
+/// close enough to be a plausible typo of it.
+fn similar_name<'a>(name: &Name, candidates: impl Iterator<Item = &'a Name>) -> Option<Name> {
+    let name = name.to_string();
+    let max_distance = cmp::max(name.len(), 3) / 3;
+    candidates
+        .map(|it| (stdx::edit_distance(&name, &it.to_string()), it))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, it)| it.clone())
+}
@@ -186,6 +186,7 @@ impl<'a> InferenceContext<'a> {
                 } else {
                     BindingMode::convert(*mode)
                 };
+                self.result.binding_modes.insert(pat, mode);
                 let inner_ty = if let Some(subpat) = subpat {
                     self.infer_pat(*subpat, expected, default_bm)
                 } else {
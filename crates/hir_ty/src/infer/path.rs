@@ -9,9 +9,9 @@ use hir_def::{
 };
 use hir_expand::name::Name;
 
-use crate::{method_resolution, Substs, Ty, ValueTyDefId};
+use crate::{method_resolution, Substs, Ty, TypeWalk, ValueTyDefId};
 
-use super::{ExprOrPatId, InferenceContext, TraitRef};
+use super::{ExprOrPatId, InferenceContext, InferenceDiagnostic, TraitRef};
 
 impl<'a> InferenceContext<'a> {
     pub(super) fn infer_path(
@@ -20,18 +20,23 @@ impl<'a> InferenceContext<'a> {
         path: &Path,
         id: ExprOrPatId,
     ) -> Option<Ty> {
-        let ty = self.resolve_value_path(resolver, path, id)?;
+        let (ty, explicit_args) = self.resolve_value_path(resolver, path, id)?;
         let ty = self.insert_type_vars(ty);
+        if let ExprOrPatId::ExprId(expr) = id {
+            self.write_generic_args(expr, &ty, explicit_args);
+        }
         let ty = self.normalize_associated_types_in(ty);
         Some(ty)
     }
 
+    /// Returns the type of the value `path` refers to, and for each of its
+    /// generic arguments whether it was given explicitly.
     fn resolve_value_path(
         &mut self,
         resolver: &Resolver,
         path: &Path,
         id: ExprOrPatId,
-    ) -> Option<Ty> {
+    ) -> Option<(Ty, Vec<bool>)> {
         let (value, self_subst) = if let Some(type_ref) = path.type_anchor() {
             if path.segments().is_empty() {
                 // This can't actually happen syntax-wise
@@ -62,7 +67,7 @@ impl<'a> InferenceContext<'a> {
             ValueNs::LocalBinding(pat) => {
                 let ty = self.result.type_of_pat.get(pat)?.clone();
                 let ty = self.resolve_ty_as_possible(ty);
-                return Some(ty);
+                return Some((ty, Vec::new()));
             }
             ValueNs::FunctionId(it) => it.into(),
             ValueNs::ConstId(it) => it.into(),
@@ -83,13 +88,13 @@ impl<'a> InferenceContext<'a> {
                 let ty = self.db.impl_self_ty(impl_id).subst(&substs);
                 if let Some((AdtId::StructId(struct_id), substs)) = ty.as_adt() {
                     let ty = self.db.value_ty(struct_id.into()).subst(&substs);
-                    return Some(ty);
+                    return Some((ty, Vec::new()));
                 } else {
                     // FIXME: diagnostic, invalid Self reference
                     return None;
                 }
             }
-            ValueNs::GenericParam(it) => return Some(self.db.const_param_ty(it)),
+            ValueNs::GenericParam(it) => return Some((self.db.const_param_ty(it), Vec::new())),
         };
 
         let ty = self.db.value_ty(typable);
@@ -97,12 +102,22 @@ impl<'a> InferenceContext<'a> {
         let parent_substs = self_subst.unwrap_or_else(Substs::empty);
         let ctx = crate::lower::TyLoweringContext::new(self.db, &self.resolver);
         let substs = Ty::substs_from_path(&ctx, path, typable, true);
+        if let ExprOrPatId::ExprId(expr) = id {
+            for mismatch in ctx.take_generic_args_mismatches() {
+                self.push_diagnostic(InferenceDiagnostic::GenericArgsCountMismatch {
+                    expr,
+                    mismatch,
+                });
+            }
+        }
         let full_substs = Substs::builder(substs.len())
             .use_parent_substs(&parent_substs)
             .fill(substs.0[parent_substs.len()..].iter().cloned())
             .build();
+        // Arguments that weren't given (or given as `_`) are still unknown here
+        let explicit_args = full_substs.iter().map(|ty| !matches!(ty, Ty::Unknown)).collect();
         let ty = ty.subst(&full_substs);
-        Some(ty)
+        Some((ty, explicit_args))
     }
 
     fn resolve_assoc_item(
@@ -193,6 +208,7 @@ impl<'a> InferenceContext<'a> {
                     AssocItemId::TypeAliasId(_) => None,
                 }
             })?;
+        let (item, substs) = self.resolve_impl_item(item, trait_ref.substs);
         let def = match item {
             AssocItemId::FunctionId(f) => ValueNs::FunctionId(f),
             AssocItemId::ConstId(c) => ValueNs::ConstId(c),
@@ -200,7 +216,33 @@ impl<'a> InferenceContext<'a> {
         };
 
         self.write_assoc_resolution(id, item);
-        Some((def, Some(trait_ref.substs)))
+        Some((def, Some(substs)))
+    }
+
+    /// Resolves an associated function or const of a trait to the item of the
+    /// impl providing it, if the self type in `trait_substs` is known. That
+    /// item can have a more precise type than the declaration in the trait.
+    /// Otherwise, the trait's item and `trait_substs` are returned as is.
+    fn resolve_impl_item(
+        &mut self,
+        item: AssocItemId,
+        trait_substs: Substs,
+    ) -> (AssocItemId, Substs) {
+        let self_ty = self.resolve_ty_as_possible(trait_substs[0].clone());
+        let mut is_concrete = true;
+        self_ty.walk(&mut |ty| is_concrete &= !matches!(ty, Ty::Unknown | Ty::Placeholder(_)));
+        let canonical_ty = self.canonicalizer().canonicalize_ty(self_ty);
+        if !is_concrete || !canonical_ty.value.kinds.is_empty() {
+            return (item, trait_substs);
+        }
+        let krate = match self.resolver.krate() {
+            Some(krate) => krate,
+            None => return (item, trait_substs),
+        };
+        match method_resolution::lookup_impl_item(self.db, krate, item, &canonical_ty.value) {
+            Some(resolved) => resolved,
+            None => (item, trait_substs),
+        }
     }
 
     fn resolve_ty_assoc_item(
@@ -258,7 +300,14 @@ impl<'a> InferenceContext<'a> {
                             trait_,
                             substs: trait_substs.clone(),
                         }));
-                        Some(trait_substs)
+                        let (item, substs) = self.resolve_impl_item(item, trait_substs);
+                        let def = match item {
+                            AssocItemId::FunctionId(f) => ValueNs::FunctionId(f),
+                            AssocItemId::ConstId(c) => ValueNs::ConstId(c),
+                            AssocItemId::TypeAliasId(_) => unreachable!(),
+                        };
+                        self.write_assoc_resolution(id, item);
+                        return Some((def, Some(substs)));
                     }
                     AssocContainerId::ContainerId(_) => None,
                 };
@@ -37,10 +37,14 @@ where
 #[derive(Debug)]
 pub(super) struct Canonicalized<T> {
     pub(super) value: Canonical<T>,
-    free_vars: Vec<(InferenceVar, TyVariableKind)>,
+    pub(super) free_vars: Vec<(InferenceVar, TyVariableKind)>,
 }
 
 impl<'a, 'b> Canonicalizer<'a, 'b> {
+    /// Returns the index of the bound variable for `free_var`. Variables are
+    /// numbered in order of first occurrence, so goals that only differ in
+    /// the inference variables they contain canonicalize to the same value and
+    /// can share `trait_solve` results.
     fn add(&mut self, free_var: InferenceVar, kind: TyVariableKind) -> usize {
         self.free_vars.iter().position(|&(v, _)| v == free_var).unwrap_or_else(|| {
             let next_index = self.free_vars.len();
@@ -91,18 +95,31 @@ impl<'a, 'b> Canonicalizer<'a, 'b> {
         mut self,
         obligation: InEnvironment<Obligation>,
     ) -> Canonicalized<InEnvironment<Obligation>> {
-        let result = match obligation.value {
+        let result = self.do_canonicalize_obligation(obligation.value);
+        self.into_canonicalized(InEnvironment {
+            value: result,
+            environment: obligation.environment,
+        })
+    }
+
+    fn do_canonicalize_obligation(&mut self, obligation: Obligation) -> Obligation {
+        match obligation {
             Obligation::Trait(tr) => {
                 Obligation::Trait(self.do_canonicalize(tr, DebruijnIndex::INNERMOST))
             }
             Obligation::Projection(pr) => {
                 Obligation::Projection(self.do_canonicalize(pr, DebruijnIndex::INNERMOST))
             }
-        };
-        self.into_canonicalized(InEnvironment {
-            value: result,
-            environment: obligation.environment,
-        })
+            Obligation::WellFormed(ty) => {
+                Obligation::WellFormed(self.do_canonicalize(ty, DebruijnIndex::INNERMOST))
+            }
+            // the binder only binds lifetimes, so the canonical variables of
+            // the types stay at the same binder level
+            Obligation::ForLifetimes(num_lifetimes, obligation) => Obligation::ForLifetimes(
+                num_lifetimes,
+                Box::new(self.do_canonicalize_obligation(*obligation)),
+            ),
+        }
     }
 }
 
@@ -150,18 +167,28 @@ impl<T> Canonicalized<T> {
 }
 
 pub(crate) fn unify(tys: &Canonical<(Ty, Ty)>) -> Option<Substs> {
+    let (ty1, ty2) = &tys.value;
+    let substs = (Substs::single(ty1.clone()), Substs::single(ty2.clone()));
+    unify_substs(&Canonical { kinds: tys.kinds.clone(), value: substs })
+}
+
+/// Like `unify`, but unifies two lists of types element-wise.
+pub(crate) fn unify_substs(substs: &Canonical<(Substs, Substs)>) -> Option<Substs> {
     let mut table = InferenceTable::new();
     let vars = Substs(
-        tys.kinds
+        substs
+            .kinds
             .iter()
             // we always use type vars here because we want everything to
             // fallback to Unknown in the end (kind of hacky, as below)
             .map(|_| table.new_type_var())
             .collect(),
     );
-    let ty1_with_vars = tys.value.0.clone().subst_bound_vars(&vars);
-    let ty2_with_vars = tys.value.1.clone().subst_bound_vars(&vars);
-    if !table.unify(&ty1_with_vars, &ty2_with_vars) {
+    let substs1_with_vars = substs.value.0.clone().subst_bound_vars(&vars);
+    let substs2_with_vars = substs.value.1.clone().subst_bound_vars(&vars);
+    if substs1_with_vars.len() != substs2_with_vars.len()
+        || !table.unify_substs(&substs1_with_vars, &substs2_with_vars, 0)
+    {
         return None;
     }
     // default any type vars that weren't unified back to their original bound vars
@@ -172,7 +199,7 @@ pub(crate) fn unify(tys: &Canonical<(Ty, Ty)>) -> Option<Substs> {
         }
     }
     Some(
-        Substs::builder(tys.kinds.len())
+        Substs::builder(substs.kinds.len())
             .fill(vars.iter().map(|v| table.resolve_ty_completely(v.clone())))
             .build(),
     )
@@ -374,6 +401,10 @@ impl InferenceTable {
                     depth + 1,
                 ) && self.unify_inner(&proj1.ty, &proj2.ty, depth + 1)
             }
+            (
+                GenericPredicate::ForLifetimes(n1, pred1),
+                GenericPredicate::ForLifetimes(n2, pred2),
+            ) if n1 == n2 => self.unify_preds(pred1, pred2, depth + 1),
             _ => false,
         }
     }
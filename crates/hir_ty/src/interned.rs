@@ -0,0 +1,236 @@
+//! Global interning of type lists. Equal lists share a single allocation, so
+//! cloning them is cheap and comparing or hashing them only needs to look at
+//! the pointer.
+use std::{
+    fmt,
+    hash::{BuildHasherDefault, Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use once_cell::sync::OnceCell;
+use rustc_hash::FxHasher;
+
+use crate::{GenericPredicate, Ty};
+
+type InternMap<T> = DashMap<Arc<T>, (), BuildHasherDefault<FxHasher>>;
+
+pub struct Interned<T: Internable + ?Sized> {
+    arc: Arc<T>,
+}
+
+impl<T: Internable + ?Sized> Interned<T> {
+    fn new(value: Arc<T>) -> Interned<T> {
+        let map = T::storage().get_or_init(DashMap::default);
+        if let Some(entry) = map.get(&*value) {
+            return Interned { arc: entry.key().clone() };
+        }
+        // someone else might have interned the same value in the meantime
+        let arc = map.entry(value).or_insert(()).key().clone();
+        Interned { arc }
+    }
+}
+
+impl<T: Internable + ?Sized> Drop for Interned<T> {
+    fn drop(&mut self) {
+        // one reference is held by the map, so if ours is the only other one,
+        // the value isn't used anymore. The map can only hand out new
+        // references while its shard is locked, so checking the count again
+        // under the lock avoids racing with them.
+        if Arc::strong_count(&self.arc) == 2 {
+            if let Some(map) = T::storage().get() {
+                map.remove_if(&self.arc, |it, _| Arc::strong_count(it) == 2);
+            }
+        }
+    }
+}
+
+impl<T: Internable + ?Sized> Clone for Interned<T> {
+    fn clone(&self) -> Interned<T> {
+        Interned { arc: self.arc.clone() }
+    }
+}
+
+impl<T: Internable + ?Sized> PartialEq for Interned<T> {
+    fn eq(&self, other: &Interned<T>) -> bool {
+        // equal values are always interned to the same allocation
+        Arc::ptr_eq(&self.arc, &other.arc)
+    }
+}
+
+impl<T: Internable + ?Sized> Eq for Interned<T> {}
+
+impl<T: Internable + ?Sized> Hash for Interned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(Arc::as_ptr(&self.arc) as *const () as usize)
+    }
+}
+
+impl<T: Internable + ?Sized> Deref for Interned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.arc
+    }
+}
+
+impl<T: Internable + ?Sized + fmt::Debug> fmt::Debug for Interned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (*self.arc).fmt(f)
+    }
+}
+
+impl<T: Clone> From<Vec<T>> for Interned<[T]>
+where
+    [T]: Internable,
+{
+    fn from(vec: Vec<T>) -> Interned<[T]> {
+        Interned::new(vec.into())
+    }
+}
+
+impl<T: Clone> From<&[T]> for Interned<[T]>
+where
+    [T]: Internable,
+{
+    fn from(slice: &[T]) -> Interned<[T]> {
+        Interned::new(slice.into())
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for Interned<[T]>
+where
+    [T]: Internable,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Interned<[T]> {
+        Interned::new(iter.into_iter().collect())
+    }
+}
+
+/// A list that is interned unless it has been detached from the interner to
+/// mutate it in place. Detached lists compare equal to and hash like their
+/// interned counterparts.
+#[derive(Clone)]
+pub(crate) enum InternedList<T>
+where
+    [T]: Internable,
+{
+    Interned(Interned<[T]>),
+    // a thin pointer, so that this is no larger than `Interned`
+    Detached(Arc<Vec<T>>),
+}
+
+impl<T: Clone> InternedList<T>
+where
+    [T]: Internable,
+{
+    fn interned(&self) -> Interned<[T]> {
+        match self {
+            InternedList::Interned(it) => it.clone(),
+            InternedList::Detached(it) => it[..].into(),
+        }
+    }
+
+    /// Gives mutable access to the list by detaching it from the interner.
+    pub(crate) fn make_mut(&mut self) -> &mut [T] {
+        if let InternedList::Interned(it) = self {
+            *self = InternedList::Detached(Arc::new(it.to_vec()));
+        }
+        match self {
+            InternedList::Detached(it) => Arc::make_mut(it).as_mut_slice(),
+            InternedList::Interned(_) => unreachable!(),
+        }
+    }
+}
+
+impl<T: Clone> PartialEq for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn eq(&self, other: &InternedList<T>) -> bool {
+        match (self, other) {
+            (InternedList::Interned(it), InternedList::Interned(other)) => it == other,
+            _ => self[..] == other[..],
+        }
+    }
+}
+
+impl<T: Clone> Eq for InternedList<T> where [T]: Internable {}
+
+impl<T: Clone> Hash for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.interned().hash(state)
+    }
+}
+
+impl<T> Deref for InternedList<T>
+where
+    [T]: Internable,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            InternedList::Interned(it) => it,
+            InternedList::Detached(it) => it,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: Clone> From<Vec<T>> for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn from(vec: Vec<T>) -> InternedList<T> {
+        InternedList::Interned(vec.into())
+    }
+}
+
+impl<T: Clone> From<&[T]> for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn from(slice: &[T]) -> InternedList<T> {
+        InternedList::Interned(slice.into())
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for InternedList<T>
+where
+    [T]: Internable,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> InternedList<T> {
+        InternedList::Interned(iter.into_iter().collect())
+    }
+}
+
+pub trait Internable: Hash + Eq + Send + Sync + 'static {
+    fn storage() -> &'static OnceCell<InternMap<Self>>;
+}
+
+impl Internable for [Ty] {
+    fn storage() -> &'static OnceCell<InternMap<Self>> {
+        static STORAGE: OnceCell<InternMap<[Ty]>> = OnceCell::new();
+        &STORAGE
+    }
+}
+
+impl Internable for [GenericPredicate] {
+    fn storage() -> &'static OnceCell<InternMap<Self>> {
+        static STORAGE: OnceCell<InternMap<[GenericPredicate]>> = OnceCell::new();
+        &STORAGE
+    }
+}
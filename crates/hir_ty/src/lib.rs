@@ -6,9 +6,11 @@ macro_rules! eprintln {
 }
 
 mod autoderef;
+mod interned;
 pub mod primitive;
 pub mod traits;
 pub mod method_resolution;
+pub mod object_safety;
 mod op;
 mod lower;
 pub(crate) mod infer;
@@ -25,24 +27,28 @@ mod test_db;
 
 use std::{iter, mem, ops::Deref, sync::Arc};
 
-use base_db::salsa;
+use base_db::{salsa, CrateId};
 use hir_def::{
     builtin_type::BuiltinType,
     expr::ExprId,
     type_ref::{Mutability, Rawness},
-    AdtId, AssocContainerId, DefWithBodyId, FunctionId, GenericDefId, HasModule, LifetimeParamId,
-    Lookup, TraitId, TypeAliasId, TypeParamId,
+    AdtId, AssocContainerId, DefWithBodyId, EnumVariantId, FunctionId, GenericDefId, HasModule,
+    LifetimeParamId, Lookup, TraitId, TypeAliasId, TypeParamId, VariantId,
 };
 use itertools::Itertools;
 
 use crate::{
     db::HirDatabase,
     display::HirDisplay,
-    utils::{generics, make_mut_slice, Generics},
+    interned::{Interned, InternedList},
+    utils::{generics, Generics},
 };
 
-pub use autoderef::autoderef;
-pub use infer::{InferenceResult, InferenceVar};
+pub use autoderef::{autoderef, autoderef_iter, Autoderef};
+pub use infer::{
+    Adjust, Adjustment, BindingMode, InferenceResult, InferenceVar, InferredGenericArgs,
+    TryFailure, TryFailureReason,
+};
 pub use lower::{
     associated_type_shorthand_candidates, callable_item_sig, CallableDefId, ImplTraitLoweringMode,
     TyDefId, TyLoweringContext, ValueTyDefId,
@@ -177,7 +183,8 @@ pub enum Ty {
     /// The type of a specific closure.
     ///
     /// The closure signature is stored in a `FnPtr` type in the first type
-    /// parameter.
+    /// parameter, and the closure kind in the second one (see
+    /// `FnTrait::to_closure_kind_ty`).
     Closure(DefWithBodyId, ExprId, Substs),
 
     /// Represents a foreign type declared in external blocks.
@@ -226,7 +233,7 @@ pub enum Ty {
     /// represents the `Self` type inside the bounds. This is currently
     /// implicit; Chalk has the `Binders` struct to make it explicit, but it
     /// didn't seem worth the overhead yet.
-    Dyn(Arc<[GenericPredicate]>),
+    Dyn(Interned<[GenericPredicate]>),
 
     /// A placeholder for a type which could not be computed; this is propagated
     /// to avoid useless error messages. Doubles as a placeholder where type
@@ -236,9 +243,10 @@ pub enum Ty {
     Unknown,
 }
 
-/// A list of substitutions for generic parameters.
+/// A list of substitutions for generic parameters. These are interned, so equal
+/// lists share their allocation.
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
-pub struct Substs(Arc<[Ty]>);
+pub struct Substs(InternedList<Ty>);
 
 impl TypeWalk for Substs {
     fn walk(&self, f: &mut impl FnMut(&Ty)) {
@@ -252,19 +260,32 @@ impl TypeWalk for Substs {
         f: &mut impl FnMut(&mut Ty, DebruijnIndex),
         binders: DebruijnIndex,
     ) {
-        for t in make_mut_slice(&mut self.0) {
+        let mut tys = self.0.to_vec();
+        for t in tys.iter_mut() {
             t.walk_mut_binders(f, binders);
         }
+        // only intern a new list if something changed
+        if tys[..] != self.0[..] {
+            self.0 = tys.into();
+        }
     }
 }
 
 impl Substs {
     pub fn empty() -> Substs {
-        Substs(Arc::new([]))
+        Substs(Vec::new().into())
     }
 
     pub fn single(ty: Ty) -> Substs {
-        Substs(Arc::new([ty]))
+        Substs(vec![ty].into())
+    }
+
+    /// A single `Ty::Unknown`, for when the argument of a generic type with one
+    /// parameter isn't known. This deliberately isn't a fresh inference
+    /// variable: `Substs` can be built outside of inference, and during
+    /// inference a variable should be created from the `InferenceTable` instead.
+    pub fn single_unknown() -> Substs {
+        Substs::single(Ty::Unknown)
     }
 
     pub fn prefix(&self, n: usize) -> Substs {
@@ -275,6 +296,20 @@ impl Substs {
         Substs(self.0[self.0.len() - std::cmp::min(self.0.len(), n)..].into())
     }
 
+    /// Composes two substitutions. The entries of `self` are stated in terms of
+    /// the parameters of some other generic item, as `Ty::Bound` variables bound
+    /// just outside of `self` (i.e. at `DebruijnIndex::INNERMOST`), and `other`
+    /// gives the values of those parameters; the result has each such variable
+    /// replaced by the corresponding entry of `other`, which must exist.
+    ///
+    /// Variables bound within the entries themselves (e.g. by a `dyn Trait`)
+    /// are left alone, and `other`'s entries are shifted in when they are
+    /// substituted under such binders. Placeholders aren't substituted; use
+    /// bound variables for the parameters that should be replaced.
+    pub fn apply(&self, other: &Substs) -> Substs {
+        self.clone().subst_bound_vars(other)
+    }
+
     pub fn as_single(&self) -> &Ty {
         if self.0.len() != 1 {
             panic!("expected substs of len 1, got {:?}", self);
@@ -354,6 +389,24 @@ impl SubstsBuilder {
         self.fill(iter::repeat(Ty::Unknown))
     }
 
+    /// Fills the remaining parameters of `def` with their defaults, or
+    /// `Ty::Unknown` for those without one. Defaults can refer to the
+    /// parameters before them, which may have been pushed already.
+    pub fn fill_with_defaults(mut self, db: &dyn HirDatabase, def: GenericDefId) -> Self {
+        if self.vec.is_empty() {
+            let defaults = db.generic_defaults_substs(def);
+            assert_eq!(defaults.len(), self.param_count);
+            return self.fill(defaults.iter().cloned());
+        }
+        let defaults = db.generic_defaults(def);
+        assert_eq!(defaults.len(), self.param_count);
+        for default_ty in defaults.iter().skip(self.vec.len()) {
+            let substs_so_far = Substs(self.vec.clone().into());
+            self.vec.push(default_ty.clone().subst(&substs_so_far));
+        }
+        self
+    }
+
     pub fn fill(mut self, filler: impl Iterator<Item = Ty>) -> Self {
         self.vec.extend(filler.take(self.remaining()));
         assert_eq!(self.remaining(), 0);
@@ -447,6 +500,29 @@ impl TraitRef {
     pub fn self_ty(&self) -> &Ty {
         &self.substs[0]
     }
+
+    /// Builds `self_ty: Trait<extra_args..>` for the trait that is the lang
+    /// item `name`, e.g. `"copy"`. Parameters without a given argument are
+    /// filled with `Unknown`. Returns `None` if there is no such lang item
+    /// trait (e.g. in a `no_std` crate), or if it takes fewer parameters than
+    /// arguments were given.
+    pub fn for_lang_item(
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        name: &str,
+        self_ty: Ty,
+        extra_args: &[Ty],
+    ) -> Option<TraitRef> {
+        let trait_ = db.lang_item(krate, name.into())?.as_trait()?;
+        if 1 + extra_args.len() > generics(db.upcast(), trait_.into()).len() {
+            return None;
+        }
+        let substs = Substs::build_for_def(db, trait_)
+            .push(self_ty)
+            .fill(extra_args.iter().cloned().chain(iter::repeat(Ty::Unknown)))
+            .build();
+        Some(TraitRef { trait_, substs })
+    }
 }
 
 impl TypeWalk for TraitRef {
@@ -471,25 +547,59 @@ pub enum GenericPredicate {
     Implemented(TraitRef),
     /// An associated type bindings like in `Iterator<Item = T>`.
     Projection(ProjectionPredicate),
+    /// A higher-ranked predicate like `for<'a> F: Fn(&'a str)`, under a binder
+    /// of the given number of lifetimes. Since lifetimes are erased in `Ty`,
+    /// the binder doesn't bind any type variables, so the De Bruijn indices in
+    /// the inner predicate aren't shifted.
+    ForLifetimes(usize, Box<GenericPredicate>),
     /// We couldn't resolve the trait reference. (If some type parameters can't
     /// be resolved, they will just be Unknown).
     Error,
 }
 
 impl GenericPredicate {
+    /// Puts `pred` under a binder of `num_lifetimes` lifetimes, merging it
+    /// with the binder `pred` may already have.
+    pub(crate) fn for_lifetimes(num_lifetimes: usize, pred: GenericPredicate) -> GenericPredicate {
+        match pred {
+            _ if num_lifetimes == 0 => pred,
+            GenericPredicate::Error => pred,
+            GenericPredicate::ForLifetimes(n, pred) => {
+                GenericPredicate::ForLifetimes(num_lifetimes + n, pred)
+            }
+            pred => GenericPredicate::ForLifetimes(num_lifetimes, Box::new(pred)),
+        }
+    }
+
+    /// The predicate without its `for<'a>` binder, if it has one.
+    pub fn skip_binders(&self) -> &GenericPredicate {
+        match self {
+            GenericPredicate::ForLifetimes(_, pred) => pred,
+            _ => self,
+        }
+    }
+
+    /// Like `skip_binders`, but by value.
+    pub fn into_skip_binders(self) -> GenericPredicate {
+        match self {
+            GenericPredicate::ForLifetimes(_, pred) => *pred,
+            _ => self,
+        }
+    }
+
     pub fn is_error(&self) -> bool {
         matches!(self, GenericPredicate::Error)
     }
 
     pub fn is_implemented(&self) -> bool {
-        matches!(self, GenericPredicate::Implemented(_))
+        matches!(self.skip_binders(), GenericPredicate::Implemented(_))
     }
 
     pub fn trait_ref(&self, db: &dyn HirDatabase) -> Option<TraitRef> {
-        match self {
+        match self.skip_binders() {
             GenericPredicate::Implemented(tr) => Some(tr.clone()),
             GenericPredicate::Projection(proj) => Some(proj.projection_ty.trait_ref(db)),
-            GenericPredicate::Error => None,
+            GenericPredicate::ForLifetimes(..) | GenericPredicate::Error => None,
         }
     }
 }
@@ -499,6 +609,7 @@ impl TypeWalk for GenericPredicate {
         match self {
             GenericPredicate::Implemented(trait_ref) => trait_ref.walk(f),
             GenericPredicate::Projection(projection_pred) => projection_pred.walk(f),
+            GenericPredicate::ForLifetimes(_, pred) => pred.walk(f),
             GenericPredicate::Error => {}
         }
     }
@@ -513,6 +624,8 @@ impl TypeWalk for GenericPredicate {
             GenericPredicate::Projection(projection_pred) => {
                 projection_pred.walk_mut_binders(f, binders)
             }
+            // the binder only binds lifetimes, so `binders` stays the same
+            GenericPredicate::ForLifetimes(_, pred) => pred.walk_mut_binders(f, binders),
             GenericPredicate::Error => {}
         }
     }
@@ -540,7 +653,7 @@ impl<T> Canonical<T> {
 /// one return type.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CallableSig {
-    params_and_return: Arc<[Ty]>,
+    params_and_return: Substs,
     is_varargs: bool,
 }
 
@@ -550,18 +663,15 @@ pub type PolyFnSig = Binders<CallableSig>;
 impl CallableSig {
     pub fn from_params_and_return(mut params: Vec<Ty>, ret: Ty, is_varargs: bool) -> CallableSig {
         params.push(ret);
-        CallableSig { params_and_return: params.into(), is_varargs }
+        CallableSig { params_and_return: Substs(params.into()), is_varargs }
     }
 
     pub fn from_fn_ptr(fn_ptr: &FnPointer) -> CallableSig {
-        CallableSig {
-            params_and_return: Arc::clone(&fn_ptr.substs.0),
-            is_varargs: fn_ptr.sig.variadic,
-        }
+        CallableSig { params_and_return: fn_ptr.substs.clone(), is_varargs: fn_ptr.sig.variadic }
     }
 
     pub fn from_substs(substs: &Substs) -> CallableSig {
-        CallableSig { params_and_return: Arc::clone(&substs.0), is_varargs: false }
+        CallableSig { params_and_return: substs.clone(), is_varargs: false }
     }
 
     pub fn params(&self) -> &[Ty] {
@@ -575,9 +685,7 @@ impl CallableSig {
 
 impl TypeWalk for CallableSig {
     fn walk(&self, f: &mut impl FnMut(&Ty)) {
-        for t in self.params_and_return.iter() {
-            t.walk(f);
-        }
+        self.params_and_return.walk(f);
     }
 
     fn walk_mut_binders(
@@ -585,9 +693,7 @@ impl TypeWalk for CallableSig {
         f: &mut impl FnMut(&mut Ty, DebruijnIndex),
         binders: DebruijnIndex,
     ) {
-        for t in make_mut_slice(&mut self.params_and_return) {
-            t.walk_mut_binders(f, binders);
-        }
+        self.params_and_return.walk_mut_binders(f, binders);
     }
 }
 
@@ -600,18 +706,23 @@ impl Ty {
         Ty::Function(FnPointer {
             num_args: sig.params().len(),
             sig: FnSig { variadic: sig.is_varargs },
-            substs: Substs(sig.params_and_return),
+            substs: sig.params_and_return,
         })
     }
 
     pub fn builtin(builtin: BuiltinType) -> Self {
-        match builtin {
-            BuiltinType::Char => Ty::Scalar(Scalar::Char),
-            BuiltinType::Bool => Ty::Scalar(Scalar::Bool),
-            BuiltinType::Str => Ty::Str,
-            BuiltinType::Int(t) => Ty::Scalar(Scalar::Int(primitive::int_ty_from_builtin(t))),
-            BuiltinType::Uint(t) => Ty::Scalar(Scalar::Uint(primitive::uint_ty_from_builtin(t))),
-            BuiltinType::Float(t) => Ty::Scalar(Scalar::Float(primitive::float_ty_from_builtin(t))),
+        match primitive::scalar_from_builtin(builtin) {
+            Some(scalar) => Ty::Scalar(scalar),
+            None => Ty::Str,
+        }
+    }
+
+    /// The inverse of `Ty::builtin`.
+    pub fn as_builtin(&self) -> Option<BuiltinType> {
+        match self {
+            Ty::Scalar(scalar) => Some(primitive::builtin_from_scalar(*scalar)),
+            Ty::Str => Some(BuiltinType::Str),
+            _ => None,
         }
     }
 
@@ -644,6 +755,37 @@ impl Ty {
         t
     }
 
+    /// Like `strip_references`, but allows replacing the referenced type in
+    /// place.
+    pub fn strip_references_mut(&mut self) -> &mut Ty {
+        self.peel_refs_mut().0
+    }
+
+    /// Returns the type behind any number of references, together with the
+    /// number of references that were stripped.
+    pub fn peel_refs_mut(&mut self) -> (&mut Ty, usize) {
+        let mut t: &mut Ty = self;
+        let mut count = 0;
+        loop {
+            // `{ t }` moves the reference out of `t`, so we can reassign it
+            match { t } {
+                Ty::Ref(_mutability, parameters) => {
+                    t = &mut parameters.0.make_mut()[0];
+                    count += 1;
+                }
+                t => return (t, count),
+            }
+        }
+    }
+
+    /// Replaces every occurrence of the type parameter `id` with `replacement`.
+    pub fn replace_placeholder(&self, id: TypeParamId, replacement: &Ty) -> Ty {
+        self.clone().fold(&mut |ty| match ty {
+            Ty::Placeholder(p) if p == id => replacement.clone(),
+            ty => ty,
+        })
+    }
+
     pub fn as_adt(&self) -> Option<(AdtId, &Substs)> {
         match self {
             Ty::Adt(adt_def, parameters) => Some((*adt_def, parameters)),
@@ -676,6 +818,91 @@ impl Ty {
         matches!(self, Ty::Unknown)
     }
 
+    /// Whether `Ty::Unknown` occurs anywhere in this type.
+    pub fn contains_unknown(&self) -> bool {
+        let mut found = false;
+        self.walk(&mut |ty| found |= ty.is_unknown());
+        found
+    }
+
+    /// Whether any inference variable occurs in this type, i.e. it isn't
+    /// fully resolved yet.
+    pub fn contains_inference_var(&self) -> bool {
+        let mut found = false;
+        self.walk(&mut |ty| found |= matches!(ty, Ty::InferenceVar(..)));
+        found
+    }
+
+    /// Whether this type has a size of zero. Returns `None` if that can't be
+    /// determined statically, e.g. because the type is generic or unsized.
+    /// Array lengths aren't tracked, so an array is only known to be
+    /// zero-sized if its element type is, and never known to be non-zero-sized.
+    pub fn is_zero_sized(&self, db: &dyn HirDatabase) -> Option<bool> {
+        self.is_zero_sized_impl(db, 0)
+    }
+
+    fn is_zero_sized_impl(&self, db: &dyn HirDatabase, depth: usize) -> Option<bool> {
+        // bail out on infinitely sized types like `struct S(S);`
+        const MAX_DEPTH: usize = 32;
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let all_zero_sized = |tys: &mut dyn Iterator<Item = Ty>| {
+            let mut res = Some(true);
+            for ty in tys {
+                match ty.is_zero_sized_impl(db, depth + 1) {
+                    Some(true) => {}
+                    Some(false) => return Some(false),
+                    None => res = None,
+                }
+            }
+            res
+        };
+        let fields_zero_sized = |variant: VariantId, substs: &Substs| {
+            let field_types = db.field_types(variant);
+            let res = all_zero_sized(&mut field_types.values().map(|ty| ty.clone().subst(substs)));
+            res
+        };
+        match self {
+            Ty::Never | Ty::FnDef(..) => Some(true),
+            Ty::Scalar(_) | Ty::Ref(..) | Ty::RawPtr(..) | Ty::Function(_) => Some(false),
+            Ty::Tuple(_, substs) => all_zero_sized(&mut substs.iter().cloned()),
+            Ty::Array(substs) => match substs.as_single().is_zero_sized_impl(db, depth + 1) {
+                Some(true) => Some(true),
+                // the length might be zero
+                _ => None,
+            },
+            Ty::Adt(AdtId::StructId(it), substs) => fields_zero_sized((*it).into(), substs),
+            Ty::Adt(AdtId::UnionId(it), substs) => fields_zero_sized((*it).into(), substs),
+            Ty::Adt(AdtId::EnumId(it), substs) => {
+                let enum_data = db.enum_data(*it);
+                let mut variants = enum_data.variants.iter();
+                match (variants.next(), variants.next()) {
+                    (None, _) => Some(true),
+                    (Some((local_id, _)), None) => {
+                        let variant = EnumVariantId { parent: *it, local_id };
+                        fields_zero_sized(variant.into(), substs)
+                    }
+                    // multiple variants need a discriminant
+                    (Some(_), Some(_)) => Some(false),
+                }
+            }
+            Ty::Str
+            | Ty::Slice(_)
+            | Ty::Dyn(_)
+            | Ty::ForeignType(_)
+            | Ty::Closure(..)
+            | Ty::OpaqueType(..)
+            | Ty::AssociatedType(..)
+            | Ty::Projection(_)
+            | Ty::Opaque(_)
+            | Ty::Placeholder(_)
+            | Ty::Bound(_)
+            | Ty::InferenceVar(..)
+            | Ty::Unknown => None,
+        }
+    }
+
     pub fn equals_ctor(&self, other: &Ty) -> bool {
         match (self, other) {
             (Ty::Adt(adt, ..), Ty::Adt(adt2, ..)) => adt == adt2,
@@ -705,7 +932,7 @@ impl Ty {
     /// If this is a `dyn Trait` type, this returns the `Trait` part.
     pub fn dyn_trait_ref(&self) -> Option<&TraitRef> {
         match self {
-            Ty::Dyn(bounds) => bounds.get(0).and_then(|b| match b {
+            Ty::Dyn(bounds) => bounds.get(0).and_then(|b| match b.skip_binders() {
                 GenericPredicate::Implemented(trait_ref) => Some(trait_ref),
                 _ => None,
             }),
@@ -718,6 +945,84 @@ impl Ty {
         self.dyn_trait_ref().map(|it| it.trait_)
     }
 
+    /// Assembles a trait object type from its components, in the order the
+    /// other `dyn` accessors expect: the principal trait first, then the
+    /// associated type bindings and finally the auto traits. The bounds are
+    /// expected to use `Ty::Bound(0)` as their `Self` type.
+    pub fn new_dyn(
+        db: &dyn HirDatabase,
+        principal: Option<TraitRef>,
+        projection_bounds: impl IntoIterator<Item = ProjectionPredicate>,
+        auto_traits: impl IntoIterator<Item = TraitId>,
+    ) -> Ty {
+        let self_ty = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+        let principal_trait = principal.as_ref().map(|it| it.trait_);
+        let auto_traits =
+            auto_traits.into_iter().filter(|&it| Some(it) != principal_trait).unique();
+        let predicates = principal
+            .map(GenericPredicate::Implemented)
+            .into_iter()
+            .chain(projection_bounds.into_iter().map(GenericPredicate::Projection))
+            .chain(auto_traits.map(|trait_| {
+                GenericPredicate::Implemented(TraitRef {
+                    trait_,
+                    substs: Substs::single(self_ty.clone()),
+                })
+            }))
+            .collect::<Vec<_>>();
+        Ty::Dyn(sort_dyn_predicates(db, predicates).into())
+    }
+
+    /// If this is a `dyn Trait` type, returns the bound of its principal, i.e.
+    /// the one trait that isn't an auto trait. `dyn Send + Sync` has none.
+    pub fn principal_trait_ref(&self, db: &dyn HirDatabase) -> Option<&TraitRef> {
+        match self {
+            Ty::Dyn(bounds) => bounds.iter().find_map(|b| match b.skip_binders() {
+                GenericPredicate::Implemented(trait_ref)
+                    if !db.trait_data(trait_ref.trait_).auto =>
+                {
+                    Some(trait_ref)
+                }
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// If this is a `dyn Trait` type, returns its auto trait bounds, like the
+    /// `Send` in `dyn Iterator<Item = u8> + Send`.
+    pub fn auto_traits<'a>(
+        &'a self,
+        db: &'a dyn HirDatabase,
+    ) -> impl Iterator<Item = TraitId> + 'a {
+        let bounds = match self {
+            Ty::Dyn(bounds) => &bounds[..],
+            _ => &[],
+        };
+        bounds.iter().filter_map(move |b| match b.skip_binders() {
+            GenericPredicate::Implemented(trait_ref)
+                if matches!(trait_ref.self_ty(), Ty::Bound(_))
+                    && db.trait_data(trait_ref.trait_).auto =>
+            {
+                Some(trait_ref.trait_)
+            }
+            _ => None,
+        })
+    }
+
+    /// If this is a `dyn Trait` type, returns its associated type bindings, like
+    /// the `Item = u8` in `dyn Iterator<Item = u8>`.
+    pub fn projection_bounds(&self) -> impl Iterator<Item = &ProjectionPredicate> {
+        let bounds = match self {
+            Ty::Dyn(bounds) => &bounds[..],
+            _ => &[],
+        };
+        bounds.iter().filter_map(|b| match b.skip_binders() {
+            GenericPredicate::Projection(proj) => Some(proj),
+            _ => None,
+        })
+    }
+
     fn builtin_deref(&self) -> Option<Ty> {
         match self {
             Ty::Ref(.., parameters) => Some(Ty::clone(parameters.as_single())),
@@ -733,6 +1038,22 @@ impl Ty {
         }
     }
 
+    pub fn as_fn_def_with_substs(&self) -> Option<(FunctionId, &Substs)> {
+        match self {
+            Ty::FnDef(CallableDefId::FunctionId(func), substs) => Some((*func, substs)),
+            _ => None,
+        }
+    }
+
+    /// Returns the item and substitutions of a function, tuple struct or enum
+    /// variant constructor type.
+    pub fn as_callable_def(&self) -> Option<(CallableDefId, &Substs)> {
+        match self {
+            Ty::FnDef(def, substs) => Some((*def, substs)),
+            _ => None,
+        }
+    }
+
     pub fn callable_sig(&self, db: &dyn HirDatabase) -> Option<CallableSig> {
         match self {
             Ty::Function(fn_ptr) => Some(CallableSig::from_fn_ptr(fn_ptr)),
@@ -810,46 +1131,9 @@ impl Ty {
     }
 
     pub fn impl_trait_bounds(&self, db: &dyn HirDatabase) -> Option<Vec<GenericPredicate>> {
-        match self {
-            Ty::OpaqueType(opaque_ty_id, ..) => {
-                match opaque_ty_id {
-                    OpaqueTyId::AsyncBlockTypeImplTrait(def, _expr) => {
-                        let krate = def.module(db.upcast()).krate();
-                        if let Some(future_trait) = db
-                            .lang_item(krate, "future_trait".into())
-                            .and_then(|item| item.as_trait())
-                        {
-                            // This is only used by type walking.
-                            // Parameters will be walked outside, and projection predicate is not used.
-                            // So just provide the Future trait.
-                            let impl_bound = GenericPredicate::Implemented(TraitRef {
-                                trait_: future_trait,
-                                substs: Substs::empty(),
-                            });
-                            Some(vec![impl_bound])
-                        } else {
-                            None
-                        }
-                    }
-                    OpaqueTyId::ReturnTypeImplTrait(..) => None,
-                }
-            }
-            Ty::Opaque(opaque_ty) => {
-                let predicates = match opaque_ty.opaque_ty_id {
-                    OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
-                        db.return_type_impl_traits(func).map(|it| {
-                            let data = (*it)
-                                .as_ref()
-                                .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone());
-                            data.subst(&opaque_ty.parameters)
-                        })
-                    }
-                    // It always has an parameter for Future::Output type.
-                    OpaqueTyId::AsyncBlockTypeImplTrait(..) => unreachable!(),
-                };
-
-                predicates.map(|it| it.value)
-            }
+        let (opaque_ty_id, parameters) = match self {
+            Ty::OpaqueType(opaque_ty_id, parameters) => (*opaque_ty_id, parameters),
+            Ty::Opaque(opaque_ty) => (opaque_ty.opaque_ty_id, &opaque_ty.parameters),
             Ty::Placeholder(id) => {
                 let generic_params = db.generic_params(id.parent);
                 let param_data = &generic_params.types[id.local_id];
@@ -861,12 +1145,39 @@ impl Ty {
                             .map(|pred| pred.value.clone())
                             .collect_vec();
 
-                        Some(predicates)
+                        return Some(predicates);
                     }
-                    _ => None,
+                    _ => return None,
                 }
             }
-            _ => None,
+            _ => return None,
+        };
+        match opaque_ty_id {
+            OpaqueTyId::AsyncBlockTypeImplTrait(def, _expr) => {
+                let krate = def.module(db.upcast()).krate();
+                let future_trait =
+                    db.lang_item(krate, "future_trait".into()).and_then(|item| item.as_trait())?;
+                // This is only used by type walking.
+                // Parameters will be walked outside, and projection predicate is not used.
+                // So just provide the Future trait.
+                let impl_bound = GenericPredicate::Implemented(TraitRef {
+                    trait_: future_trait,
+                    substs: Substs::empty(),
+                });
+                Some(vec![impl_bound])
+            }
+            OpaqueTyId::ReturnTypeImplTrait(func, idx) => {
+                let datas = db.return_type_impl_traits(func)?;
+                let bounds = (*datas)
+                    .as_ref()
+                    .map(|rpit| rpit.impl_traits[idx as usize].bounds.clone())
+                    .subst(parameters);
+                // Any nested `impl Trait` in the bounds (like the `impl Display` in
+                // `impl Iterator<Item = impl Display>`) is an opaque type of its own,
+                // whose parameters just got substituted along with ours. The `Self`
+                // type of the bounds stays bound.
+                Some(bounds.value)
+            }
         }
     }
 
@@ -889,6 +1200,35 @@ impl Ty {
     }
 }
 
+/// Puts the bounds of a trait object into the canonical order `Ty::Dyn`
+/// relies on for equality and display: the principal trait followed by its
+/// associated type bindings, then the auto traits sorted by name. Lifetime
+/// bounds aren't lowered, so `dyn A + Send + Sync + 'static` and
+/// `dyn Sync + A + 'static + Send` become the same type.
+pub(crate) fn sort_dyn_predicates(
+    db: &dyn HirDatabase,
+    mut predicates: Vec<GenericPredicate>,
+) -> Vec<GenericPredicate> {
+    // The sort is stable, so bindings stay behind the trait they belong to.
+    predicates.sort_by_cached_key(|pred| match pred.skip_binders() {
+        // associated type bounds like the `Item: Send` in
+        // `dyn Iterator<Item: Send>` stay with their trait, too
+        GenericPredicate::Implemented(tr) if matches!(tr.self_ty(), Ty::Bound(_)) => {
+            let data = db.trait_data(tr.trait_);
+            if data.auto {
+                (1, Some(data.name.clone()))
+            } else {
+                (0, None)
+            }
+        }
+        GenericPredicate::Implemented(_)
+        | GenericPredicate::Projection(_)
+        | GenericPredicate::ForLifetimes(..) => (0, None),
+        GenericPredicate::Error => (2, None),
+    });
+    predicates
+}
+
 /// This allows walking structures that contain types to do something with those
 /// types, similar to Chalk's `Fold` trait.
 pub trait TypeWalk {
@@ -1023,9 +1363,14 @@ impl TypeWalk for Ty {
                 p_ty.parameters.walk_mut_binders(f, binders);
             }
             Ty::Dyn(predicates) => {
-                for p in make_mut_slice(predicates) {
+                let mut preds = predicates.to_vec();
+                for p in preds.iter_mut() {
                     p.walk_mut_binders(f, binders.shifted_in());
                 }
+                // only intern a new list if something changed
+                if preds[..] != predicates[..] {
+                    *predicates = preds.into();
+                }
             }
             Ty::Opaque(o_ty) => {
                 o_ty.parameters.walk_mut_binders(f, binders);
@@ -27,13 +27,14 @@ use test_utils::mark;
 
 use crate::{
     db::HirDatabase,
+    sort_dyn_predicates,
     utils::{
         all_super_trait_refs, associated_type_by_name_including_super_traits, generics,
-        make_mut_slice, variant_data,
+        variant_data,
     },
     Binders, BoundVar, CallableSig, DebruijnIndex, FnPointer, FnSig, GenericPredicate, OpaqueTy,
     OpaqueTyId, PolyFnSig, ProjectionPredicate, ProjectionTy, ReturnTypeImplTrait,
-    ReturnTypeImplTraits, Substs, TraitEnvironment, TraitRef, Ty, TypeWalk,
+    ReturnTypeImplTraits, Substs, SubstsBuilder, TraitEnvironment, TraitRef, Ty, TypeWalk,
 };
 
 #[derive(Debug)]
@@ -48,6 +49,10 @@ pub struct TyLoweringContext<'a> {
     pub type_param_mode: TypeParamLoweringMode,
     pub impl_trait_mode: ImplTraitLoweringMode,
     impl_trait_counter: std::cell::Cell<u16>,
+    /// The type the `impl Trait`s lowered into opaque types are from, usually
+    /// a function's return type. The index of an opaque type is the position
+    /// of its `impl Trait` in this type, see `impl_trait_position`.
+    opaque_type_root: Option<&'a TypeRef>,
     /// When turning `impl Trait` into opaque types, we have to collect the
     /// bounds at the same time to get the IDs correct (without becoming too
     /// complicated). I don't like using interior mutability (as for the
@@ -57,6 +62,22 @@ pub struct TyLoweringContext<'a> {
     /// with the immutable context (the references to the DB and resolver).
     /// Splitting this up would be a possible fix.
     opaque_type_data: std::cell::RefCell<Vec<ReturnTypeImplTrait>>,
+    /// Path segments with the wrong number of generic arguments, collected for
+    /// diagnostics. Lowering just drops extra arguments and fills in missing
+    /// ones, so this is the only trace of such a mismatch.
+    generic_args_mismatches: std::cell::RefCell<Vec<GenericArgsMismatch>>,
+}
+
+/// A path segment given the wrong number of generic type arguments, e.g.
+/// `Vec<u32, u32, u32>`. Lifetime arguments aren't counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GenericArgsMismatch {
+    pub(crate) segment: Name,
+    /// The number of type parameters, including those with defaults.
+    pub(crate) expected: usize,
+    pub(crate) provided: usize,
+    /// How many of the type parameters have defaults.
+    pub(crate) defaults: usize,
 }
 
 impl<'a> TyLoweringContext<'a> {
@@ -66,6 +87,7 @@ impl<'a> TyLoweringContext<'a> {
         let type_param_mode = TypeParamLoweringMode::Placeholder;
         let in_binders = DebruijnIndex::INNERMOST;
         let opaque_type_data = std::cell::RefCell::new(Vec::new());
+        let generic_args_mismatches = std::cell::RefCell::new(Vec::new());
         Self {
             db,
             resolver,
@@ -73,7 +95,9 @@ impl<'a> TyLoweringContext<'a> {
             impl_trait_mode,
             impl_trait_counter,
             type_param_mode,
+            opaque_type_root: None,
             opaque_type_data,
+            generic_args_mismatches,
         }
     }
 
@@ -83,15 +107,18 @@ impl<'a> TyLoweringContext<'a> {
         f: impl FnOnce(&TyLoweringContext) -> T,
     ) -> T {
         let opaque_ty_data_vec = self.opaque_type_data.replace(Vec::new());
+        let mismatches = self.generic_args_mismatches.replace(Vec::new());
         let new_ctx = Self {
             in_binders: debruijn,
             impl_trait_counter: std::cell::Cell::new(self.impl_trait_counter.get()),
             opaque_type_data: std::cell::RefCell::new(opaque_ty_data_vec),
+            generic_args_mismatches: std::cell::RefCell::new(mismatches),
             ..*self
         };
         let result = f(&new_ctx);
         self.impl_trait_counter.set(new_ctx.impl_trait_counter.get());
         self.opaque_type_data.replace(new_ctx.opaque_type_data.into_inner());
+        self.generic_args_mismatches.replace(new_ctx.generic_args_mismatches.into_inner());
         result
     }
 
@@ -110,6 +137,37 @@ impl<'a> TyLoweringContext<'a> {
     pub fn with_type_param_mode(self, type_param_mode: TypeParamLoweringMode) -> Self {
         Self { type_param_mode, ..self }
     }
+
+    /// Lowers a list of where predicates, e.g. the where clauses of an item. A
+    /// bound with associated type bindings like `T: Iterator<Item = u32>`
+    /// produces the `Implemented` predicate for the trait, followed by a
+    /// `Projection` predicate for each binding.
+    pub(crate) fn lower_generic_predicate_list<'p>(
+        &self,
+        predicates: impl Iterator<Item = &'p WherePredicate>,
+    ) -> Vec<GenericPredicate> {
+        predicates.flat_map(|pred| GenericPredicate::from_where_predicate(self, pred)).collect()
+    }
+
+    /// Lowers the `impl Trait`s in `root` into opaque types, with one slot in
+    /// the opaque type data for each of them, whether it gets lowered or not.
+    pub(crate) fn with_opaque_type_root(self, root: &'a TypeRef) -> Self {
+        let opaque_type_data = (0..count_impl_traits(root))
+            .map(|_| ReturnTypeImplTrait { bounds: Binders::new(1, Vec::new()) })
+            .collect();
+        Self {
+            impl_trait_mode: ImplTraitLoweringMode::Opaque,
+            opaque_type_root: Some(root),
+            opaque_type_data: std::cell::RefCell::new(opaque_type_data),
+            ..self
+        }
+    }
+
+    /// Takes the generic argument count mismatches found while lowering with
+    /// this context so far.
+    pub(crate) fn take_generic_args_mismatches(&self) -> Vec<GenericArgsMismatch> {
+        self.generic_args_mismatches.replace(Vec::new())
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -147,8 +205,8 @@ impl Ty {
         let ty = match type_ref {
             TypeRef::Never => Ty::Never,
             TypeRef::Tuple(inner) => {
-                let inner_tys: Arc<[Ty]> = inner.iter().map(|tr| Ty::from_hir(ctx, tr)).collect();
-                Ty::Tuple(inner_tys.len(), Substs(inner_tys))
+                let inner_tys: Vec<Ty> = inner.iter().map(|tr| Ty::from_hir(ctx, tr)).collect();
+                Ty::Tuple(inner_tys.len(), Substs(inner_tys.into()))
             }
             TypeRef::Path(path) => {
                 let (ty, res_) = Ty::from_hir_path(ctx, path);
@@ -188,21 +246,18 @@ impl Ty {
                         .flat_map(|b| GenericPredicate::from_type_bound(ctx, b, self_ty.clone()))
                         .collect()
                 });
-                Ty::Dyn(predicates)
+                Ty::Dyn(sort_dyn_predicates(ctx.db, predicates).into())
             }
             TypeRef::ImplTrait(bounds) => {
                 match ctx.impl_trait_mode {
                     ImplTraitLoweringMode::Opaque => {
-                        let idx = ctx.impl_trait_counter.get();
-                        ctx.impl_trait_counter.set(idx + 1);
-
-                        assert!(idx as usize == ctx.opaque_type_data.borrow().len());
-                        // this dance is to make sure the data is in the right
-                        // place even if we encounter more opaque types while
-                        // lowering the bounds
-                        ctx.opaque_type_data
-                            .borrow_mut()
-                            .push(ReturnTypeImplTrait { bounds: Binders::new(1, Vec::new()) });
+                        let idx = match ctx
+                            .opaque_type_root
+                            .and_then(|root| impl_trait_position(root, type_ref))
+                        {
+                            Some(idx) => idx,
+                            None => return (Ty::Unknown, None),
+                        };
                         // We don't want to lower the bounds inside the binders
                         // we're currently in, because they don't end up inside
                         // those binders. E.g. when we have `impl Trait<impl
@@ -358,7 +413,7 @@ impl Ty {
                     // FIXME report error (ambiguous associated type)
                     Ty::Unknown
                 } else {
-                    Ty::Dyn(Arc::new([GenericPredicate::Implemented(trait_ref)]))
+                    Ty::Dyn(vec![GenericPredicate::Implemented(trait_ref)].into())
                 };
                 return (ty, None);
             }
@@ -461,7 +516,7 @@ impl Ty {
                                         "there should be generics if there's a generic param",
                                     ),
                                 );
-                                t.substs.clone().subst_bound_vars(&s)
+                                t.substs.apply(&s)
                             }
                             TypeParamLoweringMode::Variable => t.substs.clone(),
                         };
@@ -495,7 +550,7 @@ impl Ty {
             TyDefId::AdtId(it) => Some(it.into()),
             TyDefId::TypeAliasId(it) => Some(it.into()),
         };
-        let substs = substs_from_path_segment(ctx, segment, generic_def, infer_args);
+        let substs = substs_from_path_segment(ctx, segment, generic_def, infer_args, None);
         ctx.db.ty(typeable).subst(&substs)
     }
 
@@ -532,7 +587,7 @@ impl Ty {
                 (segment, Some(var.parent.into()))
             }
         };
-        substs_from_path_segment(ctx, segment, generic_def, infer_args)
+        substs_from_path_segment(ctx, segment, generic_def, infer_args, None)
     }
 }
 
@@ -541,25 +596,58 @@ fn substs_from_path_segment(
     segment: PathSegment<'_>,
     def_generic: Option<GenericDefId>,
     infer_args: bool,
+    explicit_self_ty: Option<Ty>,
 ) -> Substs {
     let mut substs = Vec::new();
     let def_generics = def_generic.map(|def| generics(ctx.db.upcast(), def));
 
     let (parent_params, self_params, type_params, impl_trait_params) =
-        def_generics.map_or((0, 0, 0, 0), |g| g.provenance_split());
+        def_generics.as_ref().map_or((0, 0, 0, 0), |g| g.provenance_split());
     let total_len = parent_params + self_params + type_params + impl_trait_params;
 
     substs.extend(iter::repeat(Ty::Unknown).take(parent_params));
 
     let mut had_explicit_type_args = false;
 
+    // the self type has to be known before the defaults get filled in, as they
+    // may refer to it, like `Rhs = Self` in `PartialEq`
+    let fill_self_params = |substs: &mut Vec<Ty>| match &explicit_self_ty {
+        Some(self_ty) if self_params == 1 => substs.push(self_ty.clone()),
+        _ => substs.extend(iter::repeat(Ty::Unknown).take(self_params)),
+    };
     if let Some(generic_args) = &segment.args_and_bindings {
         if !generic_args.has_self_type {
-            substs.extend(iter::repeat(Ty::Unknown).take(self_params));
+            fill_self_params(&mut substs);
         }
         let expected_num =
             if generic_args.has_self_type { self_params + type_params } else { type_params };
         let skip = if generic_args.has_self_type && self_params == 0 { 1 } else { 0 };
+        if let Some(def_generics) = &def_generics {
+            let provided = generic_args
+                .args
+                .iter()
+                .filter(|arg| matches!(arg, GenericArg::Type(_)))
+                .count()
+                .saturating_sub(skip);
+            let defaults = def_generics
+                .params
+                .types
+                .iter()
+                .filter(|(_, p)| {
+                    p.provenance == TypeParamProvenance::TypeParamList && p.default.is_some()
+                })
+                .count();
+            // With only lifetimes given, the type arguments may still be inferred.
+            let too_few = provided + defaults < expected_num && (provided > 0 || !infer_args);
+            if provided > expected_num || too_few {
+                ctx.generic_args_mismatches.borrow_mut().push(GenericArgsMismatch {
+                    segment: segment.name.clone(),
+                    expected: expected_num,
+                    provided,
+                    defaults,
+                });
+            }
+        }
         // if args are provided, it should be all of them, but we can't rely on that
         for arg in generic_args
             .args
@@ -577,32 +665,24 @@ fn substs_from_path_segment(
                 GenericArg::Lifetime(_) => {}
             }
         }
+    } else if explicit_self_ty.is_some() {
+        fill_self_params(&mut substs);
     }
 
+    let builder = substs.into_iter().fold(Substs::builder(total_len), SubstsBuilder::push);
+
     // handle defaults. In expression or pattern path segments without
     // explicitly specified type arguments, missing type arguments are inferred
     // (i.e. defaults aren't used).
     if !infer_args || had_explicit_type_args {
         if let Some(def_generic) = def_generic {
-            let defaults = ctx.db.generic_defaults(def_generic);
-            assert_eq!(total_len, defaults.len());
-
-            for default_ty in defaults.iter().skip(substs.len()) {
-                // each default can depend on the previous parameters
-                let substs_so_far = Substs(substs.clone().into());
-                substs.push(default_ty.clone().subst(&substs_so_far));
-            }
+            return builder.fill_with_defaults(ctx.db, def_generic).build();
         }
     }
 
     // add placeholders for args that were not provided
     // FIXME: emit diagnostics in contexts where this is not allowed
-    for _ in substs.len()..total_len {
-        substs.push(Ty::Unknown);
-    }
-    assert_eq!(substs.len(), total_len);
-
-    Substs(substs.into())
+    builder.fill_with_unknown().build()
 }
 
 impl TraitRef {
@@ -626,10 +706,7 @@ impl TraitRef {
         segment: PathSegment<'_>,
         explicit_self_ty: Option<Ty>,
     ) -> Self {
-        let mut substs = TraitRef::substs_from_path(ctx, segment, resolved);
-        if let Some(self_ty) = explicit_self_ty {
-            make_mut_slice(&mut substs.0)[0] = self_ty;
-        }
+        let substs = TraitRef::substs_from_path(ctx, segment, resolved, explicit_self_ty);
         TraitRef { trait_: resolved, substs }
     }
 
@@ -649,8 +726,9 @@ impl TraitRef {
         ctx: &TyLoweringContext<'_>,
         segment: PathSegment<'_>,
         resolved: TraitId,
+        explicit_self_ty: Option<Ty>,
     ) -> Substs {
-        substs_from_path_segment(ctx, segment, Some(resolved.into()), false)
+        substs_from_path_segment(ctx, segment, Some(resolved.into()), false, explicit_self_ty)
     }
 }
 
@@ -678,7 +756,12 @@ impl GenericPredicate {
                         }
                     }
                 };
+                let num_lifetimes = match where_predicate {
+                    WherePredicate::ForLifetime { lifetimes, .. } => lifetimes.len(),
+                    _ => 0,
+                };
                 GenericPredicate::from_type_bound(ctx, bound, self_ty)
+                    .map(|pred| GenericPredicate::for_lifetimes(num_lifetimes, pred))
                     .collect::<Vec<_>>()
                     .into_iter()
             }
@@ -692,8 +775,12 @@ impl GenericPredicate {
         self_ty: Ty,
     ) -> impl Iterator<Item = GenericPredicate> + 'a {
         let mut bindings = None;
+        let num_lifetimes = match bound {
+            TypeBound::ForLifetime(lifetimes, _) => lifetimes.len(),
+            _ => 0,
+        };
         let trait_ref = match bound {
-            TypeBound::Path(path) => {
+            TypeBound::Path(path) | TypeBound::ForLifetime(_, path) => {
                 bindings = TraitRef::from_path(ctx, path, Some(self_ty));
                 Some(
                     bindings.clone().map_or(GenericPredicate::Error, GenericPredicate::Implemented),
@@ -702,11 +789,14 @@ impl GenericPredicate {
             TypeBound::Lifetime(_) => None,
             TypeBound::Error => Some(GenericPredicate::Error),
         };
-        trait_ref.into_iter().chain(
-            bindings
-                .into_iter()
-                .flat_map(move |tr| assoc_type_bindings_from_type_bound(ctx, bound, tr)),
-        )
+        trait_ref
+            .into_iter()
+            .chain(
+                bindings
+                    .into_iter()
+                    .flat_map(move |tr| assoc_type_bindings_from_type_bound(ctx, bound, tr)),
+            )
+            .map(move |pred| GenericPredicate::for_lifetimes(num_lifetimes, pred))
     }
 }
 
@@ -716,7 +806,7 @@ fn assoc_type_bindings_from_type_bound<'a>(
     trait_ref: TraitRef,
 ) -> impl Iterator<Item = GenericPredicate> + 'a {
     let last_segment = match bound {
-        TypeBound::Path(path) => path.segments().last(),
+        TypeBound::Path(path) | TypeBound::ForLifetime(_, path) => path.segments().last(),
         TypeBound::Error | TypeBound::Lifetime(_) => None,
     };
     last_segment
@@ -768,6 +858,24 @@ impl ReturnTypeImplTrait {
     }
 }
 
+/// The index of `impl_trait` among the `impl Trait`s in `root`, in pre-order.
+/// This only depends on the syntax, not on which parts of `root` we end up
+/// lowering (e.g. the arguments of a path that doesn't resolve aren't), so the
+/// IDs of opaque types stay the same when name resolution changes.
+fn impl_trait_position(root: &TypeRef, impl_trait: &TypeRef) -> Option<u16> {
+    let mut count = 0;
+    let mut position = None;
+    root.walk(&mut |type_ref| {
+        if let TypeRef::ImplTrait(_) = type_ref {
+            if position.is_none() && std::ptr::eq(type_ref, impl_trait) {
+                position = Some(count);
+            }
+            count += 1;
+        }
+    });
+    position
+}
+
 fn count_impl_traits(type_ref: &TypeRef) -> usize {
     let mut count = 0;
     type_ref.walk(&mut |type_ref| {
@@ -778,6 +886,19 @@ fn count_impl_traits(type_ref: &TypeRef) -> usize {
     count
 }
 
+/// Lowers `type_ref` to find the paths in it with the wrong number of generic
+/// arguments. The item queries don't keep these around, so the item
+/// diagnostics lower the types from their signatures again.
+pub(crate) fn generic_args_mismatches(
+    db: &dyn HirDatabase,
+    resolver: &Resolver,
+    type_ref: &TypeRef,
+) -> Vec<GenericArgsMismatch> {
+    let ctx = TyLoweringContext::new(db, resolver);
+    Ty::from_hir(&ctx, type_ref);
+    ctx.take_generic_args_mismatches()
+}
+
 /// Build the signature of a callable item (function, struct or enum variant).
 pub fn callable_item_sig(db: &dyn HirDatabase, def: CallableDefId) -> PolyFnSig {
     match def {
@@ -801,12 +922,14 @@ pub fn associated_type_shorthand_candidates<R>(
             let predicates = db.generic_predicates_for_param(param_id);
             let mut traits_: Vec<_> = predicates
                 .iter()
-                .filter_map(|pred| match &pred.value {
+                .filter_map(|pred| match pred.value.skip_binders() {
                     GenericPredicate::Implemented(tr) => Some(tr.clone()),
                     _ => None,
                 })
                 .collect();
-            // Handle `Self::Type` referring to own associated type in trait definitions
+            // Handle `Self::Type` referring to own associated type in trait definitions.
+            // The trait's own associated types take precedence over the ones of
+            // its supertraits, so it needs to come first.
             if let GenericDefId::TraitId(trait_id) = param_id.parent {
                 let generics = generics(db.upcast(), trait_id.into());
                 if generics.params.types[param_id.local_id].provenance
@@ -816,7 +939,7 @@ pub fn associated_type_shorthand_candidates<R>(
                         trait_: trait_id,
                         substs: Substs::bound_vars(&generics, DebruijnIndex::INNERMOST),
                     };
-                    traits_.push(trait_ref);
+                    traits_.insert(0, trait_ref);
                 }
             }
             traits_
@@ -879,7 +1002,7 @@ pub(crate) fn generic_predicates_for_param_query(
     let ctx =
         TyLoweringContext::new(db, &resolver).with_type_param_mode(TypeParamLoweringMode::Variable);
     let generics = generics(db.upcast(), param_id.parent);
-    resolver
+    let predicates = resolver
         .where_predicates_in_scope()
         // we have to filter out all other predicates *first*, before attempting to lower them
         .filter(|pred| match pred {
@@ -891,11 +1014,10 @@ pub(crate) fn generic_predicates_for_param_query(
                 WherePredicateTypeTarget::TypeParam(local_id) => *local_id == param_id.local_id,
             },
             WherePredicate::Lifetime { .. } => false,
-        })
-        .flat_map(|pred| {
-            GenericPredicate::from_where_predicate(&ctx, pred)
-                .map(|p| Binders::new(generics.len(), p))
-        })
+        });
+    ctx.lower_generic_predicate_list(predicates)
+        .into_iter()
+        .map(|p| Binders::new(generics.len(), p))
         .collect()
 }
 
@@ -907,43 +1029,113 @@ pub(crate) fn generic_predicates_for_param_recover(
     Arc::new([])
 }
 
-impl TraitEnvironment {
-    pub fn lower(db: &dyn HirDatabase, resolver: &Resolver) -> Arc<TraitEnvironment> {
-        let ctx = TyLoweringContext::new(db, &resolver)
-            .with_type_param_mode(TypeParamLoweringMode::Placeholder);
-        let mut predicates = resolver
-            .where_predicates_in_scope()
-            .flat_map(|pred| GenericPredicate::from_where_predicate(&ctx, pred))
-            .collect::<Vec<_>>();
-
-        if let Some(def) = resolver.generic_def() {
-            let container: Option<AssocContainerId> = match def {
-                // FIXME: is there a function for this?
-                GenericDefId::FunctionId(f) => Some(f.lookup(db.upcast()).container),
-                GenericDefId::AdtId(_) => None,
-                GenericDefId::TraitId(_) => None,
-                GenericDefId::TypeAliasId(t) => Some(t.lookup(db.upcast()).container),
-                GenericDefId::ImplId(_) => None,
-                GenericDefId::EnumVariantId(_) => None,
-                GenericDefId::ConstId(c) => Some(c.lookup(db.upcast()).container),
+/// The bounds on a type parameter together with the ones they imply through
+/// supertraits: for `T: Copy` where `trait Copy: Clone`, that's `T: Copy` and
+/// `T: Clone`. Associated type bounds of supertraits, like the one of
+/// `trait Sub: Iterator<Item = u8>`, are included as well.
+pub(crate) fn all_transitive_bounds_query(
+    db: &dyn HirDatabase,
+    param_id: TypeParamId,
+) -> Arc<[Binders<GenericPredicate>]> {
+    let mut bounds = Vec::new();
+    let mut push = |bound: Binders<GenericPredicate>| {
+        if !bounds.contains(&bound) {
+            bounds.push(bound);
+        }
+    };
+    for pred in db.generic_predicates_for_param(param_id).iter() {
+        let num_lifetimes = match &pred.value {
+            GenericPredicate::ForLifetimes(n, _) => *n,
+            _ => 0,
+        };
+        let trait_ref = match pred.value.skip_binders() {
+            GenericPredicate::Implemented(trait_ref) => trait_ref.clone(),
+            _ => {
+                push(pred.clone());
+                continue;
+            }
+        };
+        // this includes the trait itself, and only visits each trait once,
+        // which keeps cycles like `trait A: B {} trait B: A {}` finite
+        for trait_ref in all_super_trait_refs(db, trait_ref) {
+            let trait_generics = db.generic_params(trait_ref.trait_.into());
+            let projection_bounds = match trait_generics.find_trait_self_param() {
+                Some(local_id) => {
+                    let trait_self = TypeParamId { parent: trait_ref.trait_.into(), local_id };
+                    db.generic_predicates_for_param(trait_self)
+                }
+                None => Arc::new([]),
             };
-            if let Some(AssocContainerId::TraitId(trait_id)) = container {
-                // add `Self: Trait<T1, T2, ...>` to the environment in trait
-                // function default implementations (and hypothetical code
-                // inside consts or type aliases)
-                test_utils::mark::hit!(trait_self_implements_self);
-                let substs = Substs::type_params(db, trait_id);
-                let trait_ref = TraitRef { trait_: trait_id, substs };
-                let pred = GenericPredicate::Implemented(trait_ref);
-
-                predicates.push(pred);
+            let substs = trait_ref.substs.clone();
+            push(Binders::new(
+                pred.num_binders,
+                GenericPredicate::for_lifetimes(
+                    num_lifetimes,
+                    GenericPredicate::Implemented(trait_ref),
+                ),
+            ));
+            for bound in projection_bounds.iter() {
+                if let GenericPredicate::Projection(_) = bound.value.skip_binders() {
+                    let bound = bound.clone().subst(&substs);
+                    push(Binders::new(
+                        pred.num_binders,
+                        GenericPredicate::for_lifetimes(num_lifetimes, bound),
+                    ));
+                }
             }
         }
+    }
+    bounds.into()
+}
 
-        Arc::new(TraitEnvironment { predicates })
+impl TraitEnvironment {
+    pub fn lower(db: &dyn HirDatabase, resolver: &Resolver) -> Arc<TraitEnvironment> {
+        match resolver.generic_def() {
+            Some(def) => db.trait_environment(def),
+            None => Arc::new(TraitEnvironment { predicates: Vec::new() }),
+        }
     }
 }
 
+/// The clauses we can assume inside `def`: the where clauses of `def` and of
+/// all its generic parents (like the impl of a method), with the type
+/// parameters of every level replaced by placeholders.
+pub(crate) fn trait_environment_query(
+    db: &dyn HirDatabase,
+    def: GenericDefId,
+) -> Arc<TraitEnvironment> {
+    let substs = Substs::type_params(db, def);
+    let mut predicates = db
+        .generic_predicates(def)
+        .iter()
+        .map(|pred| pred.clone().subst(&substs))
+        .collect::<Vec<_>>();
+
+    let container: Option<AssocContainerId> = match def {
+        // FIXME: is there a function for this?
+        GenericDefId::FunctionId(f) => Some(f.lookup(db.upcast()).container),
+        GenericDefId::AdtId(_) => None,
+        GenericDefId::TraitId(_) => None,
+        GenericDefId::TypeAliasId(t) => Some(t.lookup(db.upcast()).container),
+        GenericDefId::ImplId(_) => None,
+        GenericDefId::EnumVariantId(_) => None,
+        GenericDefId::ConstId(c) => Some(c.lookup(db.upcast()).container),
+    };
+    if let Some(AssocContainerId::TraitId(trait_id)) = container {
+        // add `Self: Trait<T1, T2, ...>` to the environment in trait
+        // function default implementations (and hypothetical code
+        // inside consts or type aliases)
+        test_utils::mark::hit!(trait_self_implements_self);
+        let substs = Substs::type_params(db, trait_id);
+        let trait_ref = TraitRef { trait_: trait_id, substs };
+        let pred = GenericPredicate::Implemented(trait_ref);
+
+        predicates.push(pred);
+    }
+
+    Arc::new(TraitEnvironment { predicates })
+}
+
 /// Resolve the where clause(s) of an item with generics.
 pub(crate) fn generic_predicates_query(
     db: &dyn HirDatabase,
@@ -953,12 +1145,9 @@ pub(crate) fn generic_predicates_query(
     let ctx =
         TyLoweringContext::new(db, &resolver).with_type_param_mode(TypeParamLoweringMode::Variable);
     let generics = generics(db.upcast(), def);
-    resolver
-        .where_predicates_in_scope()
-        .flat_map(|pred| {
-            GenericPredicate::from_where_predicate(&ctx, pred)
-                .map(|p| Binders::new(generics.len(), p))
-        })
+    ctx.lower_generic_predicate_list(resolver.where_predicates_in_scope())
+        .into_iter()
+        .map(|p| Binders::new(generics.len(), p))
         .collect()
 }
 
@@ -1001,6 +1190,17 @@ pub(crate) fn generic_defaults_query(
     defaults
 }
 
+pub(crate) fn generic_defaults_substs_query(db: &dyn HirDatabase, def: GenericDefId) -> Substs {
+    let defaults = db.generic_defaults(def);
+    let mut substs = Vec::with_capacity(defaults.len());
+    for default_ty in defaults.iter() {
+        // each default can depend on the previous parameters
+        let substs_so_far = Substs(substs.clone().into());
+        substs.push(default_ty.clone().subst(&substs_so_far));
+    }
+    Substs(substs.into())
+}
+
 fn fn_sig_for_fn(db: &dyn HirDatabase, def: FunctionId) -> PolyFnSig {
     let data = db.function_data(def);
     let resolver = def.resolver(db.upcast());
@@ -1009,12 +1209,94 @@ fn fn_sig_for_fn(db: &dyn HirDatabase, def: FunctionId) -> PolyFnSig {
         .with_type_param_mode(TypeParamLoweringMode::Variable);
     let params = data.params.iter().map(|tr| Ty::from_hir(&ctx_params, tr)).collect::<Vec<_>>();
     let ctx_ret = TyLoweringContext::new(db, &resolver)
-        .with_impl_trait_mode(ImplTraitLoweringMode::Opaque)
+        .with_opaque_type_root(&data.ret_type)
         .with_type_param_mode(TypeParamLoweringMode::Variable);
     let ret = Ty::from_hir(&ctx_ret, &data.ret_type);
+    let mut sig = CallableSig::from_params_and_return(params, ret, data.is_varargs);
+    if let AssocContainerId::ImplId(impl_id) = def.lookup(db.upcast()).container {
+        sig = normalize_own_projections(db, impl_id, sig);
+    }
     let generics = generics(db.upcast(), def.into());
     let num_binders = generics.len();
-    Binders::new(num_binders, CallableSig::from_params_and_return(params, ret, data.is_varargs))
+    Binders::new(num_binders, sig)
+}
+
+/// Replaces the projections to associated types the impl itself defines, like
+/// `Self::Item` in `impl Iterator for Counter { type Item = u32; .. }`, by the
+/// types the impl assigns to them. `t` has to be bound by the impl's generic
+/// parameters first, as the signatures of its items are. Projections that
+/// (indirectly) refer to themselves are left as they are.
+fn normalize_own_projections<T: TypeWalk>(db: &dyn HirDatabase, impl_id: ImplId, t: T) -> T {
+    let trait_ref = match db.impl_trait(impl_id) {
+        Some(trait_ref) => trait_ref,
+        None => return t,
+    };
+    let mut visiting = Vec::new();
+    t.fold(&mut |ty| normalize_own_projection(db, impl_id, &trait_ref, ty, &mut visiting))
+}
+
+fn normalize_own_projection(
+    db: &dyn HirDatabase,
+    impl_id: ImplId,
+    trait_ref: &Binders<TraitRef>,
+    ty: Ty,
+    visiting: &mut Vec<TypeAliasId>,
+) -> Ty {
+    let projection = match &ty {
+        Ty::Projection(projection) => projection,
+        _ => return ty,
+    };
+    if visiting.contains(&projection.associated_ty) || projection.trait_ref(db) != trait_ref.value {
+        return ty;
+    }
+    let assoc_ty = db.impl_assoc_ty(impl_id, projection.associated_ty);
+    // generic associated types would need their own arguments substituted
+    if assoc_ty.num_binders != trait_ref.num_binders || assoc_ty.value == Ty::Unknown {
+        return ty;
+    }
+    visiting.push(projection.associated_ty);
+    let normalized = assoc_ty
+        .value
+        .fold(&mut |ty| normalize_own_projection(db, impl_id, trait_ref, ty, visiting));
+    visiting.pop();
+    normalized
+}
+
+/// Normalizes a projection on an associated type placeholder using the bounds
+/// of that associated type, e.g. `<<T as Node>::Child as Node>::Parent` to `T`
+/// given `type Child: Node<Parent = Self>`. `Self` in the bounds is the trait's
+/// `Self`, not the associated type the bounds are on.
+pub(crate) fn normalize_by_assoc_type_bounds(
+    db: &dyn HirDatabase,
+    projection: &ProjectionTy,
+) -> Option<Ty> {
+    let (type_alias, alias_substs) = match &projection.parameters[0] {
+        Ty::AssociatedType(type_alias, substs) => (*type_alias, substs),
+        _ => return None,
+    };
+    let resolver = type_alias.resolver(db.upcast());
+    let ctx =
+        TyLoweringContext::new(db, &resolver).with_type_param_mode(TypeParamLoweringMode::Variable);
+    let generics = generics(db.upcast(), type_alias.into());
+    let self_ty =
+        Ty::AssociatedType(type_alias, Substs::bound_vars(&generics, DebruijnIndex::INNERMOST));
+    let type_alias_data = db.type_alias_data(type_alias);
+    let normalized = type_alias_data
+        .bounds
+        .iter()
+        .flat_map(|bound| GenericPredicate::from_type_bound(&ctx, bound, self_ty.clone()))
+        .find_map(|pred| match pred.into_skip_binders() {
+            GenericPredicate::Projection(pred) => {
+                let pred = Binders::new(generics.len(), pred).subst(alias_substs);
+                if pred.projection_ty == *projection {
+                    Some(pred.ty)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        });
+    normalized
 }
 
 /// Build the declared type of a function. This should not need to look at the
@@ -1235,6 +1517,25 @@ pub(crate) fn impl_trait_query(db: &dyn HirDatabase, impl_id: ImplId) -> Option<
     ))
 }
 
+/// The type an impl assigns to the associated type `assoc` of the implemented
+/// trait, bound by the impl's generic parameters. `assoc` may be given either
+/// as the trait's associated type or as the impl's type alias itself.
+pub(crate) fn impl_assoc_ty_query(
+    db: &dyn HirDatabase,
+    impl_id: ImplId,
+    assoc: TypeAliasId,
+) -> Binders<Ty> {
+    let name = &db.type_alias_data(assoc).name;
+    let alias = db.impl_data(impl_id).items.iter().find_map(|item| match item {
+        AssocItemId::TypeAliasId(alias) if db.type_alias_data(*alias).name == *name => Some(*alias),
+        _ => None,
+    });
+    match alias {
+        Some(alias) => db.ty(alias.into()),
+        None => Binders::new(db.impl_self_ty(impl_id).num_binders, Ty::Unknown),
+    }
+}
+
 pub(crate) fn return_type_impl_traits(
     db: &dyn HirDatabase,
     def: hir_def::FunctionId,
@@ -1243,7 +1544,7 @@ pub(crate) fn return_type_impl_traits(
     let data = db.function_data(def);
     let resolver = def.resolver(db.upcast());
     let ctx_ret = TyLoweringContext::new(db, &resolver)
-        .with_impl_trait_mode(ImplTraitLoweringMode::Opaque)
+        .with_opaque_type_root(&data.ret_type)
         .with_type_param_mode(TypeParamLoweringMode::Variable);
     let _ret = Ty::from_hir(&ctx_ret, &data.ret_type);
     let generics = generics(db.upcast(), def.into());
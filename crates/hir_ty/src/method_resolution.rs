@@ -2,13 +2,14 @@
 //! For details about how this works in rustc, see the method lookup page in the
 //! [rustc guide](https://rust-lang.github.io/rustc-guide/method-lookup.html)
 //! and the corresponding code mostly in librustc_typeck/check/method/probe.rs.
-use std::{iter, sync::Arc};
+use std::{cell::Cell, cmp::Ordering, iter, sync::Arc};
 
 use arrayvec::ArrayVec;
 use base_db::CrateId;
 use hir_def::{
-    lang_item::LangItemTarget, type_ref::Mutability, AdtId, AssocContainerId, AssocItemId,
-    FunctionId, GenericDefId, HasModule, ImplId, Lookup, ModuleId, TraitId, TypeAliasId,
+    lang_item::LangItemTarget, resolver::HasResolver, type_ref::Mutability, AdtId,
+    AssocContainerId, AssocItemId, FieldId, FunctionId, GenericDefId, HasModule, ImplId, Lookup,
+    ModuleId, TraitId, TypeAliasId, VariantId,
 };
 use hir_expand::name::Name;
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -17,9 +18,10 @@ use crate::{
     autoderef,
     db::HirDatabase,
     primitive::{self, FloatTy, IntTy, UintTy},
-    utils::all_super_traits,
-    Canonical, DebruijnIndex, FnPointer, FnSig, InEnvironment, Scalar, Substs, TraitEnvironment,
-    TraitRef, Ty, TypeWalk,
+    traits::{check_implements, Implements},
+    utils::{all_super_traits, is_fundamental, variant_data},
+    Canonical, DebruijnIndex, FnPointer, FnSig, GenericPredicate, InEnvironment, ProjectionTy,
+    Scalar, Substs, TraitEnvironment, TraitRef, Ty, TypeWalk,
 };
 
 /// This is used as a key for indexing impls.
@@ -127,6 +129,31 @@ impl TraitImpls {
         Arc::new(res)
     }
 
+    pub(crate) fn relevant_impls_for_trait_query(
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        trait_: TraitId,
+    ) -> Arc<Self> {
+        let _p = profile::span("relevant_impls_for_trait_query");
+        let crate_graph = db.crate_graph();
+        let mut res = Self { map: FxHashMap::default() };
+
+        for krate in crate_graph.transitive_deps(krate).chain(iter::once(krate)) {
+            let crate_impls = db.trait_impls_in_crate(krate);
+            let other_map = match crate_impls.map.get(&trait_) {
+                Some(it) => it,
+                None => continue,
+            };
+            let map = res.map.entry(trait_).or_default();
+            for (fp, impls) in other_map {
+                let vec = map.entry(*fp).or_default();
+                vec.extend(impls.iter().filter(|&&impl_id| is_orphan_allowed(db, krate, impl_id)));
+            }
+        }
+
+        Arc::new(res)
+    }
+
     fn merge(&mut self, other: &Self) {
         for (trait_, other_map) in &other.map {
             let map = self.map.entry(*trait_).or_default();
@@ -158,11 +185,58 @@ impl TraitImpls {
             .flat_map(|v| v.iter().copied())
     }
 
+    /// Queries all impls of any trait whose self type has the fingerprint
+    /// `self_ty`. Blanket impls are not included.
+    pub fn for_self_ty(&self, self_ty: TyFingerprint) -> impl Iterator<Item = ImplId> + '_ {
+        self.map
+            .values()
+            .flat_map(move |map| map.get(&Some(self_ty)))
+            .flat_map(|v| v.iter().copied())
+    }
+
+    /// Queries all impls of any trait whose self type is a type parameter (like
+    /// `impl<T> Trait for T`), i.e. impls that may apply to any type.
+    pub fn blanket_impls(&self) -> impl Iterator<Item = ImplId> + '_ {
+        self.map.values().flat_map(|map| map.get(&None)).flat_map(|v| v.iter().copied())
+    }
+
     pub fn all_impls(&self) -> impl Iterator<Item = ImplId> + '_ {
         self.map.values().flat_map(|map| map.values().flat_map(|v| v.iter().copied()))
     }
 }
 
+/// Checks whether the trait impl `impl_id` in `krate` is allowed by the orphan
+/// rules: either the trait is defined in `krate`, or one of the types in the
+/// trait ref is. Other impls can't apply, and only exist in broken code.
+fn is_orphan_allowed(db: &dyn HirDatabase, krate: CrateId, impl_id: ImplId) -> bool {
+    let trait_ref = match db.impl_trait(impl_id) {
+        Some(it) => it.value,
+        None => return false,
+    };
+    if trait_ref.trait_.lookup(db.upcast()).container.module(db.upcast()).krate() == krate {
+        return true;
+    }
+    trait_ref.substs.iter().any(|ty| is_local_ty(db, krate, ty))
+}
+
+fn is_local_ty(db: &dyn HirDatabase, krate: CrateId, ty: &Ty) -> bool {
+    match ty {
+        Ty::Adt(adt, substs) => {
+            adt.module(db.upcast()).krate() == krate
+                || (is_fundamental(db.upcast(), *adt)
+                    && substs.iter().any(|ty| is_local_ty(db, krate, ty)))
+        }
+        Ty::Ref(_, substs) => is_local_ty(db, krate, substs.as_single()),
+        Ty::ForeignType(alias) => {
+            alias.lookup(db.upcast()).container.module(db.upcast()).krate() == krate
+        }
+        Ty::Dyn(_) => ty.dyn_trait().map_or(false, |trait_| {
+            trait_.lookup(db.upcast()).container.module(db.upcast()).krate() == krate
+        }),
+        _ => false,
+    }
+}
+
 /// Inherent impls defined in some crate.
 ///
 /// Inherent impls can only be defined in the crate that also defines the self type of the impl
@@ -209,6 +283,60 @@ impl InherentImpls {
     }
 }
 
+/// Returns all inherent and trait impls visible from `krate` (i.e. defined in
+/// `krate` or one of its dependencies) whose self type could match `ty`.
+///
+/// Blanket impls like `impl<T> Trait for T` potentially apply to every type;
+/// they are only included if `include_blanket_impls` is set. The result is
+/// sorted, so it doesn't depend on the order in which crates were visited.
+pub fn all_impls_for_ty(
+    db: &dyn HirDatabase,
+    ty: &Ty,
+    krate: CrateId,
+    include_blanket_impls: bool,
+) -> Vec<ImplId> {
+    let fp = TyFingerprint::for_impl(ty);
+    let crate_graph = db.crate_graph();
+    let mut res = Vec::new();
+
+    for krate in crate_graph.transitive_deps(krate).chain(iter::once(krate)) {
+        if fp.is_some() {
+            res.extend_from_slice(db.inherent_impls_in_crate(krate).for_self_ty(ty));
+        }
+        let trait_impls = db.trait_impls_in_crate(krate);
+        if let Some(fp) = fp {
+            res.extend(trait_impls.for_self_ty(fp));
+        }
+        if include_blanket_impls {
+            res.extend(trait_impls.blanket_impls());
+        }
+    }
+
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// Returns all impls of `trait_` in the crate defining it and in all crates
+/// depending on that one, i.e. all crates that can see the trait. Like
+/// `all_impls_for_ty`, the result is sorted.
+pub(crate) fn all_impls_of_trait_query(db: &dyn HirDatabase, trait_: TraitId) -> Vec<ImplId> {
+    let _p = profile::span("all_impls_of_trait_query");
+    let trait_krate = trait_.lookup(db.upcast()).container.module(db.upcast()).krate();
+    let crate_graph = db.crate_graph();
+    let mut res = Vec::new();
+
+    for krate in crate_graph.iter() {
+        if krate != trait_krate && !crate_graph.transitive_deps(krate).any(|it| it == trait_krate) {
+            continue;
+        }
+        res.extend(db.trait_impls_in_crate(krate).for_trait(trait_));
+    }
+
+    res.sort();
+    res
+}
+
 impl Ty {
     pub fn def_crates(
         &self,
@@ -273,7 +401,8 @@ impl Ty {
 }
 
 /// Look up the method with the given name, returning the actual autoderefed
-/// receiver type (but without autoref applied yet).
+/// receiver type (but without autoref applied yet), along with the adjustments
+/// the receiver needs to match the method's `self` parameter.
 pub(crate) fn lookup_method(
     ty: &Canonical<Ty>,
     db: &dyn HirDatabase,
@@ -281,22 +410,137 @@ pub(crate) fn lookup_method(
     krate: CrateId,
     traits_in_scope: &FxHashSet<TraitId>,
     name: &Name,
-) -> Option<(Ty, FunctionId)> {
-    iterate_method_candidates(
+) -> Option<(Ty, FunctionId, ReceiverAdjustments)> {
+    let ctx = MethodLookupCtx::new(db, env, krate, traits_in_scope, Some(name));
+    iterate_method_candidates_with_adjustments(
         ty,
-        db,
-        env,
-        krate,
-        &traits_in_scope,
-        Some(name),
+        &ctx,
         LookupMode::MethodCall,
-        |ty, f| match f {
-            AssocItemId::FunctionId(f) => Some((ty.clone(), f)),
+        |ty, f, adjustments| match f {
+            AssocItemId::FunctionId(f) => Some((ty.clone(), f, adjustments)),
             _ => None,
         },
     )
 }
 
+/// Explains why a method call couldn't be resolved: finds a trait in scope
+/// that has a method `name`, but isn't implemented for (any autoderef step of)
+/// the receiver type `ty`. The trait's parameters other than `Self` are left
+/// unknown.
+pub fn unsatisfied_method_bound(
+    ty: &Canonical<Ty>,
+    db: &dyn HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    traits_in_scope: &FxHashSet<TraitId>,
+    name: &Name,
+) -> Option<TraitRef> {
+    let mut candidate_traits: Vec<_> = traits_in_scope
+        .iter()
+        .copied()
+        .filter(|&t| {
+            db.trait_data(t).items.iter().any(|(item_name, item)| match *item {
+                AssocItemId::FunctionId(f) => {
+                    item_name == name && db.function_data(f).has_self_param
+                }
+                _ => false,
+            })
+        })
+        .collect();
+    if candidate_traits.is_empty() {
+        return None;
+    }
+    // `traits_in_scope` is unordered; make the choice deterministic
+    candidate_traits.sort_by_key(|&t| db.trait_data(t).name.to_string());
+
+    let ty = InEnvironment { value: ty.clone(), environment: env.clone() };
+    let deref_chain = autoderef_method_receiver(db, krate, ty, &Fuel::new(db));
+    for self_ty in deref_chain {
+        for &t in &candidate_traits {
+            let goal = generic_implements_goal(db, env.clone(), t, self_ty.clone());
+            if db.trait_solve(krate, goal).is_none() {
+                let substs = Substs::build_for_def(db, t)
+                    .push(self_ty.value.clone())
+                    .fill_with_unknown()
+                    .build();
+                return Some(TraitRef { trait_: t, substs });
+            }
+        }
+    }
+    None
+}
+
+/// How the receiver of a method call gets adjusted to the type of the method's
+/// `self` parameter: it's dereferenced `autoderefs` times, and then possibly
+/// borrowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ReceiverAdjustments {
+    pub autoderefs: usize,
+    pub autoref: Option<Mutability>,
+}
+
+/// Resolves the field `name` of the (already autoderefed) type `ty`, if the
+/// field is visible from `module`. Tuple fields don't have a `FieldId`, so
+/// they aren't handled here.
+pub fn resolve_field(
+    db: &dyn HirDatabase,
+    ty: &Ty,
+    name: &Name,
+    module: ModuleId,
+) -> Option<FieldId> {
+    let field = lookup_field(db, ty, name)?;
+    if !is_field_visible(db, field, module) {
+        return None;
+    }
+    Some(field)
+}
+
+/// Like `resolve_field`, but also finds fields that aren't visible.
+pub fn lookup_field(db: &dyn HirDatabase, ty: &Ty, name: &Name) -> Option<FieldId> {
+    let variant: VariantId = match ty {
+        Ty::Adt(AdtId::StructId(s), _) => (*s).into(),
+        Ty::Adt(AdtId::UnionId(u), _) => (*u).into(),
+        _ => return None,
+    };
+    let local_id = variant_data(db.upcast(), variant).field(name)?;
+    Some(FieldId { parent: variant, local_id })
+}
+
+fn is_field_visible(db: &dyn HirDatabase, field: FieldId, module: ModuleId) -> bool {
+    let variant_data = variant_data(db.upcast(), field.parent);
+    let visibility = variant_data.fields()[field.local_id]
+        .visibility
+        .resolve(db.upcast(), &field.parent.resolver(db.upcast()));
+    visibility.is_visible_from(db.upcast(), module)
+}
+
+/// The default for `HirDatabase::method_resolution_step_limit`. This is far
+/// more than any reasonable code needs; it only exists to make sure lookups on
+/// pathological types (huge deref chains, thousands of traits in scope) don't
+/// hang the IDE.
+pub const DEFAULT_METHOD_RESOLUTION_STEP_LIMIT: usize = 100_000;
+
+/// Tracks how many steps a method lookup has left. Consuming a step is also a
+/// cancellation point.
+struct Fuel(Cell<usize>);
+
+impl Fuel {
+    fn new(db: &dyn HirDatabase) -> Fuel {
+        Fuel(Cell::new(db.method_resolution_step_limit()))
+    }
+
+    /// Returns `false` once the step limit has been reached.
+    fn consume(&self, db: &dyn HirDatabase) -> bool {
+        db.check_canceled();
+        let left = self.0.get();
+        if left == 0 {
+            return false;
+        }
+        self.0.set(left - 1);
+        true
+    }
+}
+
 /// Whether we're looking up a dotted method call (like `v.len()`) or a path
 /// (like `Vec::new`).
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -321,46 +565,81 @@ pub fn iterate_method_candidates<T>(
     name: Option<&Name>,
     mode: LookupMode,
     mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+) -> Option<T> {
+    let ctx = MethodLookupCtx::new(db, env, krate, traits_in_scope, name);
+    iterate_method_candidates_with_adjustments(ty, &ctx, mode, |ty, item, _adjustments| {
+        callback(ty, item)
+    })
+}
+
+/// The state of a method lookup that is the same for all receiver types and
+/// candidates.
+struct MethodLookupCtx<'a> {
+    db: &'a dyn HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    traits_in_scope: &'a FxHashSet<TraitId>,
+    name: Option<&'a Name>,
+    fuel: Fuel,
+}
+
+impl<'a> MethodLookupCtx<'a> {
+    fn new(
+        db: &'a dyn HirDatabase,
+        env: Arc<TraitEnvironment>,
+        krate: CrateId,
+        traits_in_scope: &'a FxHashSet<TraitId>,
+        name: Option<&'a Name>,
+    ) -> MethodLookupCtx<'a> {
+        MethodLookupCtx { db, env, krate, traits_in_scope, name, fuel: Fuel::new(db) }
+    }
+}
+
+/// Like `iterate_method_candidates`, but also passes the adjustments of the
+/// receiver to the callback. Candidates are visited in the order rustc picks
+/// them in: for each step of the autoderef chain, by value, then with `&` and
+/// then with `&mut` autoref, and for each of those, inherent methods before
+/// trait methods.
+fn iterate_method_candidates_with_adjustments<T>(
+    ty: &Canonical<Ty>,
+    ctx: &MethodLookupCtx<'_>,
+    mode: LookupMode,
+    mut callback: impl FnMut(&Ty, AssocItemId, ReceiverAdjustments) -> Option<T>,
 ) -> Option<T> {
     let mut slot = None;
-    iterate_method_candidates_impl(
-        ty,
-        db,
-        env,
-        krate,
-        traits_in_scope,
-        name,
-        mode,
-        &mut |ty, item| {
-            assert!(slot.is_none());
-            slot = callback(ty, item);
-            slot.is_some()
-        },
-    );
+    // The same item can be reached several times for the same self type, e.g.
+    // if its trait is both a bound of the receiver and in scope. Only the
+    // first (i.e. highest-priority) occurrence is reported.
+    let mut seen = FxHashSet::default();
+    iterate_method_candidates_impl(ty, ctx, mode, &mut |ty, item, adjustments| {
+        assert!(slot.is_none());
+        if !seen.insert((ty.clone(), item)) {
+            return false;
+        }
+        slot = callback(ty, item, adjustments);
+        slot.is_some()
+    });
     slot
 }
 
 fn iterate_method_candidates_impl(
     ty: &Canonical<Ty>,
-    db: &dyn HirDatabase,
-    env: Arc<TraitEnvironment>,
-    krate: CrateId,
-    traits_in_scope: &FxHashSet<TraitId>,
-    name: Option<&Name>,
+    ctx: &MethodLookupCtx<'_>,
     mode: LookupMode,
-    callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
+    callback: &mut dyn FnMut(&Ty, AssocItemId, ReceiverAdjustments) -> bool,
 ) -> bool {
+    // Make sure a receiver like `T::Item` with `T: Iterator<Item = S>` is
+    // matched against the methods of `S`.
+    let ty = &Canonical { kinds: ty.kinds.clone(), value: ctx.env.normalize_ty(ty.value.clone()) };
     match mode {
         LookupMode::MethodCall => {
             // For method calls, rust first does any number of autoderef, and then one
-            // autoref (i.e. when the method takes &self or &mut self). We just ignore
-            // the autoref currently -- when we find a method matching the given name,
-            // we assume it fits.
+            // autoref (i.e. when the method takes &self or &mut self).
 
             // Also note that when we've got a receiver like &S, even if the method we
             // find in the end takes &self, we still do the autoderef step (just as
             // rustc does an autoderef and then autoref again).
-            let ty = InEnvironment { value: ty.clone(), environment: env.clone() };
+            let ty = InEnvironment { value: ty.clone(), environment: ctx.env.clone() };
 
             // We have to be careful about the order we're looking at candidates
             // in here. Consider the case where we're resolving `x.clone()`
@@ -375,17 +654,9 @@ fn iterate_method_candidates_impl(
             // the methods by autoderef order of *receiver types*, not *self
             // types*.
 
-            let deref_chain = autoderef_method_receiver(db, krate, ty);
+            let deref_chain = autoderef_method_receiver(ctx.db, ctx.krate, ty, &ctx.fuel);
             for i in 0..deref_chain.len() {
-                if iterate_method_candidates_with_autoref(
-                    &deref_chain[i..],
-                    db,
-                    env.clone(),
-                    krate,
-                    traits_in_scope,
-                    name,
-                    callback,
-                ) {
+                if iterate_method_candidates_with_autoref(&deref_chain[i..], i, ctx, callback) {
                     return true;
                 }
             }
@@ -393,37 +664,25 @@ fn iterate_method_candidates_impl(
         }
         LookupMode::Path => {
             // No autoderef for path lookups
-            iterate_method_candidates_for_self_ty(
-                &ty,
-                db,
-                env,
-                krate,
-                traits_in_scope,
-                name,
-                callback,
-            )
+            iterate_method_candidates_for_self_ty(&ty, ctx, &mut |ty, item| {
+                callback(ty, item, ReceiverAdjustments::default())
+            })
         }
     }
 }
 
 fn iterate_method_candidates_with_autoref(
     deref_chain: &[Canonical<Ty>],
-    db: &dyn HirDatabase,
-    env: Arc<TraitEnvironment>,
-    krate: CrateId,
-    traits_in_scope: &FxHashSet<TraitId>,
-    name: Option<&Name>,
-    mut callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
+    autoderefs: usize,
+    ctx: &MethodLookupCtx<'_>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId, ReceiverAdjustments) -> bool,
 ) -> bool {
+    let adjustments = |autoref| ReceiverAdjustments { autoderefs, autoref };
     if iterate_method_candidates_by_receiver(
         &deref_chain[0],
         &deref_chain[1..],
-        db,
-        env.clone(),
-        krate,
-        &traits_in_scope,
-        name,
-        &mut callback,
+        ctx,
+        &mut |ty, item| callback(ty, item, adjustments(None)),
     ) {
         return true;
     }
@@ -431,32 +690,18 @@ fn iterate_method_candidates_with_autoref(
         kinds: deref_chain[0].kinds.clone(),
         value: Ty::Ref(Mutability::Shared, Substs::single(deref_chain[0].value.clone())),
     };
-    if iterate_method_candidates_by_receiver(
-        &refed,
-        deref_chain,
-        db,
-        env.clone(),
-        krate,
-        &traits_in_scope,
-        name,
-        &mut callback,
-    ) {
+    if iterate_method_candidates_by_receiver(&refed, deref_chain, ctx, &mut |ty, item| {
+        callback(ty, item, adjustments(Some(Mutability::Shared)))
+    }) {
         return true;
     }
     let ref_muted = Canonical {
         kinds: deref_chain[0].kinds.clone(),
         value: Ty::Ref(Mutability::Mut, Substs::single(deref_chain[0].value.clone())),
     };
-    if iterate_method_candidates_by_receiver(
-        &ref_muted,
-        deref_chain,
-        db,
-        env,
-        krate,
-        &traits_in_scope,
-        name,
-        &mut callback,
-    ) {
+    if iterate_method_candidates_by_receiver(&ref_muted, deref_chain, ctx, &mut |ty, item| {
+        callback(ty, item, adjustments(Some(Mutability::Mut)))
+    }) {
         return true;
     }
     false
@@ -465,32 +710,19 @@ fn iterate_method_candidates_with_autoref(
 fn iterate_method_candidates_by_receiver(
     receiver_ty: &Canonical<Ty>,
     rest_of_deref_chain: &[Canonical<Ty>],
-    db: &dyn HirDatabase,
-    env: Arc<TraitEnvironment>,
-    krate: CrateId,
-    traits_in_scope: &FxHashSet<TraitId>,
-    name: Option<&Name>,
+    ctx: &MethodLookupCtx<'_>,
     mut callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
 ) -> bool {
     // We're looking for methods with *receiver* type receiver_ty. These could
     // be found in any of the derefs of receiver_ty, so we have to go through
     // that.
     for self_ty in std::iter::once(receiver_ty).chain(rest_of_deref_chain) {
-        if iterate_inherent_methods(self_ty, db, name, Some(receiver_ty), krate, &mut callback) {
+        if iterate_inherent_methods(self_ty, ctx, Some(receiver_ty), &mut callback) {
             return true;
         }
     }
     for self_ty in std::iter::once(receiver_ty).chain(rest_of_deref_chain) {
-        if iterate_trait_method_candidates(
-            self_ty,
-            db,
-            env.clone(),
-            krate,
-            &traits_in_scope,
-            name,
-            Some(receiver_ty),
-            &mut callback,
-        ) {
+        if iterate_trait_method_candidates(self_ty, ctx, Some(receiver_ty), &mut callback) {
             return true;
         }
     }
@@ -499,44 +731,67 @@ fn iterate_method_candidates_by_receiver(
 
 fn iterate_method_candidates_for_self_ty(
     self_ty: &Canonical<Ty>,
-    db: &dyn HirDatabase,
-    env: Arc<TraitEnvironment>,
-    krate: CrateId,
-    traits_in_scope: &FxHashSet<TraitId>,
-    name: Option<&Name>,
+    ctx: &MethodLookupCtx<'_>,
     mut callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
 ) -> bool {
-    if iterate_inherent_methods(self_ty, db, name, None, krate, &mut callback) {
+    if iterate_inherent_methods(self_ty, ctx, None, &mut callback) {
         return true;
     }
-    iterate_trait_method_candidates(self_ty, db, env, krate, traits_in_scope, name, None, callback)
+    iterate_trait_method_candidates(self_ty, ctx, None, callback)
 }
 
 fn iterate_trait_method_candidates(
     self_ty: &Canonical<Ty>,
-    db: &dyn HirDatabase,
-    env: Arc<TraitEnvironment>,
-    krate: CrateId,
-    traits_in_scope: &FxHashSet<TraitId>,
-    name: Option<&Name>,
+    ctx: &MethodLookupCtx<'_>,
     receiver_ty: Option<&Canonical<Ty>>,
     callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
 ) -> bool {
+    let (db, env, krate, name) = (ctx.db, &ctx.env, ctx.krate, ctx.name);
     // if ty is `dyn Trait`, the trait doesn't need to be in scope
     let inherent_trait =
         self_ty.value.dyn_trait().into_iter().flat_map(|t| all_super_traits(db.upcast(), t));
-    let env_traits = if let Ty::Placeholder(_) = self_ty.value {
+    let env_traits = match &self_ty.value {
         // if we have `T: Trait` in the param env, the trait doesn't need to be in scope
-        env.trait_predicates_for_self_ty(&self_ty.value)
+        Ty::Placeholder(_) | Ty::Projection(_) => env
+            .trait_predicates_for_self_ty(&self_ty.value)
             .map(|tr| tr.trait_)
             .flat_map(|t| all_super_traits(db.upcast(), t))
-            .collect()
-    } else {
-        Vec::new()
+            .collect(),
+        // and the same goes for associated type bounds like `T: Iterator<Item: Trait>`,
+        // which are stated in terms of the unnormalized projection
+        Ty::AssociatedType(associated_ty, parameters) => {
+            let projection = Ty::Projection(ProjectionTy {
+                associated_ty: *associated_ty,
+                parameters: parameters.clone(),
+            });
+            env.trait_predicates_for_self_ty(&projection)
+                .map(|tr| tr.trait_)
+                .flat_map(|t| all_super_traits(db.upcast(), t))
+                .collect()
+        }
+        // likewise, the bounds of an `impl Trait` type don't need to be in scope
+        Ty::Opaque(_) | Ty::OpaqueType(..) => self_ty
+            .value
+            .impl_trait_bounds(db)
+            .into_iter()
+            .flatten()
+            .filter_map(|pred| match pred.into_skip_binders() {
+                GenericPredicate::Implemented(tr) => Some(tr.trait_),
+                _ => None,
+            })
+            .flat_map(|t| all_super_traits(db.upcast(), t))
+            .collect(),
+        _ => Vec::new(),
     };
-    let traits =
-        inherent_trait.chain(env_traits.into_iter()).chain(traits_in_scope.iter().copied());
+    let mut seen_traits = FxHashSet::default();
+    let traits = inherent_trait
+        .chain(env_traits.into_iter())
+        .chain(ctx.traits_in_scope.iter().copied())
+        .filter(|t| seen_traits.insert(*t));
     'traits: for t in traits {
+        if !ctx.fuel.consume(db) {
+            return false;
+        }
         let data = db.trait_data(t);
 
         // we'll be lazy about checking whether the type implements the
@@ -564,41 +819,84 @@ fn iterate_trait_method_candidates(
 
 fn iterate_inherent_methods(
     self_ty: &Canonical<Ty>,
-    db: &dyn HirDatabase,
-    name: Option<&Name>,
+    ctx: &MethodLookupCtx<'_>,
     receiver_ty: Option<&Canonical<Ty>>,
-    krate: CrateId,
     callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
 ) -> bool {
-    let def_crates = match self_ty.value.def_crates(db, krate) {
+    let db = ctx.db;
+    if let Ty::RawPtr(mutability, _) = self_ty.value {
+        // Like rustc, we take the inherent methods of raw pointers straight
+        // from the `const_ptr`/`mut_ptr` lang item impls in `core`.
+        let impls = raw_ptr_inherent_impls(db, ctx.krate, mutability);
+        return iterate_inherent_impls(&impls, self_ty, ctx, receiver_ty, callback);
+    }
+
+    let def_crates = match self_ty.value.def_crates(db, ctx.krate) {
         Some(k) => k,
         None => return false,
     };
     for krate in def_crates {
         let impls = db.inherent_impls_in_crate(krate);
+        let impls = order_impls_by_specificity(db, impls.for_self_ty(&self_ty.value), self_ty);
+        if iterate_inherent_impls(&impls, self_ty, ctx, receiver_ty, callback) {
+            return true;
+        }
+    }
+    false
+}
 
-        for &impl_def in impls.for_self_ty(&self_ty.value) {
-            for &item in db.impl_data(impl_def).items.iter() {
-                if !is_valid_candidate(db, name, receiver_ty, item, self_ty) {
-                    continue;
-                }
-                // we have to check whether the self type unifies with the type
-                // that the impl is for. If we have a receiver type, this
-                // already happens in `is_valid_candidate` above; if not, we
-                // check it here
-                if receiver_ty.is_none() && inherent_impl_substs(db, impl_def, self_ty).is_none() {
-                    test_utils::mark::hit!(impl_self_type_match_without_receiver);
-                    continue;
-                }
-                if callback(&self_ty.value, item) {
-                    return true;
-                }
+fn iterate_inherent_impls(
+    impls: &[ImplId],
+    self_ty: &Canonical<Ty>,
+    ctx: &MethodLookupCtx<'_>,
+    receiver_ty: Option<&Canonical<Ty>>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> bool,
+) -> bool {
+    let (db, name) = (ctx.db, ctx.name);
+    for &impl_def in impls {
+        if !ctx.fuel.consume(db) {
+            return false;
+        }
+        for &item in db.impl_data(impl_def).items.iter() {
+            if !is_valid_candidate(db, name, receiver_ty, item, self_ty) {
+                continue;
+            }
+            // we have to check whether the self type unifies with the type
+            // that the impl is for. If we have a receiver type, this
+            // already happens in `is_valid_candidate` above; if not, we
+            // check it here
+            if receiver_ty.is_none() && inherent_impl_substs(db, impl_def, self_ty).is_none() {
+                test_utils::mark::hit!(impl_self_type_match_without_receiver);
+                continue;
+            }
+            if callback(&self_ty.value, item) {
+                return true;
             }
         }
     }
     false
 }
 
+/// Returns the inherent impls for `*const T` or `*mut T`, i.e. the impls
+/// marked with the `const_ptr` or `mut_ptr` lang item.
+fn raw_ptr_inherent_impls(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    mutability: Mutability,
+) -> Vec<ImplId> {
+    let lang_item = match mutability {
+        Mutability::Shared => "const_ptr",
+        Mutability::Mut => "mut_ptr",
+    };
+    db.lang_item(krate, lang_item.into())
+        .into_iter()
+        .filter_map(|it| match it {
+            LangItemTarget::ImplDefId(it) => Some(it),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Returns the self type for the index trait call.
 pub fn resolve_indexing_op(
     db: &dyn HirDatabase,
@@ -608,7 +906,7 @@ pub fn resolve_indexing_op(
     index_trait: TraitId,
 ) -> Option<Canonical<Ty>> {
     let ty = InEnvironment { value: ty.clone(), environment: env.clone() };
-    let deref_chain = autoderef_method_receiver(db, krate, ty);
+    let deref_chain = autoderef_method_receiver(db, krate, ty, &Fuel::new(db));
     for ty in deref_chain {
         let goal = generic_implements_goal(db, env.clone(), index_trait, ty.clone());
         if db.trait_solve(krate, goal).is_some() {
@@ -678,6 +976,220 @@ pub(crate) fn inherent_impl_substs(
     substs.map(|s| fallback_bound_vars(s.suffix(vars.len()), self_ty.kinds.len()))
 }
 
+/// The substitutions for the generic parameters of `impl_id` that make the
+/// trait reference of its header equal to `trait_ref`, if there are any.
+/// Parameters that `trait_ref` doesn't determine are unknown.
+pub(crate) fn trait_impl_substs(
+    db: &dyn HirDatabase,
+    impl_id: ImplId,
+    trait_ref: &TraitRef,
+) -> Option<Substs> {
+    let impl_trait_ref = db.impl_trait(impl_id)?;
+    if impl_trait_ref.value.trait_ != trait_ref.trait_ {
+        return None;
+    }
+    let vars = Substs::build_for_def(db, impl_id)
+        .fill_with_bound_vars(DebruijnIndex::INNERMOST, 0)
+        .build();
+    let impl_substs = impl_trait_ref.subst(&vars).substs;
+    let kinds = iter::repeat(chalk_ir::TyVariableKind::General).take(vars.len()).collect();
+    let substs = Canonical { kinds, value: (impl_substs, trait_ref.substs.clone()) };
+    super::infer::unify_substs(&substs).map(|s| fallback_bound_vars(s, 0))
+}
+
+/// Finds the item of the impl that provides the associated function or const
+/// `item` of a trait for `self_ty`, together with the impl's substitutions.
+/// This needs the impl to be determined by the self type alone; if it isn't,
+/// or the impl doesn't override the trait's default, this returns `None`.
+pub(crate) fn lookup_impl_item(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    item: AssocItemId,
+    self_ty: &Canonical<Ty>,
+) -> Option<(AssocItemId, Substs)> {
+    let (trait_, name) = match item {
+        AssocItemId::FunctionId(f) => match f.lookup(db.upcast()).container {
+            AssocContainerId::TraitId(it) => (it, db.function_data(f).name.clone()),
+            _ => return None,
+        },
+        AssocItemId::ConstId(c) => match c.lookup(db.upcast()).container {
+            AssocContainerId::TraitId(it) => (it, db.const_data(c).name.clone()?),
+            _ => return None,
+        },
+        AssocItemId::TypeAliasId(_) => return None,
+    };
+    let impl_id = find_impl_for_self_ty(db, krate, trait_, self_ty)?;
+    let impl_item =
+        db.impl_data(impl_id).items.iter().copied().find(|impl_item| match (item, *impl_item) {
+            (AssocItemId::FunctionId(_), AssocItemId::FunctionId(f)) => {
+                db.function_data(f).name == name
+            }
+            (AssocItemId::ConstId(_), AssocItemId::ConstId(c)) => {
+                db.const_data(c).name.as_ref() == Some(&name)
+            }
+            _ => false,
+        })?;
+    let substs = inherent_impl_substs(db, impl_id, self_ty)?;
+    Some((impl_item, substs))
+}
+
+/// Looks up the function `name` of `trait_` for `ty`, like `<ty as
+/// Trait>::name` does. This is the function of the impl of `trait_` for `ty`
+/// if that impl can be determined and defines it, and the trait's declaration
+/// otherwise. Returns `None` if the trait has no such function, or `ty`
+/// definitely doesn't implement it.
+pub fn lookup_associated_fn(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    ty: &Ty,
+    trait_: TraitId,
+    name: &Name,
+    env: Arc<TraitEnvironment>,
+) -> Option<FunctionId> {
+    let trait_fn =
+        db.trait_data(trait_).items.iter().find_map(|(item_name, item)| match *item {
+            AssocItemId::FunctionId(f) if item_name == name => Some(f),
+            _ => None,
+        })?;
+    if check_implements(db, krate, env, trait_, ty.clone(), &[]) == Implements::No {
+        return None;
+    }
+    let mut is_concrete = true;
+    ty.walk(&mut |ty| {
+        is_concrete &=
+            !matches!(ty, Ty::Unknown | Ty::InferenceVar(..) | Ty::Bound(_) | Ty::Placeholder(_))
+    });
+    if !is_concrete {
+        return Some(trait_fn);
+    }
+    let impl_fn =
+        find_impl_for_self_ty(db, krate, trait_, &Canonical::new(ty.clone(), iter::empty()))
+            .and_then(|impl_id| {
+                db.impl_data(impl_id).items.iter().find_map(|item| match *item {
+                    AssocItemId::FunctionId(f) if db.function_data(f).name == *name => Some(f),
+                    _ => None,
+                })
+            });
+    Some(impl_fn.unwrap_or(trait_fn))
+}
+
+/// Finds the impl of `trait_` for `self_ty`, if it can be determined from the
+/// self type alone.
+// FIXME: check the where clauses of the impls
+fn find_impl_for_self_ty(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    trait_: TraitId,
+    self_ty: &Canonical<Ty>,
+) -> Option<ImplId> {
+    // unlike `trait_impls_in_deps`, this includes the impls of `krate` itself
+    let trait_impls = db.relevant_impls_for_trait(krate, trait_);
+    let candidates: Vec<_> = match TyFingerprint::for_impl(&self_ty.value) {
+        Some(fp) => trait_impls.for_trait_and_self_ty(trait_, fp).collect(),
+        None => trait_impls.for_trait(trait_).collect(),
+    };
+    let matching: Vec<_> = candidates
+        .into_iter()
+        .filter(|&impl_id| inherent_impl_substs(db, impl_id, self_ty).is_some())
+        .collect();
+    match select_most_specific_impls(db, &matching, self_ty)[..] {
+        [impl_id] => Some(impl_id),
+        _ => None,
+    }
+}
+
+/// Orders the given impls such that impls whose self type is more specific
+/// come first, in the sense of specialization: for `u32`, `impl Foo for u32`
+/// comes before `impl<T> Foo for T`. Impls that are incomparable or whose self
+/// type doesn't match `self_ty` keep their relative order.
+pub(crate) fn order_impls_by_specificity(
+    db: &dyn HirDatabase,
+    impls: &[ImplId],
+    self_ty: &Canonical<Ty>,
+) -> Vec<ImplId> {
+    let mut impls = impls_with_dominance(db, impls, self_ty);
+    impls.sort_by_key(|&(_, dominated_by)| dominated_by);
+    impls.into_iter().map(|(impl_id, _)| impl_id).collect()
+}
+
+/// Drops all impls that match `self_ty`, but are strictly less specific than
+/// another matching impl. This is what makes `impl Foo for u32` win over
+/// `impl<T> Foo for T` under specialization, instead of the two being
+/// ambiguous.
+pub(crate) fn select_most_specific_impls(
+    db: &dyn HirDatabase,
+    impls: &[ImplId],
+    self_ty: &Canonical<Ty>,
+) -> Vec<ImplId> {
+    impls_with_dominance(db, impls, self_ty)
+        .into_iter()
+        .filter(|&(_, dominated_by)| dominated_by == 0)
+        .map(|(impl_id, _)| impl_id)
+        .collect()
+}
+
+/// For each impl, counts the impls matching `self_ty` that are strictly more
+/// specific than it.
+fn impls_with_dominance(
+    db: &dyn HirDatabase,
+    impls: &[ImplId],
+    self_ty: &Canonical<Ty>,
+) -> Vec<(ImplId, usize)> {
+    // if we don't know anything about the self type, every impl matches and
+    // we can't prefer any of them
+    let matching = match self_ty.value {
+        _ if impls.len() < 2 => Vec::new(),
+        Ty::Bound(_) | Ty::Unknown => Vec::new(),
+        _ => impls
+            .iter()
+            .filter(|&&impl_id| inherent_impl_substs(db, impl_id, self_ty).is_some())
+            .map(|&impl_id| (impl_id, db.impl_self_ty(impl_id).value))
+            .collect::<Vec<_>>(),
+    };
+    impls
+        .iter()
+        .map(|&impl_id| {
+            let dominated_by = match matching.iter().find(|(it, _)| *it == impl_id) {
+                Some((_, impl_self_ty)) => matching
+                    .iter()
+                    .filter(|(_, other)| {
+                        compare_specificity(other, impl_self_ty) == Some(Ordering::Greater)
+                    })
+                    .count(),
+                None => 0,
+            };
+            (impl_id, dominated_by)
+        })
+        .collect()
+}
+
+/// Compares two impl self types by how specific they are. Type parameters of
+/// the impls are represented by bound variables; any concrete type constructor
+/// is more specific than a parameter, and types with the same constructor are
+/// compared by their arguments. Returns `None` if the types are incomparable.
+fn compare_specificity(a: &Ty, b: &Ty) -> Option<Ordering> {
+    match (a, b) {
+        (Ty::Bound(_), Ty::Bound(_)) => Some(Ordering::Equal),
+        (Ty::Bound(_), _) => Some(Ordering::Less),
+        (_, Ty::Bound(_)) => Some(Ordering::Greater),
+        _ if a.equals_ctor(b) => {
+            let (a_substs, b_substs) = match (a.substs(), b.substs()) {
+                (Some(a_substs), Some(b_substs)) => (a_substs, b_substs),
+                _ => return Some(Ordering::Equal),
+            };
+            a_substs.iter().zip(b_substs.iter()).try_fold(Ordering::Equal, |acc, (a, b)| {
+                match (acc, compare_specificity(a, b)?) {
+                    (acc, Ordering::Equal) => Some(acc),
+                    (Ordering::Equal, ord) => Some(ord),
+                    (acc, ord) if acc == ord => Some(acc),
+                    _ => None,
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
 /// This replaces any 'free' Bound vars in `s` (i.e. those with indices past
 /// num_vars_to_keep) by `Ty::Unknown`.
 fn fallback_bound_vars(s: Substs, num_vars_to_keep: usize) -> Substs {
@@ -769,8 +1281,10 @@ fn autoderef_method_receiver(
     db: &dyn HirDatabase,
     krate: CrateId,
     ty: InEnvironment<Canonical<Ty>>,
+    fuel: &Fuel,
 ) -> Vec<Canonical<Ty>> {
-    let mut deref_chain: Vec<_> = autoderef::autoderef(db, Some(krate), ty).collect();
+    let mut deref_chain: Vec<_> =
+        autoderef::autoderef_iter(db, Some(krate), ty).take_while(|_| fuel.consume(db)).collect();
     // As a last step, we can do array unsizing (that's the only unsizing that rustc does for method receivers!)
     if let Some(Ty::Array(parameters)) = deref_chain.last().map(|ty| &ty.value) {
         let kinds = deref_chain.last().unwrap().kinds.clone();
@@ -0,0 +1,160 @@
+//! Object safety checks, i.e. whether a trait can be used as `dyn Trait`.
+//!
+//! See https://doc.rust-lang.org/reference/items/traits.html#object-safety and
+//! `rustc_trait_selection::traits::object_safety`.
+
+use std::sync::Arc;
+
+use hir_def::{
+    lang_item::LangItemTarget, AssocItemId, ConstId, FunctionId, HasModule, Lookup, TraitId,
+    TypeAliasId,
+};
+
+use crate::{
+    db::HirDatabase,
+    utils::{all_super_traits, generics},
+    CallableDefId, GenericPredicate, Substs, Ty, TypeWalk,
+};
+
+/// A reason why a trait is not object safe.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ObjectSafetyViolation {
+    /// The trait requires `Self: Sized`.
+    SizedSelf,
+    /// A supertrait refers to `Self` in its type parameters, like in
+    /// `trait Eq: PartialEq<Self>`.
+    SupertraitSelf,
+    /// A method can't be called on a trait object.
+    Method(FunctionId, MethodViolationCode),
+    /// Associated consts can't be accessed through a trait object.
+    AssocConst(ConstId),
+    /// Generic associated types can't be specified in `dyn Trait<Assoc = ..>`.
+    GenericAssocType(TypeAliasId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MethodViolationCode {
+    /// The method has no receiver, e.g. `fn new() -> Self`.
+    StaticMethod,
+    /// The method refers to `Self` in its parameters or return type, e.g.
+    /// `fn eq(&self, other: &Self)`.
+    ReferencesSelf,
+    /// The method has type parameters.
+    Generic,
+}
+
+pub(crate) fn object_safety_violations_query(
+    db: &dyn HirDatabase,
+    trait_: TraitId,
+) -> Arc<[ObjectSafetyViolation]> {
+    let mut violations = Vec::new();
+    for trait_ in all_super_traits(db.upcast(), trait_) {
+        violations.extend(own_violations(db, trait_));
+    }
+    violations.dedup();
+    violations.into()
+}
+
+pub fn is_object_safe(db: &dyn HirDatabase, trait_: TraitId) -> bool {
+    db.object_safety_violations(trait_).is_empty()
+}
+
+/// The violations caused by the items and bounds of `trait_` itself, not
+/// counting its supertraits.
+fn own_violations(db: &dyn HirDatabase, trait_: TraitId) -> Vec<ObjectSafetyViolation> {
+    let mut violations = Vec::new();
+    let krate = trait_.lookup(db.upcast()).container.module(db.upcast()).krate();
+    let sized_trait = match db.lang_item(krate, "sized".into()) {
+        Some(LangItemTarget::TraitId(it)) => Some(it),
+        _ => None,
+    };
+    if sized_trait == Some(trait_) {
+        violations.push(ObjectSafetyViolation::SizedSelf);
+    }
+
+    let trait_substs = Substs::type_params(db, trait_);
+    let self_ty = trait_substs[0].clone();
+    let supertrait_refers_to_self = db.generic_predicates(trait_.into()).iter().any(|pred| {
+        match pred.clone().subst(&trait_substs).into_skip_binders() {
+            GenericPredicate::Implemented(tr) if tr.self_ty() == &self_ty => {
+                tr.substs[1..].iter().any(|ty| refers_to_self(ty, &self_ty))
+            }
+            _ => false,
+        }
+    });
+    if supertrait_refers_to_self {
+        violations.push(ObjectSafetyViolation::SupertraitSelf);
+    }
+
+    for (_, item) in db.trait_data(trait_).items.iter() {
+        match *item {
+            AssocItemId::FunctionId(func) => {
+                if let Some(code) = method_violation(db, func, &self_ty, sized_trait) {
+                    violations.push(ObjectSafetyViolation::Method(func, code));
+                }
+            }
+            AssocItemId::ConstId(konst) => {
+                violations.push(ObjectSafetyViolation::AssocConst(konst));
+            }
+            AssocItemId::TypeAliasId(alias) => {
+                let params = db.generic_params(alias.into());
+                if !params.types.is_empty() || !params.lifetimes.is_empty() {
+                    violations.push(ObjectSafetyViolation::GenericAssocType(alias));
+                }
+            }
+        }
+    }
+    violations
+}
+
+fn method_violation(
+    db: &dyn HirDatabase,
+    func: FunctionId,
+    self_ty: &Ty,
+    sized_trait: Option<TraitId>,
+) -> Option<MethodViolationCode> {
+    let substs = Substs::type_params(db, func);
+    // Methods with a `where Self: Sized` clause are exempt, they just can't
+    // be called on trait objects.
+    let requires_sized_self = db.generic_predicates(func.into()).iter().any(|pred| {
+        match pred.clone().subst(&substs).into_skip_binders() {
+            GenericPredicate::Implemented(tr) => {
+                Some(tr.trait_) == sized_trait && tr.self_ty() == self_ty
+            }
+            _ => false,
+        }
+    });
+    if requires_sized_self {
+        return None;
+    }
+
+    if !db.function_data(func).has_self_param {
+        return Some(MethodViolationCode::StaticMethod);
+    }
+
+    let sig = db.callable_item_signature(CallableDefId::FunctionId(func)).subst(&substs);
+    // The receiver is supposed to mention `Self`; we don't check whether it's
+    // actually a dispatchable one like `&Self` or `Box<Self>` yet.
+    if sig.params()[1..].iter().chain(Some(sig.ret())).any(|ty| refers_to_self(ty, self_ty)) {
+        return Some(MethodViolationCode::ReferencesSelf);
+    }
+
+    let (_parent, _self_params, type_params, impl_trait_params) =
+        generics(db.upcast(), func.into()).provenance_split();
+    if type_params + impl_trait_params > 0 {
+        return Some(MethodViolationCode::Generic);
+    }
+    None
+}
+
+/// Checks whether `ty` mentions `self_ty` other than as the self type of an
+/// associated type projection like `Self::Item`, which is allowed.
+fn refers_to_self(ty: &Ty, self_ty: &Ty) -> bool {
+    let ty = ty.clone().fold(&mut |ty| match ty {
+        Ty::Projection(proj) if proj.parameters[0] == *self_ty => Ty::Unknown,
+        ty => ty,
+    });
+    let mut found = false;
+    ty.walk(&mut |ty| found |= ty == self_ty);
+    found
+}
@@ -4,7 +4,9 @@
 //! * they don't belong to any particular crate.
 
 pub use chalk_ir::{FloatTy, IntTy, UintTy};
-pub use hir_def::builtin_type::{BuiltinFloat, BuiltinInt, BuiltinUint};
+pub use hir_def::builtin_type::{BuiltinFloat, BuiltinInt, BuiltinType, BuiltinUint};
+
+use crate::Scalar;
 
 pub fn int_ty_to_string(ty: IntTy) -> &'static str {
     match ty {
@@ -63,3 +65,77 @@ pub(super) fn float_ty_from_builtin(t: BuiltinFloat) -> FloatTy {
         BuiltinFloat::F64 => FloatTy::F64,
     }
 }
+
+pub(super) fn int_ty_to_builtin(t: IntTy) -> BuiltinInt {
+    match t {
+        IntTy::Isize => BuiltinInt::Isize,
+        IntTy::I8 => BuiltinInt::I8,
+        IntTy::I16 => BuiltinInt::I16,
+        IntTy::I32 => BuiltinInt::I32,
+        IntTy::I64 => BuiltinInt::I64,
+        IntTy::I128 => BuiltinInt::I128,
+    }
+}
+
+pub(super) fn uint_ty_to_builtin(t: UintTy) -> BuiltinUint {
+    match t {
+        UintTy::Usize => BuiltinUint::Usize,
+        UintTy::U8 => BuiltinUint::U8,
+        UintTy::U16 => BuiltinUint::U16,
+        UintTy::U32 => BuiltinUint::U32,
+        UintTy::U64 => BuiltinUint::U64,
+        UintTy::U128 => BuiltinUint::U128,
+    }
+}
+
+pub(super) fn float_ty_to_builtin(t: FloatTy) -> BuiltinFloat {
+    match t {
+        FloatTy::F32 => BuiltinFloat::F32,
+        FloatTy::F64 => BuiltinFloat::F64,
+    }
+}
+
+/// The scalar type of a builtin type; `None` for `str`, which isn't a scalar.
+pub fn scalar_from_builtin(t: BuiltinType) -> Option<Scalar> {
+    let scalar = match t {
+        BuiltinType::Char => Scalar::Char,
+        BuiltinType::Bool => Scalar::Bool,
+        BuiltinType::Str => return None,
+        BuiltinType::Int(t) => Scalar::Int(int_ty_from_builtin(t)),
+        BuiltinType::Uint(t) => Scalar::Uint(uint_ty_from_builtin(t)),
+        BuiltinType::Float(t) => Scalar::Float(float_ty_from_builtin(t)),
+    };
+    Some(scalar)
+}
+
+pub fn builtin_from_scalar(scalar: Scalar) -> BuiltinType {
+    match scalar {
+        Scalar::Char => BuiltinType::Char,
+        Scalar::Bool => BuiltinType::Bool,
+        Scalar::Int(t) => BuiltinType::Int(int_ty_to_builtin(t)),
+        Scalar::Uint(t) => BuiltinType::Uint(uint_ty_to_builtin(t)),
+        Scalar::Float(t) => BuiltinType::Float(float_ty_to_builtin(t)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Ty;
+
+    use super::*;
+
+    #[test]
+    fn builtin_round_trip() {
+        for (name, builtin) in BuiltinType::ALL {
+            let ty = Ty::builtin(*builtin);
+            assert_eq!(ty.as_builtin(), Some(*builtin), "{}", name);
+            if let Ty::Scalar(scalar) = ty {
+                assert_eq!(scalar_from_builtin(builtin_from_scalar(scalar)), Some(scalar));
+            } else {
+                assert_eq!(*builtin, BuiltinType::Str);
+                assert_eq!(scalar_from_builtin(*builtin), None);
+            }
+        }
+        assert_eq!(Ty::unit().as_builtin(), None);
+    }
+}
@@ -14,6 +14,11 @@ use rustc_hash::{FxHashMap, FxHashSet};
 use syntax::TextRange;
 use test_utils::extract_annotations;
 
+use crate::{
+    db::HirDatabase, method_resolution::DEFAULT_METHOD_RESOLUTION_STEP_LIMIT,
+    traits::TraitSolverConfig,
+};
+
 #[salsa::database(
     base_db::SourceDatabaseExtStorage,
     base_db::SourceDatabaseStorage,
@@ -22,11 +27,19 @@ use test_utils::extract_annotations;
     hir_def::db::DefDatabaseStorage,
     crate::db::HirDatabaseStorage
 )]
-#[derive(Default)]
 pub(crate) struct TestDB {
     storage: salsa::Storage<TestDB>,
     events: Mutex<Option<Vec<salsa::Event>>>,
 }
+
+impl Default for TestDB {
+    fn default() -> Self {
+        let mut this = Self { storage: Default::default(), events: Default::default() };
+        this.set_method_resolution_step_limit(DEFAULT_METHOD_RESOLUTION_STEP_LIMIT);
+        this.set_trait_solver_config(TraitSolverConfig::default());
+        this
+    }
+}
 impl fmt::Debug for TestDB {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TestDB").finish()
@@ -16,12 +16,13 @@ use hir_def::{
     body::{BodySourceMap, SyntheticSyntax},
     child_by_source::ChildBySource,
     db::DefDatabase,
+    expr::ExprId,
     item_scope::ItemScope,
     keys,
     nameres::DefMap,
-    AssocItemId, DefWithBodyId, LocalModuleId, Lookup, ModuleDefId,
+    AssocItemId, DefWithBodyId, FunctionId, LocalModuleId, Lookup, ModuleDefId, ModuleId,
 };
-use hir_expand::{db::AstDatabase, InFile};
+use hir_expand::{db::AstDatabase, name::AsName, InFile};
 use once_cell::race::OnceBool;
 use stdx::format_to;
 use syntax::{
@@ -57,26 +58,26 @@ fn setup_tracing() -> Option<tracing::subscriber::DefaultGuard> {
 }
 
 fn check_types(ra_fixture: &str) {
-    check_types_impl(ra_fixture, false)
+    check_types_impl(ra_fixture, |db, _, ty| ty.display_test(db).to_string())
 }
 
 fn check_types_source_code(ra_fixture: &str) {
-    check_types_impl(ra_fixture, true)
+    check_types_impl(ra_fixture, |db, module, ty| ty.display_source_code(db, module).unwrap())
 }
 
-fn check_types_impl(ra_fixture: &str, display_source: bool) {
+fn check_types_shortened(ra_fixture: &str) {
+    check_types_impl(ra_fixture, |db, module, ty| ty.display_shortened(db, module).to_string())
+}
+
+fn check_types_impl(ra_fixture: &str, display: impl Fn(&TestDB, ModuleId, &Ty) -> String) {
     let _tracing = setup_tracing();
     let db = TestDB::with_files(ra_fixture);
     let mut checked_one = false;
     for (file_id, annotations) in db.extract_annotations() {
         for (range, expected) in annotations {
             let ty = type_at_range(&db, FileRange { file_id, range });
-            let actual = if display_source {
-                let module = db.module_for_file(file_id);
-                ty.display_source_code(&db, module).unwrap()
-            } else {
-                ty.display_test(&db).to_string()
-            };
+            let module = db.module_for_file(file_id);
+            let actual = display(&db, module, &ty);
             assert_eq!(expected, actual);
             checked_one = true;
         }
@@ -85,20 +86,46 @@ fn check_types_impl(ra_fixture: &str, display_source: bool) {
 }
 
 fn type_at_range(db: &TestDB, pos: FileRange) -> Ty {
+    let (func, expr_id) = expr_at_range(db, pos);
+    db.infer(func.into())[expr_id].clone()
+}
+
+fn expr_at_range(db: &TestDB, pos: FileRange) -> (FunctionId, ExprId) {
     let file = db.parse(pos.file_id).ok().unwrap();
     let expr = algo::find_node_at_range::<ast::Expr>(file.syntax(), pos.range).unwrap();
     let fn_def = expr.syntax().ancestors().find_map(ast::Fn::cast).unwrap();
-    let module = db.module_for_file(pos.file_id);
-    let func = *module.child_by_source(db)[keys::FUNCTION]
-        .get(&InFile::new(pos.file_id.into(), fn_def))
-        .unwrap();
+    let mut module = db.module_for_file(pos.file_id);
+
+    // Walk down from the file's module to the function, through the inline
+    // modules, impls, traits and function bodies containing it.
+    let mut containers: Vec<SyntaxNode> = fn_def.syntax().ancestors().collect();
+    containers.reverse();
+    let mut map = module.child_by_source(db);
+    let mut func = None;
+    for node in containers {
+        let file_id = pos.file_id.into();
+        if let Some(module_) = ast::Module::cast(node.clone()) {
+            let name = module_.name().unwrap().as_name();
+            let def_map = module.def_map(db);
+            module = def_map.module_id(def_map[module.local_id].children[&name]);
+            map = module.child_by_source(db);
+        } else if let Some(impl_) = ast::Impl::cast(node.clone()) {
+            map = map[keys::IMPL].get(&InFile::new(file_id, impl_)).unwrap().child_by_source(db);
+        } else if let Some(trait_) = ast::Trait::cast(node.clone()) {
+            map = map[keys::TRAIT].get(&InFile::new(file_id, trait_)).unwrap().child_by_source(db);
+        } else if let Some(fn_) = ast::Fn::cast(node) {
+            let it = *map[keys::FUNCTION].get(&InFile::new(file_id, fn_)).unwrap();
+            map = DefWithBodyId::from(it).child_by_source(db);
+            func = Some(it);
+        }
+    }
+    let func = func.unwrap();
 
     let (_body, source_map) = db.body_with_source_map(func.into());
-    if let Some(expr_id) = source_map.node_expr(InFile::new(pos.file_id.into(), &expr)) {
-        let infer = db.infer(func.into());
-        return infer[expr_id].clone();
+    match source_map.node_expr(InFile::new(pos.file_id.into(), &expr)) {
+        Some(expr_id) => (func, expr_id),
+        None => panic!("Can't find expression"),
     }
-    panic!("Can't find expression")
 }
 
 fn infer(ra_fixture: &str) -> String {
@@ -1,7 +1,21 @@
+use base_db::fixture::WithFixture;
 use expect_test::expect;
 use test_utils::mark;
 
-use super::{check_infer, check_infer_with_mismatches};
+use crate::{db::HirDatabase, display::HirDisplay, test_db::TestDB, Adjust};
+
+use super::{check_infer, check_infer_with_mismatches, expr_at_range};
+
+fn check_unsize_adjustment(ra_fixture: &str, expected: Option<&str>) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let (func, expr) = expr_at_range(&db, range);
+    let infer = db.infer(func.into());
+    let actual = infer.expr_adjustment(expr).map(|adjustment| {
+        assert_eq!(adjustment.kind, Adjust::Unsize);
+        adjustment.target.display_test(&db).to_string()
+    });
+    assert_eq!(actual.as_deref(), expected);
+}
 
 #[test]
 fn infer_block_expr_type_mismatch() {
@@ -742,9 +756,6 @@ fn coerce_unsize_trait_object_simple() {
 }
 
 #[test]
-// The rust reference says this should be possible, but rustc doesn't implement
-// it. We used to support it, but Chalk doesn't.
-#[ignore]
 fn coerce_unsize_trait_object_to_trait_object() {
     check_infer_with_mismatches(
         r#"
@@ -859,3 +870,183 @@ fn coerce_unsize_generic() {
         "]],
     );
 }
+
+#[test]
+fn coerce_unsize_struct_tail() {
+    check_infer_with_mismatches(
+        r#"
+        #[lang = "unsize"]
+        pub trait Unsize<T> {}
+        #[lang = "coerce_unsized"]
+        pub trait CoerceUnsized<T> {}
+
+        impl<T: Unsize<U>, U> CoerceUnsized<&U> for &T {}
+
+        struct Foo<T> { t: T };
+        struct Bar<T>(Foo<T>);
+
+        fn test() {
+            let _: &Bar<[usize]> = &Bar(Foo { t: [1, 2, 3] });
+        }
+        "#,
+        expect![[r#"
+            209..267 '{     ... }); }': ()
+            219..220 '_': &Bar<[usize]>
+            238..264 '&Bar(F... 3] })': &Bar<[usize; _]>
+            239..242 'Bar': Bar<[usize; _]>(Foo<[usize; _]>) -> Bar<[usize; _]>
+            239..264 'Bar(Fo... 3] })': Bar<[usize; _]>
+            243..263 'Foo { ..., 3] }': Foo<[usize; _]>
+            252..261 '[1, 2, 3]': [usize; _]
+            253..254 '1': usize
+            256..257 '2': usize
+            259..260 '3': usize
+        "#]],
+    );
+}
+
+const UNSIZE_FIXTURE: &str = r#"
+#[lang = "sized"]
+pub trait Sized {}
+#[lang = "unsize"]
+pub trait Unsize<T: ?Sized> {}
+#[lang = "coerce_unsized"]
+pub trait CoerceUnsized<T> {}
+#[lang = "owned_box"]
+pub struct Box<T: ?Sized>(*mut T);
+
+impl<'a, 'b: 'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<&'a U> for &'b T {}
+impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<Box<U>> for Box<T> {}
+
+trait Trait {}
+struct S;
+impl Trait for S {}
+"#;
+
+#[test]
+fn unsize_adjustment_array_to_slice() {
+    check_unsize_adjustment(
+        &format!("{}fn test() {{ let f: &[usize] = $0&[1, 2, 3]$0; }}", UNSIZE_FIXTURE),
+        Some("&[usize]"),
+    );
+}
+
+#[test]
+fn unsize_adjustment_to_trait_object() {
+    check_unsize_adjustment(
+        &format!("{}fn test(s: &S) {{ let f: &dyn Trait = $0s$0; }}", UNSIZE_FIXTURE),
+        Some("&dyn Trait"),
+    );
+    check_unsize_adjustment(
+        &format!("{}fn test(s: Box<S>) {{ let f: Box<dyn Trait> = $0s$0; }}", UNSIZE_FIXTURE),
+        Some("Box<dyn Trait>"),
+    );
+}
+
+#[test]
+fn no_adjustment_without_unsizing() {
+    check_unsize_adjustment(
+        &format!("{}fn test(s: &S) {{ let f: &S = $0s$0; }}", UNSIZE_FIXTURE),
+        None,
+    );
+}
+
+#[test]
+fn coerce_unsize_rc_to_trait_object() {
+    check_infer_with_mismatches(
+        r#"
+        #[lang = "sized"]
+        pub trait Sized {}
+        #[lang = "unsize"]
+        pub trait Unsize<T: ?Sized> {}
+        #[lang = "coerce_unsized"]
+        pub trait CoerceUnsized<T> {}
+
+        pub struct Rc<T: ?Sized>(*const T);
+        impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<Rc<U>> for Rc<T> {}
+
+        pub trait Display {}
+        pub struct String;
+        impl Display for String {}
+
+        fn test(s: Rc<String>) {
+            let d: Rc<dyn Display> = s;
+        }
+        "#,
+        expect![[r"
+            331..332 's': Rc<String>
+            346..381 '{     ...= s; }': ()
+            356..357 'd': Rc<dyn Display>
+            377..378 's': Rc<String>
+        "]],
+    );
+}
+
+#[test]
+fn coerce_unsize_not_object_safe() {
+    check_infer_with_mismatches(
+        r#"
+        #[lang = "sized"]
+        pub trait Sized {}
+        #[lang = "unsize"]
+        pub trait Unsize<T: ?Sized> {}
+        #[lang = "coerce_unsized"]
+        pub trait CoerceUnsized<T> {}
+
+        impl<'a, 'b: 'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<&'a U> for &'b T {}
+
+        trait NotObjectSafe: Sized {}
+        struct S;
+        impl NotObjectSafe for S {}
+
+        fn test(s: &S) {
+            let d: &dyn NotObjectSafe = s;
+        }
+        "#,
+        expect![[r"
+            308..309 's': &S
+            315..353 '{     ...= s; }': ()
+            325..326 'd': &dyn NotObjectSafe
+            349..350 's': &S
+            349..350: expected &dyn NotObjectSafe, got &S
+        "]],
+    );
+}
+
+#[test]
+fn unsize_adjustment_trait_upcasting() {
+    let fixture = format!(
+        "{}{}",
+        UNSIZE_FIXTURE,
+        r#"
+auto trait Send {}
+trait Super<T> { type Assoc; }
+trait Sub<T>: Super<(T, u8)> {}
+"#
+    );
+    check_unsize_adjustment(
+        &format!("{}fn test(s: &dyn Sub<i8>) {{ let f: &dyn Super<_> = $0s$0; }}", fixture),
+        Some("&dyn Super<(i8, u8)>"),
+    );
+    check_unsize_adjustment(
+        &format!(
+            "{}fn test(s: Box<dyn Sub<i8, Assoc = u32> + Send>) {{ let f: Box<dyn Super<_, Assoc = _> + Send> = $0s$0; }}",
+            fixture
+        ),
+        Some("Box<dyn Super<(i8, u8), Assoc = u32> + Send>"),
+    );
+    // Auto traits can be dropped, but not added.
+    check_unsize_adjustment(
+        &format!(
+            "{}fn test(s: &(dyn Sub<i8> + Send)) {{ let f: &dyn Super<_> = $0s$0; }}",
+            fixture
+        ),
+        Some("&dyn Super<(i8, u8)>"),
+    );
+    check_unsize_adjustment(
+        &format!(
+            "{}fn test(s: &dyn Sub<i8>) {{ let f: &(dyn Super<_> + Send) = $0s$0; }}",
+            fixture
+        ),
+        None,
+    );
+}
@@ -1,4 +1,11 @@
-use super::check_types_source_code;
+use base_db::fixture::WithFixture;
+
+use crate::{
+    display::HirDisplay, test_db::TestDB, Binders, BoundVar, Canonical, DebruijnIndex,
+    InferenceVar, Substs, TraitRef, Ty, TyVariableKind,
+};
+
+use super::{check_types_impl, check_types_shortened, check_types_source_code, type_at_range};
 
 #[test]
 fn qualify_path_to_submodule() {
@@ -54,3 +61,140 @@ fn main() {
 "#,
     );
 }
+
+#[test]
+fn shortened_path_strips_common_module_prefix() {
+    check_types_shortened(
+        r#"
+//- /main.rs
+mod a;
+//- /a.rs
+pub mod b;
+pub mod c;
+pub struct S;
+//- /a/b.rs
+pub struct T;
+//- /a/c.rs
+fn f() {
+    let t = crate::a::b::T;
+    t;
+  //^ b::T
+    let s = crate::a::S;
+    s;
+  //^ S
+}
+"#,
+    );
+}
+
+#[test]
+fn shortened_path_to_other_crate() {
+    check_types_shortened(
+        r#"
+//- /main.rs crate:main deps:std
+mod imported;
+fn main() {
+    let map = std::collections::hash_map::new();
+    map;
+} //^ std::collections::hash_map::HashMap<u8, u8>
+//- /imported.rs
+use std::collections::hash_map::HashMap;
+fn f() {
+    let map = std::collections::hash_map::new();
+    map;
+} //^ HashMap<u8, u8>
+//- /std.rs crate:std
+pub mod collections {
+    pub mod hash_map {
+        pub struct HashMap<K, V>(K, V);
+        pub fn new() -> HashMap<u8, u8> { loop {} }
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn dbg_display_shows_variable_indices() {
+    let (db, range) = TestDB::with_range(
+        r#"
+struct S<T>(T);
+fn test() {
+    let s = S(0u32);
+    $0s$0;
+}
+"#,
+    );
+    let s = type_at_range(&db, range);
+    let ty = Ty::Tuple(
+        3,
+        Substs(
+            vec![
+                s,
+                Ty::InferenceVar(InferenceVar::new_for_test(3), TyVariableKind::Integer),
+                Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0)),
+            ]
+            .into(),
+        ),
+    );
+    let canonical = Canonical::new(ty, Some(TyVariableKind::General));
+    assert_eq!(canonical.dbg_display(&db).to_string(), "for<^0.0> (S<u32>, ?3i, ^0.0)");
+    assert_eq!(canonical.display_test(&db).to_string(), "for<T0> (S<u32>, _, T0)");
+}
+
+#[test]
+fn display_bound_vars_in_dyn_bounds() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Trait<T> {}
+fn test(d: &dyn Trait<u32>) {
+    $0*d$0;
+}
+"#,
+    );
+    let d = type_at_range(&db, range);
+    let trait_ = d.dyn_trait().unwrap();
+    let bound = |depth, index| Ty::Bound(BoundVar::new(DebruijnIndex::new(depth), index));
+    // inside the bounds, `^0.0` is the self type and the outer binders are
+    // one level further out
+    let dyn_ty = |depth, index| {
+        let substs = Substs(vec![bound(0, 0), bound(depth, index)].into());
+        Ty::new_dyn(&db, Some(TraitRef { trait_, substs }), None, None)
+    };
+
+    let ty = Ty::Tuple(2, Substs(vec![bound(0, 0), dyn_ty(1, 0)].into()));
+    let binders = Binders::new(1, ty);
+    assert_eq!(binders.display_test(&db).to_string(), "for<T0> (T0, dyn Trait<T0>)");
+
+    let ty = Ty::Tuple(3, Substs(vec![bound(1, 0), bound(0, 1), dyn_ty(2, 0)].into()));
+    let nested = Binders::new(1, Binders::new(2, ty));
+    assert_eq!(
+        nested.display_test(&db).to_string(),
+        "for<T0> for<T1, T2> (T0, T2, dyn Trait<T0>)"
+    );
+
+    let ty = Ty::Tuple(1, Substs(vec![dyn_ty(1, 1)].into()));
+    let nested = Binders::new(1, Binders::new(2, ty));
+    assert_eq!(nested.display_test(&db).to_string(), "for<T0> for<T1, T2> (dyn Trait<T2>,)");
+}
+
+#[test]
+fn truncate_long_tuples_and_param_lists() {
+    check_types_impl(
+        r#"
+fn f(a: u8, b: u16, c: u32, d: u64) {}
+fn main(p: fn(u8, u16, u32, u64) -> (i8, i16, i32, i64)) {
+    let t = (1u8, 2u16, 3u32, 4u64, 5i8, 6i16, 7i32, 8i64, 9u8, 10u8, 11u8, 12u8);
+    t;
+  //^ (u8, u16, u32, …)
+    (1u8, 2u16, 3u32);
+  //^^^^^^^^^^^^^^^^^ (u8, u16, u32)
+    f;
+  //^ fn f(u8, u16, u32, …)
+    p;
+  //^ fn(u8, u16, u32, …) -> (i8, i16, i32, …)
+}
+"#,
+        |db, _, ty| ty.display(db).with_max_list_len(Some(3)).to_string(),
+    );
+}
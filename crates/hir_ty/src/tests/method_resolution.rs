@@ -1,6 +1,25 @@
-use expect_test::expect;
-
-use super::{check_infer, check_types};
+use std::iter;
+
+use base_db::{fixture::WithFixture, SourceDatabase};
+use expect_test::{expect, Expect};
+use hir_def::{
+    db::DefDatabase, resolver::HasResolver, AssocContainerId, AssocItemId, HasModule, Lookup,
+};
+use hir_expand::name::Name;
+use test_utils::mark;
+
+use crate::{
+    db::HirDatabase,
+    display::{FnSignature, HirDisplay},
+    method_resolution::{
+        all_impls_for_ty, iterate_method_candidates, lookup_associated_fn, LookupMode,
+        ReceiverAdjustments,
+    },
+    test_db::TestDB,
+    Canonical, Mutability, Substs, TraitEnvironment, Ty,
+};
+
+use super::{check_infer, check_types, expr_at_range, type_at_range};
 
 #[test]
 fn infer_slice_method() {
@@ -243,6 +262,42 @@ fn infer_associated_method_generics_2_type_params_without_args() {
     );
 }
 
+#[test]
+fn raw_ptr_inherent_methods() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+fn test(p: *const u8, q: *mut u8) {
+    let a = p.is_null();
+    a;
+  //^ bool
+    let b = p.cast::<u32>();
+    b;
+  //^ *const u32
+    let c = q.cast::<u32>();
+    c;
+  //^ *mut u32
+    let d = q.as_const();
+    d;
+  //^ *const u8
+}
+
+//- /core.rs crate:core
+#[lang = "const_ptr"]
+impl<T: ?Sized> *const T {
+    pub fn is_null(self) -> bool { loop {} }
+    pub fn cast<U>(self) -> *const U { loop {} }
+}
+
+#[lang = "mut_ptr"]
+impl<T: ?Sized> *mut T {
+    pub fn cast<U>(self) -> *mut U { loop {} }
+    pub fn as_const(self) -> *const T { loop {} }
+}
+"#,
+    );
+}
+
 #[test]
 fn cross_crate_associated_method_call() {
     check_types(
@@ -776,6 +831,126 @@ fn test() { (&S).foo(); }
     );
 }
 
+fn check_method_pick(ra_fixture: &str, expected_container: &str, expected: ReceiverAdjustments) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let (func, expr) = expr_at_range(&db, range);
+    let infer = db.infer(func.into());
+    let method = infer.method_resolution(expr).unwrap();
+    let container = match method.lookup(&db).container {
+        AssocContainerId::TraitId(it) => db.trait_data(it).name.to_string(),
+        _ => "impl".to_string(),
+    };
+    assert_eq!(container, expected_container);
+    assert_eq!(infer.receiver_adjustments(expr), Some(expected));
+}
+
+#[test]
+fn method_pick_inherent_shadows_trait() {
+    check_method_pick(
+        r#"
+trait Trait { fn foo(&self) -> u128; }
+struct S;
+impl S { fn foo(&self) -> i8 { 0 } }
+impl Trait for S { fn foo(&self) -> u128 { 0 } }
+fn test() { $0S.foo()$0; }
+"#,
+        "impl",
+        ReceiverAdjustments { autoderefs: 0, autoref: Some(Mutability::Shared) },
+    );
+}
+
+#[test]
+fn method_pick_autoref_before_autoderef() {
+    let fixture = r#"
+#[lang = "deref"]
+trait Deref {
+    type Target;
+    fn deref(&self) -> &Self::Target;
+}
+struct S;
+struct Wrapper(S);
+impl Deref for Wrapper {
+    type Target = S;
+}
+impl Wrapper { fn foo(&self) -> u8 { 0 } }
+impl S {
+    fn foo(self) -> i8 { 0 }
+    fn bar(&mut self) {}
+}
+trait Trait { fn bar(self); }
+impl Trait for Wrapper { fn bar(self) {} }
+fn test(w: Wrapper, s: &&S) {
+    CALL;
+}
+"#;
+    // `&self` on `Wrapper` beats `self` on the derefed `S`
+    check_method_pick(
+        &fixture.replace("CALL", "$0w.foo()$0"),
+        "impl",
+        ReceiverAdjustments { autoderefs: 0, autoref: Some(Mutability::Shared) },
+    );
+    // a by-value trait method beats an autoref'd inherent one of a later step
+    check_method_pick(
+        &fixture.replace("CALL", "$0w.bar()$0"),
+        "Trait",
+        ReceiverAdjustments { autoderefs: 0, autoref: None },
+    );
+    check_method_pick(
+        &fixture.replace("CALL", "$0s.foo()$0"),
+        "impl",
+        ReceiverAdjustments { autoderefs: 2, autoref: None },
+    );
+}
+
+#[test]
+fn method_pick_call_through_fn_traits() {
+    let fixture = r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> {
+    type Output;
+    fn call_once(self, args: Args) -> Self::Output;
+}
+#[lang = "fn_mut"]
+trait FnMut<Args>: FnOnce<Args> {
+    fn call_mut(&mut self, args: Args) -> Self::Output;
+}
+#[lang = "fn"]
+trait Fn<Args>: FnMut<Args> {
+    fn call(&self, args: Args) -> Self::Output;
+}
+fn foo(x: u8) {}
+fn test<F: FnMut(u8), G: FnOnce()>(f: F, g: G, d: &dyn Fn(u8)) {
+    let c = |x: u8| x;
+    CALL;
+}
+"#;
+    check_method_pick(
+        &fixture.replace("CALL", "$0c(1)$0"),
+        "Fn",
+        ReceiverAdjustments { autoderefs: 0, autoref: Some(Mutability::Shared) },
+    );
+    check_method_pick(
+        &fixture.replace("CALL", "$0f(1)$0"),
+        "FnMut",
+        ReceiverAdjustments { autoderefs: 0, autoref: Some(Mutability::Mut) },
+    );
+    check_method_pick(
+        &fixture.replace("CALL", "$0g()$0"),
+        "FnOnce",
+        ReceiverAdjustments { autoderefs: 0, autoref: None },
+    );
+    check_method_pick(
+        &fixture.replace("CALL", "$0d(1)$0"),
+        "Fn",
+        ReceiverAdjustments { autoderefs: 1, autoref: Some(Mutability::Shared) },
+    );
+
+    // calls of fn items don't go through the traits
+    let (db, range) = TestDB::with_range(&fixture.replace("CALL", "$0foo(1)$0"));
+    let (func, expr) = expr_at_range(&db, range);
+    assert_eq!(db.infer(func.into()).method_resolution(expr), None);
+}
+
 #[test]
 fn method_resolution_unsize_array() {
     check_types(
@@ -1173,3 +1348,564 @@ fn main() {
 "#,
     );
 }
+
+#[test]
+fn method_resolution_arbitrary_self_types() {
+    check_types(
+        r#"
+#[lang = "deref"]
+pub trait Deref {
+    type Target;
+    fn deref(&self) -> &Self::Target;
+}
+struct Rc<T>(T);
+impl<T> Deref for Rc<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.0 }
+}
+struct Pin<P>(P);
+impl<P: Deref> Deref for Pin<P> {
+    type Target = P::Target;
+    fn deref(&self) -> &P::Target { loop {} }
+}
+impl<T> Deref for &mut T {
+    type Target = T;
+    fn deref(&self) -> &T { loop {} }
+}
+
+struct Foo;
+impl Foo {
+    fn by_rc(self: Rc<Self>) -> u32 { 0 }
+    fn by_pin(self: Pin<&mut Self>) -> i8 { 0 }
+}
+struct Wrapper<T>(T);
+impl<T> Wrapper<T> {
+    fn get(self: Rc<Self>) -> T { loop {} }
+}
+
+fn test(rc: Rc<Foo>, pin: Pin<&mut Foo>) {
+    rc.by_rc();
+  //^^^^^^^^^^ u32
+    pin.by_pin();
+  //^^^^^^^^^^^^ i8
+    Rc(Wrapper(1u64)).get();
+  //^^^^^^^^^^^^^^^^^^^^^^^ u64
+}
+"#,
+    );
+}
+
+#[test]
+fn method_resolution_arbitrary_self_types_unifies_receiver() {
+    check_types(
+        r#"
+#[lang = "deref"]
+pub trait Deref {
+    type Target;
+    fn deref(&self) -> &Self::Target;
+}
+struct Rc<T>(T);
+impl<T> Deref for Rc<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.0 }
+}
+struct Wrapper<T>(T);
+impl<T> Wrapper<T> {
+    fn new() -> Self { loop {} }
+    fn set(self: Rc<Self>, t: T) {}
+}
+
+fn test() {
+    let w = Rc(Wrapper::new());
+    w.set(1u8);
+    w;
+  //^ Rc<Wrapper<u8>>
+}
+"#,
+    );
+}
+
+#[test]
+fn method_resolution_on_return_position_impl_trait_uses_bounds() {
+    check_types(
+        r#"
+mod io {
+    pub trait Read {
+        fn lines(self) -> Lines { loop {} }
+    }
+    pub trait BufRead: Read {
+        fn read_line(&mut self, buf: &mut u8) -> usize { 0 }
+    }
+    pub struct Lines;
+    pub struct Stdin;
+    impl Read for Stdin {}
+    impl BufRead for Stdin {}
+    pub fn reader() -> impl BufRead { Stdin }
+}
+
+fn test() {
+    let mut r = io::reader();
+    r.read_line(&mut 0);
+  //^^^^^^^^^^^^^^^^^^^ usize
+    r.lines();
+  //^^^^^^^^^ Lines
+}
+"#,
+    );
+}
+
+#[test]
+fn method_resolution_on_argument_impl_trait_uses_bounds() {
+    check_types(
+        r#"
+#[lang = "deref"]
+pub trait Deref {
+    type Target;
+    fn deref(&self) -> &Self::Target;
+}
+mod convert {
+    pub trait AsRef<T: ?Sized> {
+        fn as_ref(&self) -> &T;
+    }
+}
+#[lang = "str"]
+impl str {
+    fn len(&self) -> usize { 0 }
+}
+struct Wrapper;
+impl Wrapper {
+    fn wrapped(&self) -> u8 { 0 }
+}
+
+fn test(s: impl convert::AsRef<str>, d: impl Deref<Target = Wrapper>) {
+    s.as_ref();
+  //^^^^^^^^^^ &str
+    s.as_ref().len();
+  //^^^^^^^^^^^^^^^^ usize
+    d.wrapped();
+  //^^^^^^^^^^^ u8
+}
+"#,
+    );
+}
+
+#[test]
+fn method_resolution_arbitrary_self_types_deref_step() {
+    check_types(
+        r#"
+#[lang = "deref"]
+pub trait Deref {
+    type Target;
+    fn deref(&self) -> &Self::Target;
+}
+#[lang = "owned_box"]
+struct Box<T>(T);
+impl<T> Deref for Box<T> {
+    type Target = T;
+    fn deref(&self) -> &T { loop {} }
+}
+struct Rc<T>(T);
+impl<T> Deref for Rc<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.0 }
+}
+
+struct Wrapper<T>(T);
+impl<T> Wrapper<T> {
+    fn new() -> Self { loop {} }
+    fn by_box(self: Box<Self>, t: T) {}
+    fn by_rc(self: Rc<Self>, t: T) {}
+    fn by_ref_rc(self: &Rc<Self>, t: T) {}
+    fn by_ref(&self, t: T) {}
+}
+
+fn test() {
+    let b = Box(Box(Wrapper::new()));
+    b.by_box(1u8);
+    b;
+  //^ Box<Box<Wrapper<u8>>>
+    let r = Rc(Rc(Wrapper::new()));
+    r.by_rc(1u16);
+    r;
+  //^ Rc<Rc<Wrapper<u16>>>
+    let r = Rc(Wrapper::new());
+    r.by_ref_rc(1u32);
+    r;
+  //^ Rc<Wrapper<u32>>
+    let r = Rc(Box(Wrapper::new()));
+    r.by_ref(1u64);
+    r;
+  //^ Rc<Box<Wrapper<u64>>>
+}
+"#,
+    );
+}
+
+fn check_all_impls_for_ty(ra_fixture: &str, include_blanket_impls: bool, expect: Expect) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let ty = type_at_range(&db, range);
+    let krate = db.module_for_file(range.file_id).krate();
+    let crate_graph = db.crate_graph();
+    let mut actual = all_impls_for_ty(&db, &ty, krate, include_blanket_impls)
+        .into_iter()
+        .map(|impl_id| {
+            let impl_crate = impl_id.lookup(&db).container.module(&db).krate();
+            let self_ty = db.impl_self_ty(impl_id).subst(&Substs::type_params(&db, impl_id));
+            let self_ty = self_ty.display(&db).to_string();
+            let trait_ = match db.impl_trait(impl_id) {
+                Some(trait_ref) => format!("{} for ", db.trait_data(trait_ref.value.trait_).name),
+                None => String::new(),
+            };
+            let crate_name = crate_graph[impl_crate].display_name.as_ref().unwrap().to_string();
+            format!("{}: impl {}{}\n", crate_name, trait_, self_ty)
+        })
+        .collect::<Vec<_>>();
+    actual.sort();
+    expect.assert_eq(&actual.concat());
+}
+
+#[test]
+fn all_impls_for_ty_across_crates() {
+    let fixture = r#"
+//- /main.rs crate:main deps:b,a
+use a::{S, TA};
+use b::TB;
+trait TMain {}
+impl TMain for S {}
+impl TMain for u32 {}
+impl<T> TMain for T {}
+fn test(s: S) {
+    $0s$0;
+}
+//- /b.rs crate:b deps:a
+use a::S;
+pub trait TB {}
+impl TB for S {}
+impl TB for a::Other {}
+//- /a.rs crate:a
+pub struct S;
+pub struct Other;
+pub trait TA {}
+impl S { fn foo(&self) {} }
+impl Other {}
+impl TA for S {}
+impl<T> TA for [T] {}
+"#;
+    check_all_impls_for_ty(
+        fixture,
+        false,
+        expect![[r#"
+            a: impl S
+            a: impl TA for S
+            b: impl TB for S
+            main: impl TMain for S
+        "#]],
+    );
+    check_all_impls_for_ty(
+        fixture,
+        true,
+        expect![[r#"
+            a: impl S
+            a: impl TA for S
+            b: impl TB for S
+            main: impl TMain for S
+            main: impl TMain for T
+        "#]],
+    );
+}
+
+fn check_method_candidates(ra_fixture: &str, expect: Expect) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let ty = Canonical::new(type_at_range(&db, range), iter::empty());
+    let module = db.module_for_file(range.file_id);
+    let resolver = module.resolver(&db);
+    let traits_in_scope = resolver.traits_in_scope(&db);
+    let env = TraitEnvironment::lower(&db, &resolver);
+    let mut actual = Vec::new();
+    iterate_method_candidates(
+        &ty,
+        &db,
+        env,
+        module.krate(),
+        &traits_in_scope,
+        None,
+        LookupMode::MethodCall,
+        |self_ty, item| {
+            if let AssocItemId::FunctionId(func) = item {
+                let container = match func.lookup(&db).container {
+                    AssocContainerId::TraitId(it) => db.trait_data(it).name.to_string(),
+                    _ => "impl".to_string(),
+                };
+                let name = &db.function_data(func).name;
+                actual.push(format!("{}: {}::{}\n", self_ty.display(&db), container, name));
+            }
+            None::<()>
+        },
+    );
+    // traits in scope aren't ordered
+    actual.sort();
+    expect.assert_eq(&actual.concat());
+}
+
+#[test]
+fn method_candidates_are_deduplicated() {
+    check_method_candidates(
+        r#"
+//- /main.rs crate:main deps:std
+mod a { pub use std::prelude::Greet; }
+mod b { pub use std::prelude::Greet as Hello; }
+use a::Greet;
+use b::Hello;
+
+trait Other { fn greet(&self) {} }
+struct S;
+impl Greet for S {}
+impl Other for S {}
+
+fn test(s: S) {
+    $0s$0;
+}
+//- /std.rs crate:std
+pub mod prelude {
+    pub trait Greet {
+        fn greet(&self) {}
+        fn wave(&self) {}
+    }
+}
+"#,
+        expect![[r#"
+            S: Greet::greet
+            S: Greet::wave
+            S: Other::greet
+        "#]],
+    );
+}
+
+#[test]
+fn method_candidates_of_dyn_trait_in_scope_are_deduplicated() {
+    check_method_candidates(
+        r#"
+trait Greet {
+    fn greet(&self);
+}
+
+fn test(d: &dyn Greet) {
+    $0d$0;
+}
+"#,
+        expect![[r#"
+            dyn Greet: Greet::greet
+        "#]],
+    );
+}
+
+#[test]
+fn method_resolution_step_limit() {
+    let mut fixture = String::from(
+        r#"
+#[lang = "deref"]
+trait Deref { type Target; fn deref(&self) -> &Self::Target; }
+impl S2 { fn shallow(&self) -> u32 { 0 } }
+impl S63 { fn deep(&self) -> u32 { 0 } }
+struct S63;
+fn test(s: S0) {
+    s.deep();
+    $0s.shallow()$0;
+}
+"#,
+    );
+    for i in 0..63 {
+        fixture.push_str(&format!(
+            "struct S{0};\nimpl Deref for S{0} {{ type Target = S{1}; fn deref(&self) -> &S{1} {{ loop {{}} }} }}\n",
+            i,
+            i + 1
+        ));
+    }
+
+    // `deep` is beyond the autoderef recursion limit, but looking for it must
+    // not blow up either
+    let (db, range) = TestDB::with_range(&fixture);
+    assert_eq!(type_at_range(&db, range).display(&db).to_string(), "u32");
+
+    let (mut db, range) = TestDB::with_range(&fixture);
+    db.set_method_resolution_step_limit(2);
+    assert_eq!(type_at_range(&db, range).display(&db).to_string(), "{unknown}");
+}
+
+#[test]
+fn all_impls_of_trait_across_crates() {
+    let (db, range) = TestDB::with_range(
+        r#"
+//- /main.rs crate:main deps:b,a
+use a::{S, T};
+struct Main;
+impl T for Main {}
+fn test(t: &dyn T) {
+    $0*t$0;
+}
+//- /b.rs crate:b deps:a
+pub struct B;
+impl a::T for B {}
+impl a::T for u32 {}
+//- /a.rs crate:a
+pub trait T {}
+pub trait Other {}
+pub struct S;
+impl T for S {}
+impl Other for S {}
+//- /c.rs crate:c
+pub trait T {}
+pub struct C;
+impl T for C {}
+"#,
+    );
+    let trait_ = type_at_range(&db, range).dyn_trait().unwrap();
+    let crate_graph = db.crate_graph();
+    let mut actual = db
+        .all_impls_of_trait(trait_)
+        .into_iter()
+        .map(|impl_id| {
+            let impl_crate = impl_id.lookup(&db).container.module(&db).krate();
+            let self_ty = db.impl_self_ty(impl_id).value.display(&db).to_string();
+            let crate_name = crate_graph[impl_crate].display_name.as_ref().unwrap().to_string();
+            format!("{}: impl T for {}\n", crate_name, self_ty)
+        })
+        .collect::<Vec<_>>();
+    actual.sort();
+    expect![[r#"
+        a: impl T for S
+        b: impl T for B
+        b: impl T for u32
+        main: impl T for Main
+    "#]]
+    .assert_eq(&actual.concat());
+}
+
+#[test]
+fn method_call_on_literal() {
+    mark::check!(method_call_on_literal_falls_back);
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+fn test() {
+    let x = 1.pow(2);
+    x;
+  //^ i32
+    let y = 1.0.sqrt();
+    y;
+  //^ f64
+    let z = 1;
+    let _: i64 = z;
+    z.pow(2);
+  //^^^^^^^^ i64
+}
+
+//- /core.rs crate:core
+#[lang = "i32"]
+impl i32 {
+    pub fn pow(self, exp: u32) -> i32 { loop {} }
+}
+#[lang = "i64"]
+impl i64 {
+    pub fn pow(self, exp: u32) -> i64 { loop {} }
+}
+#[lang = "f64"]
+impl f64 {
+    pub fn sqrt(self) -> f64 { loop {} }
+}
+"#,
+    );
+}
+
+#[test]
+fn method_call_on_literal_without_method_keeps_literal_type_open() {
+    check_types(
+        r#"
+fn test() {
+    let x = 1;
+    x.missing();
+    let _: u8 = x;
+    x;
+  //^ u8
+}
+"#,
+    );
+}
+
+fn check_method_signature(ra_fixture: &str, expect: Expect) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let (func, expr) = expr_at_range(&db, range);
+    let infer = db.infer(func.into());
+    let method = infer.method_resolution(expr).unwrap();
+    let substs = &infer.generic_args_for_expr(expr).unwrap().substs;
+    expect.assert_eq(&FnSignature::new(&db, method, substs).display(&db).to_string());
+}
+
+#[test]
+fn trait_method_signature_with_self_substituted() {
+    let fixture = r#"
+trait Collection {
+    fn len(&self) -> usize;
+    fn append(self, other: Self) -> Self;
+}
+struct Vec<T>(T);
+impl<T> Collection for Vec<T> {}
+
+fn test(v: Vec<u8>) {
+    CALL;
+}
+"#;
+    check_method_signature(
+        &fixture.replace("CALL", "$0v.len()$0"),
+        expect![["fn len(self: &Vec<u8>) -> usize"]],
+    );
+    check_method_signature(
+        &fixture.replace("CALL", "$0v.append(v)$0"),
+        expect![["fn append(self: Vec<u8>, other: Vec<u8>) -> Vec<u8>"]],
+    );
+}
+
+#[test]
+fn lookup_associated_fn_prefers_impl() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Greet {
+    fn greet(&self);
+    fn wave(&self) {}
+}
+struct S;
+impl Greet for S {
+    fn greet(&self) {}
+}
+struct Other;
+
+fn test(s: S, o: Other) {
+    $0(s, o)$0;
+}
+"#,
+    );
+    let tys = type_at_range(&db, range);
+    let module = db.module_for_file(range.file_id);
+    let resolver = module.resolver(&db);
+    let greet = resolver.traits_in_scope(&db).into_iter().next().unwrap();
+    let env = TraitEnvironment::lower(&db, &resolver);
+    let trait_data = db.trait_data(greet);
+    let lookup = |ty: &Ty, name: &str| {
+        let name = match trait_data.items.iter().find(|(it, _)| it.to_string() == name) {
+            Some((it, _)) => it.clone(),
+            None => Name::missing(),
+        };
+        lookup_associated_fn(&db, module.krate(), ty, greet, &name, env.clone()).map(|func| {
+            match func.lookup(&db).container {
+                AssocContainerId::TraitId(_) => "trait",
+                _ => "impl",
+            }
+        })
+    };
+    let s = &tys.substs().unwrap()[0];
+    let o = &tys.substs().unwrap()[1];
+    assert_eq!(lookup(s, "greet"), Some("impl"));
+    assert_eq!(lookup(s, "wave"), Some("trait"));
+    assert_eq!(lookup(s, "shout"), None);
+    assert_eq!(lookup(o, "greet"), None);
+}
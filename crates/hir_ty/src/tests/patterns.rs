@@ -1,7 +1,11 @@
+use base_db::fixture::WithFixture;
 use expect_test::expect;
+use hir_def::{db::DefDatabase, expr::Pat, type_ref::Mutability, ModuleDefId};
 use test_utils::mark;
 
-use super::{check_infer, check_infer_with_mismatches};
+use crate::{db::HirDatabase, test_db::TestDB, BindingMode};
+
+use super::{check_infer, check_infer_with_mismatches, check_types};
 
 #[test]
 fn infer_pattern() {
@@ -159,7 +163,9 @@ fn infer_range_pattern() {
             38..42 '2u32': u32
             43..45 '{}': ()
             50..73 'if let...u32 {}': ()
+            57..58 '1': u32
             57..63 '1..=76': u32
+            61..63 '76': u32
             66..70 '2u32': u32
             71..73 '{}': ()
         "#]],
@@ -804,3 +810,164 @@ fn foo(foo: Foo) {
         "#]],
     );
 }
+
+#[test]
+fn match_scrutinee_type_from_literal_arm_patterns() {
+    check_types(
+        r#"
+trait Default { fn default() -> Self; }
+impl Default for u32 { fn default() -> Self { 0 } }
+trait Half { fn half(self) -> u16; }
+impl Half for u32 { fn half(self) -> u16 { 0 } }
+
+fn test() {
+    match Default::default() {
+        n if n > 10 => {
+            n.half();
+          //^^^^^^^^ u16
+        }
+        0u32 | 1u32 => {}
+        _ => {}
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_wildcard_let_initializer() {
+    check_infer_with_mismatches(
+        r#"
+        fn foo(x: u32) -> u32 { x }
+        fn test() {
+            let _ = foo("x");
+            let _: u32 = "str";
+        }
+        "#,
+        expect![[r#"
+            7..8 'x': u32
+            22..27 '{ x }': u32
+            24..25 'x': u32
+            38..87 '{     ...tr"; }': ()
+            48..49 '_': u32
+            52..55 'foo': fn foo(u32) -> u32
+            52..60 'foo("x")': u32
+            56..59 '"x"': &str
+            70..71 '_': u32
+            79..84 '"str"': &str
+            56..59: expected u32, got &str
+            79..84: expected u32, got &str
+        "#]],
+    );
+}
+
+#[test]
+fn infer_let_chain() {
+    check_types(
+        r#"
+enum Option<T> { Some(T), None }
+enum Result<T, E> { Ok(T), Err(E) }
+use Option::Some;
+use Result::Ok;
+
+fn test(a: Option<u32>, b: Result<i64, ()>) {
+    if let Some(x) = a && let Ok(y) = b {
+        x;
+      //^ u32
+        y;
+      //^ i64
+    }
+    while let Some(x) = a && x > 0 && let Ok(y) = b {
+        y;
+      //^ i64
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_let_chain_non_bool_operand() {
+    check_infer_with_mismatches(
+        r#"
+        enum E { A(u32), B }
+        fn test(a: E, b: u32) {
+            if let E::A(x) = a && b {}
+        }
+        "#,
+        expect![[r#"
+            29..30 'a': E
+            35..36 'b': u32
+            43..77 '{     ...b {} }': ()
+            49..75 'if let...& b {}': ()
+            56..63 'E::A(x)': E
+            61..62 'x': u32
+            66..67 'a': E
+            71..72 'b': u32
+            73..75 '{}': ()
+            71..72: expected bool, got u32
+        "#]],
+    );
+}
+
+fn check_binding_modes(ra_fixture: &str, expected: &[(&str, BindingMode)]) {
+    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    let module = db.module_for_file(file_id);
+    let def_map = module.def_map(&db);
+    let func = def_map[module.local_id]
+        .scope
+        .declarations()
+        .find_map(|def| match def {
+            ModuleDefId::FunctionId(it) => Some(it),
+            _ => None,
+        })
+        .unwrap();
+    let body = db.body(func.into());
+    let infer = db.infer(func.into());
+    let mut actual: Vec<_> = body
+        .pats
+        .iter()
+        .filter_map(|(id, pat)| match pat {
+            Pat::Bind { name, .. } => Some((name.to_string(), infer.binding_mode(id)?)),
+            _ => None,
+        })
+        .collect();
+    actual.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let actual: Vec<_> = actual.iter().map(|(name, mode)| (name.as_str(), *mode)).collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn binding_modes_through_references() {
+    check_binding_modes(
+        r#"
+enum Option<T> { Some(T), None }
+use Option::*;
+struct S { a: u32, b: (u32, u32) }
+
+fn test(opt: &Option<u32>, s: &mut S, t: (u32, u32)) {
+    if let Some(x) = opt {}
+    let S { a, b: (c, ref d) } = s;
+    let (e, ref mut f) = t;
+    match &opt {
+        Some(g) => {}
+        None => {}
+    }
+    let &Some(h) = opt;
+}
+"#,
+        &[
+            ("a", BindingMode::Ref(Mutability::Mut)),
+            ("c", BindingMode::Ref(Mutability::Mut)),
+            ("d", BindingMode::Ref(Mutability::Shared)),
+            ("e", BindingMode::Move),
+            ("f", BindingMode::Ref(Mutability::Mut)),
+            ("g", BindingMode::Ref(Mutability::Shared)),
+            ("h", BindingMode::Move),
+            ("opt", BindingMode::Move),
+            ("s", BindingMode::Move),
+            ("t", BindingMode::Move),
+            ("x", BindingMode::Ref(Mutability::Shared)),
+        ],
+    );
+}
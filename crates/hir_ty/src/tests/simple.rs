@@ -1,7 +1,22 @@
+use std::{
+    hash::{Hash, Hasher},
+    iter,
+    sync::Arc,
+};
+
+use base_db::fixture::WithFixture;
 use expect_test::expect;
+use hir_def::{type_ref::Mutability, AssocContainerId, AssocItemId, Lookup};
+use rustc_hash::FxHasher;
 use test_utils::mark;
 
-use super::{check_infer, check_types};
+use crate::{
+    autoderef_iter, db::HirDatabase, display::HirDisplay, primitive::UintTy, test_db::TestDB,
+    BoundVar, CallableDefId, Canonical, DebruijnIndex, GenericPredicate, InEnvironment,
+    InferenceVar, Scalar, Substs, TraitEnvironment, Ty, TyVariableKind,
+};
+
+use super::{check_infer, check_types, expr_at_range, type_at_range};
 
 #[test]
 fn infer_box() {
@@ -877,6 +892,51 @@ fn infer_field_autoderef() {
     );
 }
 
+#[test]
+fn infer_field_skips_private_fields_in_autoderef() {
+    check_types(
+        r#"
+#[lang = "deref"]
+trait Deref { type Target; fn deref(&self) -> &Self::Target; }
+
+mod m {
+    pub struct Inner { pub x: u32 }
+    pub struct Outer { x: i8, pub(crate) inner: Inner }
+    impl super::Deref for Outer {
+        type Target = Inner;
+        fn deref(&self) -> &Inner { &self.inner }
+    }
+
+    fn f(o: Outer) {
+        o.x;
+      //^^^ i8
+    }
+}
+
+fn test(o: m::Outer) {
+    o.x;
+  //^^^ u32
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_private_field_without_visible_alternative() {
+    check_types(
+        r#"
+mod m {
+    pub struct S { x: i8 }
+}
+
+fn test(s: &m::S) {
+    s.x;
+  //^^^ i8
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_argument_autoderef() {
     check_infer(
@@ -1588,6 +1648,90 @@ fn infer_associated_const() {
     );
 }
 
+#[test]
+fn assoc_const_type_through_impl() {
+    check_types(
+        r#"
+trait Trait {
+    type Item;
+    const DEFAULT: Self::Item;
+    const SIZE: usize = 1;
+}
+struct S;
+impl Trait for S {
+    type Item = u32;
+    const DEFAULT: u32 = 0;
+}
+struct W<T>(T);
+impl<T> Trait for W<T> {
+    type Item = (T, T);
+    const DEFAULT: (T, T) = loop {};
+}
+fn test() {
+    let a = <S as Trait>::DEFAULT;
+    a;
+  //^ u32
+    let b = <W<u8> as Trait>::DEFAULT;
+    b;
+  //^ (u8, u8)
+    let c = W::<i16>::DEFAULT;
+    c;
+  //^ (i16, i16)
+    let d = <S as Trait>::SIZE;
+    d;
+  //^ usize
+}
+"#,
+    );
+}
+
+#[test]
+fn assoc_const_type_through_where_clause() {
+    check_types(
+        r#"
+trait Trait {
+    type Item;
+    const DEFAULT: Self::Item;
+    const SIZE: usize;
+}
+fn test<T: Trait<Item = u16>>() {
+    let a = T::DEFAULT;
+    a;
+  //^ u16
+    let b = <T as Trait>::SIZE;
+    b;
+  //^ usize
+}
+"#,
+    );
+}
+
+#[test]
+fn assoc_const_resolves_to_impl_const() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Trait {
+    const DEFAULT: u32;
+    const SIZE: usize = 1;
+}
+struct W<T>(T);
+impl<T> Trait for W<T> {
+    const DEFAULT: u32 = 0;
+}
+fn test() {
+    $0<W<u8> as Trait>::DEFAULT$0;
+}
+"#,
+    );
+    let (func, expr) = expr_at_range(&db, range);
+    let infer = db.infer(func.into());
+    let konst = match infer.assoc_resolutions_for_expr(expr) {
+        Some(AssocItemId::ConstId(konst)) => konst,
+        it => panic!("not resolved to a const: {:?}", it),
+    };
+    assert!(matches!(konst.lookup(&db).container, AssocContainerId::ImplId(_)));
+}
+
 #[test]
 fn infer_type_alias() {
     check_infer(
@@ -2546,3 +2690,371 @@ fn test() {
         "#]],
     )
 }
+
+fn check_zero_sized(ra_fixture: &str, expected: Option<bool>) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let ty = type_at_range(&db, range);
+    assert_eq!(ty.is_zero_sized(&db), expected);
+}
+
+#[test]
+fn zero_sized_types() {
+    check_zero_sized(r#"fn f() { $0()$0; }"#, Some(true));
+    check_zero_sized(r#"fn f(x: ((), [(); 3])) { $0x$0; }"#, Some(true));
+    check_zero_sized(
+        r#"
+struct Unit;
+struct Wrapper(Unit, ());
+enum Single { A(Wrapper) }
+enum Void {}
+fn f(x: (Wrapper, Single, Void)) { $0x$0; }
+"#,
+        Some(true),
+    );
+    check_zero_sized(
+        r#"
+struct Marker<T>(T);
+fn f(x: Marker<()>) { $0x$0; }
+"#,
+        Some(true),
+    );
+}
+
+#[test]
+fn non_zero_sized_types() {
+    check_zero_sized(r#"fn f(x: ((), u8)) { $0x$0; }"#, Some(false));
+    check_zero_sized(r#"fn f(x: &()) { $0x$0; }"#, Some(false));
+    check_zero_sized(
+        r#"
+enum E { A, B }
+struct S { a: (), e: E }
+fn f(x: S) { $0x$0; }
+"#,
+        Some(false),
+    );
+}
+
+#[test]
+fn undetermined_zero_sized_types() {
+    check_zero_sized(r#"fn f<T>(x: (T, ())) { $0x$0; }"#, None);
+    // array lengths aren't known
+    check_zero_sized(r#"fn f(x: [u8; 0]) { $0x$0; }"#, None);
+    check_zero_sized(r#"fn f(x: [u8; 3]) { $0x$0; }"#, None);
+    check_zero_sized(
+        r#"
+extern { type Foreign; }
+fn f(x: &Foreign) { $0*x$0; }
+"#,
+        None,
+    );
+    check_zero_sized(
+        r#"
+struct S(S);
+fn f(x: S) { $0x$0; }
+"#,
+        None,
+    );
+}
+
+#[test]
+fn infer_transmute_from_expected_type() {
+    check_types(
+        r#"
+mod mem {
+    extern "rust-intrinsic" {
+        pub fn transmute<T, U>(e: T) -> U;
+    }
+}
+struct Wrapper(u32);
+
+fn test() {
+    let w: Wrapper = unsafe { mem::transmute(1u32) };
+                            //^^^^^^^^^^^^^^^^^^^^ Wrapper
+    let b = unsafe { mem::transmute::<_, u8>(true) };
+                   //^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ u8
+}
+"#,
+    );
+}
+
+#[test]
+fn autoderef_iter_is_lazy() {
+    // `A` derefs to `B`, which derefs back to `A`, so the chain only ends at
+    // the recursion limit
+    let fixture = r#"
+#[lang = "deref"]
+trait Deref { type Target; fn deref(&self) -> &Self::Target; }
+struct A;
+struct B;
+impl Deref for A { type Target = B; fn deref(&self) -> &B { loop {} } }
+impl Deref for B { type Target = A; fn deref(&self) -> &A { loop {} } }
+
+fn test(a: A) {
+    $0a$0;
+}
+"#;
+    let trait_solves_for_steps = |steps: usize| {
+        let (db, range) = TestDB::with_range(fixture);
+        let ty = type_at_range(&db, range);
+        let krate = db.module_for_file(range.file_id).krate();
+        let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+        let ty = InEnvironment::new(env, Canonical::new(ty, iter::empty()));
+        let events = db.log_executed(|| {
+            assert_eq!(autoderef_iter(&db, Some(krate), ty).take(steps).count(), steps);
+        });
+        events.iter().filter(|it| it.starts_with("trait_solve_query(")).count()
+    };
+    assert_eq!(trait_solves_for_steps(1), 0);
+    assert!(trait_solves_for_steps(2) < trait_solves_for_steps(3));
+}
+
+fn check_generic_args(call: &str, expected: &str) {
+    let fixture = format!(
+        r#"
+fn id<T>(t: T) -> T {{ t }}
+struct W<T>(T);
+impl<T> W<T> {{ fn get<U>(self, u: U) -> (T, U) {{ loop {{}} }} }}
+fn test() {{
+    {};
+}}
+"#,
+        call
+    );
+    let (db, range) = TestDB::with_range(&fixture);
+    let (func, expr) = expr_at_range(&db, range);
+    let infer = db.infer(func.into());
+    let args = infer.generic_args_for_expr(expr).unwrap();
+    let actual = args
+        .substs
+        .iter()
+        .zip(&args.explicit)
+        .map(|(ty, explicit)| {
+            format!("{}{}", ty.display(&db), if *explicit { " (explicit)" } else { "" })
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn records_inferred_generic_args() {
+    check_generic_args("$0id$0(1u8)", "u8");
+    check_generic_args("$0id::<u16>$0(1)", "u16 (explicit)");
+    check_generic_args("$0id::<_>$0(1u32)", "u32");
+    check_generic_args("$0W(1u8).get(2u32)$0", "u8, u32");
+    check_generic_args("$0W(1u8).get::<u64>(2)$0", "u8, u64 (explicit)");
+}
+
+#[test]
+fn replace_placeholder_in_type() {
+    let (db, range) = TestDB::with_range(
+        r#"
+fn test<T, U>(t: T, u: U) {
+    $0(t, [(u, t)], &t)$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let placeholder = |ty: &Ty| match ty {
+        Ty::Placeholder(id) => *id,
+        _ => panic!("expected a type parameter, got {}", ty.display(&db)),
+    };
+    let fields = ty.as_tuple().unwrap();
+    let t = placeholder(&fields[0]);
+    let u = placeholder(&fields[1].substs().unwrap()[0].as_tuple().unwrap()[0]);
+    let replaced = ty.replace_placeholder(t, &Ty::Str);
+    assert_eq!(replaced.display(&db).to_string(), "(str, [(U, str); _], &str)");
+    let replaced = replaced.replace_placeholder(u, &Ty::Never);
+    assert_eq!(replaced.display(&db).to_string(), "(str, [(!, str); _], &str)");
+}
+
+#[test]
+fn equal_substs_share_their_allocation() {
+    let a = Substs::single(Ty::Str);
+    let b: Substs = Substs(vec![Ty::Str].into());
+    assert!(std::ptr::eq(&a[..], &b[..]));
+
+    // mutating a `Ty` in place detaches its substs from the interner
+    let mut c = Ty::Ref(Mutability::Shared, b.clone());
+    *c.strip_references_mut() = Ty::Never;
+    assert_eq!(b, a);
+    let d = Ty::Ref(Mutability::Shared, Substs::single(Ty::Never));
+    assert_eq!(c, d);
+    let hash = |ty: &Ty| {
+        let mut hasher = FxHasher::default();
+        ty.hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_eq!(hash(&c), hash(&d));
+
+    let e = Ty::Dyn(vec![GenericPredicate::Error].into());
+    let f = Ty::Dyn(vec![GenericPredicate::Error].into());
+    match (&e, &f) {
+        (Ty::Dyn(e), Ty::Dyn(f)) => assert!(std::ptr::eq(&e[..], &f[..])),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn strip_references_mut_replaces_pointee() {
+    let (db, range) = TestDB::with_range(
+        r#"
+fn test(x: &&mut (u32, &u8)) {
+    $0x$0;
+}
+"#,
+    );
+    let mut ty = type_at_range(&db, range);
+    let original = ty.clone();
+
+    let (pointee, count) = ty.peel_refs_mut();
+    assert_eq!(count, 2);
+    *pointee = Ty::Str;
+    assert_eq!(ty.display(&db).to_string(), "&&mut str");
+
+    *ty.strip_references_mut() = Ty::Never;
+    assert_eq!(ty.display(&db).to_string(), "&&mut !");
+    assert_eq!(original.display(&db).to_string(), "&&mut (u32, &u8)");
+
+    let mut not_a_ref = Ty::Str;
+    assert_eq!(not_a_ref.peel_refs_mut().1, 0);
+}
+
+#[test]
+fn as_callable_def_handles_all_callables() {
+    let (db, range) = TestDB::with_range(
+        r#"
+struct S<T>(T);
+enum E { V(u32) }
+fn f<T>(t: T) {}
+
+fn test() {
+    $0(f::<u8>, S::<u16>, E::V, 0u8)$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let fields = ty.as_tuple().unwrap();
+
+    let (func, substs) = fields[0].as_fn_def_with_substs().unwrap();
+    assert_eq!(fields[0].as_fn_def(), Some(func));
+    assert_eq!(substs[0], Ty::Scalar(Scalar::Uint(UintTy::U8)));
+
+    let (def, substs) = fields[1].as_callable_def().unwrap();
+    assert!(matches!(def, CallableDefId::StructId(_)));
+    assert_eq!(substs[0], Ty::Scalar(Scalar::Uint(UintTy::U16)));
+    assert!(fields[1].as_fn_def_with_substs().is_none());
+
+    let (def, substs) = fields[2].as_callable_def().unwrap();
+    assert!(matches!(def, CallableDefId::EnumVariantId(_)));
+    assert!(substs.is_empty());
+
+    assert!(fields[3].as_callable_def().is_none());
+}
+
+#[test]
+fn generic_defaults_substs() {
+    let (db, range) = TestDB::with_range(
+        r#"
+struct S<T, U = T, V = u8>(T, U, V);
+
+fn test(s: S<u32>) {
+    $0s$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let (adt, substs) = match &ty {
+        Ty::Adt(adt, substs) => (*adt, substs),
+        _ => panic!("expected an ADT, got {:?}", ty),
+    };
+    let u32_ty = Ty::Scalar(Scalar::Uint(UintTy::U32));
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+    assert_eq!(&substs[..], &[u32_ty.clone(), u32_ty.clone(), u8_ty.clone()]);
+
+    let defaults = db.generic_defaults_substs(adt.into());
+    assert_eq!(&defaults[..], &[Ty::Unknown, Ty::Unknown, u8_ty.clone()]);
+    let filled = Substs::build_for_def(&db, adt).fill_with_defaults(&db, adt.into()).build();
+    assert_eq!(filled, defaults);
+
+    let filled =
+        Substs::build_for_def(&db, adt).push(u32_ty.clone()).fill_with_defaults(&db, adt.into());
+    assert_eq!(&filled.build()[..], &[u32_ty.clone(), u32_ty, u8_ty]);
+}
+
+#[test]
+fn fill_with_defaults_hash_map() {
+    let (db, range) = TestDB::with_range(
+        r#"
+struct RandomState;
+struct HashMap<K, V, S = RandomState>(K, V, S);
+
+fn test(m: HashMap<u32, u8>, s: RandomState) {
+    $0(m, s)$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let fields = ty.as_tuple().unwrap();
+    let (adt, substs) = match &fields[0] {
+        Ty::Adt(adt, substs) => (*adt, substs),
+        ty => panic!("expected an ADT, got {:?}", ty),
+    };
+    let random_state = fields[1].clone();
+    let u32_ty = Ty::Scalar(Scalar::Uint(UintTy::U32));
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+    assert_eq!(&substs[..], &[u32_ty.clone(), u8_ty.clone(), random_state.clone()]);
+
+    let filled = Substs::build_for_def(&db, adt)
+        .push(u32_ty)
+        .push(u8_ty)
+        .fill_with_defaults(&db, adt.into())
+        .build();
+    assert_eq!(&filled, substs);
+    let filled = Substs::build_for_def(&db, adt).fill_with_defaults(&db, adt.into()).build();
+    assert_eq!(&filled[..], &[Ty::Unknown, Ty::Unknown, random_state]);
+}
+
+#[test]
+fn substs_apply() {
+    let u32_ty = Ty::Scalar(Scalar::Uint(UintTy::U32));
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+    let bound = |idx| Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, idx));
+
+    // `<[T1], (T0, u8)>`, stated in terms of some `<T0, T1>`
+    let inner = Substs(
+        vec![
+            Ty::Slice(Substs::single(bound(1))),
+            Ty::Tuple(2, Substs(vec![bound(0), u8_ty.clone()].into())),
+        ]
+        .into(),
+    );
+    let outer = Substs(vec![u32_ty.clone(), u8_ty.clone()].into());
+    let composed = inner.apply(&outer);
+    assert_eq!(
+        &composed[..],
+        &[
+            Ty::Slice(Substs::single(u8_ty.clone())),
+            Ty::Tuple(2, Substs(vec![u32_ty.clone(), u8_ty.clone()].into())),
+        ]
+    );
+
+    // `<T1>` in terms of `<U0, U1>`, then `<U0, U1>` in terms of `<V0>`
+    let first = Substs::single(bound(1));
+    let second = Substs(vec![u8_ty.clone(), Ty::Slice(Substs::single(bound(0)))].into());
+    let third = Substs::single(u32_ty.clone());
+    let expected = Substs::single(Ty::Slice(Substs::single(u32_ty)));
+    assert_eq!(first.apply(&second).apply(&third), expected);
+    assert_eq!(first.apply(&second.apply(&third)), expected);
+}
+
+#[test]
+fn contains_inference_var() {
+    let var = Ty::InferenceVar(InferenceVar::new_for_test(0), TyVariableKind::General);
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+    assert!(var.contains_inference_var());
+    assert!(Ty::Tuple(2, Substs(vec![u8_ty.clone(), Ty::Slice(Substs::single(var))].into()))
+        .contains_inference_var());
+    assert!(!Ty::Slice(Substs::single(u8_ty)).contains_inference_var());
+    assert!(!Ty::Unknown.contains_inference_var());
+}
@@ -1,7 +1,27 @@
+use std::sync::Arc;
+
+use base_db::fixture::WithFixture;
 use expect_test::expect;
+use hir_def::{db::DefDatabase, resolver::HasResolver, AdtId, ModuleDefId, ModuleId, TraitId};
+use hir_expand::name::name;
 use test_utils::mark;
 
-use super::{check_infer, check_infer_with_mismatches, check_types};
+use crate::{
+    db::HirDatabase,
+    display::HirDisplay,
+    object_safety::{is_object_safe, ObjectSafetyViolation},
+    primitive::UintTy,
+    test_db::TestDB,
+    traits::{
+        all_fields_implement, check_implements, implements, implied_bounds, is_well_formed,
+        normalize_assoc, normalize_projection, Implements, Solution, TraitSolverConfig,
+    },
+    BoundVar, Canonical, DebruijnIndex, GenericPredicate, InEnvironment, Obligation, OpaqueTy,
+    OpaqueTyId, ProjectionPredicate, ProjectionTy, Scalar, Substs, TraitEnvironment, TraitRef, Ty,
+    TypeWalk,
+};
+
+use super::{check_infer, check_infer_with_mismatches, check_types, expr_at_range, type_at_range};
 
 #[test]
 fn infer_await() {
@@ -85,6 +105,65 @@ mod future {
     );
 }
 
+#[test]
+fn infer_async_fn_item() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+async fn foo() -> u64 {
+    128
+}
+
+fn test() {
+    let f = foo;
+    f;
+} //^ fn foo() -> impl Future<Output = u64>
+
+//- /core.rs crate:core
+#[prelude_import] use future::*;
+mod future {
+    #[lang = "future_trait"]
+    trait Future {
+        type Output;
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_async_fn_body_returns_output() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+trait Default {
+    fn default() -> Self;
+}
+impl Default for u64 {
+    fn default() -> Self { loop {} }
+}
+impl Default for u32 {
+    fn default() -> Self { loop {} }
+}
+
+async fn foo() -> u64 {
+    let x = Default::default();
+    x
+  //^ u64
+}
+
+//- /core.rs crate:core
+#[prelude_import] use future::*;
+mod future {
+    #[lang = "future_trait"]
+    trait Future {
+        type Output;
+    }
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_async_block() {
     check_types(
@@ -161,6 +240,69 @@ mod result {
     );
 }
 
+#[test]
+fn infer_try_block() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+struct IoError;
+fn read() -> Result<u32, IoError> { loop {} }
+
+fn test() {
+    let r: Result<_, IoError> = try {
+        let n = read()?;
+        n
+    };
+    r;
+} //^ Result<u32, IoError>
+
+//- /core.rs crate:core
+#[prelude_import] use ops::*;
+pub mod ops {
+    pub trait Try {
+        type Ok;
+        type Error;
+    }
+}
+
+#[prelude_import] use result::*;
+pub mod result {
+    pub enum Result<O, E> {
+        Ok(O),
+        Err(E)
+    }
+
+    impl<O, E> crate::ops::Try for Result<O, E> {
+        type Ok = O;
+        type Error = E;
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_try_block_ok_from_context() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+fn test() {
+    let r: Result<u8, u32> = try { 1 };
+                                 //^ u8
+}
+
+//- /core.rs crate:core
+#[prelude_import] use result::*;
+pub mod result {
+    pub enum Result<O, E> {
+        Ok(O),
+        Err(E)
+    }
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_for_loop() {
     check_types(
@@ -364,6 +506,65 @@ fn trait_default_method_self_bound_implements_super_trait() {
     );
 }
 
+#[test]
+fn impl_bound_in_method_env() {
+    check_types(
+        r#"
+trait Serialize {
+    fn serialize(&self) -> u32;
+}
+trait Log {
+    fn log(&self) -> i64;
+}
+struct Handler<T>(T);
+impl<T: Serialize> Handler<T> {
+    fn handle<U: Log>(&self, t: T, u: U) {
+        t.serialize();
+      //^^^^^^^^^^^^^ u32
+        u.log();
+      //^^^^^^^ i64
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn trait_bound_in_default_method_env() {
+    check_types(
+        r#"
+trait Serialize {
+    fn serialize(&self) -> u32;
+}
+trait Handler<T: Serialize> {
+    fn handle(&self, t: T) {
+        t.serialize();
+      //^^^^^^^^^^^^^ u32
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn nested_fn_env() {
+    check_types(
+        r#"
+trait Serialize {
+    fn serialize(&self) -> u32;
+}
+fn outer<T: Serialize>(t: T) {
+    fn inner<U: Serialize>(u: U) {
+        u.serialize();
+      //^^^^^^^^^^^^^ u32
+    }
+    t.serialize();
+  //^^^^^^^^^^^^^ u32
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_project_associated_type() {
     check_infer(
@@ -436,6 +637,35 @@ fn infer_return_associated_type() {
     );
 }
 
+#[test]
+fn impl_method_signature_normalizes_own_assoc_types() {
+    check_types(
+        r#"
+trait Trait {
+    type A;
+    type B;
+    type C;
+    fn a(&self) -> Self::A;
+    fn c(&self) -> Self::C;
+}
+struct S;
+impl Trait for S {
+    type A = Self::B;
+    type B = (u32, Self::C);
+    type C = Self::C;
+    fn a(&self) -> Self::A { loop {} }
+    fn c(&self) -> Self::C { loop {} }
+}
+fn test() {
+    S::a;
+  //^^^^ fn a(&S) -> (u32, <S as Trait>::C)
+    S::c;
+  //^^^^ fn c(&S) -> <S as Trait>::C
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_associated_type_bound() {
     check_infer(
@@ -643,6 +873,93 @@ mod ops {
     );
 }
 
+#[test]
+fn infer_ops_index_ranges() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+fn test(s: &str, a: &[u8]) {
+    s[1..3];
+  //^^^^^^^ str
+    s[1..];
+  //^^^^^^ str
+    s[..3];
+  //^^^^^^ str
+    s[..];
+  //^^^^^ str
+    s[1..=3];
+  //^^^^^^^^ str
+    s[..=3];
+  //^^^^^^^ str
+    a[1];
+  //^^^^ u8
+    a[1..3];
+  //^^^^^^^ [u8]
+    a[1..];
+  //^^^^^^ [u8]
+    a[..3];
+  //^^^^^^ [u8]
+    a[..];
+  //^^^^^ [u8]
+    a[1..=3];
+  //^^^^^^^^ [u8]
+    a[..=3];
+  //^^^^^^^ [u8]
+}
+
+//- /core.rs crate:core
+pub mod ops {
+    #[lang = "index"]
+    pub trait Index<Idx: ?Sized> {
+        type Output: ?Sized;
+    }
+
+    pub struct Range<Idx> { pub start: Idx, pub end: Idx }
+    pub struct RangeFrom<Idx> { pub start: Idx }
+    pub struct RangeTo<Idx> { pub end: Idx }
+    pub struct RangeFull;
+    pub struct RangeInclusive<Idx> { start: Idx, end: Idx }
+    pub struct RangeToInclusive<Idx> { pub end: Idx }
+}
+
+pub mod slice {
+    use crate::ops::*;
+
+    pub trait SliceIndex<T: ?Sized> {
+        type Output: ?Sized;
+    }
+
+    impl<T> SliceIndex<[T]> for usize { type Output = T; }
+    impl<T> SliceIndex<[T]> for Range<usize> { type Output = [T]; }
+    impl<T> SliceIndex<[T]> for RangeFrom<usize> { type Output = [T]; }
+    impl<T> SliceIndex<[T]> for RangeTo<usize> { type Output = [T]; }
+    impl<T> SliceIndex<[T]> for RangeFull { type Output = [T]; }
+    impl<T> SliceIndex<[T]> for RangeInclusive<usize> { type Output = [T]; }
+    impl<T> SliceIndex<[T]> for RangeToInclusive<usize> { type Output = [T]; }
+
+    impl<T, I: SliceIndex<[T]>> Index<I> for [T] {
+        type Output = I::Output;
+    }
+}
+
+pub mod str {
+    use crate::{ops::*, slice::SliceIndex};
+
+    impl SliceIndex<str> for Range<usize> { type Output = str; }
+    impl SliceIndex<str> for RangeFrom<usize> { type Output = str; }
+    impl SliceIndex<str> for RangeTo<usize> { type Output = str; }
+    impl SliceIndex<str> for RangeFull { type Output = str; }
+    impl SliceIndex<str> for RangeInclusive<usize> { type Output = str; }
+    impl SliceIndex<str> for RangeToInclusive<usize> { type Output = str; }
+
+    impl<I: SliceIndex<str>> Index<I> for str {
+        type Output = I::Output;
+    }
+}
+"#,
+    );
+}
+
 #[test]
 fn deref_trait() {
     check_types(
@@ -1217,6 +1534,25 @@ fn simple_return_pos_impl_trait() {
     );
 }
 
+#[test]
+fn return_pos_impl_trait_displays_all_bounds() {
+    check_types(
+        r#"
+trait Clone {}
+trait Debug {}
+fn foo() -> impl Clone + Debug { loop {} }
+
+fn test() {
+    let a = foo();
+    a;
+  //^ impl Clone + Debug
+    &a;
+  //^^ &(impl Clone + Debug)
+}
+"#,
+    );
+}
+
 #[test]
 fn more_return_pos_impl_trait() {
     check_infer(
@@ -1276,6 +1612,106 @@ fn more_return_pos_impl_trait() {
     );
 }
 
+#[test]
+fn return_pos_impl_trait_indices_are_syntactic() {
+    // `impl A` never gets lowered because its path doesn't resolve, but it
+    // still takes up the first index
+    let (db, range) = TestDB::with_range(
+        r#"
+trait A {}
+trait B {}
+fn foo() -> (Unresolved<impl A>, impl B) { loop {} }
+fn test() {
+    $0foo()$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let mut indices = Vec::new();
+    ty.walk(&mut |ty| {
+        if let Ty::Opaque(opaque_ty) = ty {
+            if let OpaqueTyId::ReturnTypeImplTrait(_, idx) = opaque_ty.opaque_ty_id {
+                indices.push(idx);
+            }
+        }
+    });
+    assert_eq!(indices, vec![1]);
+    assert_eq!(ty.display(&db).to_string(), "({unknown}, impl B)");
+}
+
+fn check_hidden_ty(ra_fixture: &str, expect: Option<&str>) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let (func, idx) = match type_at_range(&db, range) {
+        Ty::Opaque(OpaqueTy {
+            opaque_ty_id: OpaqueTyId::ReturnTypeImplTrait(func, idx), ..
+        }) => (func, idx),
+        ty => panic!("not a return position impl trait: {:?}", ty),
+    };
+    let hidden_ty = db.return_type_impl_trait_hidden_ty(func, idx);
+    assert_eq!(hidden_ty.map(|ty| ty.display(&db).to_string()).as_deref(), expect);
+}
+
+#[test]
+fn return_pos_impl_trait_hidden_ty() {
+    check_hidden_ty(
+        r#"
+trait Iterator { type Item; }
+struct Counter;
+impl Iterator for Counter { type Item = u32; }
+fn make(b: bool) -> impl Iterator<Item = u32> {
+    if b {
+        return Counter;
+    }
+    let f = || { return 1u32; };
+    Counter
+}
+fn test() {
+    $0make(true)$0;
+}
+"#,
+        Some("Counter"),
+    );
+}
+
+#[test]
+fn return_pos_impl_trait_hidden_ty_disagrees() {
+    check_hidden_ty(
+        r#"
+trait Iterator { type Item; }
+struct Counter;
+struct Other;
+impl Iterator for Counter { type Item = u32; }
+impl Iterator for Other { type Item = u32; }
+fn make(b: bool) -> impl Iterator<Item = u32> {
+    if b {
+        return Counter;
+    }
+    Other
+}
+fn test() {
+    $0make(true)$0;
+}
+"#,
+        None,
+    );
+}
+
+#[test]
+fn return_pos_impl_trait_hidden_ty_recursive() {
+    check_hidden_ty(
+        r#"
+trait Iterator { type Item; }
+fn make(b: bool) -> impl Iterator<Item = u32> {
+    make(b)
+}
+fn test() {
+    $0make(true)$0;
+}
+"#,
+        None,
+    );
+}
+
 #[test]
 fn dyn_trait() {
     check_infer(
@@ -1501,6 +1937,30 @@ fn assoc_type_bindings() {
     );
 }
 
+#[test]
+fn where_clause_projection_equality_normalizes_everywhere() {
+    check_types(
+        r#"
+enum Option<T> { Some(T), None }
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+struct S;
+impl S { fn foo(&self) -> u8 { 0 } }
+
+fn test<T>(mut t: T, item: T::Item) where T: Iterator<Item = S> {
+    let x = t.next();
+    x;
+  //^ Option<S>
+    let y = item.foo();
+    y;
+  //^ u8
+}
+"#,
+    );
+}
+
 #[test]
 fn impl_trait_assoc_binding_projection_bug() {
     check_types(
@@ -1859,24 +2319,137 @@ fn fn_trait_deref_with_ty_default() {
 }
 
 #[test]
-fn closure_1() {
-    check_infer(
+fn hrtb_fn_bound() {
+    check_types(
         r#"
-        #[lang = "fn_once"]
-        trait FnOnce<Args> {
-            type Output;
-        }
-
-        enum Option<T> { Some(T), None }
-        impl<T> Option<T> {
-            fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Option<U> {}
-        }
+#[lang = "fn_once"]
+trait FnOnce<Args> {
+    type Output;
+}
+#[lang = "deref"]
+trait Deref {
+    type Target: ?Sized;
+}
+struct String;
+impl Deref for String {
+    type Target = str;
+}
 
-        fn test() {
-            let x = Option::Some(1u32);
-            x.map(|v| v + 1);
-            x.map(|_v| 1u64);
-            let y: Option<i64> = x.map(|_v| 1);
+fn apply<F>(f: F)
+where
+    F: for<'a> FnOnce(&'a str) -> &'a str,
+{
+    let s = String;
+    let r = f(&s);
+    r;
+  //^ &str
+}
+"#,
+    );
+}
+
+#[test]
+fn hrtb_trait_bound() {
+    check_types(
+        r#"
+trait Deserialize<'de>: Sized {
+    fn deserialize(s: &'de str) -> Self;
+}
+struct S;
+impl<'de> Deserialize<'de> for S {
+    fn deserialize(s: &'de str) -> S { S }
+}
+
+fn from_str<T>(s: &str) -> T
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let t = T::deserialize(s);
+    t
+  //^ T
+}
+
+fn test() {
+    let x = from_str::<S>("");
+    x;
+  //^ S
+}
+"#,
+    );
+}
+
+#[test]
+fn hrtb_bound_keeps_its_binder() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Deserialize<'de> {}
+
+fn test<T: for<'de> Deserialize<'de>>(t: T) {
+    $0t$0;
+}
+"#,
+    );
+    let (func, expr) = expr_at_range(&db, range);
+    let param_id = match &db.infer(func.into())[expr] {
+        Ty::Placeholder(id) => *id,
+        ty => panic!("not a type parameter: {:?}", ty),
+    };
+    let preds = db.generic_predicates_for_param(param_id);
+    match &preds[..] {
+        [pred] => match &pred.value {
+            GenericPredicate::ForLifetimes(1, inner) => {
+                assert!(matches!(**inner, GenericPredicate::Implemented(_)))
+            }
+            pred => panic!("expected a higher-ranked predicate: {:?}", pred),
+        },
+        preds => panic!("expected one predicate: {:?}", preds),
+    }
+}
+
+#[test]
+fn hrtb_closure_param() {
+    check_types(
+        r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> {
+    type Output;
+}
+
+fn for_each<T, F>(items: &[T], f: F)
+where
+    F: for<'a> FnOnce(&'a T),
+{
+}
+
+fn test() {
+    for_each(&[1u32, 2], |x| {
+        x;
+      //^ &u32
+    });
+}
+"#,
+    );
+}
+
+#[test]
+fn closure_1() {
+    check_infer(
+        r#"
+        #[lang = "fn_once"]
+        trait FnOnce<Args> {
+            type Output;
+        }
+
+        enum Option<T> { Some(T), None }
+        impl<T> Option<T> {
+            fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Option<U> {}
+        }
+
+        fn test() {
+            let x = Option::Some(1u32);
+            x.map(|v| v + 1);
+            x.map(|_v| 1u64);
+            let y: Option<i64> = x.map(|_v| 1);
         }
         "#,
         expect![[r#"
@@ -2052,6 +2625,143 @@ fn test() {
     );
 }
 
+#[test]
+fn closure_fn_trait_output_in_map_chain() {
+    check_types(
+        r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> { type Output; }
+#[lang = "fn_mut"]
+trait FnMut<Args>: FnOnce<Args> {}
+#[lang = "fn"]
+trait Fn<Args>: FnMut<Args> {}
+enum Option<T> { Some(T), None }
+impl<T> Option<T> {
+    fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Option<U> { loop {} }
+    fn filter<P: FnOnce(&T) -> bool>(self, predicate: P) -> Self { loop {} }
+}
+
+fn test(x: Option<u32>) {
+    let y = x.map(|v| v as u64).filter(|v| *v > 0).map(|v| (v, true));
+    y;
+  //^ Option<(u64, bool)>
+}
+"#,
+    );
+}
+
+#[test]
+fn fn_item_and_fn_ptr_implement_fn_traits() {
+    check_types(
+        r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> { type Output; }
+#[lang = "fn_mut"]
+trait FnMut<Args>: FnOnce<Args> {}
+#[lang = "fn"]
+trait Fn<Args>: FnMut<Args> {}
+fn apply<R>(f: impl Fn(u32) -> R) -> R { loop {} }
+fn apply_once<R>(f: impl FnOnce(u32) -> R) -> R { loop {} }
+fn foo(x: u32) -> i64 { 0 }
+
+fn test(p: fn(u32) -> bool) {
+    apply(foo);
+  //^^^^^^^^^^ i64
+    apply_once(p);
+  //^^^^^^^^^^^^^ bool
+}
+"#,
+    );
+}
+
+#[test]
+fn closure_satisfies_fn_mut_bound() {
+    check_types(
+        r#"
+#[lang = "fn_once"]
+trait FnOnce<Args> { type Output; }
+#[lang = "fn_mut"]
+trait FnMut<Args>: FnOnce<Args> {}
+#[lang = "fn"]
+trait Fn<Args>: FnMut<Args> {}
+fn call_mut<R, F: FnMut(u8) -> R>(mut f: F) -> R { loop {} }
+
+fn test() {
+    let mut count = 0u32;
+    call_mut(|b| { count += 1; b as u16 });
+  //^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ u16
+}
+"#,
+    );
+}
+
+#[test]
+fn closure_kind_from_capture_usage() {
+    check_types(
+        r#"
+#[lang = "copy"]
+trait Copy {}
+#[lang = "fn_once"]
+trait FnOnce<Args> { type Output; }
+#[lang = "fn_mut"]
+trait FnMut<Args>: FnOnce<Args> {}
+#[lang = "fn"]
+trait Fn<Args>: FnMut<Args> {}
+trait IsFn { fn is_fn(&self) -> u8 { 0 } }
+impl<F: Fn()> IsFn for F {}
+trait IsFnMut { fn is_fn_mut(&self) -> u16 { 0 } }
+impl<F: FnMut()> IsFnMut for F {}
+trait IsFnOnce { fn is_fn_once(&self) -> u32 { 0 } }
+impl<F: FnOnce()> IsFnOnce for F {}
+
+enum Option<T> { Some(T), None }
+struct S;
+impl S {
+    fn by_ref(&self) {}
+    fn by_mut(&mut self) {}
+    fn by_value(self) {}
+}
+fn consume(s: S) {}
+
+fn test(s: S, t: S, u: S, v: Option<S>) {
+    let mut n = 0u32;
+    let reads = || { s.by_ref(); let m = n; };
+    reads.is_fn();
+  //^^^^^^^^^^^^^ u8
+    let mutates = || { n += 1; };
+    mutates.is_fn();
+  //^^^^^^^^^^^^^^^ {unknown}
+    mutates.is_fn_mut();
+  //^^^^^^^^^^^^^^^^^^^ u16
+    let borrows_mut = || t.by_mut();
+    borrows_mut.is_fn();
+  //^^^^^^^^^^^^^^^^^^^ {unknown}
+    borrows_mut.is_fn_mut();
+  //^^^^^^^^^^^^^^^^^^^^^^^ u16
+    let moves = || consume(u);
+    moves.is_fn_mut();
+  //^^^^^^^^^^^^^^^^^ {unknown}
+    moves.is_fn_once();
+  //^^^^^^^^^^^^^^^^^^ u32
+    let matches = || match v { Option::Some(it) => it.by_value(), Option::None => {} };
+    matches.is_fn_mut();
+  //^^^^^^^^^^^^^^^^^^^ {unknown}
+    let matches_by_ref = || match &v { Option::Some(it) => it.by_ref(), Option::None => {} };
+    matches_by_ref.is_fn();
+  //^^^^^^^^^^^^^^^^^^^^^^ u8
+    let calls_fn_mut = || mutates();
+    calls_fn_mut.is_fn();
+  //^^^^^^^^^^^^^^^^^^^^ {unknown}
+    calls_fn_mut.is_fn_mut();
+  //^^^^^^^^^^^^^^^^^^^^^^^^ u16
+    let own_locals = || { let mut y = S; y.by_mut(); consume(y) };
+    own_locals.is_fn();
+  //^^^^^^^^^^^^^^^^^^ u8
+}
+"#,
+    );
+}
+
 #[test]
 fn unselected_projection_in_trait_env_1() {
     check_types(
@@ -2299,6 +3009,35 @@ fn test<I: Iterator<Item: Iterator<Item = u32>>>() {
     );
 }
 
+#[test]
+fn inline_assoc_type_bounds_in_argument_position() {
+    check_types(
+        r#"
+mod fmt {
+    pub trait Display {
+        fn fmt(&self) -> u128;
+    }
+}
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+enum Option<T> { Some(T), None }
+impl<T> Option<T> {
+    fn unwrap(self) -> T { loop {} }
+}
+
+fn test(mut iter: impl Iterator<Item: fmt::Display>) {
+    iter;
+  //^^^^ impl Iterator<Item: Display>
+    let item = iter.next().unwrap();
+    item.fmt();
+  //^^^^^^^^^^ u128
+}
+"#,
+    );
+}
+
 #[test]
 fn proc_macro_server_types() {
     check_infer(
@@ -3095,6 +3834,48 @@ fn foo() {
     );
 }
 
+#[test]
+fn infer_higher_ranked_fn_bounds() {
+    check_types(
+        r#"
+#[lang = "fn_once"]
+pub trait FnOnce<Args> {
+    type Output;
+    extern "rust-call" fn call_once(self, args: Args) -> Self::Output;
+}
+
+#[lang = "fn"]
+pub trait Fn<Args>: FnOnce<Args> {
+    extern "rust-call" fn call(&self, args: Args) -> Self::Output;
+}
+
+trait Trait<'a> { fn get(&self) -> &'a u8; }
+
+fn test<F, G, T>(d: &dyn for<'a> Fn(&'a str) -> &'a str, f: F, g: G, t: T)
+where
+    F: for<'a> Fn(&'a str) -> &'a str,
+    G: for<'a, 'b> Fn(&'a u8, &'b u8) -> u32,
+    for<'a> T: Trait<'a>,
+{
+    d("");
+  //^^^^^ &str
+    f("");
+  //^^^^^ &str
+    g(&0, &1);
+  //^^^^^^^^^ u32
+    t.get();
+  //^^^^^^^ &u8
+}
+
+fn test_impl(f: impl for<'a> Fn(&'a [u8]) -> Option<&'a u8>) {
+    f(&[]);
+  //^^^^^^ Option<&u8>
+}
+enum Option<T> { Some(T), None }
+"#,
+    );
+}
+
 #[test]
 fn variable_kinds_1() {
     check_types(
@@ -3205,3 +3986,1368 @@ fn f() {
         "#,
     );
 }
+
+#[test]
+fn specialized_assoc_type_prefers_most_specific_impl() {
+    check_types(
+        r#"
+trait Foo {
+    type Out;
+    fn foo(&self) -> Self::Out;
+}
+impl<T> Foo for T {
+    default type Out = ();
+    default fn foo(&self) -> Self::Out { loop {} }
+}
+impl Foo for u32 {
+    type Out = i64;
+    fn foo(&self) -> i64 { 0 }
+}
+struct S;
+
+fn test() {
+    5u32.foo();
+  //^^^^^^^^^^ i64
+    S.foo();
+  //^^^^^^^ ()
+}
+        "#,
+    );
+}
+
+#[test]
+fn specialized_assoc_type_compares_type_arguments() {
+    check_types(
+        r#"
+trait Foo {
+    type Out;
+}
+struct Wrap<T>(T);
+impl<T> Foo for Wrap<T> {
+    default type Out = ();
+}
+impl Foo for Wrap<u32> {
+    type Out = i64;
+}
+
+fn test(a: <Wrap<u32> as Foo>::Out, b: <Wrap<u8> as Foo>::Out) {
+    a;
+  //^ i64
+    b;
+  //^ ()
+}
+        "#,
+    );
+}
+
+#[test]
+fn specialization_does_not_pick_impl_for_unknown_self_type() {
+    check_types(
+        r#"
+trait Copy {}
+trait Foo {}
+struct Wrap<T>(T);
+struct Local;
+impl<T: Copy> Foo for Wrap<T> {}
+impl Foo for Wrap<Local> {}
+fn make<T>() -> T { loop {} }
+fn foo<F: Foo>(f: F) {}
+
+fn test() {
+    let x = make();
+    foo(Wrap(x));
+    x;
+  //^ {unknown}
+}
+        "#,
+    );
+}
+
+#[test]
+fn normalize_projection_with_unknowns() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Deref { type Target; }
+struct MyVec<T>(T);
+impl<T> Deref for MyVec<T> { type Target = [T]; }
+struct Single;
+impl Deref for Single { type Target = u32; }
+
+fn test(v: MyVec<u8>) {
+    $0v$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let module = db.module_for_file(range.file_id);
+    let krate = module.krate();
+    let deref = find_trait(&db, module, "Deref");
+    let target = db.trait_data(deref).associated_type_by_name(&name![Target]).unwrap();
+    let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+    let normalize = |self_ty: Ty| {
+        let projection =
+            ProjectionTy { associated_ty: target, parameters: Substs::single(self_ty) };
+        normalize_projection(&db, krate, projection, env.clone())
+            .map(|ty| ty.display(&db).to_string())
+    };
+
+    let adt = match ty.as_adt() {
+        Some((adt, _)) => adt,
+        None => panic!("not an ADT: {:?}", ty),
+    };
+    assert_eq!(normalize(ty.clone()).as_deref(), Some("[u8]"));
+    assert_eq!(normalize(Ty::Adt(adt, Substs::single_unknown())).as_deref(), Some("[{unknown}]"));
+    // could be either impl
+    assert_eq!(normalize(Ty::Unknown), None);
+}
+
+#[test]
+fn normalize_assoc_picks_impl_value() {
+    let (db, range) = TestDB::with_range(
+        r#"
+#[lang = "deref"]
+trait Deref { type Target; }
+struct MyVec<T>(T);
+impl<T> Deref for MyVec<T> { type Target = [T]; }
+
+fn test(v: MyVec<u8>) {
+    $0v$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let krate = db.module_for_file(range.file_id).krate();
+    let deref = db.lang_item(krate, "deref".into()).and_then(|it| it.as_trait()).unwrap();
+    let target = db.trait_data(deref).associated_type_by_name(&name![Target]).unwrap();
+
+    let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+    let trait_ref = TraitRef { trait_: deref, substs: Substs::single(ty) };
+    let normalized = normalize_assoc(&db, krate, env, trait_ref, target);
+    assert_eq!(normalized.display(&db).to_string(), "[u8]");
+
+    let impl_id = db.trait_impls_in_crate(krate).for_trait(deref).next().unwrap();
+    let impl_ty = db.impl_assoc_ty(impl_id, target);
+    let u32_ty = Ty::Scalar(Scalar::Uint(UintTy::U32));
+    assert_eq!(impl_ty.subst(&Substs::single(u32_ty)).display(&db).to_string(), "[u32]");
+}
+
+#[test]
+fn normalize_assoc_selects_impl_by_trait_args_and_where_clauses() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+trait Marker {}
+impl Marker for u8 {}
+trait Convert<T> { type Output; }
+struct S;
+struct W<T>(T);
+impl Convert<u8> for S { type Output = u16; }
+impl Convert<u16> for S { type Output = u32; }
+impl<T: Marker> Convert<T> for W<T> { type Output = [T]; }
+"#,
+    );
+    let krate = db.module_for_file(file_id).krate();
+    let module = db.module_for_file(file_id);
+    let def_map = db.crate_def_map(krate);
+    let scope = &def_map[module.local_id].scope;
+    let convert = scope
+        .declarations()
+        .find_map(|def| match def {
+            ModuleDefId::TraitId(it) if db.trait_data(it).name.to_string() == "Convert" => Some(it),
+            _ => None,
+        })
+        .unwrap();
+    let adt = |name: &str| {
+        scope
+            .declarations()
+            .find_map(|def| match def {
+                ModuleDefId::AdtId(it @ AdtId::StructId(s))
+                    if db.struct_data(s).name.to_string() == name =>
+                {
+                    Some(it)
+                }
+                _ => None,
+            })
+            .unwrap()
+    };
+    let output = db.trait_data(convert).associated_type_by_name(&name![Output]).unwrap();
+    let uint = |ty| Ty::Scalar(Scalar::Uint(ty));
+    let normalize = |self_ty: Ty, arg: Ty| {
+        let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+        let substs = Substs::build_for_def(&db, convert).push(self_ty).push(arg).build();
+        let trait_ref = TraitRef { trait_: convert, substs };
+        normalize_assoc(&db, krate, env, trait_ref, output).display(&db).to_string()
+    };
+
+    let s = Ty::Adt(adt("S"), Substs::empty());
+    assert_eq!(normalize(s.clone(), uint(UintTy::U8)), "u16");
+    assert_eq!(normalize(s, uint(UintTy::U16)), "u32");
+    let w = |ty: Ty| Ty::Adt(adt("W"), Substs::single(ty));
+    assert_eq!(normalize(w(uint(UintTy::U8)), uint(UintTy::U8)), "[u8]");
+    // `u16: Marker` doesn't hold, so no impl applies
+    assert_eq!(
+        normalize(w(uint(UintTy::U16)), uint(UintTy::U16)),
+        "<W<u16> as Convert<u16>>::Output"
+    );
+}
+
+#[test]
+fn self_assoc_type_in_default_method_body() {
+    check_types(
+        r#"
+trait Trait {
+    type Item;
+    fn get(&self) -> Self::Item;
+    fn get_twice(&self) {
+        let a: Self::Item = self.get();
+        a;
+      //^ Trait::Item<Self>
+        let b: <Self as Trait>::Item = self.get();
+        b;
+      //^ Trait::Item<Self>
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn self_assoc_type_prefers_own_trait_over_supertrait() {
+    check_types(
+        r#"
+trait Super {
+    type Item;
+}
+trait Trait: Super {
+    type Item;
+    fn f(&self) {
+        let a: Self::Item = loop {};
+        a;
+      //^ Trait::Item<Self>
+        let b: <Self as Super>::Item = loop {};
+        b;
+      //^ Super::Item<Self>
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn self_assoc_type_in_impl_body() {
+    check_types(
+        r#"
+trait Trait {
+    type Item;
+    fn get(&self) -> Self::Item;
+}
+struct S;
+impl Trait for S {
+    type Item = u32;
+    fn get(&self) -> Self::Item {
+        let a: Self::Item = 0;
+        a;
+      //^ u32
+        let b: <Self as Trait>::Item = 0;
+        b;
+      //^ u32
+        a
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn generic_predicates_with_assoc_type_binding() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+trait Iterator { type Item; }
+fn foo<T, U>() where T: Iterator<Item = U> {}
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let iterator = find_trait(&db, module, "Iterator");
+    let item = db.trait_data(iterator).associated_type_by_name(&name![Item]).unwrap();
+    let def_map = module.def_map(&db);
+    let foo = def_map[module.local_id]
+        .scope
+        .declarations()
+        .find_map(|def| match def {
+            ModuleDefId::FunctionId(it) => Some(it),
+            _ => None,
+        })
+        .unwrap();
+
+    let predicates = db.generic_predicates(foo.into());
+    let t = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 0));
+    let u = Ty::Bound(BoundVar::new(DebruijnIndex::INNERMOST, 1));
+    let values: Vec<_> = predicates.iter().map(|pred| pred.value.clone()).collect();
+    assert_eq!(
+        values,
+        vec![
+            GenericPredicate::Implemented(TraitRef {
+                trait_: iterator,
+                substs: Substs::single(t.clone()),
+            }),
+            GenericPredicate::Projection(ProjectionPredicate {
+                projection_ty: ProjectionTy { associated_ty: item, parameters: Substs::single(t) },
+                ty: u,
+            }),
+        ]
+    );
+    assert!(predicates.iter().all(|pred| pred.num_binders == 2));
+}
+
+fn find_trait(db: &TestDB, module: ModuleId, name: &str) -> TraitId {
+    let def_map = module.def_map(db);
+    let trait_ = def_map[module.local_id]
+        .scope
+        .declarations()
+        .find_map(|def| match def {
+            ModuleDefId::TraitId(it) if db.trait_data(it).name.to_string() == name => Some(it),
+            _ => None,
+        })
+        .unwrap();
+    trait_
+}
+
+#[test]
+fn trait_ref_for_lang_item() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[lang = "copy"]
+trait Copy {}
+#[lang = "iterator"]
+trait Iterator {
+    type Item;
+}
+#[lang = "add"]
+trait Add<Rhs> {}
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let krate = module.krate();
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+
+    let copy = TraitRef::for_lang_item(&db, krate, "copy", u8_ty.clone(), &[]).unwrap();
+    assert_eq!(copy.trait_, find_trait(&db, module, "Copy"));
+    assert_eq!(&copy.substs[..], &[u8_ty.clone()]);
+
+    let iterator = TraitRef::for_lang_item(&db, krate, "iterator", u8_ty.clone(), &[]).unwrap();
+    assert_eq!(iterator.trait_, find_trait(&db, module, "Iterator"));
+    assert_eq!(iterator.self_ty(), &u8_ty);
+
+    let add = TraitRef::for_lang_item(&db, krate, "add", u8_ty.clone(), &[]).unwrap();
+    assert_eq!(&add.substs[..], &[u8_ty.clone(), Ty::Unknown]);
+
+    assert!(TraitRef::for_lang_item(&db, krate, "copy", u8_ty.clone(), &[u8_ty.clone()]).is_none());
+    assert!(TraitRef::for_lang_item(&db, krate, "sized", u8_ty, &[]).is_none());
+}
+
+fn check_implements_at_range(
+    ra_fixture: &str,
+    trait_name: &str,
+    args: &[Ty],
+    expected: Implements,
+) {
+    let (db, range) = TestDB::with_range(ra_fixture);
+    let ty = type_at_range(&db, range);
+    let module = db.module_for_file(range.file_id);
+    let trait_ = find_trait(&db, module, trait_name);
+    let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+    let krate = module.krate();
+    assert_eq!(check_implements(&db, krate, env.clone(), trait_, ty.clone(), args), expected);
+    assert_eq!(implements(&db, krate, env, trait_, ty, args), expected == Implements::Yes);
+}
+
+#[test]
+fn check_implements_yes_and_no() {
+    let fixture = r#"
+trait Trait {}
+trait Other {}
+struct S;
+impl Trait for S {}
+
+fn test() {
+    $0S$0;
+}
+"#;
+    check_implements_at_range(fixture, "Trait", &[], Implements::Yes);
+    check_implements_at_range(fixture, "Other", &[], Implements::No);
+}
+
+#[test]
+fn check_implements_with_args() {
+    let fixture = r#"
+trait Convert<T> {}
+struct S;
+impl Convert<u32> for S {}
+
+fn test() {
+    $0S$0;
+}
+"#;
+    let u32_ty = Ty::Scalar(Scalar::Uint(UintTy::U32));
+    let u8_ty = Ty::Scalar(Scalar::Uint(UintTy::U8));
+    check_implements_at_range(fixture, "Convert", &[u32_ty], Implements::Yes);
+    check_implements_at_range(fixture, "Convert", &[u8_ty], Implements::No);
+    // a missing argument can be anything, and there's only one impl
+    check_implements_at_range(fixture, "Convert", &[], Implements::Yes);
+}
+
+#[test]
+fn check_implements_ambiguous() {
+    let fixture = r#"
+trait Trait {}
+struct W<T>(T);
+impl Trait for W<u32> {}
+impl Trait for W<i64> {}
+
+fn test() {
+    $0W(unknown())$0;
+}
+"#;
+    check_implements_at_range(fixture, "Trait", &[], Implements::Maybe);
+}
+
+const WELL_FORMED_FIXTURE: &str = r#"
+#[lang = "sized"]
+trait Sized {}
+#[lang = "copy"]
+trait Copy {}
+trait Trait {}
+struct NeedsCopy<T: Copy>(T);
+struct S;
+impl Copy for u32 {}
+
+fn test(a: &dyn Sized, b: &dyn Trait, c: NeedsCopy<S>, d: &[NeedsCopy<u32>]) {
+    a;
+    b;
+    c;
+    d;
+}
+"#;
+
+fn check_well_formed(expr: &str, expected: bool) {
+    let fixture =
+        WELL_FORMED_FIXTURE.replace(&format!("    {};", expr), &format!("    $0{}$0;", expr));
+    let (db, range) = TestDB::with_range(&fixture);
+    let ty = type_at_range(&db, range);
+    let krate = db.module_for_file(range.file_id).krate();
+    let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+    assert_eq!(is_well_formed(&db, krate, env, &ty), expected, "{}", ty.display(&db));
+}
+
+#[test]
+fn well_formed_types() {
+    check_well_formed("a", false);
+    check_well_formed("b", true);
+    check_well_formed("c", false);
+    check_well_formed("d", true);
+}
+
+#[test]
+fn all_fields_implement_partial_eq() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[lang = "eq"]
+trait PartialEq<Rhs: ?Sized = Self> {}
+impl PartialEq for u32 {}
+impl PartialEq<u64> for u32 {}
+struct NoEq;
+struct OnlyU64;
+impl PartialEq<u64> for OnlyU64 {}
+
+struct Good<T> { a: u32, b: T }
+struct Bad<T> { a: u32, b: T, c: NoEq }
+enum BadEnum { A(u32), B(OnlyU64) }
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let krate = module.krate();
+    let partial_eq = find_trait(&db, module, "PartialEq");
+    let def_map = module.def_map(&db);
+    let check = |name: &str| {
+        let adt = def_map[module.local_id]
+            .scope
+            .declarations()
+            .find_map(|def| match def {
+                ModuleDefId::AdtId(AdtId::StructId(it))
+                    if db.struct_data(it).name.to_string() == name =>
+                {
+                    Some(it.into())
+                }
+                ModuleDefId::AdtId(AdtId::EnumId(it))
+                    if db.enum_data(it).name.to_string() == name =>
+                {
+                    Some(it.into())
+                }
+                _ => None,
+            })
+            .unwrap();
+        all_fields_implement(&db, krate, adt, partial_eq).map(|ty| ty.display(&db).to_string())
+    };
+    assert_eq!(check("Good"), None);
+    assert_eq!(check("Bad").as_deref(), Some("NoEq"));
+    assert_eq!(check("BadEnum").as_deref(), Some("OnlyU64"));
+}
+
+#[test]
+fn self_in_assoc_type_bound_refers_to_trait_self() {
+    check_types(
+        r#"
+trait Node {
+    type Child: Node<Parent = Self>;
+    type Parent;
+    fn child(&self) -> Self::Child;
+    fn parent(&self) -> Self::Parent;
+}
+
+fn test<T: Node>(t: T) {
+    t.child().parent();
+  //^^^^^^^^^^^^^^^^^^ T
+    t.child().child().parent().parent();
+  //^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ T
+}
+"#,
+    );
+}
+
+#[test]
+fn self_in_assoc_type_bound_multi_level() {
+    check_types(
+        r#"
+trait Convert<T> {}
+trait Graph {
+    type Node: Convert<Self::Edge>;
+    type Edge: Convert<Self>;
+    fn node(&self) -> Self::Node;
+}
+trait Into2<T> { fn into2(self) -> T; }
+impl<T, U: Convert<T>> Into2<T> for U { fn into2(self) -> T { loop {} } }
+
+fn test<G: Graph>(g: G) {
+    let edge: G::Edge = g.node().into2();
+    let graph = edge.into2();
+    graph;
+  //^^^^^ G
+}
+"#,
+    );
+}
+
+#[test]
+fn trait_solve_results_are_shared_between_bodies() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[lang = "clone"]
+trait Clone { fn clone(&self) -> Self; }
+impl Clone for u8 { fn clone(&self) -> Self { *self } }
+impl Clone for u64 { fn clone(&self) -> Self { *self } }
+trait Trait { fn method(&self) -> u32; }
+struct S<T>(T);
+impl<T: Clone> Clone for S<T> { fn clone(&self) -> Self { loop {} } }
+impl<T: Clone> Trait for S<T> { fn method(&self) -> u32 { 0 } }
+
+fn f1() {
+    let s = S(1u8);
+    s.method();
+    let t = S(S(2u64)).clone();
+    t.method();
+}
+
+// the where clause doesn't matter for any of the goals
+fn f2<X: Clone>(x: X) {
+    let s = S(1u8);
+    s.method();
+    let t = S(S(2u64)).clone();
+    t.method();
+}
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let def_map = module.def_map(&db);
+    let function = |name: &str| {
+        def_map[module.local_id]
+            .scope
+            .declarations()
+            .find_map(|def| match def {
+                ModuleDefId::FunctionId(it) if db.function_data(it).name.to_string() == name => {
+                    Some(it)
+                }
+                _ => None,
+            })
+            .unwrap()
+    };
+    let trait_solves = |name: &str| {
+        let func = function(name);
+        let events = db.log_executed(|| {
+            db.infer(func.into());
+        });
+        events.iter().filter(|it| it.starts_with("trait_solve_query(")).count()
+    };
+    let first = trait_solves("f1");
+    let second = trait_solves("f2");
+    assert!(first > 0);
+    assert!(second * 2 < first, "{} solves for f1, {} for f2", first, second);
+}
+
+#[test]
+fn where_clause_without_params_is_used_for_concrete_goals() {
+    check_types(
+        r#"
+trait Foo { type Out; fn foo(&self) -> Self::Out; }
+trait Bar {}
+struct S;
+
+fn test<T: Bar>(t: T) where S: Foo<Out = u8> {
+    S.foo();
+  //^^^^^^^ u8
+}
+"#,
+    );
+}
+
+#[test]
+fn recursive_impl_chain_overflow_is_ambiguous() {
+    let (mut db, range) = TestDB::with_range(
+        r#"
+trait Trait {}
+struct S<T>(T);
+impl<T> Trait for S<T> where S<S<T>>: Trait {}
+fn test() {
+    let s = S(0u32);
+    $0s$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let module = db.module_for_file(range.file_id);
+    let trait_ = find_trait(&db, module, "Trait");
+    let krate = module.krate();
+    let env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+
+    // With the default limits, the goal gets truncated.
+    assert_eq!(
+        check_implements(&db, krate, env.clone(), trait_, ty.clone(), &[]),
+        Implements::Maybe
+    );
+
+    db.set_trait_solver_config(TraitSolverConfig {
+        fuel: 10,
+        max_size: 1000,
+        ..TraitSolverConfig::default()
+    });
+    let trait_ref = TraitRef { trait_, substs: Substs::single(ty.clone()) };
+    let goal = Canonical::new(InEnvironment::new(env.clone(), Obligation::Trait(trait_ref)), None);
+    assert_eq!(db.trait_solve(krate, goal), Some(Solution::Overflow));
+    assert_eq!(check_implements(&db, krate, env, trait_, ty, &[]), Implements::Maybe);
+}
+
+const AUTO_TRAIT_PRELUDE: &str = r#"
+auto trait Send {}
+struct Rc<T>(*const T);
+impl<T> !Send for Rc<T> {}
+#[lang = "phantom_data"]
+struct PhantomData<T>;
+#[lang = "future_trait"]
+trait Future { type Output; }
+fn rc() -> Rc<u32> { loop {} }
+"#;
+
+fn check_send(ra_fixture: &str, expected: Implements) {
+    let fixture = format!("{}{}", AUTO_TRAIT_PRELUDE, ra_fixture);
+    check_implements_at_range(&fixture, "Send", &[], expected);
+}
+
+#[test]
+fn auto_trait_structural_for_adts() {
+    check_send(
+        r#"
+struct Plain { a: u32, b: (i64, [u8; 2]), c: &'static str }
+fn test(p: Plain) { $0p$0; }
+"#,
+        Implements::Yes,
+    );
+    check_send(
+        r#"
+fn test() { let r = rc(); $0r$0; }
+"#,
+        Implements::No,
+    );
+    check_send(
+        r#"
+struct S<T> { x: u32, rc: Rc<T> }
+fn test(s: S<u32>) { $0s$0; }
+"#,
+        Implements::No,
+    );
+    check_send(
+        r#"
+enum E { A(u32), B(Option<Rc<u8>>) }
+enum Option<T> { Some(T), None }
+fn test(e: E) { $0e$0; }
+"#,
+        Implements::No,
+    );
+}
+
+#[test]
+fn auto_trait_phantom_data_delegates_to_parameter() {
+    check_send(
+        r#"
+struct W<T>(PhantomData<T>);
+fn test(w: W<u32>) { $0w$0; }
+"#,
+        Implements::Yes,
+    );
+    check_send(
+        r#"
+struct W<T>(PhantomData<T>);
+fn test(w: W<Rc<u32>>) { $0w$0; }
+"#,
+        Implements::No,
+    );
+}
+
+#[test]
+fn auto_trait_leaks_through_async_block() {
+    check_send(
+        r#"
+fn test() {
+    let x = 1u32;
+    let fut = async move { x };
+    $0fut$0;
+}
+"#,
+        Implements::Yes,
+    );
+    check_send(
+        r#"
+async fn yield_now() {}
+fn test() {
+    let fut = async {
+        let r = rc();
+        yield_now().await;
+        r
+    };
+    $0fut$0;
+}
+"#,
+        Implements::No,
+    );
+}
+
+#[test]
+fn auto_trait_leaks_through_return_position_impl_trait() {
+    check_send(
+        r#"
+trait Tr {}
+impl<T> Tr for T {}
+fn wrap<T>(t: T) -> impl Tr { t }
+fn test() {
+    let w = wrap(1u32);
+    $0w$0;
+}
+"#,
+        Implements::Yes,
+    );
+    check_send(
+        r#"
+trait Tr {}
+impl<T> Tr for T {}
+fn wrap<T>(t: T) -> impl Tr { t }
+fn test() {
+    let w = wrap(rc());
+    $0w$0;
+}
+"#,
+        Implements::No,
+    );
+}
+
+#[test]
+fn fundamental_flag_and_blanket_impl_through_box() {
+    let fixture = r#"
+#[lang = "owned_box"]
+#[fundamental]
+struct Box<T>(T);
+trait Trait {}
+struct S;
+impl Trait for S {}
+impl<T: Trait> Trait for Box<T> {}
+
+fn test() {
+    let b = Box(Box(S));
+    $0b$0;
+}
+"#;
+    check_implements_at_range(fixture, "Trait", &[], Implements::Yes);
+
+    let (db, range) = TestDB::with_range(fixture);
+    let ty = type_at_range(&db, range);
+    let krate = db.module_for_file(range.file_id).krate();
+    let (adt, _) = ty.as_adt().unwrap();
+    let datum = db.struct_datum(krate, chalk_ir::AdtId(adt));
+    assert!(datum.flags.fundamental);
+    let s = ty.substs().unwrap()[0].substs().unwrap()[0].clone();
+    let datum = db.struct_datum(krate, chalk_ir::AdtId(s.as_adt().unwrap().0));
+    assert!(!datum.flags.fundamental);
+}
+
+#[test]
+fn blanket_impl_through_box_requires_inner_impl() {
+    check_implements_at_range(
+        r#"
+#[lang = "owned_box"]
+#[fundamental]
+struct Box<T>(T);
+trait Trait {}
+struct S;
+impl<T: Trait> Trait for Box<T> {}
+
+fn test() {
+    let b = Box(S);
+    $0b$0;
+}
+"#,
+        "Trait",
+        &[],
+        Implements::No,
+    );
+}
+
+#[test]
+fn orphan_impls_in_sibling_crates_are_ignored() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core,a,b
+use core::{Tr, S};
+fn test() {
+    S.get();
+  //^^^^^^^ u32
+    b::B.get();
+  //^^^^^^^^^^ i64
+}
+
+//- /a.rs crate:a deps:core
+impl core::Tr for core::S { type Out = u8; }
+
+//- /b.rs crate:b deps:core
+pub struct B;
+impl core::Tr for core::S { type Out = u16; }
+impl core::Tr for B { type Out = i64; }
+
+//- /core.rs crate:core
+pub trait Tr { type Out; fn get(&self) -> Self::Out { loop {} } }
+pub struct S;
+impl Tr for S { type Out = u32; }
+"#,
+    );
+}
+
+#[test]
+fn orphan_rules_consider_fundamental_types() {
+    check_types(
+        r#"
+//- /main.rs crate:main deps:core
+use core::{Box, Tr};
+struct Local;
+impl Tr for Box<Local> { type Out = u8; }
+impl Tr for &Local { type Out = i8; }
+fn test() {
+    Box(Local).get();
+  //^^^^^^^^^^^^^^^^ u8
+    (&Local).get();
+  //^^^^^^^^^^^^^^ i8
+}
+
+//- /core.rs crate:core
+pub trait Tr { type Out; fn get(self) -> Self::Out; }
+#[fundamental]
+pub struct Box<T>(pub T);
+"#,
+    );
+}
+
+fn check_object_safety(ra_fixture: &str, expected: &[(&str, bool)]) {
+    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    let module = db.module_for_file(file_id);
+    for &(name, object_safe) in expected {
+        let trait_ = find_trait(&db, module, name);
+        assert_eq!(is_object_safe(&db, trait_), object_safe, "object safety of `{}`", name);
+    }
+}
+
+#[test]
+fn object_safety_sized_self() {
+    check_object_safety(
+        r#"
+#[lang = "sized"]
+trait Sized {}
+trait Bar: Sized {}
+trait Baz where Self: Sized {}
+trait Sub: Baz {}
+trait Unsized {}
+"#,
+        &[("Bar", false), ("Baz", false), ("Sub", false), ("Unsized", true)],
+    );
+}
+
+#[test]
+fn object_safety_methods() {
+    check_object_safety(
+        r#"
+#[lang = "sized"]
+trait Sized {}
+trait Generic { fn bar<T>(&self, t: T); }
+trait GenericSized { fn bar<T>(&self, t: T) where Self: Sized; }
+trait ImplTraitArg { fn bar(&self, t: impl Sized); }
+trait ArgSelf { fn bar(&self, x: &Self); }
+trait ReturnsSelf { fn bar(&self) -> Self; }
+trait ReturnsProjection { type Item; fn bar(&self) -> Self::Item; }
+trait Static { fn new() -> u32; }
+trait StaticSized { fn new() -> Self where Self: Sized; }
+trait Receivers { fn a(&self); fn b(&mut self) -> u32; fn c(self); }
+"#,
+        &[
+            ("Generic", false),
+            ("GenericSized", true),
+            ("ImplTraitArg", false),
+            ("ArgSelf", false),
+            ("ReturnsSelf", false),
+            ("ReturnsProjection", true),
+            ("Static", false),
+            ("StaticSized", true),
+            ("Receivers", true),
+        ],
+    );
+}
+
+#[test]
+fn object_safety_other_items_and_supertraits() {
+    check_object_safety(
+        r#"
+trait Bar<T> {}
+trait SupertraitSelf: Bar<Self> {}
+trait SupertraitOther: Bar<u32> {}
+trait AssocConst { const N: usize; }
+trait AssocType { type Assoc; }
+trait Gat { type Assoc<T>; }
+trait InheritsViolation: AssocConst {}
+"#,
+        &[
+            ("SupertraitSelf", false),
+            ("SupertraitOther", true),
+            ("AssocConst", false),
+            ("AssocType", true),
+            ("Gat", false),
+            ("InheritsViolation", false),
+        ],
+    );
+}
+
+#[test]
+fn object_safety_violations_are_structured() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+trait Trait { const N: usize; fn new() -> u32; fn eq(&self, other: &Self) -> bool; }
+"#,
+    );
+    let module = db.module_for_file(file_id);
+    let trait_ = find_trait(&db, module, "Trait");
+    let violations: Vec<_> = db
+        .object_safety_violations(trait_)
+        .iter()
+        .map(|violation| match violation {
+            ObjectSafetyViolation::Method(func, code) => {
+                format!("{}: {:?}", db.function_data(*func).name, code)
+            }
+            violation => format!("{:?}", violation).split('(').next().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(violations, ["AssocConst", "new: StaticMethod", "eq: ReferencesSelf"]);
+}
+
+#[test]
+fn obligation_resolved_by_later_statement() {
+    check_types(
+        r#"
+trait Convert { type Out; }
+impl Convert for u32 { type Out = bool; }
+impl Convert for i64 { type Out = char; }
+fn make<T>() -> T { loop {} }
+fn convert<T: Convert>(t: T) -> T::Out { loop {} }
+
+fn test() {
+    let a = make();
+    let b = convert(a);
+    b;
+  //^ bool
+    let c: u32 = a;
+}
+"#,
+    );
+}
+
+#[test]
+fn obligations_resolved_to_fixed_point() {
+    check_types(
+        r#"
+trait Convert { type Out; }
+impl Convert for u32 { type Out = bool; }
+impl Convert for bool { type Out = i64; }
+fn make<T>() -> T { loop {} }
+fn convert<T: Convert>(t: T) -> T::Out { loop {} }
+fn same<T>(x: T, y: T) {}
+
+fn test() {
+    let a = make();
+    let b = make();
+    let d = convert(b);
+    let c = convert(a);
+    same(b, c);
+    d;
+  //^ i64
+    let e: u32 = a;
+}
+"#,
+    );
+}
+
+#[test]
+fn dyn_trait_components() {
+    let (db, range) = TestDB::with_range(
+        r#"
+unsafe auto trait Send {}
+trait Iterator { type Item; }
+
+fn test(a: &(dyn Iterator<Item = u8> + Send), b: &(dyn Send + Iterator<Item = u8>)) {
+    $0(*a, *b)$0;
+}
+"#,
+    );
+    let module = db.module_for_file(range.file_id);
+    let iterator = find_trait(&db, module, "Iterator");
+    let send = find_trait(&db, module, "Send");
+    let ty = type_at_range(&db, range);
+    let fields = ty.as_tuple().unwrap();
+
+    for dyn_ty in fields.iter() {
+        assert_eq!(dyn_ty.principal_trait_ref(&db).map(|it| it.trait_), Some(iterator));
+        assert_eq!(dyn_ty.auto_traits(&db).collect::<Vec<_>>(), vec![send]);
+        let projections = dyn_ty.projection_bounds().collect::<Vec<_>>();
+        assert_eq!(projections.len(), 1);
+        assert_eq!(projections[0].ty, Ty::Scalar(Scalar::Uint(UintTy::U8)));
+    }
+
+    // Rebuilding either type from its components puts the principal first.
+    let rebuilt = Ty::new_dyn(
+        &db,
+        fields[1].principal_trait_ref(&db).cloned(),
+        fields[1].projection_bounds().cloned(),
+        fields[1].auto_traits(&db),
+    );
+    assert_eq!(rebuilt, fields[0]);
+    assert_eq!(rebuilt.dyn_trait(), Some(iterator));
+
+    let send_only = Ty::new_dyn(&db, None, None, vec![send, send]);
+    assert_eq!(send_only.principal_trait_ref(&db), None);
+    assert_eq!(send_only.auto_traits(&db).collect::<Vec<_>>(), vec![send]);
+}
+
+#[test]
+fn dyn_trait_bounds_are_ordered_canonically() {
+    let (db, range) = TestDB::with_range(
+        r#"
+unsafe auto trait Send {}
+unsafe auto trait Sync {}
+trait Foo {}
+
+fn test(a: &(dyn Foo + Send + Sync + 'static), b: &(dyn Sync + 'static + Send + Foo)) {
+    $0(a, b)$0;
+}
+"#,
+    );
+    let ty = type_at_range(&db, range);
+    let fields = ty.as_tuple().unwrap();
+    assert_eq!(fields[0], fields[1]);
+    assert_eq!(fields[1].display(&db).to_string(), "&(dyn Foo + Send + Sync)");
+
+    let module = db.module_for_file(range.file_id);
+    let send = find_trait(&db, module, "Send");
+    let sync = find_trait(&db, module, "Sync");
+    let pointee = fields[0].as_reference().unwrap().0;
+    assert_eq!(
+        Ty::new_dyn(&db, pointee.dyn_trait_ref().cloned(), None, vec![sync, send]),
+        *pointee
+    );
+}
+
+#[test]
+fn is_copy_depends_on_environment() {
+    let (db, range) = TestDB::with_range(
+        r#"
+#[lang = "copy"]
+trait Copy {}
+#[lang = "clone"]
+trait Clone {}
+
+struct W<T>(T);
+impl<T: Copy> Copy for W<T> {}
+impl<T: Clone> Clone for W<T> {}
+
+fn test<T: Copy + Clone, U: Clone>(t: T, u: U) {
+    $0(W(t), W(u))$0;
+}
+"#,
+    );
+    let (func, expr) = expr_at_range(&db, range);
+    let ty = db.infer(func.into())[expr].clone();
+    let fields = ty.as_tuple().unwrap();
+    let module = db.module_for_file(range.file_id);
+    let krate = module.krate();
+    let env = TraitEnvironment::lower(&db, &func.resolver(&db));
+
+    assert!(fields[0].is_copy(&db, krate, env.clone()));
+    assert!(fields[0].is_clone(&db, krate, env.clone()));
+    assert!(!fields[1].is_copy(&db, krate, env.clone()));
+    assert!(fields[1].is_clone(&db, krate, env));
+
+    // Outside of `test`, nothing is known about `T`.
+    let empty_env = Arc::new(TraitEnvironment { predicates: Vec::new() });
+    assert!(!fields[0].is_copy(&db, krate, empty_env.clone()));
+
+    // Whether `W<_>` is `Copy` depends on what `_` turns out to be.
+    let copy_trait = find_trait(&db, module, "Copy");
+    let w_unknown = fields[0].clone().apply_substs(Substs::single_unknown());
+    assert_eq!(
+        w_unknown.check_impls_trait(&db, krate, empty_env.clone(), copy_trait),
+        Implements::Maybe
+    );
+    assert!(!w_unknown.impls_trait(&db, krate, empty_env, copy_trait));
+}
+
+#[test]
+fn implied_bounds_include_super_traits() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait Clone {}
+trait Copy: Clone {}
+trait Other {}
+trait Sub: Other + Clone {}
+
+fn test<T: Copy, U: Sub + Copy>(t: T, u: U, d: &dyn Sub) {
+    $0(t, u, *d)$0;
+}
+"#,
+    );
+    let (func, expr) = expr_at_range(&db, range);
+    let ty = db.infer(func.into())[expr].clone();
+    let fields = ty.as_tuple().unwrap();
+    let module = db.module_for_file(range.file_id);
+    let env = TraitEnvironment::lower(&db, &func.resolver(&db));
+    let trait_ = |name| find_trait(&db, module, name);
+
+    let implied = |ty: &Ty| {
+        implied_bounds(&db, ty, env.clone())
+            .into_iter()
+            .inspect(|tr| assert_eq!(tr.self_ty(), ty))
+            .map(|tr| tr.trait_)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(implied(&fields[0]), vec![trait_("Copy"), trait_("Clone")]);
+    assert_eq!(
+        implied(&fields[1]),
+        vec![trait_("Sub"), trait_("Other"), trait_("Clone"), trait_("Copy")]
+    );
+    assert_eq!(implied(&fields[2]), vec![trait_("Sub"), trait_("Other"), trait_("Clone")]);
+}
+
+#[test]
+fn all_transitive_bounds_follow_super_traits() {
+    let (db, range) = TestDB::with_range(
+        r#"
+trait A: B {}
+trait B: A + C<u8> {}
+trait C<X> { type Item; }
+trait D: C<u32, Item = i8> {}
+
+fn test<T: A, U: D + C<u32>>(t: T, u: U) {
+    $0(t, u)$0;
+}
+"#,
+    );
+    let (func, expr) = expr_at_range(&db, range);
+    let ty = db.infer(func.into())[expr].clone();
+    let fields = ty.as_tuple().unwrap();
+    let bounds = |ty: &Ty| {
+        let param_id = match ty {
+            Ty::Placeholder(id) => *id,
+            _ => panic!("not a type parameter: {:?}", ty),
+        };
+        db.all_transitive_bounds(param_id)
+            .iter()
+            .map(|bound| match bound.value.skip_binders() {
+                GenericPredicate::Implemented(tr) => {
+                    let args = tr.substs[1..].iter().map(|it| it.display(&db).to_string());
+                    format!(
+                        "{}<{}>",
+                        db.trait_data(tr.trait_).name,
+                        args.collect::<Vec<_>>().join(", ")
+                    )
+                }
+                GenericPredicate::Projection(proj) => format!(
+                    "{} = {}",
+                    db.type_alias_data(proj.projection_ty.associated_ty).name,
+                    proj.ty.display(&db)
+                ),
+                GenericPredicate::ForLifetimes(..) => unreachable!(),
+                GenericPredicate::Error => "{error}".to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(bounds(&fields[0]), vec!["A<>", "B<>", "C<u8>"]);
+    assert_eq!(bounds(&fields[1]), vec!["D<>", "Item = i8", "C<u32>"]);
+}
+
+#[test]
+fn dyn_trait_implements_super_traits() {
+    let fixture = r#"
+trait Super<T> {}
+trait Sub: Super<u8> {}
+
+fn test(s: &dyn Sub) {
+    $0*s$0;
+}
+"#;
+    check_implements_at_range(
+        fixture,
+        "Super",
+        &[Ty::Scalar(Scalar::Uint(UintTy::U8))],
+        Implements::Yes,
+    );
+    check_implements_at_range(
+        fixture,
+        "Super",
+        &[Ty::Scalar(Scalar::Uint(UintTy::U16))],
+        Implements::No,
+    );
+}
+
+#[test]
+fn dyn_trait_super_trait_bound_with_assoc_type() {
+    check_types(
+        r#"
+#[lang = "sized"]
+trait Sized {}
+trait Super { type Assoc; }
+trait Sub: Super {}
+fn get<T: Super + ?Sized>(t: &T) -> T::Assoc { loop {} }
+
+fn test(s: &dyn Sub<Assoc = u32>) {
+    get(s);
+  //^^^^^^ u32
+}
+"#,
+    );
+}
+
+#[test]
+fn const_generic_array_impls() {
+    check_types(
+        r#"
+trait Clone {
+    fn clone(&self) -> Self;
+}
+trait Default {
+    fn default() -> Self;
+}
+struct String;
+impl Clone for String {
+    fn clone(&self) -> Self { loop {} }
+}
+impl Default for u8 {
+    fn default() -> Self { loop {} }
+}
+impl<T: Clone, const N: usize> Clone for [T; N] {
+    fn clone(&self) -> Self { loop {} }
+}
+impl<T: Default, const N: usize> Default for [T; N] {
+    fn default() -> Self { loop {} }
+}
+
+fn test(a: [String; 3]) {
+    a.clone();
+  //^^^^^^^^^ [String; _]
+    <[u8; 4]>::default();
+  //^^^^^^^^^^^^^^^^^^^^ [u8; _]
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_from_definite_guidance() {
+    check_types(
+        r#"
+trait Marker {}
+impl Marker for u32 {}
+impl Marker for i64 {}
+struct Wrap<T>(T);
+trait Inner<T> {}
+impl<X: Marker> Inner<Wrap<X>> for S {}
+trait Conv<T> {
+    fn conv(self) -> T;
+}
+struct S;
+impl<T> Conv<T> for S where S: Inner<T> {
+    fn conv(self) -> T { loop {} }
+}
+
+fn test(s: S) {
+    let x = s.conv();
+    x;
+} //^ Wrap<{unknown}>
+"#,
+    );
+}
+
+#[test]
+fn no_infer_from_single_impl() {
+    check_types(
+        r#"
+trait FromStr: Sized {}
+impl FromStr for u32 {}
+#[lang = "str"]
+impl str {
+    fn parse<F: FromStr>(&self) -> F { loop {} }
+}
+
+fn test() {
+    let x = "3".parse();
+    x;
+} //^ {unknown}
+"#,
+    );
+}
+
+#[test]
+fn no_infer_from_ambiguous_impls() {
+    check_types(
+        r#"
+trait FromStr: Sized {}
+impl FromStr for u32 {}
+impl FromStr for i64 {}
+#[lang = "str"]
+impl str {
+    fn parse<F: FromStr>(&self) -> F { loop {} }
+}
+
+fn test() {
+    let x = "3".parse();
+    x;
+} //^ {unknown}
+"#,
+    );
+}
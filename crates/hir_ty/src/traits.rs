@@ -1,14 +1,22 @@
 //! Trait solving using Chalk.
 use std::env::var;
-use std::sync::Arc;
+use std::{cell::Cell, iter, panic, sync::Arc};
 
 use base_db::CrateId;
 use chalk_ir::cast::Cast;
 use chalk_solve::{logging_db::LoggingRustIrDatabase, Solver};
-use hir_def::{lang_item::LangItemTarget, TraitId};
+use hir_def::{lang_item::LangItemTarget, AdtId, ImplId, TraitId, TypeAliasId};
+use hir_expand::name::{name, Name};
 use stdx::panic_context;
 
-use crate::{db::HirDatabase, DebruijnIndex, Substs};
+use crate::{
+    db::HirDatabase,
+    method_resolution::trait_impl_substs,
+    object_safety::is_object_safe,
+    primitive::IntTy,
+    utils::{adt_variants, all_super_trait_refs},
+    BoundVar, DebruijnIndex, Scalar, Substs, TyVariableKind,
+};
 
 use super::{Canonical, GenericPredicate, HirDisplay, ProjectionTy, TraitRef, Ty, TypeWalk};
 
@@ -16,21 +24,67 @@ use self::chalk::{from_chalk, Interner, ToChalk};
 
 pub(crate) mod chalk;
 
-/// This controls how much 'time' we give the Chalk solver before giving up.
-const CHALK_SOLVER_FUEL: i32 = 100;
+/// How many trait solving results to keep in memory. Goals are canonicalized
+/// before solving, so structurally identical goals from different function
+/// bodies share results; but most goals are only needed while inferring a
+/// single body, so we don't want to keep all of them around forever.
+pub const TRAIT_SOLVE_LRU_CAP: usize = 16 * 1024;
+
+/// Limits for the Chalk solver, so that goals like `S<T>: Trait` where
+/// `impl<T> Trait for S<T> where S<S<T>>: Trait` don't make it run forever.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TraitSolverConfig {
+    /// The maximum depth of the solver's goal stack; deeper goals overflow.
+    pub overflow_depth: usize,
+    /// The maximum size of goals and answers; bigger ones get truncated,
+    /// making the result ambiguous.
+    pub max_size: usize,
+    /// This controls how much 'time' we give the Chalk solver before giving up,
+    /// counted in the impl lookups it does.
+    pub fuel: i32,
+    /// Whether to log goals that overflowed.
+    pub log_overflow: bool,
+}
+
+impl Default for TraitSolverConfig {
+    fn default() -> TraitSolverConfig {
+        TraitSolverConfig {
+            overflow_depth: var("CHALK_OVERFLOW_DEPTH")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(100),
+            max_size: var("CHALK_SOLVER_MAX_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(30),
+            fuel: 1000,
+            log_overflow: var("CHALK_LOG_OVERFLOW").is_ok(),
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 struct ChalkContext<'a> {
     db: &'a dyn HirDatabase,
     krate: CrateId,
+    /// The fuel left for the current goal.
+    fuel: &'a Cell<i32>,
+}
+
+impl ChalkContext<'_> {
+    /// Returns `false` once the fuel is exhausted. The recursive solver
+    /// doesn't check `should_continue`, so without fuel we stop giving it
+    /// impls to make sure it comes to an end.
+    fn consume_fuel(&self) -> bool {
+        let remaining = self.fuel.get();
+        self.fuel.set(remaining - 1);
+        if remaining == 0 {
+            log::debug!("fuel exhausted");
+        }
+        remaining > 0
+    }
 }
 
-fn create_chalk_solver() -> chalk_recursive::RecursiveSolver<Interner> {
-    let overflow_depth =
-        var("CHALK_OVERFLOW_DEPTH").ok().and_then(|s| s.parse().ok()).unwrap_or(100);
+fn create_chalk_solver(config: &TraitSolverConfig) -> chalk_recursive::RecursiveSolver<Interner> {
     let caching_enabled = true;
-    let max_size = var("CHALK_SOLVER_MAX_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(30);
-    chalk_recursive::RecursiveSolver::new(overflow_depth, max_size, caching_enabled)
+    chalk_recursive::RecursiveSolver::new(config.overflow_depth, config.max_size, caching_enabled)
 }
 
 /// A set of clauses that we assume to be true. E.g. if we are inside this function:
@@ -51,11 +105,34 @@ impl TraitEnvironment {
         &'a self,
         ty: &'a Ty,
     ) -> impl Iterator<Item = &'a TraitRef> + 'a {
-        self.predicates.iter().filter_map(move |pred| match pred {
+        self.predicates.iter().filter_map(move |pred| match pred.skip_binders() {
             GenericPredicate::Implemented(tr) if tr.self_ty() == ty => Some(tr),
             _ => None,
         })
     }
+
+    /// Returns the type a projection is equal to according to a where clause
+    /// in this trait env. E.g. if we are in `foo<T: Iterator<Item = u32>>()`,
+    /// this normalizes `<T as Iterator>::Item` to `u32`.
+    pub fn normalize_projection_ty(&self, projection_ty: &ProjectionTy) -> Option<Ty> {
+        self.predicates.iter().find_map(|pred| match pred.skip_binders() {
+            GenericPredicate::Projection(proj) if proj.projection_ty == *projection_ty => {
+                Some(proj.ty.clone())
+            }
+            _ => None,
+        })
+    }
+
+    /// Replaces all projections in `ty` that have an equality in this trait
+    /// env by the type they are equal to.
+    pub fn normalize_ty(&self, ty: Ty) -> Ty {
+        ty.fold(&mut |ty| match ty {
+            Ty::Projection(proj_ty) => {
+                self.normalize_projection_ty(&proj_ty).unwrap_or(Ty::Projection(proj_ty))
+            }
+            _ => ty,
+        })
+    }
 }
 
 /// Something (usually a goal), along with an environment.
@@ -80,6 +157,11 @@ pub enum Obligation {
     /// parameter to the `TraitRef`).
     Trait(TraitRef),
     Projection(ProjectionPredicate),
+    /// Prove that a type is well-formed, see `is_well_formed`.
+    WellFormed(Ty),
+    /// Prove the obligation for all lifetimes of a `for<'a>` binder, see
+    /// `GenericPredicate::ForLifetimes`.
+    ForLifetimes(usize, Box<Obligation>),
 }
 
 impl Obligation {
@@ -89,9 +171,21 @@ impl Obligation {
             GenericPredicate::Projection(projection_pred) => {
                 Some(Obligation::Projection(projection_pred))
             }
+            GenericPredicate::ForLifetimes(num_lifetimes, pred) => {
+                let obligation = Obligation::from_predicate(*pred)?;
+                Some(Obligation::ForLifetimes(num_lifetimes, Box::new(obligation)))
+            }
             GenericPredicate::Error => None,
         }
     }
+
+    /// The obligation without its `for<'a>` binder, if it has one.
+    pub fn skip_binders(&self) -> &Obligation {
+        match self {
+            Obligation::ForLifetimes(_, obligation) => obligation,
+            _ => self,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -116,53 +210,518 @@ impl TypeWalk for ProjectionPredicate {
     }
 }
 
-/// Solve a trait goal using Chalk.
+/// Normalizes the associated type `assoc` of `trait_ref`, i.e. computes the
+/// type that the impl of `trait_ref` assigns to it (e.g. `[T]` for `<Vec<T> as
+/// Deref>::Target`). If no single impl can be selected, e.g. because
+/// `trait_ref` only holds by a where clause in `environment`, the projection
+/// is normalized by the solver instead. Returns `Ty::Unknown` if there's no
+/// unique answer.
+pub fn normalize_assoc(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    trait_ref: TraitRef,
+    assoc: TypeAliasId,
+) -> Ty {
+    normalize_assoc_inner(db, krate, environment, trait_ref, assoc, 0)
+}
+
+/// How many impls `normalize_assoc` goes through for associated types that
+/// are defined in terms of other associated types before leaving the rest to
+/// the solver.
+const NORMALIZE_ASSOC_DEPTH_LIMIT: usize = 16;
+
+fn normalize_assoc_inner(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    trait_ref: TraitRef,
+    assoc: TypeAliasId,
+    depth: usize,
+) -> Ty {
+    if depth < NORMALIZE_ASSOC_DEPTH_LIMIT {
+        if let Some((impl_id, substs)) = select_impl(db, krate, environment.clone(), &trait_ref) {
+            let assoc_ty = db.impl_assoc_ty(impl_id, assoc);
+            // generic associated types would need their own arguments as well
+            if assoc_ty.num_binders == substs.len() {
+                // the impl's type can refer to other associated types, like
+                // `I::Item` in `impl<I: Iterator> Iterator for Take<I>`
+                return assoc_ty.subst(&substs).fold(&mut |ty| match ty {
+                    Ty::Projection(projection) => {
+                        let trait_ref = projection.trait_ref(db);
+                        let assoc = projection.associated_ty;
+                        let env = environment.clone();
+                        match normalize_assoc_inner(db, krate, env, trait_ref, assoc, depth + 1) {
+                            Ty::Unknown => Ty::Projection(projection),
+                            ty => ty,
+                        }
+                    }
+                    ty => ty,
+                });
+            }
+        }
+    }
+    let projection = ProjectionTy { associated_ty: assoc, parameters: trait_ref.substs };
+    normalize_projection(db, krate, projection, environment).unwrap_or(Ty::Unknown)
+}
+
+/// Selects the impl that implements `trait_ref`, together with the arguments
+/// for its generic parameters: the only impl whose header matches `trait_ref`
+/// and whose where clauses the solver doesn't rule out.
+fn select_impl(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    trait_ref: &TraitRef,
+) -> Option<(ImplId, Substs)> {
+    let impls = db.relevant_impls_for_trait(krate, trait_ref.trait_);
+    let mut candidates = impls.for_trait(trait_ref.trait_).filter_map(|impl_id| {
+        let substs = trait_impl_substs(db, impl_id, trait_ref)?;
+        let where_clauses_hold = db.generic_predicates(impl_id.into()).iter().all(|pred| {
+            let pred = canonicalize_unknowns(pred.clone().subst(&substs));
+            let obligation = match Obligation::from_predicate(pred.value) {
+                Some(it) => it,
+                None => return true,
+            };
+            let goal = Canonical::new(
+                InEnvironment::new(environment.clone(), obligation),
+                pred.kinds.iter().copied(),
+            );
+            solve_implements(db, krate, goal) != Implements::No
+        });
+        if where_clauses_hold {
+            Some((impl_id, substs))
+        } else {
+            None
+        }
+    });
+    let selected = candidates.next()?;
+    if candidates.next().is_some() {
+        return None;
+    }
+    Some(selected)
+}
+
+/// Normalizes `projection` to the type the trait solver determines for it,
+/// either from an impl or from a where clause in `environment`. `Ty::Unknown`s
+/// in the projection are treated as arbitrary types; if the result depends on
+/// them, those parts are unknown as well. Returns `None` if there's no unique
+/// answer.
+pub fn normalize_projection(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    projection: ProjectionTy,
+    environment: Arc<TraitEnvironment>,
+) -> Option<Ty> {
+    let predicate =
+        canonicalize_unknowns(ProjectionPredicate { projection_ty: projection, ty: Ty::Unknown });
+    // the result is the last variable, since it's walked last
+    let result_var = predicate.kinds.len() - 1;
+    let goal = Canonical::new(
+        InEnvironment::new(environment, Obligation::Projection(predicate.value)),
+        predicate.kinds.iter().copied(),
+    );
+    match db.trait_solve(krate, goal)? {
+        Solution::Unique(SolutionVariables(vars)) => {
+            let ty = vars.value.get(result_var)?.clone();
+            Some(ty.fold_binders(
+                &mut |ty, binders| match ty {
+                    Ty::Bound(bound) if bound.debruijn >= binders => Ty::Unknown,
+                    ty => ty,
+                },
+                DebruijnIndex::INNERMOST,
+            ))
+        }
+        Solution::Ambig(_) | Solution::Overflow => None,
+    }
+}
+
+/// Whether a type implements a trait, as far as we can tell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Implements {
+    Yes,
+    No,
+    /// The answer depends on types we don't know (yet), or the solver gave up.
+    Maybe,
+}
+
+/// Checks whether `self_ty` implements `trait_` with the given generic
+/// arguments (not including `Self`). Missing arguments, `Ty::Unknown`s and
+/// inference variables are treated as arbitrary types.
+pub fn check_implements(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    trait_: TraitId,
+    self_ty: Ty,
+    args: &[Ty],
+) -> Implements {
+    let substs = Substs::build_for_def(db, trait_)
+        .push(self_ty)
+        .fill(args.iter().cloned().chain(iter::repeat(Ty::Unknown)))
+        .build();
+    let trait_ref = canonicalize_unknowns(TraitRef { trait_, substs });
+    let goal = Canonical::new(
+        InEnvironment::new(environment, Obligation::Trait(trait_ref.value)),
+        trait_ref.kinds.iter().copied(),
+    );
+    solve_implements(db, krate, goal)
+}
+
+/// Replaces `Ty::Unknown`s and inference variables in `value` by bound
+/// variables, so that the solver treats them as arbitrary types.
+fn canonicalize_unknowns<T: TypeWalk>(mut value: T) -> Canonical<T> {
+    let mut kinds = Vec::new();
+    let mut free_vars = Vec::new();
+    value.walk_mut_binders(
+        &mut |ty, binders| match *ty {
+            Ty::Unknown => {
+                *ty = Ty::Bound(BoundVar::new(binders, kinds.len()));
+                kinds.push(TyVariableKind::General);
+            }
+            Ty::InferenceVar(var, kind) => {
+                let idx = match free_vars.iter().find(|(it, _)| *it == var) {
+                    Some(&(_, idx)) => idx,
+                    None => {
+                        free_vars.push((var, kinds.len()));
+                        kinds.push(kind);
+                        kinds.len() - 1
+                    }
+                };
+                *ty = Ty::Bound(BoundVar::new(binders, idx));
+            }
+            _ => {}
+        },
+        DebruijnIndex::INNERMOST,
+    );
+    Canonical::new(value, kinds)
+}
+
+/// Like `check_implements`, but only returns `true` if `self_ty` definitely
+/// implements `trait_`.
+pub fn implements(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    trait_: TraitId,
+    self_ty: Ty,
+    args: &[Ty],
+) -> bool {
+    check_implements(db, krate, environment, trait_, self_ty, args) == Implements::Yes
+}
+
+/// The trait bounds that hold for `ty` without having to be checked: the where
+/// clauses in `env` on `ty` itself and the traits of a `dyn Trait`, together
+/// with all their super traits (e.g. `T: Clone` from `T: Copy`). This is what
+/// Chalk derives from the `FromEnv` clauses of a well-formed environment, so a
+/// bound in this list is redundant when stated again. Each bound is only
+/// listed once.
+pub fn implied_bounds(db: &dyn HirDatabase, ty: &Ty, env: Arc<TraitEnvironment>) -> Vec<TraitRef> {
+    let dyn_bounds = match ty {
+        Ty::Dyn(predicates) => predicates
+            .iter()
+            .filter_map(|pred| match pred.skip_binders() {
+                GenericPredicate::Implemented(tr) => {
+                    Some(tr.clone().subst_bound_vars(&Substs::single(ty.clone())))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut result: Vec<TraitRef> = Vec::new();
+    for trait_ref in env.trait_predicates_for_self_ty(ty).cloned().chain(dyn_bounds) {
+        for implied in all_super_trait_refs(db, trait_ref) {
+            if !result.contains(&implied) {
+                result.push(implied);
+            }
+        }
+    }
+    result
+}
+
+/// Checks whether `ty` is well-formed, i.e. whether the bounds required by its
+/// components hold (like `T: Copy` for `S<T>` with `struct S<T: Copy>`) and
+/// all trait objects in it are object safe, so that e.g. `&dyn Sized` is not.
+/// Unknown types and ambiguous answers count as well-formed.
+pub fn is_well_formed(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    environment: Arc<TraitEnvironment>,
+    ty: &Ty,
+) -> bool {
+    let mut well_formed = true;
+    ty.walk(&mut |ty| {
+        if !well_formed {
+            return;
+        }
+        well_formed = match ty {
+            Ty::Dyn(predicates) => predicates.iter().all(|pred| match pred.skip_binders() {
+                GenericPredicate::Implemented(tr) => is_object_safe(db, tr.trait_),
+                _ => true,
+            }),
+            // type parameters are well-formed by assumption; and we can't
+            // ask about types referring to the binders of an outer type
+            Ty::Placeholder(_) | Ty::Unknown | Ty::InferenceVar(..) | Ty::Bound(_) => true,
+            _ if has_bound_vars(ty) => true,
+            _ => {
+                let ty = canonicalize_unknowns(ty.clone());
+                let goal = Canonical::new(
+                    InEnvironment::new(environment.clone(), Obligation::WellFormed(ty.value)),
+                    ty.kinds.iter().copied(),
+                );
+                solve_implements(db, krate, goal) != Implements::No
+            }
+        };
+    });
+    well_formed
+}
+
+/// Checks whether the types of all fields of `adt` implement `trait_`, as
+/// needed to derive it. Like the derive macros, this assumes that the type
+/// parameters of `adt` implement `trait_` as well. Further parameters of the
+/// trait get their defaults, e.g. `Rhs = Self` for `PartialEq`. Returns the
+/// first field type that definitely doesn't implement `trait_`.
+pub fn all_fields_implement(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    adt: AdtId,
+    trait_: TraitId,
+) -> Option<Ty> {
+    let substs = Substs::type_params(db, adt);
+    let mut environment = (*db.trait_environment(adt.into())).clone();
+    environment.predicates.extend(
+        substs
+            .iter()
+            .map(|ty| GenericPredicate::Implemented(trait_ref_with_defaults(db, trait_, ty))),
+    );
+    let environment = Arc::new(environment);
+    adt_variants(db.upcast(), adt).into_iter().find_map(|variant| {
+        db.field_types(variant).iter().map(|(_, ty)| ty.clone().subst(&substs)).find(|ty| {
+            let trait_ref = canonicalize_unknowns(trait_ref_with_defaults(db, trait_, ty));
+            let goal = Canonical::new(
+                InEnvironment::new(environment.clone(), Obligation::Trait(trait_ref.value)),
+                trait_ref.kinds.iter().copied(),
+            );
+            solve_implements(db, krate, goal) == Implements::No
+        })
+    })
+}
+
+/// `self_ty: trait_`, with the defaults for the other parameters of the trait.
+fn trait_ref_with_defaults(db: &dyn HirDatabase, trait_: TraitId, self_ty: &Ty) -> TraitRef {
+    let substs = Substs::build_for_def(db, trait_)
+        .push(self_ty.clone())
+        .fill_with_defaults(db, trait_.into())
+        .build();
+    TraitRef { trait_, substs }
+}
+
+fn has_bound_vars(ty: &Ty) -> bool {
+    let mut found = false;
+    ty.walk(&mut |ty| found |= matches!(ty, Ty::Bound(_)));
+    found
+}
+
+/// Solves an already canonicalized trait goal.
+pub(crate) fn solve_implements(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    goal: Canonical<InEnvironment<Obligation>>,
+) -> Implements {
+    match db.trait_solve(krate, goal) {
+        Some(Solution::Unique(_)) => Implements::Yes,
+        Some(Solution::Ambig(_)) | Some(Solution::Overflow) => Implements::Maybe,
+        None => Implements::No,
+    }
+}
+
+impl Ty {
+    /// Checks whether this type implements `trait_`, which mustn't have any
+    /// generic parameters besides `Self`, or only ones that may be anything.
+    pub fn check_impls_trait(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        environment: Arc<TraitEnvironment>,
+        trait_: TraitId,
+    ) -> Implements {
+        check_implements(db, krate, environment, trait_, self.clone(), &[])
+    }
+
+    /// Like `check_impls_trait`, but ambiguous answers count as `false`.
+    pub fn impls_trait(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        environment: Arc<TraitEnvironment>,
+        trait_: TraitId,
+    ) -> bool {
+        self.check_impls_trait(db, krate, environment, trait_) == Implements::Yes
+    }
+
+    pub fn is_copy(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        environment: Arc<TraitEnvironment>,
+    ) -> bool {
+        self.impls_lang_trait(db, krate, environment, "copy")
+    }
+
+    pub fn is_clone(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        environment: Arc<TraitEnvironment>,
+    ) -> bool {
+        self.impls_lang_trait(db, krate, environment, "clone")
+    }
+
+    fn impls_lang_trait(
+        &self,
+        db: &dyn HirDatabase,
+        krate: CrateId,
+        environment: Arc<TraitEnvironment>,
+        lang_item: &str,
+    ) -> bool {
+        match db.lang_item(krate, lang_item.into()) {
+            Some(LangItemTarget::TraitId(trait_)) => {
+                self.impls_trait(db, krate, environment, trait_)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Solve a trait goal using Chalk. This first removes the parts of the
+/// environment that can't matter for the goal, so that equal goals from bodies
+/// with different where clauses share their results.
+pub(crate) fn trait_solve(
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    goal: Canonical<InEnvironment<Obligation>>,
+) -> Option<Solution> {
+    db.trait_solve_query(krate, trim_environment(goal))
+}
+
+/// A goal that doesn't mention any type parameters and has no variables can
+/// only be proven using where clauses that don't mention type parameters
+/// either (like `where u32: Foo`), so the others can be dropped. We don't do
+/// this if the environment contains associated types, since normalizing them
+/// might turn a where clause into one that doesn't mention type parameters
+/// anymore.
+fn trim_environment(
+    goal: Canonical<InEnvironment<Obligation>>,
+) -> Canonical<InEnvironment<Obligation>> {
+    let environment = &goal.value.environment;
+    if environment.predicates.is_empty() || !goal.kinds.is_empty() {
+        return goal;
+    }
+    let mut goal_has_params = false;
+    walk_obligation(&goal.value.value, &mut |ty| {
+        goal_has_params |= matches!(ty, Ty::Placeholder(_))
+    });
+    if goal_has_params {
+        return goal;
+    }
+    let has_assoc_types = environment.predicates.iter().any(|pred| match pred.skip_binders() {
+        // the projection itself can only be used for goals mentioning it
+        GenericPredicate::Projection(proj) => any_ty(&proj.ty, is_assoc_type),
+        _ => any_ty(pred, is_assoc_type),
+    });
+    if has_assoc_types {
+        return goal;
+    }
+    let predicates: Vec<_> = environment
+        .predicates
+        .iter()
+        .filter(|pred| !any_ty(*pred, |ty| matches!(ty, Ty::Placeholder(_))))
+        .cloned()
+        .collect();
+    if predicates.len() == environment.predicates.len() {
+        return goal;
+    }
+    let environment = Arc::new(TraitEnvironment { predicates });
+    Canonical { value: InEnvironment::new(environment, goal.value.value), kinds: goal.kinds }
+}
+
+fn is_assoc_type(ty: &Ty) -> bool {
+    matches!(ty, Ty::Projection(_) | Ty::AssociatedType(..))
+}
+
+fn any_ty(t: &impl TypeWalk, mut pred: impl FnMut(&Ty) -> bool) -> bool {
+    let mut found = false;
+    t.walk(&mut |ty| found |= pred(ty));
+    found
+}
+
+fn walk_obligation(obligation: &Obligation, f: &mut impl FnMut(&Ty)) {
+    match obligation {
+        Obligation::Trait(tr) => tr.walk(f),
+        Obligation::Projection(pr) => pr.walk(f),
+        Obligation::WellFormed(ty) => ty.walk(f),
+        Obligation::ForLifetimes(_, obligation) => walk_obligation(obligation, f),
+    }
+}
+
 pub(crate) fn trait_solve_query(
     db: &dyn HirDatabase,
     krate: CrateId,
     goal: Canonical<InEnvironment<Obligation>>,
 ) -> Option<Solution> {
-    let _p = profile::span("trait_solve_query").detail(|| match &goal.value.value {
+    let _p = profile::span("trait_solve_query").detail(|| match goal.value.value.skip_binders() {
         Obligation::Trait(it) => db.trait_data(it.trait_).name.to_string(),
         Obligation::Projection(_) => "projection".to_string(),
+        Obligation::WellFormed(_) => "well-formed".to_string(),
+        Obligation::ForLifetimes(..) => "for".to_string(),
     });
     log::info!("trait_solve_query({})", goal.value.value.display(db));
 
-    if let Obligation::Projection(pred) = &goal.value.value {
+    if let Obligation::Projection(pred) = goal.value.value.skip_binders() {
         if let Ty::Bound(_) = &pred.projection_ty.parameters[0] {
             // Hack: don't ask Chalk to normalize with an unknown self type, it'll say that's impossible
             return Some(Solution::Ambig(Guidance::Unknown));
         }
     }
 
-    let canonical = goal.to_chalk(db).cast(&Interner);
+    let canonical = goal.clone().to_chalk(db).cast(&Interner);
 
     // We currently don't deal with universes (I think / hope they're not yet
     // relevant for our use cases?)
     let u_canonical = chalk_ir::UCanonical { canonical, universes: 1 };
-    let solution = solve(db, krate, &u_canonical);
-    solution.map(|solution| solution_from_chalk(db, solution))
+    let config = db.trait_solver_config();
+    match solve(db, krate, &config, &u_canonical) {
+        SolveResult::Solved(solution) => solution.map(|solution| solution_from_chalk(db, solution)),
+        SolveResult::Overflow => {
+            if config.log_overflow {
+                log::warn!("trait solving overflowed: {}", goal.value.value.display(db));
+            }
+            Some(Solution::Overflow)
+        }
+    }
+}
+
+enum SolveResult {
+    Solved(Option<chalk_solve::Solution<Interner>>),
+    /// The solver ran out of fuel.
+    Overflow,
 }
 
 fn solve(
     db: &dyn HirDatabase,
     krate: CrateId,
+    config: &TraitSolverConfig,
     goal: &chalk_ir::UCanonical<chalk_ir::InEnvironment<chalk_ir::Goal<Interner>>>,
-) -> Option<chalk_solve::Solution<Interner>> {
-    let context = ChalkContext { db, krate };
+) -> SolveResult {
+    let fuel = Cell::new(config.fuel);
+    let context = ChalkContext { db, krate, fuel: &fuel };
     log::debug!("solve goal: {:?}", goal);
-    let mut solver = create_chalk_solver();
-
-    let fuel = std::cell::Cell::new(CHALK_SOLVER_FUEL);
+    let mut solver = create_chalk_solver(config);
 
     let should_continue = || {
         context.db.check_canceled();
-        let remaining = fuel.get();
-        fuel.set(remaining - 1);
-        if remaining == 0 {
-            log::debug!("fuel exhausted");
-        }
-        remaining > 0
+        context.fuel.get() > 0
     };
 
     let mut solve = || {
@@ -190,7 +749,13 @@ fn solve(
     let solution =
         if is_chalk_debug() { chalk::tls::set_current_program(db, solve) } else { solve() };
 
-    solution
+    // once the fuel is exhausted, the solver returns without having seen all
+    // impls, so its answer isn't a real one
+    if fuel.get() < 0 {
+        SolveResult::Overflow
+    } else {
+        SolveResult::Solved(solution)
+    }
 }
 
 struct LoggingRustIrDatabaseLoggingOnDrop<'a>(LoggingRustIrDatabase<Interner, ChalkContext<'a>>);
@@ -252,6 +817,10 @@ pub enum Solution {
     /// constraints, since we have not "committed" to any particular solution
     /// yet.
     Ambig(Guidance),
+
+    /// The solver gave up, e.g. because of deeply recursive impls. This should
+    /// be treated like an ambiguous result.
+    Overflow,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -288,6 +857,36 @@ impl FnTrait {
         }
     }
 
+    /// The method of the trait that calls with `()` syntax go through.
+    pub fn method_name(self) -> Name {
+        match self {
+            FnTrait::FnOnce => name![call_once],
+            FnTrait::FnMut => name![call_mut],
+            FnTrait::Fn => name![call],
+        }
+    }
+
+    /// The type standing for this closure kind in the second parameter of
+    /// `Ty::Closure`. Like rustc, we use `i8` for `Fn`, `i16` for `FnMut` and
+    /// `i32` for `FnOnce`.
+    pub(crate) fn to_closure_kind_ty(self) -> Ty {
+        let int_ty = match self {
+            FnTrait::Fn => IntTy::I8,
+            FnTrait::FnMut => IntTy::I16,
+            FnTrait::FnOnce => IntTy::I32,
+        };
+        Ty::Scalar(Scalar::Int(int_ty))
+    }
+
+    pub(crate) fn from_closure_kind_ty(ty: &Ty) -> Option<FnTrait> {
+        match ty {
+            Ty::Scalar(Scalar::Int(IntTy::I8)) => Some(FnTrait::Fn),
+            Ty::Scalar(Scalar::Int(IntTy::I16)) => Some(FnTrait::FnMut),
+            Ty::Scalar(Scalar::Int(IntTy::I32)) => Some(FnTrait::FnOnce),
+            _ => None,
+        }
+    }
+
     pub fn get_id(&self, db: &dyn HirDatabase, krate: CrateId) -> Option<TraitId> {
         let target = db.lang_item(krate, self.lang_item_name().into())?;
         match target {
@@ -1,5 +1,5 @@
 //! Conversion code from/to Chalk.
-use std::sync::Arc;
+use std::{cell::Cell, sync::Arc};
 
 use log::debug;
 
@@ -13,14 +13,14 @@ use hir_def::{
 };
 use hir_expand::name::name;
 
-use super::ChalkContext;
+use super::{ChalkContext, FnTrait};
 use crate::{
     db::HirDatabase,
     display::HirDisplay,
-    method_resolution::{TyFingerprint, ALL_FLOAT_FPS, ALL_INT_FPS},
-    utils::generics,
-    BoundVar, CallableDefId, CallableSig, DebruijnIndex, GenericPredicate, ProjectionPredicate,
-    ProjectionTy, Substs, TraitRef, Ty,
+    method_resolution::{select_most_specific_impls, TyFingerprint, ALL_FLOAT_FPS, ALL_INT_FPS},
+    utils::{adt_variants, generics, is_fundamental},
+    BoundVar, CallableDefId, CallableSig, Canonical, DebruijnIndex, GenericPredicate,
+    ProjectionPredicate, ProjectionTy, Substs, TraitRef, Ty, TypeWalk,
 };
 use mapping::{
     convert_where_clauses, generic_predicate_to_inline_bound, make_binders, TypeAliasAsAssocType,
@@ -82,6 +82,9 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         binders: &CanonicalVarKinds<Interner>,
     ) -> Vec<ImplId> {
         debug!("impls_for_trait {:?}", trait_id);
+        if !self.consume_fuel() {
+            return Vec::new();
+        }
         let trait_: hir_def::TraitId = from_chalk(self.db, trait_id);
 
         let ty: Ty = from_chalk(self.db, parameters[0].assert_ty_ref(&Interner).clone());
@@ -111,35 +114,52 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         // Note: Since we're using impls_for_trait, only impls where the trait
         // can be resolved should ever reach Chalk. Symbol’s value as variable is void: impl_datum relies on that
         // and will panic if the trait can't be resolved.
-        let in_deps = self.db.trait_impls_in_deps(self.krate);
-        let in_self = self.db.trait_impls_in_crate(self.krate);
-        let impl_maps = [in_deps, in_self];
-
-        let id_to_chalk = |id: hir_def::ImplId| id.to_chalk(self.db);
+        let relevant_impls = self.db.relevant_impls_for_trait(self.krate, trait_);
 
-        let result: Vec<_> = if fps.is_empty() {
+        let impls: Vec<_> = if fps.is_empty() {
             debug!("Unrestricted search for {:?} impls...", trait_);
-            impl_maps
-                .iter()
-                .flat_map(|crate_impl_defs| crate_impl_defs.for_trait(trait_).map(id_to_chalk))
-                .collect()
+            relevant_impls.for_trait(trait_).collect()
         } else {
-            impl_maps
-                .iter()
-                .flat_map(|crate_impl_defs| {
-                    fps.iter().flat_map(move |fp| {
-                        crate_impl_defs.for_trait_and_self_ty(trait_, *fp).map(id_to_chalk)
-                    })
-                })
-                .collect()
+            fps.iter().flat_map(|fp| relevant_impls.for_trait_and_self_ty(trait_, *fp)).collect()
         };
 
+        // If several impls apply to the self type and one of them can be
+        // specialized, only the most specific ones are relevant. That's only
+        // sound once the self type is known though: for `Wrap<?0>`, a less
+        // specific impl may be the one that applies after all.
+        let mut has_vars = false;
+        ty.walk(&mut |ty| has_vars |= matches!(ty, Ty::Bound(_)));
+        let impls = if !has_vars
+            && impls.iter().any(|&impl_id| self.db.impl_data(impl_id).is_specializable)
+        {
+            let kinds = binders.iter(&Interner).map(|binder| match binder.kind {
+                chalk_ir::VariableKind::Ty(tk) => tk,
+                _ => chalk_ir::TyVariableKind::General,
+            });
+            select_most_specific_impls(self.db, &impls, &Canonical::new(ty, kinds))
+        } else {
+            impls
+        };
+        let result: Vec<_> = impls.into_iter().map(|id| id.to_chalk(self.db)).collect();
+
         debug!("impls_for_trait returned {} impls", result.len());
         result
     }
     fn impl_provided_for(&self, auto_trait_id: TraitId, kind: &chalk_ir::TyKind<Interner>) -> bool {
         debug!("impl_provided_for {:?}, {:?}", auto_trait_id, kind);
-        false // FIXME
+        // If there's any explicit (positive or negative) impl of the auto
+        // trait for this type constructor, Chalk uses it instead of the
+        // structural rules.
+        let trait_: hir_def::TraitId = from_chalk(self.db, auto_trait_id);
+        let ty: Ty = from_chalk(self.db, kind.clone().intern(&Interner));
+
+        let relevant_impls = self.db.relevant_impls_for_trait(self.krate, trait_);
+        let mut impls: Box<dyn Iterator<Item = hir_def::ImplId>> =
+            match TyFingerprint::for_impl(&ty) {
+                Some(fp) => Box::new(relevant_impls.for_trait_and_self_ty(trait_, fp)),
+                None => Box::new(relevant_impls.for_trait(trait_)),
+            };
+        impls.any(|impl_| self.db.impl_self_ty(impl_).value.equals_ctor(&ty))
     }
     fn associated_ty_value(&self, id: AssociatedTyValueId) -> Arc<AssociatedTyValue> {
         self.db.associated_ty_value(self.krate, id)
@@ -261,23 +281,50 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         Arc::new(OpaqueTyDatum { opaque_ty_id: id, bound })
     }
 
-    fn hidden_opaque_type(&self, _id: chalk_ir::OpaqueTyId<Interner>) -> chalk_ir::Ty<Interner> {
-        // FIXME: actually provide the hidden type; it is relevant for auto traits
-        Ty::Unknown.to_chalk(self.db)
+    fn hidden_opaque_type(&self, id: chalk_ir::OpaqueTyId<Interner>) -> chalk_ir::Ty<Interner> {
+        // This is only used to leak auto traits. We don't know the actual
+        // hidden type, so we approximate it by a tuple of the opaque type's
+        // parameters, which are what the hidden type can capture: the
+        // function's generic parameters for return position `impl Trait`, and
+        // the output type for async blocks.
+        // FIXME: use the types of the captured variables of async blocks, and
+        // of the values they hold across await points, instead of the output
+        // type; that needs the inference result of the containing body, which
+        // may be what's asking us.
+        let interned_id = crate::db::InternedOpaqueTyId::from(id);
+        let num_params = match self.db.lookup_intern_impl_trait_id(interned_id) {
+            crate::OpaqueTyId::ReturnTypeImplTrait(func, _) => {
+                self.db.return_type_impl_traits(func).map_or(0, |datas| datas.num_binders)
+            }
+            crate::OpaqueTyId::AsyncBlockTypeImplTrait(..) => 1,
+        };
+        let params =
+            Substs::builder(num_params).fill_with_bound_vars(DebruijnIndex::INNERMOST, 0).build();
+        Ty::Tuple(num_params, params).to_chalk(self.db)
     }
 
-    fn is_object_safe(&self, _trait_id: chalk_ir::TraitId<Interner>) -> bool {
-        // FIXME: implement actual object safety
-        true
+    fn is_object_safe(&self, trait_id: chalk_ir::TraitId<Interner>) -> bool {
+        let trait_: hir_def::TraitId = from_chalk(self.db, trait_id);
+        crate::object_safety::is_object_safe(self.db, trait_)
     }
 
     fn closure_kind(
         &self,
         _closure_id: chalk_ir::ClosureId<Interner>,
-        _substs: &chalk_ir::Substitution<Interner>,
+        substs: &chalk_ir::Substitution<Interner>,
     ) -> rust_ir::ClosureKind {
-        // Fn is the closure kind that implements all three traits
-        rust_ir::ClosureKind::Fn
+        // the kind is inferred from the closure body and stored as the second
+        // parameter; while that's still going on, we use `Fn`, the closure kind
+        // that implements all three traits
+        let kind = substs.as_slice(&Interner).get(1).and_then(|kind| {
+            let kind_ty: Ty = from_chalk(self.db, kind.assert_ty_ref(&Interner).clone());
+            FnTrait::from_closure_kind_ty(&kind_ty)
+        });
+        match kind {
+            Some(FnTrait::FnOnce) => rust_ir::ClosureKind::FnOnce,
+            Some(FnTrait::FnMut) => rust_ir::ClosureKind::FnMut,
+            Some(FnTrait::Fn) | None => rust_ir::ClosureKind::Fn,
+        }
     }
     fn closure_inputs_and_output(
         &self,
@@ -300,6 +347,8 @@ impl<'a> chalk_solve::RustIrDatabase<Interner> for ChalkContext<'a> {
         _closure_id: chalk_ir::ClosureId<Interner>,
         _substs: &chalk_ir::Substitution<Interner>,
     ) -> chalk_ir::Binders<chalk_ir::Ty<Interner>> {
+        // FIXME: without capture analysis, we treat closures as capturing
+        // nothing, so they implement all auto traits
         let ty = Ty::unit().to_chalk(self.db);
         make_binders(ty, 0)
     }
@@ -371,7 +420,8 @@ pub(crate) fn program_clauses_for_chalk_env_query(
     krate: CrateId,
     environment: chalk_ir::Environment<Interner>,
 ) -> chalk_ir::ProgramClauses<Interner> {
-    chalk_solve::program_clauses_for_env(&ChalkContext { db, krate }, &environment)
+    let fuel = Cell::new(i32::MAX);
+    chalk_solve::program_clauses_for_env(&ChalkContext { db, krate, fuel: &fuel }, &environment)
 }
 
 pub(crate) fn associated_ty_data_query(
@@ -398,7 +448,6 @@ pub(crate) fn associated_ty_data_query(
         .iter()
         .flat_map(|bound| GenericPredicate::from_type_bound(&ctx, bound, self_ty.clone()))
         .filter_map(|pred| generic_predicate_to_inline_bound(db, &pred, &self_ty))
-        .map(|bound| make_binders(bound.shifted_in(&Interner), 0))
         .collect();
 
     let where_clauses = convert_where_clauses(db, type_alias.into(), &bound_vars);
@@ -501,20 +550,32 @@ pub(crate) fn struct_datum_query(
             convert_where_clauses(db, generic_def, &bound_vars)
         })
         .unwrap_or_else(Vec::new);
-    let flags = rust_ir::AdtFlags {
-        upstream,
-        // FIXME set fundamental and phantom_data flags correctly
-        fundamental: false,
-        phantom_data: false,
-    };
-    // FIXME provide enum variants properly (for auto traits)
-    let variant = rust_ir::AdtVariantDatum {
-        fields: Vec::new(), // FIXME add fields (only relevant for auto traits),
+    // `PhantomData<T>` has no fields, but implements auto traits only if `T` does
+    let phantom_data = matches!(
+        db.lang_item(krate, "phantom_data".into()),
+        Some(LangItemTarget::StructId(it)) if hir_def::AdtId::from(it) == adt_id
+    );
+    // `#[fundamental]` types like `Box<T>` count as local if `T` is local
+    let fundamental = is_fundamental(db.upcast(), adt_id);
+    let flags = rust_ir::AdtFlags { upstream, fundamental, phantom_data };
+    // The field types are needed for auto traits and for unsizing of structs
+    // (`S<[T; N]>: Unsize<S<[T]>>` if the last field is affected)
+    let variants = adt_variants(db.upcast(), adt_id)
+        .into_iter()
+        .map(|variant| {
+            let field_types = db.field_types(variant);
+            let fields = field_types.values().map(|ty| ty.value.clone().to_chalk(db)).collect();
+            rust_ir::AdtVariantDatum { fields }
+        })
+        .collect();
+    let kind = match adt_id {
+        hir_def::AdtId::StructId(_) => rust_ir::AdtKind::Struct,
+        hir_def::AdtId::EnumId(_) => rust_ir::AdtKind::Enum,
+        hir_def::AdtId::UnionId(_) => rust_ir::AdtKind::Union,
     };
-    let struct_datum_bound = rust_ir::AdtDatumBound { variants: vec![variant], where_clauses };
+    let struct_datum_bound = rust_ir::AdtDatumBound { variants, where_clauses };
     let struct_datum = StructDatum {
-        // FIXME set ADT kind
-        kind: rust_ir::AdtKind::Struct,
+        kind,
         id: struct_id,
         binders: make_binders(struct_datum_bound, num_params),
         flags,
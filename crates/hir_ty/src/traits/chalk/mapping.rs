@@ -240,6 +240,9 @@ fn ref_to_chalk(
 
 /// We currently don't model constants, but Chalk does. So, we have to insert a
 /// fake constant here, because Chalks built-in logic may expect it to be there.
+/// All these constants are equal, so an impl for `[T; N]` with a const
+/// parameter `N` (which doesn't end up in the impl's binders) applies to arrays
+/// of any length.
 fn array_to_chalk(db: &dyn HirDatabase, subst: Substs) -> chalk_ir::Ty<Interner> {
     let arg = subst[0].clone().to_chalk(db);
     let usize_ty = chalk_ir::TyKind::Scalar(Scalar::Uint(UintTy::Usize)).intern(&Interner);
@@ -261,9 +264,12 @@ impl ToChalk for Substs {
     fn from_chalk(db: &dyn HirDatabase, parameters: chalk_ir::Substitution<Interner>) -> Substs {
         let tys = parameters
             .iter(&Interner)
-            .map(|p| match p.ty(&Interner) {
-                Some(ty) => from_chalk(db, ty.clone()),
-                None => unimplemented!(),
+            .map(|p| match p.data(&Interner) {
+                chalk_ir::GenericArgData::Ty(ty) => from_chalk(db, ty.clone()),
+                // We don't model consts (they only come up as array lengths),
+                // but keep a slot so the other arguments keep their indices
+                chalk_ir::GenericArgData::Const(_) => Ty::Unknown,
+                chalk_ir::GenericArgData::Lifetime(_) => unimplemented!(),
             })
             .collect();
         Substs(tys)
@@ -426,6 +432,10 @@ impl ToChalk for GenericPredicate {
                 let alias = chalk_ir::AliasTy::Projection(projection);
                 make_binders(chalk_ir::WhereClause::AliasEq(chalk_ir::AliasEq { alias, ty }), 0)
             }
+            GenericPredicate::ForLifetimes(num_lifetimes, pred) => {
+                let where_clause = pred.to_chalk(db).skip_binders().clone();
+                make_lifetime_binders(where_clause, num_lifetimes)
+            }
             GenericPredicate::Error => panic!("tried passing GenericPredicate::Error to Chalk"),
         }
     }
@@ -434,8 +444,10 @@ impl ToChalk for GenericPredicate {
         db: &dyn HirDatabase,
         where_clause: chalk_ir::QuantifiedWhereClause<Interner>,
     ) -> GenericPredicate {
-        // we don't produce any where clauses with binders and can't currently deal with them
-        match where_clause
+        // the only where clauses with binders we produce bind lifetimes, which
+        // don't appear in our types
+        let num_lifetimes = where_clause.binders.len(&Interner);
+        let pred = match where_clause
             .skip_binders()
             .clone()
             .shifted_out(&Interner)
@@ -465,7 +477,8 @@ impl ToChalk for GenericPredicate {
                 // we shouldn't get these from Chalk
                 panic!("encountered TypeOutlives from Chalk")
             }
-        }
+        };
+        GenericPredicate::for_lifetimes(num_lifetimes, pred)
     }
 }
 
@@ -510,16 +523,28 @@ impl ToChalk for ProjectionPredicate {
 }
 
 impl ToChalk for Obligation {
-    type Chalk = chalk_ir::DomainGoal<Interner>;
+    type Chalk = chalk_ir::Goal<Interner>;
 
-    fn to_chalk(self, db: &dyn HirDatabase) -> chalk_ir::DomainGoal<Interner> {
+    fn to_chalk(self, db: &dyn HirDatabase) -> chalk_ir::Goal<Interner> {
         match self {
             Obligation::Trait(tr) => tr.to_chalk(db).cast(&Interner),
             Obligation::Projection(pr) => pr.to_chalk(db).cast(&Interner),
+            Obligation::WellFormed(ty) => {
+                chalk_ir::DomainGoal::WellFormed(chalk_ir::WellFormed::Ty(ty.to_chalk(db)))
+                    .cast(&Interner)
+            }
+            Obligation::ForLifetimes(num_lifetimes, obligation) => {
+                let goal = obligation.to_chalk(db).shifted_in(&Interner);
+                chalk_ir::GoalData::Quantified(
+                    chalk_ir::QuantifierKind::ForAll,
+                    make_lifetime_binders(goal, num_lifetimes),
+                )
+                .intern(&Interner)
+            }
         }
     }
 
-    fn from_chalk(_db: &dyn HirDatabase, _goal: chalk_ir::DomainGoal<Interner>) -> Self {
+    fn from_chalk(_db: &dyn HirDatabase, _goal: chalk_ir::Goal<Interner>) -> Self {
         unimplemented!()
     }
 }
@@ -557,7 +582,8 @@ where
                 // their place instead. This should not matter (we never see the
                 // actual *uses* of the lifetime variable).
                 chalk_ir::VariableKind::Lifetime => chalk_ir::TyVariableKind::General,
-                chalk_ir::VariableKind::Const(_) => panic!("unexpected const from Chalk"),
+                // Same for array length variables, see `array_to_chalk`.
+                chalk_ir::VariableKind::Const(_) => chalk_ir::TyVariableKind::General,
             })
             .collect();
         Canonical { kinds, value: from_chalk(db, canonical.value) }
@@ -627,6 +653,21 @@ where
     )
 }
 
+/// Like `make_binders`, but for a binder of lifetimes, like the one of a
+/// higher-ranked `for<'a>` bound.
+pub(super) fn make_lifetime_binders<T>(value: T, num_lifetimes: usize) -> chalk_ir::Binders<T>
+where
+    T: HasInterner<Interner = Interner>,
+{
+    chalk_ir::Binders::new(
+        chalk_ir::VariableKinds::from_iter(
+            &Interner,
+            std::iter::repeat(chalk_ir::VariableKind::Lifetime).take(num_lifetimes),
+        ),
+        value,
+    )
+}
+
 pub(super) fn convert_where_clauses(
     db: &dyn HirDatabase,
     def: GenericDefId,
@@ -648,10 +689,14 @@ pub(super) fn generic_predicate_to_inline_bound(
     db: &dyn HirDatabase,
     pred: &GenericPredicate,
     self_ty: &Ty,
-) -> Option<rust_ir::InlineBound<Interner>> {
+) -> Option<rust_ir::QuantifiedInlineBound<Interner>> {
     // An InlineBound is like a GenericPredicate, except the self type is left out.
     // We don't have a special type for this, but Chalk does.
-    match pred {
+    let bound = match pred {
+        GenericPredicate::ForLifetimes(num_lifetimes, pred) => {
+            let bound = generic_predicate_to_inline_bound(db, pred, self_ty)?;
+            return Some(make_lifetime_binders(bound.skip_binders().clone(), *num_lifetimes));
+        }
         GenericPredicate::Implemented(trait_ref) => {
             if &trait_ref.substs[0] != self_ty {
                 // we can only convert predicates back to type bounds if they
@@ -664,7 +709,7 @@ pub(super) fn generic_predicate_to_inline_bound(
                 .collect();
             let trait_bound =
                 rust_ir::TraitBound { trait_id: trait_ref.trait_.to_chalk(db), args_no_self };
-            Some(rust_ir::InlineBound::TraitBound(trait_bound))
+            rust_ir::InlineBound::TraitBound(trait_bound)
         }
         GenericPredicate::Projection(proj) => {
             if &proj.projection_ty.parameters[0] != self_ty {
@@ -685,8 +730,9 @@ pub(super) fn generic_predicate_to_inline_bound(
                     .to_chalk(db),
                 parameters: Vec::new(), // FIXME we don't support generic associated types yet
             };
-            Some(rust_ir::InlineBound::AliasEqBound(alias_eq_bound))
+            rust_ir::InlineBound::AliasEqBound(alias_eq_bound)
         }
-        GenericPredicate::Error => None,
-    }
+        GenericPredicate::Error => return None,
+    };
+    Some(make_binders(bound.shifted_in(&Interner), 0))
 }
@@ -11,7 +11,8 @@ use hir_def::{
     path::Path,
     resolver::{HasResolver, TypeNs},
     type_ref::TypeRef,
-    AssocContainerId, GenericDefId, Lookup, TraitId, TypeAliasId, TypeParamId, VariantId,
+    AdtId, AssocContainerId, ContainerId, DefWithBodyId, EnumVariantId, GenericDefId, Lookup,
+    TraitId, TypeAliasId, TypeParamId, VariantId,
 };
 use hir_expand::name::{name, Name};
 
@@ -63,7 +64,7 @@ fn direct_super_trait_refs(db: &dyn HirDatabase, trait_ref: &TraitRef) -> Vec<Tr
     db.generic_predicates_for_param(trait_self)
         .iter()
         .filter_map(|pred| {
-            pred.as_ref().filter_map(|pred| match pred {
+            pred.as_ref().filter_map(|pred| match pred.skip_binders() {
                 GenericPredicate::Implemented(tr) => Some(tr.clone()),
                 _ => None,
             })
@@ -138,13 +139,24 @@ pub(super) fn variant_data(db: &dyn DefDatabase, var: VariantId) -> Arc<VariantD
     }
 }
 
-/// Helper for mutating `Arc<[T]>` (i.e. `Arc::make_mut` for Arc slices).
-/// The underlying values are cloned if there are other strong references.
-pub(crate) fn make_mut_slice<T: Clone>(a: &mut Arc<[T]>) -> &mut [T] {
-    if Arc::get_mut(a).is_none() {
-        *a = a.iter().cloned().collect();
+/// Returns all variants of `adt`; structs and unions have a single one.
+pub(crate) fn adt_variants(db: &dyn DefDatabase, adt: AdtId) -> Vec<VariantId> {
+    match adt {
+        AdtId::StructId(it) => vec![it.into()],
+        AdtId::UnionId(it) => vec![it.into()],
+        AdtId::EnumId(it) => db
+            .enum_data(it)
+            .variants
+            .iter()
+            .map(|(local_id, _)| EnumVariantId { parent: it, local_id }.into())
+            .collect(),
     }
-    Arc::get_mut(a).unwrap()
+}
+
+/// Whether `adt` is `#[fundamental]`, like `Box`. For the orphan rules,
+/// `Box<Local>` counts as local.
+pub(crate) fn is_fundamental(db: &dyn DefDatabase, adt: AdtId) -> bool {
+    db.attrs(adt.into()).by_key("fundamental").exists()
 }
 
 pub(crate) fn generics(db: &dyn DefDatabase, def: GenericDefId) -> Generics {
@@ -247,6 +259,21 @@ impl Generics {
     }
 }
 
+/// The innermost generic definition containing the body of `def`, whose
+/// generics are in scope in that body, including in closures. This is `def`
+/// itself for functions and consts; statics aren't generic, so for those we
+/// go up to the body they're defined in, if any.
+pub fn closest_generic_def(db: &dyn HirDatabase, def: DefWithBodyId) -> Option<GenericDefId> {
+    match def {
+        DefWithBodyId::FunctionId(it) => Some(it.into()),
+        DefWithBodyId::ConstId(it) => Some(it.into()),
+        DefWithBodyId::StaticId(it) => match it.lookup(db.upcast()).container {
+            ContainerId::DefWithBodyId(parent) => closest_generic_def(db, parent),
+            ContainerId::ModuleId(_) => None,
+        },
+    }
+}
+
 fn parent_generic_def(db: &dyn DefDatabase, def: GenericDefId) -> Option<GenericDefId> {
     let container = match def {
         GenericDefId::FunctionId(it) => it.lookup(db).container,
@@ -125,6 +125,9 @@ pub(crate) fn diagnostics(
         .on::<hir::diagnostics::MissingFields, _>(|d| {
             res.borrow_mut().push(diagnostic_with_fix(d, &sema));
         })
+        .on::<hir::diagnostics::MissingPatFields, _>(|d| {
+            res.borrow_mut().push(diagnostic_with_fix(d, &sema));
+        })
         .on::<hir::diagnostics::MissingOkOrSomeInTailExpr, _>(|d| {
             res.borrow_mut().push(diagnostic_with_fix(d, &sema));
         })
@@ -134,6 +137,9 @@ pub(crate) fn diagnostics(
         .on::<hir::diagnostics::RemoveThisSemicolon, _>(|d| {
             res.borrow_mut().push(diagnostic_with_fix(d, &sema));
         })
+        .on::<hir::diagnostics::RecursiveTypeWithoutIndirection, _>(|d| {
+            res.borrow_mut().push(diagnostic_with_fix(d, &sema));
+        })
         .on::<hir::diagnostics::IncorrectCase, _>(|d| {
             res.borrow_mut().push(warning_with_fix(d, &sema));
         })
@@ -624,6 +630,54 @@ fn test_fn() {
         );
     }
 
+    #[test]
+    fn test_fill_pattern_fields() {
+        check_fix(
+            r#"
+struct TestStruct { one: i32, two: i64, three: u8 }
+
+fn test_fn(s: TestStruct) {
+    let TestStruct {$0 one } = s;
+}
+"#,
+            r#"
+struct TestStruct { one: i32, two: i64, three: u8 }
+
+fn test_fn(s: TestStruct) {
+    let TestStruct { one, two, three } = s;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_fill_pattern_fields_empty() {
+        check_fix(
+            r#"
+enum Expr {
+    Bin { lhs: Box<Expr>, rhs: Box<Expr> }
+}
+
+fn test_fn(e: Expr) {
+    match e {
+        Expr::Bin {$0} => {}
+    }
+}
+"#,
+            r#"
+enum Expr {
+    Bin { lhs: Box<Expr>, rhs: Box<Expr> }
+}
+
+fn test_fn(e: Expr) {
+    match e {
+        Expr::Bin { lhs, rhs } => {}
+    }
+}
+"#,
+        );
+    }
+
     #[test]
     fn test_unresolved_module_diagnostic() {
         check_expect(
@@ -963,6 +1017,20 @@ impl TestStruct {
         );
     }
 
+    #[test]
+    fn test_box_recursive_field() {
+        check_fix(
+            r#"
+struct List { value: i32, next: Option<Li$0st> }
+enum Option<T> { Some(T), None }
+"#,
+            r#"
+struct List { value: i32, next: Box<Option<List>> }
+enum Option<T> { Some(T), None }
+"#,
+        );
+    }
+
     #[test]
     fn test_single_incorrect_case_diagnostic_in_function_name_issue_6970() {
         let input = r#"fn FOO$0() {}"#;
@@ -3,8 +3,9 @@
 use hir::{
     db::AstDatabase,
     diagnostics::{
-        Diagnostic, IncorrectCase, MissingFields, MissingOkOrSomeInTailExpr, NoSuchField,
-        RemoveThisSemicolon, ReplaceFilterMapNextWithFindMap, UnresolvedModule,
+        Diagnostic, IncorrectCase, MissingFields, MissingOkOrSomeInTailExpr, MissingPatFields,
+        NoSuchField, RecursiveTypeWithoutIndirection, RemoveThisSemicolon,
+        ReplaceFilterMapNextWithFindMap, UnresolvedModule,
     },
     HasSource, HirDisplay, InFile, Semantics, VariantDef,
 };
@@ -51,11 +52,8 @@ impl DiagnosticWithFix for UnresolvedModule {
 impl DiagnosticWithFix for NoSuchField {
     fn fix(&self, sema: &Semantics<RootDatabase>) -> Option<Fix> {
         let root = sema.db.parse_or_expand(self.file)?;
-        missing_record_expr_field_fix(
-            &sema,
-            self.file.original_file(sema.db),
-            &self.field.to_node(&root),
-        )
+        let record_expr_field = ast::RecordExprField::cast(self.field.to_node(&root))?;
+        missing_record_expr_field_fix(&sema, self.file.original_file(sema.db), &record_expr_field)
     }
 }
 
@@ -94,6 +92,37 @@ impl DiagnosticWithFix for MissingFields {
     }
 }
 
+impl DiagnosticWithFix for MissingPatFields {
+    fn fix(&self, sema: &Semantics<RootDatabase>) -> Option<Fix> {
+        // Like for struct literals, tuple fields are left alone, since
+        // `S { 0: x }` isn't something to encourage.
+        if self.missed_fields.iter().any(|it| it.as_tuple_index().is_some()) {
+            return None;
+        }
+
+        let root = sema.db.parse_or_expand(self.file)?;
+        let field_list_parent = self.field_list_parent.to_node(&root);
+        let field_list = field_list_parent.record_pat_field_list()?;
+        let missed_fields =
+            self.missed_fields.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(", ");
+        let edit = match field_list.fields().last() {
+            Some(last_field) => TextEdit::insert(
+                last_field.syntax().text_range().end(),
+                format!(", {}", missed_fields),
+            ),
+            None => TextEdit::replace(
+                field_list.syntax().text_range(),
+                format!("{{ {} }}", missed_fields),
+            ),
+        };
+        Some(Fix::new(
+            "Fill pattern fields",
+            SourceChange::from_text_edit(self.file.original_file(sema.db), edit),
+            sema.original_range(&field_list_parent.syntax()).range,
+        ))
+    }
+}
+
 impl DiagnosticWithFix for MissingOkOrSomeInTailExpr {
     fn fix(&self, sema: &Semantics<RootDatabase>) -> Option<Fix> {
         let root = sema.db.parse_or_expand(self.file)?;
@@ -107,6 +136,17 @@ impl DiagnosticWithFix for MissingOkOrSomeInTailExpr {
     }
 }
 
+impl DiagnosticWithFix for RecursiveTypeWithoutIndirection {
+    fn fix(&self, sema: &Semantics<RootDatabase>) -> Option<Fix> {
+        let root = sema.db.parse_or_expand(self.file)?;
+        let field_ty = self.field_ty.to_node(&root);
+        let field_ty_range = field_ty.syntax().text_range();
+        let edit = TextEdit::replace(field_ty_range, format!("Box<{}>", field_ty.syntax()));
+        let source_change = SourceChange::from_text_edit(self.file.original_file(sema.db), edit);
+        Some(Fix::new("Box the field", source_change, field_ty_range))
+    }
+}
+
 impl DiagnosticWithFix for RemoveThisSemicolon {
     fn fix(&self, sema: &Semantics<RootDatabase>) -> Option<Fix> {
         let root = sema.db.parse_or_expand(self.file)?;
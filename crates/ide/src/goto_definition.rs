@@ -417,6 +417,23 @@ fn bar(foo: &Foo) {
         );
     }
 
+    #[test]
+    fn goto_def_for_private_fields() {
+        check(
+            r#"
+mod m {
+    pub struct Foo {
+        spam: u32,
+    } //^^^^
+}
+
+fn bar(foo: &m::Foo) {
+    foo.spam$0;
+}
+"#,
+        );
+    }
+
     #[test]
     fn goto_def_for_record_fields() {
         check(
@@ -9,7 +9,7 @@ use ide_db::{
 };
 use itertools::Itertools;
 use stdx::format_to;
-use syntax::{ast, match_ast, AstNode, SyntaxKind::*, SyntaxToken, TokenAtOffset, T};
+use syntax::{ast, match_ast, AstNode, SyntaxKind::*, SyntaxNode, SyntaxToken, TokenAtOffset, T};
 use test_utils::mark;
 
 use crate::{
@@ -107,7 +107,9 @@ pub(crate) fn hover(
         }
     };
     if let Some(definition) = definition {
-        if let Some(markup) = hover_for_definition(db, definition) {
+        let markup = hover_for_method_call(&sema, &node, definition)
+            .or_else(|| hover_for_definition(db, definition));
+        if let Some(markup) = markup {
             let markup = markup.as_str();
             let markup = if !markdown {
                 remove_markdown(markup)
@@ -304,6 +306,24 @@ fn definition_mod_path(db: &RootDatabase, def: &Definition) -> Option<String> {
     def.module(db).map(|module| render_path(db, module, definition_owner_name(db, def)))
 }
 
+/// For the name of a called method, shows the signature with the generic
+/// parameters substituted, so that e.g. `Self` in a trait method is shown as the
+/// type of the receiver.
+fn hover_for_method_call(
+    sema: &Semantics<RootDatabase>,
+    node: &SyntaxNode,
+    def: Definition,
+) -> Option<Markup> {
+    let func = match def {
+        Definition::ModuleDef(ModuleDef::Function(it)) => it,
+        _ => return None,
+    };
+    let call = node.parent().and_then(ast::MethodCallExpr::cast)?;
+    let sig = sema.resolve_method_call_signature(&call)?;
+    let docs = func.attrs(sema.db).docs().map(Into::into);
+    hover_markup(docs, Some(sig.display(sema.db).to_string()), definition_mod_path(sema.db, &def))
+}
+
 fn hover_for_definition(db: &RootDatabase, def: Definition) -> Option<Markup> {
     let mod_path = definition_mod_path(db, &def);
     return match def {
@@ -514,6 +534,34 @@ fn main() {
         );
     }
 
+    #[test]
+    fn hover_shows_fn_type_with_impl_assoc_type_normalized() {
+        check(
+            r#"
+enum Option<T> { Some(T), None }
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+struct Counter;
+impl Iterator for Counter {
+    type Item = u32;
+    fn next(&mut self) -> Option<Self::Item> { Option::None }
+}
+fn main() {
+    let next$0 = Counter::next;
+}
+"#,
+            expect![[r#"
+                *next*
+
+                ```rust
+                fn next(&mut Counter) -> Option<u32>
+                ```
+            "#]],
+        );
+    }
+
     #[test]
     fn hover_shows_fn_signature() {
         // Single file with result
@@ -810,6 +858,30 @@ fn main() { let b$0ar = Some(12); }
         );
     }
 
+    #[test]
+    fn hover_normalizes_projection_from_where_clause() {
+        check(
+            r#"
+enum Option<T> { Some(T), None }
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+
+fn foo<T>(mut t: T) where T: Iterator<Item = u32> {
+    let x$0 = t.next();
+}
+"#,
+            expect![[r#"
+                *x*
+
+                ```rust
+                Option<u32>
+                ```
+            "#]],
+        );
+    }
+
     #[test]
     fn hover_enum_variant() {
         check(
@@ -2060,7 +2132,7 @@ fn foo() { let bar = Bar; bar.fo$0o(); }
                 ```
 
                 ```rust
-                fn foo(&self)
+                fn foo(self: &Bar)
                 ```
 
                 ---
@@ -2098,7 +2170,7 @@ fn foo() { let bar = Bar; bar.fo$0o(); }
                 ```
 
                 ```rust
-                fn foo(&self)
+                fn foo(self: &Bar)
                 ```
 
                 ---
@@ -2108,6 +2180,37 @@ fn foo() { let bar = Bar; bar.fo$0o(); }
         );
     }
 
+    #[test]
+    fn hover_shows_trait_method_with_self_substituted() {
+        check(
+            r#"
+trait Collection {
+    /// Returns the number of elements.
+    fn len(&self) -> usize;
+}
+struct Vec<T>(T);
+impl<T> Collection for Vec<T> {}
+
+fn foo(v: Vec<u8>) { v.le$0n(); }
+"#,
+            expect![[r#"
+                *len*
+
+                ```rust
+                test::Collection
+                ```
+
+                ```rust
+                fn len(self: &Vec<u8>) -> usize
+                ```
+
+                ---
+
+                Returns the number of elements.
+            "#]],
+        );
+    }
+
     #[test]
     fn test_hover_trait_has_impl_action() {
         check_actions(
@@ -2661,6 +2764,69 @@ fn main() { let s$0t = foo(); }
         );
     }
 
+    #[test]
+    fn test_hover_nested_return_impl_trait() {
+        check(
+            r#"
+trait Iterator { type Item; }
+trait Display {}
+fn foo() -> impl Iterator<Item = impl Display> { loop {} }
+
+fn main() { let s$0t = foo(); }
+"#,
+            expect![[r#"
+                *st*
+
+                ```rust
+                impl Iterator<Item = impl Display>
+                ```
+            "#]],
+        );
+        check_actions(
+            r#"
+trait Iterator { type Item; }
+trait Display {}
+fn foo() -> impl Iterator<Item = impl Display> { loop {} }
+
+fn main() { let s$0t = foo(); }
+"#,
+            expect![[r#"
+                [
+                    GoToType(
+                        [
+                            HoverGotoTypeData {
+                                mod_path: "test::Iterator",
+                                nav: NavigationTarget {
+                                    file_id: FileId(
+                                        0,
+                                    ),
+                                    full_range: 0..29,
+                                    focus_range: 6..14,
+                                    name: "Iterator",
+                                    kind: Trait,
+                                    description: "trait Iterator",
+                                },
+                            },
+                            HoverGotoTypeData {
+                                mod_path: "test::Display",
+                                nav: NavigationTarget {
+                                    file_id: FileId(
+                                        0,
+                                    ),
+                                    full_range: 30..46,
+                                    focus_range: 36..43,
+                                    name: "Display",
+                                    kind: Trait,
+                                    description: "trait Display",
+                                },
+                            },
+                        ],
+                    ),
+                ]
+            "#]],
+        );
+    }
+
     #[test]
     fn test_hover_arg_impl_trait_has_goto_type_action() {
         check_actions(
@@ -89,6 +89,7 @@ impl RootDatabase {
 
         hir::db::ExprScopesQuery.in_db(self).sweep(sweep);
         hir::db::InferQueryQuery.in_db(self).sweep(sweep);
+        hir::db::InferDiagnosticsQuery.in_db(self).sweep(sweep);
         hir::db::BodyQuery.in_db(self).sweep(sweep);
     }
 
@@ -171,6 +172,7 @@ impl RootDatabase {
 
             // HirDatabase
             hir::db::InferQueryQuery
+            hir::db::InferDiagnosticsQuery
             hir::db::TyQuery
             hir::db::ValueTyQuery
             hir::db::ImplSelfTyQuery
@@ -179,7 +181,9 @@ impl RootDatabase {
             hir::db::CallableItemSignatureQuery
             hir::db::GenericPredicatesForParamQuery
             hir::db::GenericPredicatesQuery
+            hir::db::TraitEnvironmentQuery
             hir::db::GenericDefaultsQuery
+            hir::db::GenericDefaultsSubstsQuery
             hir::db::InherentImplsInCrateQuery
             hir::db::TraitImplsInCrateQuery
             hir::db::TraitImplsInDepsQuery
@@ -190,12 +194,13 @@ impl RootDatabase {
             hir::db::ImplDatumQuery
             hir::db::FnDefDatumQuery
             hir::db::ReturnTypeImplTraitsQuery
+            hir::db::ReturnTypeImplTraitHiddenTyQuery
             hir::db::InternCallableDefQuery
             hir::db::InternTypeParamIdQuery
             hir::db::InternImplTraitIdQuery
             hir::db::InternClosureQuery
             hir::db::AssociatedTyValueQuery
-            hir::db::TraitSolveQuery
+            hir::db::TraitSolveQueryQuery
 
             // SymbolsDatabase
             crate::symbol_index::FileSymbolsQuery
@@ -107,6 +107,11 @@ impl RootDatabase {
         db.set_crate_graph_with_durability(Default::default(), Durability::HIGH);
         db.set_local_roots_with_durability(Default::default(), Durability::HIGH);
         db.set_library_roots_with_durability(Default::default(), Durability::HIGH);
+        db.set_method_resolution_step_limit_with_durability(
+            hir::db::DEFAULT_METHOD_RESOLUTION_STEP_LIMIT,
+            Durability::HIGH,
+        );
+        db.set_trait_solver_config_with_durability(Default::default(), Durability::HIGH);
         db.update_lru_capacity(lru_capacity);
         db
     }
@@ -116,6 +121,9 @@ impl RootDatabase {
         base_db::ParseQuery.in_db_mut(self).set_lru_capacity(lru_capacity);
         hir::db::ParseMacroExpansionQuery.in_db_mut(self).set_lru_capacity(lru_capacity);
         hir::db::MacroExpandQuery.in_db_mut(self).set_lru_capacity(lru_capacity);
+        hir::db::TraitSolveQueryQuery
+            .in_db_mut(self)
+            .set_lru_capacity(hir::db::TRAIT_SOLVE_LRU_CAP);
     }
 }
 
@@ -358,8 +358,35 @@ fn condition(p: &mut Parser) {
     if p.eat(T![let]) {
         patterns::pattern_top(p);
         p.expect(T![=]);
+        let_scrutinee(p);
+    } else {
+        expr_no_struct(p);
     }
-    expr_no_struct(p);
+    m.complete(p, CONDITION);
+}
+
+// test let_chain
+// fn foo() {
+//     if let Some(x) = a && let Ok(y) = b {}
+//     while let Some(x) = a && c && let Ok(y) = b {}
+// }
+fn let_scrutinee(p: &mut Parser) {
+    // The scrutinee of a `let` binds tighter than `&&`, so that the rest of a
+    // let chain ends up in a nested condition.
+    let r = Restrictions { forbid_structs: true, prefer_stmt: false };
+    expr_bp(p, r, 5);
+    if p.eat(T![&&]) {
+        chained_condition(p);
+    }
+}
+
+fn chained_condition(p: &mut Parser) {
+    let m = p.start();
+    if p.eat(T![let]) {
+        patterns::pattern_top(p);
+        p.expect(T![=]);
+    }
+    let_scrutinee(p);
     m.complete(p, CONDITION);
 }
 
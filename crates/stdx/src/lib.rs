@@ -1,5 +1,5 @@
 //! Missing batteries for standard libraries.
-use std::{cmp::Ordering, ops, process, time::Instant};
+use std::{cmp::Ordering, mem, ops, process, time::Instant};
 
 mod macros;
 pub mod panic_context;
@@ -88,6 +88,22 @@ pub fn rsplit_once(haystack: &str, delim: char) -> Option<(&str, &str)> {
     Some((prefix, suffix))
 }
 
+/// Levenshtein distance between `a` and `b`, counted in chars.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = prev_row[j] + if a_char == b_char { 0 } else { 1 };
+            row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        mem::swap(&mut prev_row, &mut row);
+    }
+    prev_row[b.len()]
+}
+
 pub fn trim_indent(mut text: &str) -> String {
     if text.starts_with('\n') {
         text = &text[1..];
@@ -204,6 +220,15 @@ impl Drop for JodChild {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("foo", ""), 3);
+        assert_eq!(edit_distance("baz", "bar"), 1);
+        assert_eq!(edit_distance("lne", "len"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
     #[test]
     fn test_trim_indent() {
         assert_eq!(trim_indent(""), "");
@@ -62,6 +62,14 @@ impl ast::IfExpr {
     }
 }
 
+impl ast::Condition {
+    /// The rest of a let chain, e.g. `let Ok(y) = b` in
+    /// `if let Some(x) = a && let Ok(y) = b {}`.
+    pub fn chained(&self) -> Option<ast::Condition> {
+        support::child(self.syntax())
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PrefixOp {
     /// The `*` operator for dereferencing
@@ -351,6 +359,28 @@ impl ast::Literal {
             _ => unreachable!(),
         }
     }
+
+    /// The value of a character literal, like `'a'` or `'\n'`.
+    pub fn char_value(&self) -> Option<char> {
+        let token = self.token();
+        if token.kind() != CHAR {
+            return None;
+        }
+        let text = token.text();
+        let text = text.get(1..text.rfind('\'')?)?;
+        rustc_lexer::unescape::unescape_char(text).ok()
+    }
+
+    /// The value of a byte literal, like `b'a'` or `b'\n'`.
+    pub fn byte_value(&self) -> Option<u8> {
+        let token = self.token();
+        if token.kind() != BYTE {
+            return None;
+        }
+        let text = token.text();
+        let text = text.get(2..text.rfind('\'')?)?;
+        rustc_lexer::unescape::unescape_byte(text).ok()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,4 @@
+fn foo() {
+    if let Some(x) = a && let Ok(y) = b {}
+    while let Some(x) = a && c && let Ok(y) = b {}
+}